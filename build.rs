@@ -9,5 +9,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         .all_rustc()
         .all_sysinfo()
         .emit()?;
+    // protoc is vendored so that building doesn't need it installed
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::compile_protos("proto/embeddings.proto")?;
     Ok(())
 }
@@ -0,0 +1,55 @@
+import json
+
+from typing import List, Optional
+
+BEDROCK_MODEL_DIMENSIONS = {
+    "amazon.titan-embed-text-v1": 1536,
+    "amazon.titan-embed-text-v2:0": 1024,
+    "cohere.embed-english-v3": 1024,
+    "cohere.embed-multilingual-v3": 1024,
+}
+
+
+class BedrockEmbedding:
+
+    def __init__(self, model_name: str = "amazon.titan-embed-text-v1", region: Optional[str] = None) -> None:
+        if model_name not in BEDROCK_MODEL_DIMENSIONS:
+            raise ValueError(f"unsupported bedrock embedding model: {model_name}")
+        # boto3 is an optional dependency, only needed by this extractor
+        import boto3
+
+        self._model_name = model_name
+        # Credentials are resolved through the standard AWS credential chain
+        self._client = boto3.client("bedrock-runtime", region_name=region)
+
+    def dimensions(self) -> int:
+        return BEDROCK_MODEL_DIMENSIONS[self._model_name]
+
+    def embed_ctx(self, inputs: List[str]) -> List[List[float]]:
+        if self._is_cohere():
+            return self._embed_cohere(inputs, "search_document")
+        return [self._embed_titan(input) for input in inputs]
+
+    def embed_query(self, query: str) -> List[float]:
+        if self._is_cohere():
+            return self._embed_cohere([query], "search_query")[0]
+        return self._embed_titan(query)
+
+    def _is_cohere(self) -> bool:
+        return self._model_name.startswith("cohere.")
+
+    def _embed_titan(self, input: str) -> List[float]:
+        # Titan models embed a single text per invocation
+        return self._invoke({"inputText": input})["embedding"]
+
+    def _embed_cohere(self, inputs: List[str], input_type: str) -> List[List[float]]:
+        return self._invoke({"texts": inputs, "input_type": input_type})["embeddings"]
+
+    def _invoke(self, body: dict) -> dict:
+        response = self._client.invoke_model(
+            modelId=self._model_name,
+            body=json.dumps(body),
+            contentType="application/json",
+            accept="application/json",
+        )
+        return json.loads(response["body"].read())
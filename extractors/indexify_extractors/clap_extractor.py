@@ -0,0 +1,87 @@
+import base64
+import io
+import os
+from typing import Any, List
+
+import librosa
+import numpy as np
+import requests
+import torch
+from transformers import ClapModel, ClapProcessor
+
+from .clip_extractor import is_url
+from .embedding_extractor import EmbeddingSchema
+from .extractor_base import Content, ExtractedEmbedding, Extractor, ExtractorInfo
+
+# CLAP's feature extractor expects audio sampled at 48kHz
+CLAP_SAMPLING_RATE = 48000
+
+
+def load_audio(data: str, sampling_rate: int = CLAP_SAMPLING_RATE) -> np.ndarray:
+    """
+    Audio clips are passed as content data the same way as images, as a url,
+    a local path or base64 encoded bytes, in any format librosa can decode.
+    Clips are resampled to `sampling_rate` and mixed down to mono.
+    """
+    if is_url(data):
+        response = requests.get(data)
+        response.raise_for_status()
+        source = io.BytesIO(response.content)
+    elif os.path.isfile(data):
+        source = data
+    else:
+        source = io.BytesIO(base64.b64decode(data))
+    audio, _ = librosa.load(source, sr=sampling_rate, mono=True)
+    return audio
+
+
+class CLAPEmbedding:
+
+    def __init__(self, model_name: str = "laion/clap-htsat-unfused") -> None:
+        self._model = ClapModel.from_pretrained(model_name)
+        self._processor = ClapProcessor.from_pretrained(model_name)
+
+    def embed_audio(self, clips: List[np.ndarray], sampling_rate: int = CLAP_SAMPLING_RATE) -> List[List[float]]:
+        if sampling_rate != CLAP_SAMPLING_RATE:
+            clips = [librosa.resample(c, orig_sr=sampling_rate, target_sr=CLAP_SAMPLING_RATE) for c in clips]
+        inputs = self._processor(audios=clips, sampling_rate=CLAP_SAMPLING_RATE, return_tensors="pt")
+        with torch.no_grad():
+            embeddings = self._model.get_audio_features(**inputs)
+        return torch.nn.functional.normalize(embeddings, p=2, dim=1).tolist()
+
+    def embed_query(self, query: str) -> List[float]:
+        inputs = self._processor(text=[query], padding=True, return_tensors="pt")
+        with torch.no_grad():
+            embeddings = self._model.get_text_features(**inputs)
+        return torch.nn.functional.normalize(embeddings, p=2, dim=1)[0].tolist()
+
+    def dimensions(self) -> int:
+        return self._model.config.projection_dim
+
+
+class CLAPAudioExtractor(Extractor):
+
+    def __init__(self, model_name: str = "laion/clap-htsat-unfused"):
+        self._model_name = model_name
+        self._model = CLAPEmbedding(model_name=model_name)
+
+    def extract(self, content: List[Content], params: dict[str, Any]) -> List[ExtractedEmbedding]:
+        clips = [load_audio(c.data) for c in content]
+        embeddings = self._model.embed_audio(clips)
+        extracted_embeddings = []
+        for (c, e) in zip(content, embeddings):
+            text = c.data if is_url(c.data) or os.path.isfile(c.data) else ""
+            extracted_embeddings.append(ExtractedEmbedding(content_id=c.id, text=text, embeddings=e))
+        return extracted_embeddings
+
+    def extract_query_embeddings(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name.split("/")[-1],
+            description="CLAP audio embeddings, searchable with text queries",
+            output_datatype="embedding",
+            input_params="{}",
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._model.dimensions()),
+        )
@@ -0,0 +1,81 @@
+import base64
+import io
+import os
+from typing import Any, List
+
+import requests
+import torch
+from PIL import Image
+from transformers import CLIPModel, CLIPProcessor
+
+from .embedding_extractor import EmbeddingSchema
+from .extractor_base import Content, ExtractedEmbedding, Extractor, ExtractorInfo
+
+
+def is_url(data: str) -> bool:
+    return data.startswith("http://") or data.startswith("https://")
+
+
+def load_image(data: str) -> Image.Image:
+    """
+    Images are passed as content data either as a http(s) url, a path on the
+    local filesystem or the base64 encoded bytes of the image.
+    """
+    if is_url(data):
+        response = requests.get(data)
+        response.raise_for_status()
+        return Image.open(io.BytesIO(response.content)).convert("RGB")
+    if os.path.isfile(data):
+        return Image.open(data).convert("RGB")
+    return Image.open(io.BytesIO(base64.b64decode(data))).convert("RGB")
+
+
+class CLIPEmbedding:
+
+    def __init__(self, model_name: str = "openai/clip-vit-base-patch32") -> None:
+        self._model = CLIPModel.from_pretrained(model_name)
+        self._processor = CLIPProcessor.from_pretrained(model_name)
+
+    def embed_images(self, images: List[Image.Image]) -> List[List[float]]:
+        inputs = self._processor(images=images, return_tensors="pt")
+        with torch.no_grad():
+            embeddings = self._model.get_image_features(**inputs)
+        return torch.nn.functional.normalize(embeddings, p=2, dim=1).tolist()
+
+    def embed_query(self, query: str) -> List[float]:
+        inputs = self._processor(text=[query], padding=True, truncation=True, return_tensors="pt")
+        with torch.no_grad():
+            embeddings = self._model.get_text_features(**inputs)
+        return torch.nn.functional.normalize(embeddings, p=2, dim=1)[0].tolist()
+
+    def dimensions(self) -> int:
+        return self._model.config.projection_dim
+
+
+class CLIPImageExtractor(Extractor):
+
+    def __init__(self, model_name: str = "openai/clip-vit-base-patch32"):
+        self._model_name = model_name
+        self._model = CLIPEmbedding(model_name=model_name)
+
+    def extract(self, content: List[Content], params: dict[str, Any]) -> List[ExtractedEmbedding]:
+        images = [load_image(c.data) for c in content]
+        embeddings = self._model.embed_images(images)
+        extracted_embeddings = []
+        for (c, e) in zip(content, embeddings):
+            # Only keep references to the image as its text, not the encoded bytes
+            text = c.data if is_url(c.data) or os.path.isfile(c.data) else ""
+            extracted_embeddings.append(ExtractedEmbedding(content_id=c.id, text=text, embeddings=e))
+        return extracted_embeddings
+
+    def extract_query_embeddings(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name.split("/")[-1],
+            description="CLIP image embeddings, searchable with text queries",
+            output_datatype="embedding",
+            input_params="{}",
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._model.dimensions()),
+        )
@@ -0,0 +1,53 @@
+import os
+
+from typing import List, Optional
+
+from .retry import RetryPolicy, post_with_retry
+
+COHERE_EMBED_URL = "https://api.cohere.ai/v1/embed"
+
+# The embed endpoint accepts at most 96 texts per request
+COHERE_MAX_BATCH_SIZE = 96
+
+COHERE_MODEL_DIMENSIONS = {
+    "embed-english-v3.0": 1024,
+    "embed-multilingual-v3.0": 1024,
+    "embed-english-light-v3.0": 384,
+    "embed-multilingual-light-v3.0": 384,
+}
+
+
+class CohereEmbedding:
+
+    def __init__(
+        self, model_name: str = "embed-english-v3.0", api_key: Optional[str] = None,
+        max_retries: int = 3, initial_backoff: float = 0.5,
+    ) -> None:
+        if model_name not in COHERE_MODEL_DIMENSIONS:
+            raise ValueError(f"unsupported cohere embedding model: {model_name}")
+        self._model_name = model_name
+        self._retry_policy = RetryPolicy(max_retries=max_retries, initial_backoff=initial_backoff)
+        self._api_key = api_key or os.environ.get("COHERE_API_KEY")
+        if not self._api_key:
+            raise ValueError("cohere api key is not set, pass api_key or set COHERE_API_KEY")
+
+    def dimensions(self) -> int:
+        return COHERE_MODEL_DIMENSIONS[self._model_name]
+
+    def embed_ctx(self, inputs: List[str]) -> List[List[float]]:
+        embeddings = []
+        for i in range(0, len(inputs), COHERE_MAX_BATCH_SIZE):
+            embeddings.extend(self._embed(inputs[i:i + COHERE_MAX_BATCH_SIZE], "search_document"))
+        return embeddings
+
+    def embed_query(self, query: str) -> List[float]:
+        return self._embed([query], "search_query")[0]
+
+    def _embed(self, inputs: List[str], input_type: str) -> List[List[float]]:
+        response = post_with_retry(
+            COHERE_EMBED_URL,
+            self._retry_policy,
+            headers={"Authorization": f"Bearer {self._api_key}"},
+            json={"model": self._model_name, "texts": inputs, "input_type": input_type},
+        )
+        return response.json()["embeddings"]
@@ -0,0 +1,87 @@
+import json
+import torch
+import torch.nn.functional as F
+from huggingface_hub import hf_hub_download
+from transformers import AutoModel, AutoTokenizer
+from typing import List
+
+from .extractor_base import Content, ExtractedAttributes, Extractor, ExtractorInfo
+
+# ColBERT marks queries and documents with these (otherwise unused) tokens
+QUERY_MARKER = "[unused0]"
+DOCUMENT_MARKER = "[unused1]"
+
+
+def maxsim(query_vectors: List[List[float]], document_vectors: List[List[float]]) -> float:
+    """
+    Late interaction score of a document for a query: every query token is
+    matched with its most similar document token and the similarities summed.
+    """
+    score = 0.0
+    for q in query_vectors:
+        score += max(sum(a * b for (a, b) in zip(q, d)) for d in document_vectors)
+    return score
+
+
+class ColBERTEmbedding:
+
+    def __init__(self, model_name: str = "colbert-ir/colbertv2.0", query_length: int = 32) -> None:
+        self._tokenizer = AutoTokenizer.from_pretrained(model_name)
+        self._model = AutoModel.from_pretrained(model_name)
+        # The projection to the token embedding space is not part of the
+        # transformer, load it from the checkpoint separately.
+        state_dict = torch.load(hf_hub_download(model_name, "pytorch_model.bin"), map_location="cpu")
+        weight = state_dict["linear.weight"]
+        self._linear = torch.nn.Linear(weight.shape[1], weight.shape[0], bias=False)
+        self._linear.load_state_dict({"weight": weight})
+        self._query_length = query_length
+
+    def embed_docs(self, inputs: List[str]) -> List[List[List[float]]]:
+        encoded_input = self._tokenizer(
+            [f"{DOCUMENT_MARKER} {input}" for input in inputs], padding=True, truncation=True, return_tensors='pt'
+        )
+        vectors = self._embed(encoded_input)
+        mask = encoded_input['attention_mask'].bool()
+        return [v[m].tolist() for (v, m) in zip(vectors, mask)]
+
+    def embed_query(self, query: str) -> List[List[float]]:
+        # Queries are padded with [MASK] tokens, which ColBERT uses for query augmentation
+        encoded_input = self._tokenizer(
+            f"{QUERY_MARKER} {query}", padding='max_length', truncation=True,
+            max_length=self._query_length, return_tensors='pt'
+        )
+        encoded_input['input_ids'][encoded_input['input_ids'] == self._tokenizer.pad_token_id] = self._tokenizer.mask_token_id
+        encoded_input['attention_mask'] = torch.ones_like(encoded_input['attention_mask'])
+        return self._embed(encoded_input)[0].tolist()
+
+    def _embed(self, encoded_input) -> torch.Tensor:
+        with torch.no_grad():
+            token_embeddings = self._model(**encoded_input)[0]
+            return F.normalize(self._linear(token_embeddings), p=2, dim=2)
+
+
+class ColBERTExtractor(Extractor):
+
+    def __init__(self, model_name: str = "colbert-ir/colbertv2.0"):
+        self._model = ColBERTEmbedding(model_name=model_name)
+
+    def extract(self, content: List[Content], params: dict[str, str]) -> List[ExtractedAttributes]:
+        texts = [c.data for c in content]
+        vectors = self._model.embed_docs(texts)
+        return [
+            ExtractedAttributes(content_id=c.id, json=json.dumps({"vectors": v}))
+            for (c, v) in zip(content, vectors)
+        ]
+
+    def extract_query_embeddings(self, query: str) -> List[List[float]]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        schema = {"vectors": "list[list[float]]"}
+        return ExtractorInfo(
+            name="ColBERTExtractor",
+            description="ColBERT per token embeddings for late interaction scoring",
+            output_datatype="attributes",
+            input_params=json.dumps({}),
+            output_schema=json.dumps(schema),
+        )
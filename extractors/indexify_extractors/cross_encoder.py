@@ -0,0 +1,28 @@
+from typing import List
+
+import torch
+from transformers import AutoModelForSequenceClassification, AutoTokenizer
+
+
+class CrossEncoderReranker:
+    """
+    Scores (query, text) pairs with a cross encoder. It's used to rerank the
+    results of a vector search rather than to extract from content.
+    """
+
+    def __init__(self, model_name: str = "cross-encoder/ms-marco-MiniLM-L-6-v2", max_length: int = 512):
+        self._tokenizer = AutoTokenizer.from_pretrained(model_name)
+        self._model = AutoModelForSequenceClassification.from_pretrained(model_name)
+        self._model.eval()
+        self._max_length = max_length
+
+    def score_pairs(self, query: str, texts: List[str]) -> List[float]:
+        if len(texts) == 0:
+            return []
+        encoded_input = self._tokenizer(
+            [query] * len(texts), texts, padding=True, truncation=True,
+            max_length=self._max_length, return_tensors='pt'
+        )
+        with torch.no_grad():
+            logits = self._model(**encoded_input).logits
+        return logits[:, 0].tolist()
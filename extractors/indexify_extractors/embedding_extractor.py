@@ -1,6 +1,7 @@
+import os
 from abc import abstractmethod
 from dataclasses import dataclass
-from typing import Any, Callable, List, Literal
+from typing import Any, Callable, List, Literal, Optional
 
 # alias to avoid name conflict with FlagEmbedding class below
 from fastembed.embedding import FlagEmbedding as FastFlagEmbedding
@@ -9,12 +10,23 @@ from pydantic import BaseModel
 import langchain
 
 from .extractor_base import Content, ExtractedEmbedding, Extractor, ExtractorInfo
+from .bedrock_embedding import BedrockEmbedding
+from .cohere_embedding import CohereEmbedding
+from .hf_inference_embedding import HFInferenceEmbedding
+from .instructor_embedding import DEFAULT_DOCUMENT_INSTRUCTION, DEFAULT_QUERY_INSTRUCTION, InstructorEmbedding
+from .llamacpp_embedding import LlamaCppEmbedding
+from .matryoshka import l2_normalize
+from .ollama_embedding import OLLAMA_DEFAULT_URL, OllamaEmbedding
+from .openai_embedding import OpenAIEmbedding
 from .sentence_transformer import SentenceTransformersEmbedding
 
 
 class EmbeddingInputParams(BaseModel):
     overlap: int = 0
     text_splitter: Literal['char', 'recursive']  = 'recursive'
+    # Scale embeddings to unit length, for indexes which use dot product
+    # distance. Defaults to the extractor's `normalize`.
+    normalize: Optional[bool] = None
 
 @dataclass
 class EmbeddingSchema:
@@ -22,17 +34,25 @@ class EmbeddingSchema:
     dim: int
 
 class BaseEmbeddingExtractor(Extractor):
+    # Extractors which take extra input params subclass EmbeddingInputParams
+    input_params_model = EmbeddingInputParams
 
-    def __init__(self, max_context_length: int):
+    def __init__(self, max_context_length: int, normalize: bool = False):
         self._model_context_length: int = max_context_length
+        # Queries are embedded without input params, so they're normalized as
+        # configured for the extractor, which bindings can override
+        self._normalize = normalize
 
     def extract(self, content_list: List[Content], params: dict[str, Any]) -> List[Any]:
-        input_params: EmbeddingInputParams = EmbeddingInputParams.parse_obj(params)
+        input_params: EmbeddingInputParams = self.input_params_model.parse_obj(params)
         text_splitter: Any[[str], List[str]] = self._create_splitter(input_params.text_splitter)
         extracted_embeddings = []
         for content in content_list:
             chunks: List[str] = text_splitter(content.data)
-            embeddings_list = self.extract_embeddings(chunks)
+            embeddings_list = self._embed_chunks(chunks, input_params)
+            normalize = self._normalize if input_params.normalize is None else input_params.normalize
+            if normalize:
+                embeddings_list = l2_normalize(embeddings_list)
             for (chunk, embeddings) in zip(chunks, embeddings_list):
                 extracted_embeddings.append(ExtractedEmbedding(content_id=content.id, text=chunk, embeddings=embeddings))
         return extracted_embeddings
@@ -43,42 +63,79 @@ class BaseEmbeddingExtractor(Extractor):
         elif text_splitter == "char":
             return langchain.text_splitter.CharacterTextSplitter(chunk_size=self._model_context_length, separator="\n\n").split_text
 
+    def count_tokens(self, texts: List[str]) -> List[int]:
+        """
+        Number of tokens in each text, for extractors whose model has a local tokenizer.
+        """
+        model = getattr(self, "_model", None)
+        if model is None or not hasattr(model, "count_tokens"):
+            raise NotImplementedError(f"{self.info().name} doesn't support counting tokens")
+        return model.count_tokens(texts)
+
+    def model_info(self) -> dict[str, Any]:
+        model = getattr(self, "_model", None)
+        if model is None or not hasattr(model, "model_info"):
+            return {}
+        return model.model_info()
+
+    def _embed_chunks(self, chunks: List[str], input_params: EmbeddingInputParams) -> List[List[float]]:
+        return self.extract_embeddings(chunks)
+
+    def extract_query_embeddings(self, query: str) -> List[float]:
+        embeddings = self._embed_query(query)
+        if self._normalize:
+            embeddings = l2_normalize([embeddings])[0]
+        return embeddings
+
     @abstractmethod        
     def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
         ...
 
+    @abstractmethod
+    def _embed_query(self, query: str) -> List[float]:
+        ...
+
 
 class FlagEmbedding(BaseEmbeddingExtractor):
-    def __init__(self, max_length: int = 512):
-        super(FlagEmbedding, self).__init__(max_context_length=max_length)
-        self.embedding_model = FastFlagEmbedding(model_name="BAAI/bge-small-en", max_length=max_length)
+    def __init__(self, max_length: int = 512, model_name: str = "BAAI/bge-small-en", dim: int = 384, normalize: bool = False):
+        super(FlagEmbedding, self).__init__(max_context_length=max_length, normalize=normalize)
+        self._model_name = model_name
+        self._dim = dim
+        self.embedding_model = FastFlagEmbedding(model_name=model_name, max_length=max_length)
     
 
     def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
         return list(self.embedding_model.passage_embed(texts))
 
-    def extract_query_embeddings(self, query: str) -> List[float]:
+    def _embed_query(self, query: str) -> List[float]:
         return list(next(self.embedding_model.query_embed([query])))
 
     def info(self) -> ExtractorInfo:
         return ExtractorInfo(
-            name="bge-small-en",
+            name=self._model_name.split("/")[-1],
             description="Flag Embeddings",
             output_datatype="embedding",
             input_params=EmbeddingInputParams.schema_json(),
-            output_schema=EmbeddingSchema(distance_metric="cosine", dim=384),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._dim),
         )   
 
+class BGEBaseExtractor(FlagEmbedding):
+    def __init__(self, normalize: bool = False):
+        super(BGEBaseExtractor, self).__init__(model_name="BAAI/bge-base-en", dim=768, normalize=normalize)
+
 class MiniLML6Extractor(BaseEmbeddingExtractor):
 
-    def __init__(self):
-        super(MiniLML6Extractor, self).__init__(max_context_length=384)
-        self._model = SentenceTransformersEmbedding(model_name="all-MiniLM-L6-v2")
+    def __init__(
+        self, backend: str = "torch", quantization: Optional[str] = None, pooling: str = "mean",
+        max_tokens: Optional[int] = None, truncation: str = "head", device: str = "cpu", normalize: bool = False,
+    ):
+        super(MiniLML6Extractor, self).__init__(max_context_length=384, normalize=normalize)
+        self._model = SentenceTransformersEmbedding(model_name="all-MiniLM-L6-v2", backend=backend, quantization=quantization, pooling=pooling, max_tokens=max_tokens, truncation=truncation, device=device)
 
     def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
         return self._model.embed_ctx(texts)
 
-    def extract_query_embeddings(self, query: str) -> List[float]:
+    def _embed_query(self, query: str) -> List[float]:
         return self._model.embed_query(query)
 
     def info(self) -> ExtractorInfo:
@@ -88,4 +145,412 @@ class MiniLML6Extractor(BaseEmbeddingExtractor):
             output_datatype="embedding",
             input_params=EmbeddingInputParams.schema_json(),
             output_schema=EmbeddingSchema(distance_metric="cosine", dim=384),
-        )        
\ No newline at end of file
+        )
+
+class MPNetV2Extractor(BaseEmbeddingExtractor):
+
+    def __init__(
+        self, backend: str = "torch", quantization: Optional[str] = None, pooling: str = "mean",
+        max_tokens: Optional[int] = None, truncation: str = "head", device: str = "cpu", normalize: bool = False,
+    ):
+        super(MPNetV2Extractor, self).__init__(max_context_length=384, normalize=normalize)
+        self._model = SentenceTransformersEmbedding(model_name="all-mpnet-base-v2", backend=backend, quantization=quantization, pooling=pooling, max_tokens=max_tokens, truncation=truncation, device=device)
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name="MPNetV2",
+            description="all-mpnet-base-v2 Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=768),
+        )
+
+class MultilingualMiniLML12Extractor(BaseEmbeddingExtractor):
+
+    def __init__(
+        self, backend: str = "torch", quantization: Optional[str] = None, pooling: str = "mean",
+        max_tokens: Optional[int] = None, truncation: str = "head", device: str = "cpu", normalize: bool = False,
+    ):
+        super(MultilingualMiniLML12Extractor, self).__init__(max_context_length=128, normalize=normalize)
+        self._model = SentenceTransformersEmbedding(model_name="paraphrase-multilingual-MiniLM-L12-v2", backend=backend, quantization=quantization, pooling=pooling, max_tokens=max_tokens, truncation=truncation, device=device)
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name="MultilingualMiniLML12",
+            description="paraphrase-multilingual-MiniLM-L12-v2 Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=384),
+        )
+
+class DistilUSEExtractor(BaseEmbeddingExtractor):
+
+    def __init__(
+        self, backend: str = "torch", quantization: Optional[str] = None, pooling: str = "mean",
+        max_tokens: Optional[int] = None, truncation: str = "head", device: str = "cpu", normalize: bool = False,
+    ):
+        super(DistilUSEExtractor, self).__init__(max_context_length=128, normalize=normalize)
+        self._model = SentenceTransformersEmbedding(model_name="distiluse-base-multilingual-cased-v2", dense_layer="2_Dense", backend=backend, quantization=quantization, pooling=pooling, max_tokens=max_tokens, truncation=truncation, device=device)
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name="DistilUSE",
+            description="distiluse-base-multilingual-cased-v2 Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=512),
+        )
+
+class LocalSentenceTransformerExtractor(BaseEmbeddingExtractor):
+    """
+    Loads a sentence transformer saved to a local directory, for environments
+    which can't download models from the hub.
+    """
+
+    def __init__(
+        self, path: str, dense_layer: Optional[str] = None, max_length: int = 512, backend: str = "torch",
+        quantization: Optional[str] = None, output_dimensions: Optional[int] = None, pooling: str = "mean",
+        max_tokens: Optional[int] = None, truncation: str = "head", device: str = "cpu", normalize: bool = False,
+    ):
+        super(LocalSentenceTransformerExtractor, self).__init__(max_context_length=max_length, normalize=normalize)
+        if not os.path.isdir(path):
+            raise ValueError(f"model directory not found: {path}")
+        self._path = path
+        self._model = SentenceTransformersEmbedding(
+            model_name=path, dense_layer=dense_layer, backend=backend, quantization=quantization,
+            output_dimensions=output_dimensions, pooling=pooling, max_tokens=max_tokens, truncation=truncation,
+            device=device,
+        )
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=os.path.basename(os.path.normpath(self._path)),
+            description=f"Sentence Transformer Embeddings loaded from {self._path}",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._model.dimensions()),
+        )
+
+class E5Extractor(BaseEmbeddingExtractor):
+    """
+    E5 models are trained with "query: " and "passage: " prefixes and produce
+    poor embeddings without them, so they are added here rather than by callers.
+    """
+
+    def __init__(
+        self, model_name: str = "intfloat/e5-small-v2", dim: int = 384, backend: str = "torch", quantization: Optional[str] = None, pooling: str = "mean",
+        max_tokens: Optional[int] = None, truncation: str = "head", device: str = "cpu", normalize: bool = False,
+    ):
+        super(E5Extractor, self).__init__(max_context_length=512, normalize=normalize)
+        self._model_name = model_name
+        self._dim = dim
+        self._model = SentenceTransformersEmbedding(model_name=model_name, backend=backend, quantization=quantization, pooling=pooling, max_tokens=max_tokens, truncation=truncation, device=device)
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx([f"passage: {text}" for text in texts])
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(f"query: {query}")
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name.split("/")[-1],
+            description="E5 Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._dim),
+        )
+
+class E5BaseExtractor(E5Extractor):
+    def __init__(
+        self, backend: str = "torch", quantization: Optional[str] = None, pooling: str = "mean",
+        max_tokens: Optional[int] = None, truncation: str = "head", device: str = "cpu", normalize: bool = False,
+    ):
+        super(E5BaseExtractor, self).__init__(model_name="intfloat/e5-base-v2", dim=768, backend=backend, quantization=quantization, pooling=pooling, max_tokens=max_tokens, truncation=truncation, device=device, normalize=normalize)
+
+class GTEExtractor(BaseEmbeddingExtractor):
+
+    def __init__(
+        self, model_name: str = "thenlper/gte-small", dim: int = 384, backend: str = "torch", quantization: Optional[str] = None, pooling: str = "mean",
+        max_tokens: Optional[int] = None, truncation: str = "head", device: str = "cpu", normalize: bool = False,
+    ):
+        super(GTEExtractor, self).__init__(max_context_length=512, normalize=normalize)
+        self._model_name = model_name
+        self._dim = dim
+        self._model = SentenceTransformersEmbedding(model_name=model_name, backend=backend, quantization=quantization, pooling=pooling, max_tokens=max_tokens, truncation=truncation, device=device)
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name.split("/")[-1],
+            description="GTE Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._dim),
+        )
+
+class InstructorInputParams(EmbeddingInputParams):
+    instruction: Optional[str] = None
+
+class InstructorExtractor(BaseEmbeddingExtractor):
+    """
+    Instructor models embed text along with an instruction describing the task.
+    Documents are embedded with `document_instruction` unless the extraction
+    input params carry an `instruction`, and queries with `query_instruction`.
+    """
+    input_params_model = InstructorInputParams
+
+    def __init__(
+        self,
+        model_name: str = "hkunlp/instructor-large",
+        document_instruction: str = DEFAULT_DOCUMENT_INSTRUCTION,
+        query_instruction: str = DEFAULT_QUERY_INSTRUCTION,
+        normalize: bool = False,
+    ):
+        super(InstructorExtractor, self).__init__(max_context_length=512, normalize=normalize)
+        self._model_name = model_name
+        self._document_instruction = document_instruction
+        self._query_instruction = query_instruction
+        self._model = InstructorEmbedding(model_name=model_name)
+
+    def _embed_chunks(self, chunks: List[str], input_params: InstructorInputParams) -> List[List[float]]:
+        return self._model.embed(chunks, input_params.instruction or self._document_instruction)
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed(texts, self._document_instruction)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed([query], self._query_instruction)[0]
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name.split("/")[-1],
+            description="Instructor Embeddings",
+            output_datatype="embedding",
+            input_params=InstructorInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._model.dimensions()),
+        )
+
+class InstructorXLExtractor(InstructorExtractor):
+    def __init__(self, normalize: bool = False):
+        super(InstructorXLExtractor, self).__init__(model_name="hkunlp/instructor-xl", normalize=normalize)
+
+class LlamaCppEmbeddingExtractor(BaseEmbeddingExtractor):
+    """
+    Runs quantized GGUF embedding models, e.g. nomic-embed-text, with llama.cpp.
+    Some models expect the text to be prefixed with the task, which can be set
+    with `document_prefix` and `query_prefix`.
+    """
+
+    def __init__(
+        self,
+        model_path: Optional[str] = None,
+        repo_id: Optional[str] = None,
+        filename: Optional[str] = None,
+        n_ctx: int = 2048,
+        n_threads: Optional[int] = None,
+        document_prefix: str = "",
+        query_prefix: str = "",
+        output_dimensions: Optional[int] = None,
+        normalize: bool = False,
+    ):
+        super(LlamaCppEmbeddingExtractor, self).__init__(max_context_length=n_ctx, normalize=normalize)
+        self._name = (filename or model_path or "").split("/")[-1].removesuffix(".gguf")
+        self._document_prefix = document_prefix
+        self._query_prefix = query_prefix
+        self._model = LlamaCppEmbedding(
+            model_path=model_path, repo_id=repo_id, filename=filename, n_ctx=n_ctx, n_threads=n_threads,
+            output_dimensions=output_dimensions,
+        )
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed([f"{self._document_prefix}{text}" for text in texts])
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed([f"{self._query_prefix}{query}"])[0]
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._name,
+            description="GGUF Embeddings run with llama.cpp",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._model.dimensions()),
+        )
+
+class OpenAIEmbeddingExtractor(BaseEmbeddingExtractor):
+
+    def __init__(
+        self, model_name: str = "text-embedding-3-small", api_key: Optional[str] = None,
+        output_dimensions: Optional[int] = None, max_retries: int = 3, initial_backoff: float = 0.5, normalize: bool = False,
+    ):
+        super(OpenAIEmbeddingExtractor, self).__init__(max_context_length=8191, normalize=normalize)
+        self._model = OpenAIEmbedding(
+            model_name=model_name, api_key=api_key, output_dimensions=output_dimensions,
+            max_retries=max_retries, initial_backoff=initial_backoff,
+        )
+        self._model_name = model_name
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name,
+            description="OpenAI Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._model.dimensions()),
+        )
+
+class CohereEmbeddingExtractor(BaseEmbeddingExtractor):
+
+    def __init__(
+        self, model_name: str = "embed-english-v3.0", api_key: Optional[str] = None,
+        max_retries: int = 3, initial_backoff: float = 0.5, normalize: bool = False,
+    ):
+        super(CohereEmbeddingExtractor, self).__init__(max_context_length=2048, normalize=normalize)
+        self._model = CohereEmbedding(
+            model_name=model_name, api_key=api_key, max_retries=max_retries, initial_backoff=initial_backoff,
+        )
+        self._model_name = model_name
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name,
+            description="Cohere Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._model.dimensions()),
+        )
+
+class HFInferenceExtractor(BaseEmbeddingExtractor):
+
+    def __init__(
+        self,
+        model_name: str = "sentence-transformers/all-MiniLM-L6-v2",
+        api_token: Optional[str] = None,
+        endpoint_url: Optional[str] = None,
+        dim: Optional[int] = None,
+        max_length: int = 512,
+        max_retries: int = 3,
+        initial_backoff: float = 0.5,
+        normalize: bool = False,
+    ):
+        super(HFInferenceExtractor, self).__init__(max_context_length=max_length, normalize=normalize)
+        self._model = HFInferenceEmbedding(
+            model_name=model_name, api_token=api_token, endpoint_url=endpoint_url,
+            max_retries=max_retries, initial_backoff=initial_backoff,
+        )
+        self._model_name = model_name
+        # Hub models don't advertise their dimensions, so ask the model once
+        self._dim = dim or len(self._model.embed_query("indexify"))
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name.split("/")[-1],
+            description="Hugging Face Inference API Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._dim),
+        )
+
+class OllamaEmbeddingExtractor(BaseEmbeddingExtractor):
+
+    def __init__(
+        self,
+        model_name: str = "nomic-embed-text",
+        url: str = OLLAMA_DEFAULT_URL,
+        dim: Optional[int] = None,
+        max_length: int = 2048,
+        max_retries: int = 3,
+        initial_backoff: float = 0.5,
+        normalize: bool = False,
+    ):
+        super(OllamaEmbeddingExtractor, self).__init__(max_context_length=max_length, normalize=normalize)
+        self._model = OllamaEmbedding(
+            model_name=model_name, url=url, max_retries=max_retries, initial_backoff=initial_backoff,
+        )
+        self._model_name = model_name
+        self._dim = dim or len(self._model.embed_query("indexify"))
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name,
+            description="Ollama Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._dim),
+        )
+
+class BedrockEmbeddingExtractor(BaseEmbeddingExtractor):
+
+    def __init__(self, model_name: str = "amazon.titan-embed-text-v1", region: Optional[str] = None, normalize: bool = False):
+        super(BedrockEmbeddingExtractor, self).__init__(max_context_length=2048, normalize=normalize)
+        self._model = BedrockEmbedding(model_name=model_name, region=region)
+        self._model_name = model_name
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return self._model.embed_ctx(texts)
+
+    def _embed_query(self, query: str) -> List[float]:
+        return self._model.embed_query(query)
+
+    def info(self) -> ExtractorInfo:
+        return ExtractorInfo(
+            name=self._model_name,
+            description="AWS Bedrock Embeddings",
+            output_datatype="embedding",
+            input_params=EmbeddingInputParams.schema_json(),
+            output_schema=EmbeddingSchema(distance_metric="cosine", dim=self._model.dimensions()),
+        )
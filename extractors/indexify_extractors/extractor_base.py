@@ -42,4 +42,11 @@ class Extractor(ABC):
 
     @abstractmethod
     def info(self) -> ExtractorInfo:
-        pass
\ No newline at end of file
+        pass
+
+    def model_info(self) -> dict[str, Any]:
+        """
+        Runtime details of the extractor's model - max_sequence_length, backend
+        and device. Keys which don't apply to the extractor are left out.
+        """
+        return {}
\ No newline at end of file
@@ -0,0 +1,47 @@
+import os
+
+from typing import List, Optional
+
+from .retry import RetryPolicy, post_with_retry
+
+HF_INFERENCE_URL = "https://api-inference.huggingface.co/pipeline/feature-extraction"
+
+
+class HFInferenceEmbedding:
+
+    def __init__(
+        self,
+        model_name: str = "sentence-transformers/all-MiniLM-L6-v2",
+        api_token: Optional[str] = None,
+        endpoint_url: Optional[str] = None,
+        max_retries: int = 3,
+        initial_backoff: float = 0.5,
+    ) -> None:
+        self._model_name = model_name
+        self._retry_policy = RetryPolicy(max_retries=max_retries, initial_backoff=initial_backoff)
+        # Dedicated inference endpoints serve a single model at their own url
+        self._url = endpoint_url or f"{HF_INFERENCE_URL}/{model_name}"
+        self._api_token = api_token or os.environ.get("HUGGINGFACE_API_TOKEN")
+
+    def embed_ctx(self, inputs: List[str]) -> List[List[float]]:
+        return self._embed(inputs)
+
+    def embed_query(self, query: str) -> List[float]:
+        return self._embed([query])[0]
+
+    def _embed(self, inputs: List[str]) -> List[List[float]]:
+        headers = {"Authorization": f"Bearer {self._api_token}"} if self._api_token else {}
+        response = post_with_retry(
+            self._url,
+            self._retry_policy,
+            headers=headers,
+            json={"inputs": inputs, "options": {"wait_for_model": True}},
+        )
+        return [self._pool(output) for output in response.json()]
+
+    def _pool(self, output) -> List[float]:
+        # Sentence transformer models return one vector per input, plain
+        # transformer models return one vector per token which are mean pooled.
+        if output and isinstance(output[0], list):
+            return [sum(column) / len(output) for column in zip(*output)]
+        return output
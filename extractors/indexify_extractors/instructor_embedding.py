@@ -0,0 +1,21 @@
+from typing import List
+
+DEFAULT_DOCUMENT_INSTRUCTION = "Represent the document for retrieval:"
+DEFAULT_QUERY_INSTRUCTION = "Represent the question for retrieving supporting documents:"
+
+
+class InstructorEmbedding:
+
+    def __init__(self, model_name: str = "hkunlp/instructor-large") -> None:
+        # InstructorEmbedding is an optional dependency, only needed by this extractor
+        from InstructorEmbedding import INSTRUCTOR
+
+        self._model = INSTRUCTOR(model_name)
+
+    def dimensions(self) -> int:
+        return self._model.get_sentence_embedding_dimension()
+
+    def embed(self, inputs: List[str], instruction: str) -> List[List[float]]:
+        # The model pools over the text only, the instruction just conditions it
+        embeddings = self._model.encode([[instruction, input] for input in inputs], normalize_embeddings=True)
+        return embeddings.tolist()
@@ -0,0 +1,40 @@
+from typing import List, Optional
+
+from .matryoshka import truncate_embeddings
+
+
+class LlamaCppEmbedding:
+    """
+    Embeds text with GGUF models through llama.cpp. Models are loaded from a
+    local `model_path`, or downloaded from the hub with `repo_id` and `filename`.
+    """
+
+    def __init__(
+        self,
+        model_path: Optional[str] = None,
+        repo_id: Optional[str] = None,
+        filename: Optional[str] = None,
+        n_ctx: int = 2048,
+        n_threads: Optional[int] = None,
+        output_dimensions: Optional[int] = None,
+    ) -> None:
+        # llama-cpp-python is an optional dependency, only needed by this extractor
+        from llama_cpp import Llama
+
+        kwargs = dict(embedding=True, n_ctx=n_ctx, n_threads=n_threads, verbose=False)
+        if model_path is not None:
+            self._model = Llama(model_path=model_path, **kwargs)
+        elif repo_id is not None and filename is not None:
+            self._model = Llama.from_pretrained(repo_id=repo_id, filename=filename, **kwargs)
+        else:
+            raise ValueError("either model_path or repo_id and filename are required")
+        self._output_dimensions = output_dimensions
+
+    def dimensions(self) -> int:
+        return self._output_dimensions or self._model.n_embd()
+
+    def embed(self, inputs: List[str]) -> List[List[float]]:
+        embeddings = self._model.embed(inputs, normalize=True)
+        if self._output_dimensions is not None:
+            return truncate_embeddings(embeddings, self._output_dimensions)
+        return embeddings
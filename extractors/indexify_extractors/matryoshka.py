@@ -0,0 +1,22 @@
+import math
+from typing import List
+
+
+def l2_normalize(embeddings: List[List[float]]) -> List[List[float]]:
+    normalized = []
+    for embedding in embeddings:
+        norm = math.sqrt(sum(e * e for e in embedding)) or 1.0
+        normalized.append([e / norm for e in embedding])
+    return normalized
+
+
+def truncate_embeddings(embeddings: List[List[float]], output_dimensions: int) -> List[List[float]]:
+    """
+    Models trained with Matryoshka representation learning keep most of their
+    quality in the leading dimensions, so their embeddings can be shortened by
+    keeping the first `output_dimensions` and normalizing them again.
+    """
+    for embedding in embeddings:
+        if output_dimensions > len(embedding):
+            raise ValueError(f"can't truncate {len(embedding)} dimensional embeddings to {output_dimensions}")
+    return l2_normalize([embedding[:output_dimensions] for embedding in embeddings])
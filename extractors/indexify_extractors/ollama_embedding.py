@@ -0,0 +1,31 @@
+from typing import List
+
+from .retry import RetryPolicy, post_with_retry
+
+OLLAMA_DEFAULT_URL = "http://localhost:11434"
+
+
+class OllamaEmbedding:
+
+    def __init__(
+        self, model_name: str = "nomic-embed-text", url: str = OLLAMA_DEFAULT_URL,
+        max_retries: int = 3, initial_backoff: float = 0.5,
+    ) -> None:
+        self._model_name = model_name
+        self._retry_policy = RetryPolicy(max_retries=max_retries, initial_backoff=initial_backoff)
+        self._url = url.rstrip("/")
+
+    def embed_ctx(self, inputs: List[str]) -> List[List[float]]:
+        return [self._embed(input) for input in inputs]
+
+    def embed_query(self, query: str) -> List[float]:
+        return self._embed(query)
+
+    def _embed(self, input: str) -> List[float]:
+        # The embeddings endpoint takes a single prompt per request
+        response = post_with_retry(
+            f"{self._url}/api/embeddings",
+            self._retry_policy,
+            json={"model": self._model_name, "prompt": input},
+        )
+        return response.json()["embedding"]
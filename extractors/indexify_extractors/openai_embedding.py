@@ -0,0 +1,57 @@
+import os
+
+from typing import List, Optional
+
+from .retry import RetryPolicy, post_with_retry
+
+OPENAI_EMBEDDINGS_URL = "https://api.openai.com/v1/embeddings"
+
+OPENAI_MODEL_DIMENSIONS = {
+    "text-embedding-3-small": 1536,
+    "text-embedding-3-large": 3072,
+    "text-embedding-ada-002": 1536,
+}
+
+
+class OpenAIEmbedding:
+
+    def __init__(
+        self, model_name: str = "text-embedding-3-small", api_key: Optional[str] = None,
+        output_dimensions: Optional[int] = None, max_retries: int = 3, initial_backoff: float = 0.5,
+    ) -> None:
+        if model_name not in OPENAI_MODEL_DIMENSIONS:
+            raise ValueError(f"unsupported openai embedding model: {model_name}")
+        # The text-embedding-3 models are trained to be shortened by the api
+        if output_dimensions is not None and not model_name.startswith("text-embedding-3"):
+            raise ValueError(f"{model_name} doesn't support output_dimensions")
+        if output_dimensions is not None and output_dimensions > OPENAI_MODEL_DIMENSIONS[model_name]:
+            raise ValueError(f"{model_name} has at most {OPENAI_MODEL_DIMENSIONS[model_name]} dimensions")
+        self._model_name = model_name
+        self._retry_policy = RetryPolicy(max_retries=max_retries, initial_backoff=initial_backoff)
+        self._output_dimensions = output_dimensions
+        self._api_key = api_key or os.environ.get("OPENAI_API_KEY")
+        if not self._api_key:
+            raise ValueError("openai api key is not set, pass api_key or set OPENAI_API_KEY")
+
+    def dimensions(self) -> int:
+        return self._output_dimensions or OPENAI_MODEL_DIMENSIONS[self._model_name]
+
+    def embed_ctx(self, inputs: List[str]) -> List[List[float]]:
+        return self._embed(inputs)
+
+    def embed_query(self, query: str) -> List[float]:
+        return self._embed([query])[0]
+
+    def _embed(self, inputs: List[str]) -> List[List[float]]:
+        body = {"model": self._model_name, "input": inputs}
+        if self._output_dimensions is not None:
+            body["dimensions"] = self._output_dimensions
+        response = post_with_retry(
+            OPENAI_EMBEDDINGS_URL,
+            self._retry_policy,
+            headers={"Authorization": f"Bearer {self._api_key}"},
+            json=body,
+        )
+        # Results are not guaranteed to be in input order
+        data = sorted(response.json()["data"], key=lambda d: d["index"])
+        return [d["embedding"] for d in data]
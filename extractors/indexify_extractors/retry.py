@@ -0,0 +1,50 @@
+import random
+import time
+from dataclasses import dataclass
+from typing import Optional
+
+import requests
+
+# Rate limits and server errors of remote embedding apis are usually transient
+RETRYABLE_STATUS_CODES = {429, 500, 502, 503, 504}
+
+
+@dataclass
+class RetryPolicy:
+    max_retries: int = 3
+    initial_backoff: float = 0.5
+    max_backoff: float = 30.0
+
+    def backoff(self, attempt: int) -> float:
+        # Full jitter keeps clients which were rate limited together from
+        # retrying together
+        return random.uniform(0, min(self.max_backoff, self.initial_backoff * 2 ** attempt))
+
+
+def retry_after(response: requests.Response) -> Optional[float]:
+    value = response.headers.get("Retry-After")
+    try:
+        return float(value) if value is not None else None
+    except ValueError:
+        return None
+
+
+def post_with_retry(url: str, retry_policy: RetryPolicy, **kwargs) -> requests.Response:
+    """
+    POSTs to a remote api, retrying connection errors, rate limits and server
+    errors with exponential backoff. Other errors are raised straight away.
+    """
+    for attempt in range(retry_policy.max_retries + 1):
+        last_attempt = attempt == retry_policy.max_retries
+        try:
+            response = requests.post(url, **kwargs)
+        except (requests.ConnectionError, requests.Timeout):
+            if last_attempt:
+                raise
+            time.sleep(retry_policy.backoff(attempt))
+            continue
+        if response.status_code not in RETRYABLE_STATUS_CODES or last_attempt:
+            response.raise_for_status()
+            return response
+        delay = retry_after(response)
+        time.sleep(min(delay, retry_policy.max_backoff) if delay is not None else retry_policy.backoff(attempt))
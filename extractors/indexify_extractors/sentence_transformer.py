@@ -1,21 +1,155 @@
 from transformers import AutoTokenizer, AutoModel
+from huggingface_hub import hf_hub_download
+import json
+import logging
+import os
 import torch
 import torch.nn.functional as F
 
-from typing import List
+from typing import List, Optional
 
 def mean_pooling(model_output, attention_mask):
     token_embeddings = model_output[0] #First element of model_output contains all token embeddings
     input_mask_expanded = attention_mask.unsqueeze(-1).expand(token_embeddings.size()).float()
     return torch.sum(token_embeddings * input_mask_expanded, 1) / torch.clamp(input_mask_expanded.sum(1), min=1e-9)
 
+def cls_pooling(model_output, attention_mask):
+    return model_output[0][:, 0]
+
+def max_pooling(model_output, attention_mask):
+    token_embeddings = model_output[0]
+    input_mask_expanded = attention_mask.unsqueeze(-1).expand(token_embeddings.size())
+    # Padding tokens must not be picked as the maximum
+    token_embeddings = token_embeddings.masked_fill(input_mask_expanded == 0, -1e9)
+    return torch.max(token_embeddings, 1)[0]
+
+POOLING_STRATEGIES = {
+    'mean': mean_pooling,
+    'cls': cls_pooling,
+    'max': max_pooling,
+}
+
+TRUNCATION_STRATEGIES = ('head', 'tail', 'head_tail')
+
+def truncate_tokens(ids: List[int], max_length: int, strategy: str) -> List[int]:
+    """
+    Keeps at most `max_length` tokens from the start (head), the end (tail),
+    or half from each end (head_tail) of the text.
+    """
+    if len(ids) <= max_length:
+        return ids
+    if strategy == 'head':
+        return ids[:max_length]
+    if strategy == 'tail':
+        return ids[len(ids) - max_length:]
+    head = max_length // 2
+    return ids[:head] + ids[len(ids) - (max_length - head):]
+
+def model_file(repo_id: str, filename: str) -> str:
+    # Models saved to a local directory are loaded without going to the hub
+    if os.path.isdir(repo_id):
+        return os.path.join(repo_id, filename)
+    return hf_hub_download(repo_id, filename)
+
+def load_dense_layer(repo_id: str, layer_dir: str) -> torch.nn.Module:
+    # Some sentence transformers (e.g. distiluse) project the pooled output
+    # through a Dense layer which is stored next to the transformer weights.
+    with open(model_file(repo_id, f'{layer_dir}/config.json')) as f:
+        config = json.load(f)
+    linear = torch.nn.Linear(config['in_features'], config['out_features'], bias=config.get('bias', True))
+    state_dict = torch.load(model_file(repo_id, f'{layer_dir}/pytorch_model.bin'), map_location='cpu')
+    linear.load_state_dict({k.removeprefix('linear.'): v for k, v in state_dict.items()})
+    if config.get('activation_function', '').endswith('Tanh'):
+        return torch.nn.Sequential(linear, torch.nn.Tanh())
+    return linear
+
+
+def resolve_device(device: str) -> torch.device:
+    """
+    Checks that a device like "cpu", "cuda" or "cuda:1" is available before
+    any weights are loaded on it. "mps" falls back to the cpu where Apple's
+    Metal GPUs aren't available.
+    """
+    resolved = torch.device(device)
+    if resolved.type == 'mps':
+        if torch.backends.mps.is_available():
+            return resolved
+        logging.warning("mps is not available, running on the cpu")
+        return torch.device('cpu')
+    if resolved.type == 'cuda':
+        if not torch.cuda.is_available():
+            raise ValueError(f"device {device} is not available, CUDA isn't available")
+        index = resolved.index or 0
+        if index >= torch.cuda.device_count():
+            raise ValueError(f"device {device} is not available, found {torch.cuda.device_count()} CUDA devices")
+    elif resolved.type != 'cpu':
+        raise ValueError(f"unsupported device: {device}")
+    return resolved
+
+
+def load_transformer(repo_id: str, backend: str, quantization: Optional[str] = None):
+    if quantization not in (None, 'int8'):
+        raise ValueError(f"unsupported quantization: {quantization}")
+    if backend == 'torch':
+        model = AutoModel.from_pretrained(repo_id)
+        if quantization == 'int8':
+            # Dynamic quantization stores the weights of Linear layers as int8
+            # and quantizes activations on the fly, which speeds up CPU inference.
+            model = torch.quantization.quantize_dynamic(model, {torch.nn.Linear}, dtype=torch.qint8)
+        return model
+    if quantization is not None:
+        raise ValueError("quantization is only supported with the torch backend")
+    if backend == 'onnx':
+        # optimum is only needed for the onnx backend. The model is exported to
+        # ONNX on load, and runs on ONNX Runtime rather than libtorch.
+        from optimum.onnxruntime import ORTModelForFeatureExtraction
+        return ORTModelForFeatureExtraction.from_pretrained(repo_id, export=True)
+    raise ValueError(f"unsupported backend: {backend}")
+
 
 class SentenceTransformersEmbedding:
 
-    def __init__(self, model_name) -> None:
+    def __init__(
+        self, model_name, dense_layer: Optional[str] = None, backend: str = "torch",
+        quantization: Optional[str] = None, output_dimensions: Optional[int] = None,
+        pooling: str = "mean", max_tokens: Optional[int] = None, truncation: str = "head",
+        device: str = "cpu",
+    ) -> None:
+        if pooling not in POOLING_STRATEGIES:
+            raise ValueError(f"unsupported pooling: {pooling}")
+        if truncation not in TRUNCATION_STRATEGIES:
+            raise ValueError(f"unsupported truncation: {truncation}")
+        self._device = resolve_device(device)
+        if self._device.type != 'cpu' and (backend != 'torch' or quantization is not None):
+            raise ValueError("devices other than cpu are only supported with the torch backend, without quantization")
+        self._max_tokens = max_tokens
+        self._truncation = truncation
         self._model_name = model_name
-        self._tokenizer = AutoTokenizer.from_pretrained(f'sentence-transformers/{model_name}')
-        self._model = AutoModel.from_pretrained(f'sentence-transformers/{model_name}')
+        self._pooling = POOLING_STRATEGIES[pooling]
+        self._output_dimensions = output_dimensions
+        # Bare names refer to the sentence-transformers organization on the hub,
+        # unless they are a local directory the model was saved to
+        if '/' in model_name or os.path.isdir(model_name):
+            repo_id = model_name
+        else:
+            repo_id = f'sentence-transformers/{model_name}'
+        self._tokenizer = AutoTokenizer.from_pretrained(repo_id)
+        self._backend = backend
+        self._model = load_transformer(repo_id, backend, quantization)
+        self._dense = load_dense_layer(repo_id, dense_layer) if dense_layer else None
+        if self._device.type != 'cpu':
+            self._model = self._model.to(self._device)
+            if self._dense is not None:
+                self._dense = self._dense.to(self._device)
+
+    def dimensions(self) -> int:
+        if self._output_dimensions is not None:
+            return self._output_dimensions
+        if isinstance(self._dense, torch.nn.Sequential):
+            return self._dense[0].out_features
+        if self._dense is not None:
+            return self._dense.out_features
+        return self._model.config.hidden_size
 
     def embed_ctx(self, inputs: List[str])  -> List[List[float]]:
         result = self._embed(inputs)
@@ -25,13 +159,45 @@ class SentenceTransformersEmbedding:
        result = self._embed([query])
        return result[0].tolist()
     
+    def _encode(self, inputs: List[str]):
+        if self._max_tokens is None and self._truncation == 'head':
+            return self._tokenizer(inputs, padding=True, truncation=True, return_tensors='pt')
+        max_tokens = min(self._max_tokens or self._tokenizer.model_max_length, self._tokenizer.model_max_length)
+        max_length = max_tokens - self._tokenizer.num_special_tokens_to_add()
+        encoded_inputs = []
+        for input in inputs:
+            ids = self._tokenizer(input, add_special_tokens=False)['input_ids']
+            ids = truncate_tokens(ids, max_length, self._truncation)
+            encoded_inputs.append(self._tokenizer.prepare_for_model(ids, add_special_tokens=True))
+        return self._tokenizer.pad(encoded_inputs, return_tensors='pt')
+
     def _embed(self, inputs: List[str]) -> torch.Tensor:
-        encoded_input = self._tokenizer(inputs, padding=True, truncation=True, return_tensors='pt')
+        encoded_input = self._encode(inputs)
+        if self._device.type != 'cpu':
+            encoded_input = encoded_input.to(self._device)
         with torch.no_grad():
             model_output = self._model(**encoded_input)
-        sentence_embeddings = mean_pooling(model_output, encoded_input['attention_mask'])
-        return F.normalize(sentence_embeddings, p=2, dim=1)
+        sentence_embeddings = self._pooling(model_output, encoded_input['attention_mask'])
+        if self._dense is not None:
+            with torch.no_grad():
+                sentence_embeddings = self._dense(sentence_embeddings)
+        if self._output_dimensions is not None:
+            # Matryoshka models can be truncated to their leading dimensions
+            sentence_embeddings = sentence_embeddings[:, :self._output_dimensions]
+        return F.normalize(sentence_embeddings, p=2, dim=1).cpu()
     
+    def model_info(self) -> dict:
+        max_length = self._tokenizer.model_max_length
+        return {
+            "max_sequence_length": min(self._max_tokens or max_length, max_length),
+            "backend": self._backend,
+            "device": str(self._device),
+        }
+
+    def count_tokens(self, inputs: List[str]) -> List[int]:
+        # Counts include special tokens, and are not limited by truncation
+        return [len(ids) for ids in self._tokenizer(inputs)['input_ids']]
+
     def tokenizer_encode(self, inputs: List[str]) -> List[List[int]]:
         return self._tokenizer.batch_encode_plus(inputs)['input_ids']
 
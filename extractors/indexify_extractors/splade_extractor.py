@@ -0,0 +1,53 @@
+import json
+import torch
+from transformers import AutoModelForMaskedLM, AutoTokenizer
+from typing import List
+
+from .extractor_base import Content, ExtractedAttributes, Extractor, ExtractorInfo
+
+
+class SpladeEmbedding:
+
+    def __init__(self, model_name: str = "naver/splade-cocondenser-ensembledistil") -> None:
+        self._tokenizer = AutoTokenizer.from_pretrained(model_name)
+        self._model = AutoModelForMaskedLM.from_pretrained(model_name)
+
+    def embed(self, inputs: List[str]) -> List[dict]:
+        encoded_input = self._tokenizer(inputs, padding=True, truncation=True, return_tensors='pt')
+        with torch.no_grad():
+            logits = self._model(**encoded_input).logits
+        # SPLADE term weights are max pooled log saturated MLM logits
+        weights = torch.log1p(torch.relu(logits)) * encoded_input['attention_mask'].unsqueeze(-1)
+        weights = torch.max(weights, dim=1).values
+        result = []
+        for row in weights:
+            indices = row.nonzero().squeeze(-1)
+            result.append({"indices": indices.tolist(), "values": row[indices].tolist()})
+        return result
+
+
+class SpladeExtractor(Extractor):
+
+    def __init__(self, model_name: str = "naver/splade-cocondenser-ensembledistil"):
+        self._model = SpladeEmbedding(model_name=model_name)
+
+    def extract(self, content: List[Content], params: dict[str, str]) -> List[ExtractedAttributes]:
+        texts = [c.data for c in content]
+        sparse_vectors = self._model.embed(texts)
+        return [
+            ExtractedAttributes(content_id=c.id, json=json.dumps(vector))
+            for (c, vector) in zip(content, sparse_vectors)
+        ]
+
+    def extract_query_embeddings(self, query: str) -> dict:
+        return self._model.embed([query])[0]
+
+    def info(self) -> ExtractorInfo:
+        schema = {"indices": "list[int]", "values": "list[float]"}
+        return ExtractorInfo(
+            name="SpladeExtractor",
+            description="SPLADE sparse lexical embeddings",
+            output_datatype="attributes",
+            input_params=json.dumps({}),
+            output_schema=json.dumps(schema),
+        )
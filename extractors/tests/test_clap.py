@@ -0,0 +1,25 @@
+import unittest
+
+import numpy as np
+
+from indexify_extractors.clap_extractor import CLAPEmbedding
+
+
+class TestCLAP(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestCLAP, self).__init__(*args, **kwargs)
+
+    def test_audio_embeddings(self):
+        clap = CLAPEmbedding()
+        # one second of a 440Hz tone sampled at 16kHz
+        tone = np.sin(2 * np.pi * 440 * np.arange(16000) / 16000).astype(np.float32)
+        embeddings = clap.embed_audio([tone], sampling_rate=16000)
+        self.assertEqual(len(embeddings), 1)
+        self.assertEqual(len(embeddings[0]), 512)
+        query = clap.embed_query("a sine wave")
+        self.assertEqual(len(query), 512)
+
+
+if __name__ == "__main__":
+    unittest.main()
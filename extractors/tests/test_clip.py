@@ -0,0 +1,30 @@
+import base64
+import io
+import unittest
+
+from PIL import Image
+
+from indexify_extractors.clip_extractor import CLIPImageExtractor
+from indexify_extractors.extractor_base import Content
+
+
+class TestCLIP(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestCLIP, self).__init__(*args, **kwargs)
+
+    def test_image_embeddings(self):
+        buffer = io.BytesIO()
+        Image.new("RGB", (64, 64), color="red").save(buffer, format="PNG")
+        data = base64.b64encode(buffer.getvalue()).decode()
+        extractor = CLIPImageExtractor()
+        embeddings = extractor.extract([Content(id="1", data=data)], {})
+        self.assertEqual(len(embeddings), 1)
+        self.assertEqual(len(embeddings[0].embeddings), 512)
+        self.assertEqual(embeddings[0].text, "")
+        query = extractor.extract_query_embeddings("a red square")
+        self.assertEqual(len(query), 512)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,26 @@
+import unittest
+from indexify_extractors.colbert_extractor import ColBERTEmbedding, maxsim
+
+
+class TestColBERT(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestColBERT, self).__init__(*args, **kwargs)
+
+    def test_maxsim(self):
+        query = [[1.0, 0.0], [0.0, 1.0]]
+        document = [[1.0, 0.0], [0.5, 0.5]]
+        self.assertAlmostEqual(maxsim(query, document), 1.5)
+
+    def test_embeddings(self):
+        colbert = ColBERTEmbedding()
+        docs = colbert.embed_docs(["hello world", "indexify is a retrieval service"])
+        self.assertEqual(len(docs), 2)
+        self.assertEqual(len(docs[0][0]), 128)
+        query = colbert.embed_query("what is indexify?")
+        self.assertEqual(len(query), 32)
+        self.assertGreater(maxsim(query, docs[1]), maxsim(query, docs[0]))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,52 @@
+import unittest
+from typing import List
+
+from indexify_extractors.embedding_extractor import BaseEmbeddingExtractor
+from indexify_extractors.extractor_base import Content
+
+
+class ScaledExtractor(BaseEmbeddingExtractor):
+
+    def __init__(self, normalize: bool = False):
+        super(ScaledExtractor, self).__init__(max_context_length=128, normalize=normalize)
+
+    def extract_embeddings(self, texts: List[str]) -> List[List[float]]:
+        return [[3.0, 4.0] for _ in texts]
+
+    def _embed_query(self, query: str) -> List[float]:
+        return [3.0, 4.0]
+
+    def info(self):
+        ...
+
+
+class TestEmbeddingExtractor(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestEmbeddingExtractor, self).__init__(*args, **kwargs)
+
+    def test_normalize_default(self):
+        extractor = ScaledExtractor()
+        self.assertEqual(extractor.extract_query_embeddings("hello"), [3.0, 4.0])
+        embeddings = extractor.extract([Content(id="1", data="hello")], {})
+        self.assertEqual(embeddings[0].embeddings, [3.0, 4.0])
+
+    def test_normalize_extractor(self):
+        extractor = ScaledExtractor(normalize=True)
+        query = extractor.extract_query_embeddings("hello")
+        self.assertAlmostEqual(query[0], 0.6)
+        self.assertAlmostEqual(query[1], 0.8)
+        embeddings = extractor.extract([Content(id="1", data="hello")], {})
+        self.assertEqual(embeddings[0].embeddings, query)
+
+    def test_normalize_override(self):
+        extractor = ScaledExtractor(normalize=True)
+        embeddings = extractor.extract([Content(id="1", data="hello")], {"normalize": False})
+        self.assertEqual(embeddings[0].embeddings, [3.0, 4.0])
+        extractor = ScaledExtractor()
+        embeddings = extractor.extract([Content(id="1", data="hello")], {"normalize": True})
+        self.assertAlmostEqual(embeddings[0].embeddings[0], 0.6)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,25 @@
+import unittest
+from unittest import mock
+
+from indexify_extractors.instructor_embedding import DEFAULT_DOCUMENT_INSTRUCTION, InstructorEmbedding
+
+
+class TestInstructorEmbedding(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestInstructorEmbedding, self).__init__(*args, **kwargs)
+
+    def test_instruction_is_paired_with_text(self):
+        module = mock.Mock()
+        model = module.INSTRUCTOR.return_value
+        model.encode.return_value.tolist.return_value = [[1.0, 0.0], [0.0, 1.0]]
+        with mock.patch.dict("sys.modules", {"InstructorEmbedding": module}):
+            instructor = InstructorEmbedding()
+        embeddings = instructor.embed(["hello", "world"], DEFAULT_DOCUMENT_INSTRUCTION)
+        self.assertEqual(len(embeddings), 2)
+        pairs = model.encode.call_args.args[0]
+        self.assertEqual(pairs[1], [DEFAULT_DOCUMENT_INSTRUCTION, "world"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,39 @@
+import unittest
+from unittest import mock
+
+from indexify_extractors.llamacpp_embedding import LlamaCppEmbedding
+
+
+class TestLlamaCppEmbedding(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestLlamaCppEmbedding, self).__init__(*args, **kwargs)
+
+    def llama_cpp(self):
+        module = mock.Mock()
+        module.Llama.return_value.n_embd.return_value = 768
+        module.Llama.from_pretrained.return_value.n_embd.return_value = 768
+        return module
+
+    def test_model_path(self):
+        module = self.llama_cpp()
+        with mock.patch.dict("sys.modules", {"llama_cpp": module}):
+            llama = LlamaCppEmbedding(model_path="/models/nomic-embed-text.gguf")
+        self.assertEqual(llama.dimensions(), 768)
+        self.assertEqual(module.Llama.call_args.kwargs["model_path"], "/models/nomic-embed-text.gguf")
+        self.assertTrue(module.Llama.call_args.kwargs["embedding"])
+
+    def test_hub_download(self):
+        module = self.llama_cpp()
+        with mock.patch.dict("sys.modules", {"llama_cpp": module}):
+            LlamaCppEmbedding(repo_id="nomic-ai/nomic-embed-text-v1.5-GGUF", filename="nomic-embed-text-v1.5.Q4_K_M.gguf")
+        self.assertEqual(module.Llama.from_pretrained.call_args.kwargs["repo_id"], "nomic-ai/nomic-embed-text-v1.5-GGUF")
+
+    def test_model_is_required(self):
+        with mock.patch.dict("sys.modules", {"llama_cpp": self.llama_cpp()}):
+            with self.assertRaises(ValueError):
+                LlamaCppEmbedding()
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,29 @@
+import unittest
+
+from indexify_extractors.matryoshka import l2_normalize, truncate_embeddings
+
+
+class TestMatryoshka(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestMatryoshka, self).__init__(*args, **kwargs)
+
+    def test_truncate_embeddings(self):
+        embeddings = truncate_embeddings([[3.0, 4.0, 12.0]], 2)
+        self.assertEqual(len(embeddings[0]), 2)
+        self.assertAlmostEqual(embeddings[0][0], 0.6)
+        self.assertAlmostEqual(embeddings[0][1], 0.8)
+
+    def test_l2_normalize(self):
+        embeddings = l2_normalize([[3.0, 4.0], [0.0, 0.0]])
+        self.assertAlmostEqual(embeddings[0][0], 0.6)
+        self.assertAlmostEqual(embeddings[0][1], 0.8)
+        self.assertEqual(embeddings[1], [0.0, 0.0])
+
+    def test_truncate_to_more_dimensions(self):
+        with self.assertRaises(ValueError):
+            truncate_embeddings([[1.0, 0.0]], 4)
+
+
+if __name__ == "__main__":
+    unittest.main()
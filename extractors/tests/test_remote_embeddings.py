@@ -0,0 +1,133 @@
+import io
+import json
+import unittest
+from unittest import mock
+
+from indexify_extractors.bedrock_embedding import BedrockEmbedding
+from indexify_extractors.cohere_embedding import CohereEmbedding
+from indexify_extractors.hf_inference_embedding import HFInferenceEmbedding
+from indexify_extractors.ollama_embedding import OllamaEmbedding
+from indexify_extractors.openai_embedding import OpenAIEmbedding
+
+
+def mock_response(payload):
+    response = mock.Mock()
+    response.json.return_value = payload
+    return response
+
+
+class TestOpenAIEmbedding(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestOpenAIEmbedding, self).__init__(*args, **kwargs)
+
+    @mock.patch("indexify_extractors.retry.requests.post")
+    def test_embeddings_are_ordered_by_index(self, post):
+        post.return_value = mock_response({"data": [
+            {"index": 1, "embedding": [0.0, 1.0]},
+            {"index": 0, "embedding": [1.0, 0.0]},
+        ]})
+        openai = OpenAIEmbedding(api_key="key")
+        embeddings = openai.embed_ctx(["hello", "world"])
+        self.assertEqual(embeddings, [[1.0, 0.0], [0.0, 1.0]])
+        self.assertEqual(post.call_args.kwargs["json"]["input"], ["hello", "world"])
+
+    def test_unknown_model(self):
+        with self.assertRaises(ValueError):
+            OpenAIEmbedding(model_name="not-a-model", api_key="key")
+
+    @mock.patch("indexify_extractors.retry.requests.post")
+    def test_output_dimensions(self, post):
+        post.return_value = mock_response({"data": [{"index": 0, "embedding": [1.0, 0.0]}]})
+        openai = OpenAIEmbedding(api_key="key", output_dimensions=256)
+        self.assertEqual(openai.dimensions(), 256)
+        openai.embed_query("hello")
+        self.assertEqual(post.call_args.kwargs["json"]["dimensions"], 256)
+        with self.assertRaises(ValueError):
+            OpenAIEmbedding(model_name="text-embedding-ada-002", api_key="key", output_dimensions=256)
+
+
+class TestCohereEmbedding(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestCohereEmbedding, self).__init__(*args, **kwargs)
+
+    @mock.patch("indexify_extractors.retry.requests.post")
+    def test_input_types(self, post):
+        post.return_value = mock_response({"embeddings": [[1.0, 0.0]]})
+        cohere = CohereEmbedding(api_key="key")
+        cohere.embed_ctx(["hello"])
+        self.assertEqual(post.call_args.kwargs["json"]["input_type"], "search_document")
+        cohere.embed_query("hello")
+        self.assertEqual(post.call_args.kwargs["json"]["input_type"], "search_query")
+
+    @mock.patch("indexify_extractors.retry.requests.post")
+    def test_batches(self, post):
+        post.side_effect = lambda *args, **kwargs: mock_response(
+            {"embeddings": [[1.0] for _ in kwargs["json"]["texts"]]})
+        cohere = CohereEmbedding(api_key="key")
+        embeddings = cohere.embed_ctx(["hello"] * 100)
+        self.assertEqual(len(embeddings), 100)
+        self.assertEqual(post.call_count, 2)
+
+
+class TestHFInferenceEmbedding(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestHFInferenceEmbedding, self).__init__(*args, **kwargs)
+
+    @mock.patch("indexify_extractors.retry.requests.post")
+    def test_token_embeddings_are_pooled(self, post):
+        post.return_value = mock_response([[[1.0, 2.0], [3.0, 4.0]]])
+        hf = HFInferenceEmbedding(model_name="bert-base-uncased")
+        self.assertEqual(hf.embed_query("hello"), [2.0, 3.0])
+
+    @mock.patch("indexify_extractors.retry.requests.post")
+    def test_endpoint_url(self, post):
+        post.return_value = mock_response([[1.0, 2.0]])
+        hf = HFInferenceEmbedding(endpoint_url="http://localhost:8080")
+        self.assertEqual(hf.embed_query("hello"), [1.0, 2.0])
+        self.assertEqual(post.call_args.args[0], "http://localhost:8080")
+
+
+class TestOllamaEmbedding(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestOllamaEmbedding, self).__init__(*args, **kwargs)
+
+    @mock.patch("indexify_extractors.retry.requests.post")
+    def test_one_request_per_text(self, post):
+        post.return_value = mock_response({"embedding": [1.0, 0.0]})
+        ollama = OllamaEmbedding(url="http://localhost:11434/")
+        embeddings = ollama.embed_ctx(["hello", "world"])
+        self.assertEqual(len(embeddings), 2)
+        self.assertEqual(post.call_count, 2)
+        self.assertEqual(post.call_args.args[0], "http://localhost:11434/api/embeddings")
+
+
+class TestBedrockEmbedding(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestBedrockEmbedding, self).__init__(*args, **kwargs)
+
+    def bedrock(self, model_name, payload):
+        boto3 = mock.Mock()
+        boto3.client.return_value.invoke_model.side_effect = lambda **kwargs: {
+            "body": io.BytesIO(json.dumps(payload).encode())}
+        with mock.patch.dict("sys.modules", {"boto3": boto3}):
+            return BedrockEmbedding(model_name=model_name), boto3.client.return_value
+
+    def test_titan(self):
+        bedrock, client = self.bedrock("amazon.titan-embed-text-v1", {"embedding": [1.0, 0.0]})
+        self.assertEqual(len(bedrock.embed_ctx(["hello", "world"])), 2)
+        self.assertEqual(client.invoke_model.call_count, 2)
+
+    def test_cohere_input_type(self):
+        bedrock, client = self.bedrock("cohere.embed-english-v3", {"embeddings": [[1.0, 0.0]]})
+        self.assertEqual(bedrock.embed_query("hello"), [1.0, 0.0])
+        body = json.loads(client.invoke_model.call_args.kwargs["body"])
+        self.assertEqual(body["input_type"], "search_query")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,57 @@
+import unittest
+from unittest import mock
+
+import requests
+
+from indexify_extractors.retry import RetryPolicy, post_with_retry
+
+
+def mock_response(status_code, headers=None):
+    response = mock.Mock()
+    response.status_code = status_code
+    response.headers = headers or {}
+    if status_code >= 400:
+        response.raise_for_status.side_effect = requests.HTTPError(str(status_code))
+    return response
+
+
+@mock.patch("indexify_extractors.retry.time.sleep")
+@mock.patch("indexify_extractors.retry.requests.post")
+class TestPostWithRetry(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestPostWithRetry, self).__init__(*args, **kwargs)
+
+    def test_retries_transient_errors(self, post, sleep):
+        post.side_effect = [mock_response(503), requests.ConnectionError(), mock_response(200)]
+        response = post_with_retry("http://api", RetryPolicy(max_retries=3), json={})
+        self.assertEqual(response.status_code, 200)
+        self.assertEqual(post.call_count, 3)
+        self.assertEqual(sleep.call_count, 2)
+
+    def test_gives_up_after_max_retries(self, post, sleep):
+        post.return_value = mock_response(429)
+        with self.assertRaises(requests.HTTPError):
+            post_with_retry("http://api", RetryPolicy(max_retries=2))
+        self.assertEqual(post.call_count, 3)
+
+    def test_does_not_retry_client_errors(self, post, sleep):
+        post.return_value = mock_response(400)
+        with self.assertRaises(requests.HTTPError):
+            post_with_retry("http://api", RetryPolicy())
+        self.assertEqual(post.call_count, 1)
+        sleep.assert_not_called()
+
+    def test_honors_retry_after(self, post, sleep):
+        post.side_effect = [mock_response(429, {"Retry-After": "2"}), mock_response(200)]
+        post_with_retry("http://api", RetryPolicy())
+        sleep.assert_called_once_with(2.0)
+
+    def test_backoff_is_bounded(self, post, sleep):
+        policy = RetryPolicy(initial_backoff=1.0, max_backoff=4.0)
+        for attempt in range(10):
+            self.assertLessEqual(policy.backoff(attempt), 4.0)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -1,6 +1,8 @@
+import tempfile
 import unittest
 from indexify_extractors.sentence_transformer import SentenceTransformersEmbedding
 from indexify_extractors.dpr import DPREmbeddings
+from indexify_extractors.embedding_extractor import MiniLML6Extractor
 
 
 class TestSTEmbeddings(unittest.TestCase):
@@ -20,6 +22,10 @@ class TestSTEmbeddings(unittest.TestCase):
         self.assertEqual(len(embeddings), 2)
         self.assertEqual(len(embeddings[0]), 384)
 
+    def test_count_tokens(self):
+        st = SentenceTransformersEmbedding("all-MiniLM-L6-v2")
+        self.assertEqual(st.count_tokens(["hello world", "hello"]), [4, 3])
+
     def test_tokenize(self):
         st = SentenceTransformersEmbedding("all-MiniLM-L6-v2")
         chunks = st.tokenize(["hello", "world hi"])
@@ -34,6 +40,64 @@ class TestSTEmbeddings(unittest.TestCase):
         self.assertEqual(len(texts), 2)
         self.assertAlmostEqual(texts[0], "hello")
         self.assertAlmostEqual(texts[1], "world hi")
+
+    def test_mpnet_embeddings(self):
+        st = SentenceTransformersEmbedding("all-mpnet-base-v2")
+        embeddings = st.embed_ctx(["hello", "world"])
+        self.assertEqual(len(embeddings), 2)
+        self.assertEqual(len(embeddings[0]), 768)
+
+    def test_dense_layer_embeddings(self):
+        st = SentenceTransformersEmbedding("distiluse-base-multilingual-cased-v2", dense_layer="2_Dense")
+        embeddings = st.embed_ctx(["hello", "welt"])
+        self.assertEqual(len(embeddings), 2)
+        self.assertEqual(len(embeddings[0]), 512)
+
+    def test_onnx_backend(self):
+        st = SentenceTransformersEmbedding("all-MiniLM-L6-v2")
+        onnx = SentenceTransformersEmbedding("all-MiniLM-L6-v2", backend="onnx")
+        embeddings = onnx.embed_query("hello world")
+        self.assertEqual(len(embeddings), 384)
+        for (a, b) in zip(embeddings, st.embed_query("hello world")):
+            self.assertAlmostEqual(a, b, places=4)
+
+    def test_int8_quantization(self):
+        st = SentenceTransformersEmbedding("all-MiniLM-L6-v2")
+        quantized = SentenceTransformersEmbedding("all-MiniLM-L6-v2", quantization="int8")
+        embeddings = quantized.embed_query("hello world")
+        self.assertEqual(len(embeddings), 384)
+        similarity = sum(a * b for (a, b) in zip(embeddings, st.embed_query("hello world")))
+        self.assertGreater(similarity, 0.95)
+
+    def test_pooling(self):
+        mean = SentenceTransformersEmbedding("all-MiniLM-L6-v2")
+        cls = SentenceTransformersEmbedding("all-MiniLM-L6-v2", pooling="cls")
+        embeddings = cls.embed_query("hello world")
+        self.assertEqual(len(embeddings), 384)
+        self.assertNotEqual(embeddings, mean.embed_query("hello world"))
+        with self.assertRaises(ValueError):
+            SentenceTransformersEmbedding("all-MiniLM-L6-v2", pooling="sum")
+
+    def test_extractor_pooling(self):
+        mean = MiniLML6Extractor()
+        cls = MiniLML6Extractor(pooling="cls")
+        self.assertNotEqual(cls.extract_query_embeddings("hello world"), mean.extract_query_embeddings("hello world"))
+
+    def test_extractor_truncation(self):
+        head = MiniLML6Extractor(max_tokens=8)
+        tail = MiniLML6Extractor(max_tokens=8, truncation="tail")
+        text = "the start of a long text which ends somewhere else entirely"
+        self.assertNotEqual(head.extract_query_embeddings(text), tail.extract_query_embeddings(text))
+        self.assertEqual(head.model_info()["max_sequence_length"], 8)
+
+    def test_local_model(self):
+        st = SentenceTransformersEmbedding("all-MiniLM-L6-v2")
+        with tempfile.TemporaryDirectory() as path:
+            st._model.save_pretrained(path)
+            st._tokenizer.save_pretrained(path)
+            local = SentenceTransformersEmbedding(path)
+            self.assertEqual(local.dimensions(), 384)
+            self.assertEqual(local.embed_query("hello world"), st.embed_query("hello world"))
  
 
 
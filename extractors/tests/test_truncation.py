@@ -0,0 +1,22 @@
+import unittest
+
+from indexify_extractors.sentence_transformer import truncate_tokens
+
+
+class TestTruncation(unittest.TestCase):
+
+    def __init__(self, *args, **kwargs):
+        super(TestTruncation, self).__init__(*args, **kwargs)
+
+    def test_short_input(self):
+        self.assertEqual(truncate_tokens([1, 2, 3], 5, "tail"), [1, 2, 3])
+
+    def test_strategies(self):
+        ids = list(range(10))
+        self.assertEqual(truncate_tokens(ids, 4, "head"), [0, 1, 2, 3])
+        self.assertEqual(truncate_tokens(ids, 4, "tail"), [6, 7, 8, 9])
+        self.assertEqual(truncate_tokens(ids, 5, "head_tail"), [0, 1, 7, 8, 9])
+
+
+if __name__ == "__main__":
+    unittest.main()
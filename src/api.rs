@@ -1,13 +1,25 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
+use axum::Json;
+use base64::{engine::general_purpose, Engine};
 use serde::{Deserialize, Serialize};
 use smart_default::SmartDefault;
 use strum_macros::{Display, EnumString};
 use utoipa::{IntoParams, ToSchema};
 
+use crate::auth::AuthError;
+use crate::chunking;
+use crate::data_repository_manager;
+use crate::embedding_jobs;
+use crate::extractors;
+use crate::idempotency::IdempotencyError;
+use crate::index::IndexError;
+use crate::namespace::NamespaceError;
 use crate::persistence;
+use crate::vector_index;
 use crate::vectordbs;
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -16,7 +28,7 @@ pub enum ExtractorType {
     #[serde(rename = "embedding")]
     Embedding { dim: usize, distance: IndexDistance },
 
-    #[serde(rename = "embedding")]
+    #[serde(rename = "attributes")]
     Attributes { schema: String },
 }
 
@@ -115,25 +127,33 @@ impl From<persistence::ExtractorBinding> for ExtractorBinding {
     }
 }
 
-pub fn into_persistence_extractor_binding(
-    repository: &str,
-    extractor_binding: ExtractorBinding,
-) -> persistence::ExtractorBinding {
-    let mut extraction_filters = vec![];
-    for filter in extractor_binding.filters.unwrap_or_default() {
+pub fn into_persistence_filters(
+    filters: Vec<ExtractorFilter>,
+) -> Vec<persistence::ExtractorFilter> {
+    let mut persistence_filters = vec![];
+    for filter in filters {
         match filter {
             ExtractorFilter::Eq { filters } => {
                 for (field, value) in filters {
-                    extraction_filters.push(persistence::ExtractorFilter::Eq { field, value });
+                    persistence_filters.push(persistence::ExtractorFilter::Eq { field, value });
                 }
             }
             ExtractorFilter::Neq { filters } => {
                 for (field, value) in filters {
-                    extraction_filters.push(persistence::ExtractorFilter::Neq { field, value });
+                    persistence_filters.push(persistence::ExtractorFilter::Neq { field, value });
                 }
             }
         }
     }
+    persistence_filters
+}
+
+pub fn into_persistence_extractor_binding(
+    repository: &str,
+    extractor_binding: ExtractorBinding,
+) -> persistence::ExtractorBinding {
+    let extraction_filters =
+        into_persistence_filters(extractor_binding.filters.unwrap_or_default());
     persistence::ExtractorBinding::new(
         repository,
         extractor_binding.extractor_name.clone(),
@@ -305,6 +325,31 @@ impl From<persistence::ExtractorConfig> for ExtractorConfig {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ModelInfo {
+    pub max_sequence_length: Option<usize>,
+    pub backend: Option<String>,
+    pub device: Option<String>,
+}
+
+impl From<extractors::ModelInfo> for ModelInfo {
+    fn from(value: extractors::ModelInfo) -> Self {
+        Self {
+            max_sequence_length: value.max_sequence_length,
+            backend: value.backend,
+            device: value.device,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GetExtractorResponse {
+    pub extractor: ExtractorConfig,
+    /// Whether the extractor's model is loaded by the server
+    pub loaded: bool,
+    pub model: Option<ModelInfo>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Executor {
     pub id: String,
@@ -324,11 +369,425 @@ pub struct ListExtractorsResponse {
 #[derive(Debug, Serialize, Deserialize, Default, ToSchema)]
 pub struct TextAdditionResponse {}
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CountTokensRequest {
+    pub texts: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CountTokensResponse {
+    pub counts: Vec<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateIndexRequest {
+    pub name: String,
+    /// Name or alias of the embedding extractor whose embeddings the index
+    /// holds, which sets the index's dimensions and distance.
+    pub extractor: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Index {
+    pub name: String,
+    pub repository: String,
+    pub extractor: String,
+    /// Dimensions and distance of an embedding index, or schema of an
+    /// attribute index. Unknown if the extractor is no longer registered.
+    pub extractor_type: Option<ExtractorType>,
+    pub num_vectors: Option<u64>,
+}
+
+impl From<data_repository_manager::IndexDescription> for Index {
+    fn from(value: data_repository_manager::IndexDescription) -> Self {
+        Self {
+            name: value.name,
+            repository: value.repository,
+            extractor: value.extractor_name,
+            extractor_type: value.extractor_type.map(|t| t.into()),
+            num_vectors: value.num_vectors,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AddDocumentsRequest {
+    pub documents: Vec<Text>,
+    /// Splits documents into chunks before they're embedded, documents are
+    /// embedded whole if unset.
+    pub chunking: Option<chunking::Chunking>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AddDocumentsResponse {
+    /// Number of chunks embedded into the index.
+    pub chunks: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListIndexesResponse {
+    pub indexes: Vec<Index>,
+}
+
+/// How embeddings are sent in responses. Sending them base64 encoded spares
+/// formatting and parsing thousands of floats as JSON for large batches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodingFormat {
+    #[default]
+    Float,
+    /// The little endian bytes of the embedding's 32 bit floats, base64
+    /// encoded.
+    Base64,
+    /// The little endian bytes of the embedding's floats as 16 bit floats,
+    /// base64 encoded, which is half the size of `base64` at the cost of
+    /// precision.
+    Base64F16,
+}
+
+/// Little endian bytes of an embedding's floats, as 16 bit floats if `f16`,
+/// base64 encoded.
+fn base64_embedding(embedding: &[f32], f16: bool) -> String {
+    let bytes: Vec<u8> = if f16 {
+        embedding
+            .iter()
+            .flat_map(|f| half::f16::from_f32(*f).to_le_bytes())
+            .collect()
+    } else {
+        embedding.iter().flat_map(|f| f.to_le_bytes()).collect()
+    };
+    general_purpose::STANDARD.encode(bytes)
+}
+
+/// An embedding in the `EncodingFormat` of the request, either a list of
+/// floats or a base64 encoded string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum EmbeddingVector {
+    Float(Vec<f32>),
+    Base64(String),
+}
+
+impl EmbeddingVector {
+    pub fn new(embedding: Vec<f32>, encoding_format: EncodingFormat) -> Self {
+        match encoding_format {
+            EncodingFormat::Float => EmbeddingVector::Float(embedding),
+            EncodingFormat::Base64 => EmbeddingVector::Base64(base64_embedding(&embedding, false)),
+            EncodingFormat::Base64F16 => {
+                EmbeddingVector::Base64(base64_embedding(&embedding, true))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmbeddingsRequest {
+    /// Name or alias of the embedding extractor.
+    pub model: String,
+    pub texts: Vec<String>,
+    #[serde(default)]
+    pub encoding_format: EncodingFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmbeddingsResponse {
+    pub embeddings: Vec<EmbeddingVector>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchEmbeddingResult {
+    /// Position of the text in the batch
+    pub index: usize,
+    pub embedding: Option<EmbeddingVector>,
+    pub error: Option<String>,
+}
+
+impl BatchEmbeddingResult {
+    pub fn new(
+        (index, result): (usize, vector_index::EmbeddingResult),
+        encoding_format: EncodingFormat,
+    ) -> Self {
+        match result {
+            vector_index::EmbeddingResult::Embedding(embedding) => Self {
+                index,
+                embedding: Some(EmbeddingVector::new(embedding, encoding_format)),
+                error: None,
+            },
+            vector_index::EmbeddingResult::Error(error) => Self {
+                index,
+                embedding: None,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchEmbeddingsResponse {
+    pub results: Vec<BatchEmbeddingResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct EmbeddingSessionParams {
+    /// Name or alias of the embedding extractor the session's texts are
+    /// embedded with, unless a request names another.
+    pub model: Option<String>,
+}
+
+/// Texts sent in a message of an embedding session.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmbeddingSessionRequest {
+    /// Sent back with the response, since responses are sent as soon as
+    /// they're ready rather than in the order of the requests.
+    pub id: Option<String>,
+    pub model: Option<String>,
+    pub texts: Vec<String>,
+    #[serde(default)]
+    pub encoding_format: EncodingFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmbeddingSessionError {
+    /// Status the HTTP API would have responded with.
+    pub status: u16,
+    pub error: String,
+    /// Position of the text the request was turned away for.
+    pub index: Option<usize>,
+    /// Seconds to wait before trying again, for a rate limited request.
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Sent in a message of an embedding session for each of its requests, with
+/// either the `embeddings` of the request's texts, in their order, or the
+/// `error` it failed with. Sessions stay open after a request fails.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmbeddingSessionResponse {
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embeddings: Option<Vec<EmbeddingVector>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<EmbeddingSessionError>,
+}
+
+impl EmbeddingSessionResponse {
+    pub fn embeddings(id: Option<String>, embeddings: Vec<EmbeddingVector>) -> Self {
+        Self {
+            id,
+            embeddings: Some(embeddings),
+            error: None,
+        }
+    }
+
+    pub fn error(id: Option<String>, e: IndexifyAPIError) -> Self {
+        Self {
+            id,
+            embeddings: None,
+            error: Some(EmbeddingSessionError {
+                status: e.status_code.as_u16(),
+                error: e.message,
+                index: e.invalid_request.and_then(|invalid| invalid.index),
+                retry_after_secs: e.retry_after.map(|retry_after| {
+                    retry_after.as_secs() + (retry_after.subsec_nanos() > 0) as u64
+                }),
+            }),
+        }
+    }
+}
+
+/// Last line of a newline delimited JSON response which failed after it
+/// started, in place of the rest of the results.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreamError {
+    pub error: String,
+}
+
+/// Texts of an OpenAI embeddings request, either one or several.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum OpenAIEmbeddingInput {
+    Text(String),
+    Texts(Vec<String>),
+}
+
+impl OpenAIEmbeddingInput {
+    pub fn into_texts(self) -> Vec<String> {
+        match self {
+            OpenAIEmbeddingInput::Text(text) => vec![text],
+            OpenAIEmbeddingInput::Texts(texts) => texts,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenAIEncodingFormat {
+    #[default]
+    Float,
+    /// The little endian bytes of the embedding's floats, base64 encoded.
+    Base64,
+}
+
+/// Embeddings request in the schema of OpenAI's embeddings API.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OpenAIEmbeddingsRequest {
+    pub input: OpenAIEmbeddingInput,
+    /// Name or alias of the embedding extractor.
+    pub model: String,
+    #[serde(default)]
+    pub encoding_format: OpenAIEncodingFormat,
+    /// Accepted for compatibility, callers are named by the
+    /// `x-indexify-caller` header instead.
+    #[serde(default)]
+    pub user: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum OpenAIEmbeddingVector {
+    Float(Vec<f32>),
+    Base64(String),
+}
+
+impl OpenAIEmbeddingVector {
+    pub fn new(embedding: Vec<f32>, encoding_format: OpenAIEncodingFormat) -> Self {
+        match encoding_format {
+            OpenAIEncodingFormat::Float => OpenAIEmbeddingVector::Float(embedding),
+            OpenAIEncodingFormat::Base64 => {
+                OpenAIEmbeddingVector::Base64(base64_embedding(&embedding, false))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OpenAIEmbedding {
+    pub object: String,
+    pub index: usize,
+    pub embedding: OpenAIEmbeddingVector,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OpenAIUsage {
+    pub prompt_tokens: usize,
+    pub total_tokens: usize,
+}
+
+/// Embeddings response in the schema of OpenAI's embeddings API.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OpenAIEmbeddingsResponse {
+    pub object: String,
+    pub data: Vec<OpenAIEmbedding>,
+    pub model: String,
+    pub usage: OpenAIUsage,
+}
+
 #[derive(Debug, Serialize, Deserialize, IntoParams, ToSchema)]
 pub struct SearchRequest {
     pub index: String,
     pub query: String,
     pub k: Option<u64>,
+    /// Name of a configured reranker to reorder the results with.
+    pub rerank: Option<String>,
+    /// Seconds to wait for the results, including reranking, before giving
+    /// up. Extractors are still limited by the server's extractor timeout.
+    pub timeout_secs: Option<u64>,
+    /// Fail with a `503` straight away, rather than wait, when the extractors
+    /// are busy, e.g. to fall back to cached results.
+    #[serde(default)]
+    pub no_wait: bool,
+    /// Filters on the metadata of the documents, in the form of the filters
+    /// of extractor bindings, which every result matches.
+    pub filters: Option<Vec<ExtractorFilter>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateEmbeddingJobRequest {
+    pub extractor: String,
+    pub texts: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateEmbeddingJobResponse {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmbeddingJob {
+    pub id: String,
+    pub extractor: String,
+    pub total: usize,
+    /// Texts which have been embedded
+    pub done: usize,
+    /// Texts which couldn't be embedded
+    pub failed: usize,
+    pub finished: bool,
+    /// Whether the job was deleted before it finished
+    pub cancelled: bool,
+    /// Estimated seconds until the job finishes
+    pub eta_secs: Option<f64>,
+}
+
+impl From<embedding_jobs::EmbeddingJobStatus> for EmbeddingJob {
+    fn from(value: embedding_jobs::EmbeddingJobStatus) -> Self {
+        Self {
+            id: value.id,
+            extractor: value.extractor,
+            total: value.total,
+            done: value.done,
+            failed: value.failed,
+            finished: value.finished,
+            cancelled: value.cancelled,
+            eta_secs: value.eta.map(|eta| eta.as_secs_f64()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct EmbeddingJobResultsRequest {
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default = "default_embedding_job_results_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    #[param(inline)]
+    pub encoding_format: EncodingFormat,
+}
+
+fn default_embedding_job_results_limit() -> usize {
+    100
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmbeddingJobResult {
+    /// Position of the text in the job
+    pub index: usize,
+    pub embedding: Option<EmbeddingVector>,
+    pub error: Option<String>,
+}
+
+impl EmbeddingJobResult {
+    pub fn new(
+        (index, result): (usize, vector_index::EmbeddingResult),
+        encoding_format: EncodingFormat,
+    ) -> Self {
+        match result {
+            vector_index::EmbeddingResult::Embedding(embedding) => Self {
+                index,
+                embedding: Some(EmbeddingVector::new(embedding, encoding_format)),
+                error: None,
+            },
+            vector_index::EmbeddingResult::Error(error) => Self {
+                index,
+                embedding: None,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmbeddingJobResultsResponse {
+    pub results: Vec<EmbeddingJobResult>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -404,13 +863,143 @@ pub struct DocumentFragment {
     pub metadata: HashMap<String, serde_json::Value>,
 }
 
+/// Documents to delete from an index, by their ids or by the metadata they
+/// were added with, but not both.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeleteDocumentsRequest {
+    pub ids: Option<Vec<String>>,
+    pub metadata: Option<HashMap<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeleteDocumentsResponse {
+    /// Documents deleted from the repository, which had no chunks left in
+    /// any of its indexes.
+    pub documents: u64,
+    pub chunks: u64,
+}
+
+/// Search of one index, by a query which is embedded by the server or by an
+/// embedding, but not both.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchIndexRequest {
+    pub query: Option<String>,
+    /// Embedding with the dimensions of the index's extractor.
+    pub vector: Option<Vec<f32>>,
+    pub k: Option<u64>,
+    /// Filters on the metadata of the documents, in the form of the filters
+    /// of extractor bindings, which every result matches.
+    pub filters: Option<Vec<ExtractorFilter>>,
+    /// Keywords matched against the text of the chunks, whose matches are
+    /// ranked along with the nearest chunks.
+    pub text_query: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchHit {
+    /// Id of the content the chunk is from.
+    pub id: String,
+    pub text: String,
+    pub metadata: HashMap<String, serde_json::Value>,
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchIndexResponse {
+    pub results: Vec<SearchHit>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, ToSchema)]
 pub struct IndexSearchResponse {
     pub results: Vec<DocumentFragment>,
 }
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StartupResponse {
+    pub started: bool,
+    /// Extractors loaded as the server starts which are still loading.
+    pub loading: Vec<String>,
+    /// Extractors which failed to load as the server started, with their
+    /// errors.
+    pub failed: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExtractorReadiness {
+    pub status: extractors::ExtractorStatus,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub started: bool,
+    /// Whether the server is shutting down, and only finishing the requests
+    /// it already has.
+    pub draining: bool,
+    pub extractors: HashMap<String, ExtractorReadiness>,
+    /// Why the vector store can't be reached, if it can't.
+    pub vector_store_error: Option<String>,
+}
+
+/// Loads a model without restarting the server, from one of the extractors
+/// in the server's configuration by its `name`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LoadModelRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ModelStats {
+    /// Calls to the model since it was loaded.
+    pub calls: u64,
+    pub errors: u64,
+    /// Texts the model was called with.
+    pub texts: u64,
+    pub mean_call_seconds: Option<f64>,
+}
+
+impl From<extractors::ExtractorStats> for ModelStats {
+    fn from(value: extractors::ExtractorStats) -> Self {
+        Self {
+            calls: value.calls,
+            errors: value.errors,
+            texts: value.texts,
+            mean_call_seconds: (value.calls > 0)
+                .then(|| value.call_time.as_secs_f64() / value.calls as f64),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LoadedModel {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub status: extractors::ExtractorStatus,
+    pub last_error: Option<String>,
+    pub stats: ModelStats,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListModelsResponse {
+    pub models: Vec<LoadedModel>,
+}
+
+/// Body of a `400` for a request which the server turned away before any of
+/// it was embedded.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvalidRequest {
+    pub error: String,
+    /// Position of the text or document the request was turned away for, in
+    /// the texts or documents of the request.
+    pub index: Option<usize>,
+}
+
 pub struct IndexifyAPIError {
     status_code: StatusCode,
     message: String,
+    retry_after: Option<Duration>,
+    invalid_request: Option<InvalidRequest>,
 }
 
 impl IndexifyAPIError {
@@ -418,12 +1007,143 @@ impl IndexifyAPIError {
         Self {
             status_code,
             message,
+            retry_after: None,
+            invalid_request: None,
+        }
+    }
+
+    /// Responds with an `InvalidRequest` body, naming the item the request
+    /// was turned away for, if any.
+    pub fn invalid(message: String, index: Option<usize>) -> Self {
+        Self {
+            invalid_request: Some(InvalidRequest {
+                error: message.clone(),
+                index,
+            }),
+            ..Self::new(StatusCode::BAD_REQUEST, message)
         }
     }
+
+    /// Tells the client when to try again with a `Retry-After` header.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+}
+
+impl From<IndexError> for IndexifyAPIError {
+    fn from(e: IndexError) -> Self {
+        let status_code = match &e {
+            IndexError::RateLimited { retry_after, .. } => {
+                let retry_after = *retry_after;
+                return Self::new(StatusCode::TOO_MANY_REQUESTS, e.to_string())
+                    .with_retry_after(retry_after);
+            }
+            IndexError::InvalidInput(_) => return Self::invalid(e.to_string(), None),
+            IndexError::InvalidText { index, .. } => {
+                let index = *index;
+                return Self::invalid(e.to_string(), Some(index));
+            }
+            IndexError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            e if e.is_retryable() => StatusCode::SERVICE_UNAVAILABLE,
+            IndexError::ExtractorNotFound(_)
+            | IndexError::RerankerNotFound(_)
+            | IndexError::ChunkNotFound(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status_code, e.to_string())
+    }
+}
+
+impl From<AuthError> for IndexifyAPIError {
+    fn from(e: AuthError) -> Self {
+        let status = match e {
+            AuthError::MissingKey | AuthError::InvalidKey | AuthError::InvalidToken(_) => {
+                StatusCode::UNAUTHORIZED
+            }
+            AuthError::KeysUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AuthError::MissingScope(..)
+            | AuthError::WrongTenant(..)
+            | AuthError::TenantOnly(_)
+            | AuthError::NamespaceOnly(_) => StatusCode::FORBIDDEN,
+        };
+        Self::new(status, e.to_string())
+    }
+}
+
+impl From<NamespaceError> for IndexifyAPIError {
+    fn from(e: NamespaceError) -> Self {
+        Self::invalid(e.to_string(), None)
+    }
+}
+
+impl From<IdempotencyError> for IndexifyAPIError {
+    fn from(e: IdempotencyError) -> Self {
+        let status = match e {
+            IdempotencyError::InvalidKey => StatusCode::BAD_REQUEST,
+            IdempotencyError::InProgress(_) => StatusCode::CONFLICT,
+            IdempotencyError::KeyReused(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        Self::new(status, e.to_string())
+    }
 }
 
 impl IntoResponse for IndexifyAPIError {
     fn into_response(self) -> Response {
-        (self.status_code, self.message).into_response()
+        if let Some(invalid_request) = self.invalid_request {
+            return (self.status_code, Json(invalid_request)).into_response();
+        }
+        match self.retry_after {
+            Some(retry_after) => {
+                // Retry-After is in whole seconds, rounded up so clients don't
+                // retry too early
+                let secs = retry_after.as_secs() + (retry_after.subsec_nanos() > 0) as u64;
+                (
+                    self.status_code,
+                    [(header::RETRY_AFTER, secs.to_string())],
+                    self.message,
+                )
+                    .into_response()
+            }
+            None => (self.status_code, self.message).into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_embeddings() {
+        let embedding = vec![1.0, -2.5];
+        assert_eq!(
+            serde_json::to_string(&EmbeddingVector::new(
+                embedding.clone(),
+                EncodingFormat::Float
+            ))
+            .unwrap(),
+            "[1.0,-2.5]"
+        );
+        let EmbeddingVector::Base64(f32s) =
+            EmbeddingVector::new(embedding.clone(), EncodingFormat::Base64)
+        else {
+            panic!("expected a base64 embedding");
+        };
+        let bytes = general_purpose::STANDARD.decode(f32s).unwrap();
+        assert_eq!(bytes.len(), 8);
+        assert_eq!(f32::from_le_bytes(bytes[4..8].try_into().unwrap()), -2.5);
+
+        let EmbeddingVector::Base64(f16s) =
+            EmbeddingVector::new(embedding, EncodingFormat::Base64F16)
+        else {
+            panic!("expected a base64 embedding");
+        };
+        let bytes = general_purpose::STANDARD.decode(f16s).unwrap();
+        assert_eq!(bytes.len(), 4);
+        assert_eq!(
+            half::f16::from_le_bytes([bytes[2], bytes[3]]).to_f32(),
+            -2.5
+        );
     }
 }
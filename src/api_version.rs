@@ -0,0 +1,89 @@
+use std::{convert::Infallible, fmt};
+
+use async_trait::async_trait;
+use axum::{extract::FromRequestParts, http::request::Parts};
+
+/// Versions of the HTTP API, each of which is served under its own prefix.
+/// Breaking changes to requests and responses go into a new version, served
+/// side by side with the older ones, so that clients keep working until they
+/// move to it. Handlers whose behavior changes between versions take the
+/// version the request was made to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    /// Every version the server serves, oldest first.
+    pub const ALL: &'static [ApiVersion] = &[ApiVersion::V1];
+
+    /// Version served by the paths from before the API was versioned, which
+    /// stays the first version so that the clients using them don't break.
+    pub const UNVERSIONED: ApiVersion = ApiVersion::V1;
+
+    pub fn number(self) -> u32 {
+        match self {
+            ApiVersion::V1 => 1,
+        }
+    }
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/api/v1",
+        }
+    }
+
+    /// Version a path is served by, along with the path within the version.
+    pub fn of_path(path: &str) -> (ApiVersion, &str) {
+        for version in Self::ALL {
+            if let Some(path) = path.strip_prefix(version.prefix()) {
+                if path.starts_with('/') {
+                    return (*version, path);
+                }
+            }
+        }
+        (Self::UNVERSIONED, path)
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.number())
+    }
+}
+
+/// Version of the API a request was routed to.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ApiVersion>()
+            .copied()
+            .unwrap_or(Self::UNVERSIONED))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versioned_paths() {
+        assert_eq!(
+            ApiVersion::of_path("/api/v1/repositories/:repository_name"),
+            (ApiVersion::V1, "/repositories/:repository_name")
+        );
+        assert_eq!(
+            ApiVersion::of_path("/repositories"),
+            (ApiVersion::UNVERSIONED, "/repositories")
+        );
+        assert_eq!(
+            ApiVersion::of_path("/api/v12/repositories"),
+            (ApiVersion::UNVERSIONED, "/api/v12/repositories")
+        );
+        assert_eq!(ApiVersion::V1.to_string(), "1");
+    }
+}
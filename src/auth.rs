@@ -0,0 +1,499 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::{
+    namespace::Namespace,
+    server_config::{ApiKey, AuthConfig, JwtConfig, Scope},
+};
+
+/// Least time between fetches of the key set, so that tokens signed with
+/// unknown keys can't have the server hammer the provider.
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Hash of an API key which the configuration has in place of the key.
+pub fn hash_api_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// The key of an `Authorization: Bearer <key>` header or metadata value.
+pub fn bearer_key(authorization: &str) -> Option<&str> {
+    authorization.strip_prefix("Bearer ").map(str::trim)
+}
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("an api key is required")]
+    MissingKey,
+
+    #[error("the api key is not valid")]
+    InvalidKey,
+
+    #[error("the token is not valid: `{0}`")]
+    InvalidToken(String),
+
+    #[error("unable to fetch the keys tokens are signed with: `{0}`")]
+    KeysUnavailable(String),
+
+    #[error("`{0}` doesn't have the `{1}` scope")]
+    MissingScope(String, Scope),
+
+    #[error("`{0}` can't reach the repository `{1}`")]
+    WrongTenant(String, String),
+
+    #[error("`{0}` can only reach the repositories of its tenants")]
+    TenantOnly(String),
+
+    #[error("`{0}` can only reach the repositories of its namespace")]
+    NamespaceOnly(String),
+}
+
+/// Repositories a request reaches.
+pub enum Repositories<'a> {
+    /// Requests which aren't of a repository, such as to embed texts.
+    None,
+    One(&'a str),
+    /// Requests of every repository, such as to list or create them.
+    All,
+}
+
+/// Who a request is made by, and what they may do.
+#[derive(Debug)]
+pub struct Principal {
+    pub name: String,
+    scopes: Vec<Scope>,
+    /// Repositories the principal can reach, every one if unset.
+    tenants: Option<Vec<String>>,
+    pub namespace: Namespace,
+}
+
+impl Principal {
+    pub fn allows(&self, scope: Scope, repositories: Repositories) -> Result<(), AuthError> {
+        if !self.scopes.iter().any(|s| *s >= scope) {
+            return Err(AuthError::MissingScope(self.name.clone(), scope));
+        }
+        match (&self.tenants, repositories) {
+            (None, _) | (Some(_), Repositories::None) => Ok(()),
+            (Some(tenants), Repositories::One(repository)) => {
+                if tenants.iter().any(|tenant| tenant == repository) {
+                    Ok(())
+                } else {
+                    Err(AuthError::WrongTenant(
+                        self.name.clone(),
+                        repository.to_string(),
+                    ))
+                }
+            }
+            (Some(_), Repositories::All) => Err(AuthError::TenantOnly(self.name.clone())),
+        }
+    }
+}
+
+/// Checks the credentials of requests, which are API keys, or tokens of an
+/// OpenID Connect provider if one is configured.
+pub struct Authenticator {
+    /// Keys, along with their namespaces, by the hashes of the keys.
+    api_keys: HashMap<String, (ApiKey, Namespace)>,
+    jwt: Option<JwtValidator>,
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthConfig) -> Result<Self> {
+        let mut api_keys = config.api_keys.clone();
+        if let Some(path) = &config.api_keys_file {
+            let keys: Vec<ApiKey> = serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+            api_keys.extend(keys);
+        }
+        let api_keys = api_keys
+            .into_iter()
+            .map(|api_key| {
+                let namespace = match &api_key.namespace {
+                    Some(namespace) => Namespace::named(namespace)?,
+                    None => Namespace::default(),
+                };
+                Ok((api_key.key_hash.to_lowercase(), (api_key, namespace)))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            api_keys,
+            jwt: config.jwt.clone().map(JwtValidator::new),
+        })
+    }
+
+    pub async fn authenticate(&self, credential: Option<&str>) -> Result<Principal, AuthError> {
+        let credential = credential.ok_or(AuthError::MissingKey)?;
+        if let Some((api_key, namespace)) = self.api_keys.get(&hash_api_key(credential)) {
+            return Ok(Principal {
+                name: api_key.name.clone(),
+                scopes: api_key.scopes.clone(),
+                tenants: None,
+                namespace: namespace.clone(),
+            });
+        }
+        match &self.jwt {
+            Some(jwt) => jwt.authenticate(credential).await,
+            None => Err(AuthError::InvalidKey),
+        }
+    }
+}
+
+struct KeySet {
+    jwks: JwkSet,
+    fetched: Option<Instant>,
+}
+
+/// Key a token is verified with, along with the algorithm the key set names
+/// for it, if any.
+type VerifyingKey = (DecodingKey, Option<Algorithm>);
+
+impl KeySet {
+    fn decoding_key(&self, kid: &str) -> Option<Result<VerifyingKey, AuthError>> {
+        self.jwks.find(kid).map(|jwk| {
+            let key =
+                DecodingKey::from_jwk(jwk).map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+            let algorithm = jwk
+                .common
+                .key_algorithm
+                .map(|algorithm| Algorithm::from_str(&algorithm.to_string()))
+                .transpose()
+                .map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+            Ok((key, algorithm))
+        })
+    }
+
+    fn fetched_recently(&self) -> bool {
+        self.fetched
+            .is_some_and(|fetched| fetched.elapsed() < JWKS_REFETCH_INTERVAL)
+    }
+}
+
+struct JwtValidator {
+    config: JwtConfig,
+    client: reqwest::Client,
+    keys: RwLock<KeySet>,
+}
+
+impl JwtValidator {
+    fn new(config: JwtConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            keys: RwLock::new(KeySet {
+                jwks: JwkSet { keys: vec![] },
+                fetched: None,
+            }),
+        }
+    }
+
+    async fn authenticate(&self, token: &str) -> Result<Principal, AuthError> {
+        let header = decode_header(token).map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+        let kid = header
+            .kid
+            .ok_or(AuthError::InvalidToken("the token has no key id".into()))?;
+        let (key, algorithm) = self.decoding_key(&kid).await?;
+        // The header's `alg` is the token's own claim, so it's only checked
+        // against the algorithms of the key, never trusted
+        let mut validation = Validation::default();
+        validation.algorithms = match algorithm {
+            Some(algorithm) => vec![algorithm],
+            None => self.config.algorithms.clone(),
+        };
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.audience]);
+        let claims = decode::<HashMap<String, serde_json::Value>>(token, &key, &validation)
+            .map_err(|e| AuthError::InvalidToken(e.to_string()))?
+            .claims;
+        // Subjects name the callers of rate limits and logs, so each token
+        // needs one of its own
+        let name = claims
+            .get("sub")
+            .and_then(|sub| sub.as_str())
+            .filter(|sub| !sub.is_empty())
+            .ok_or(AuthError::InvalidToken("the token has no subject".into()))?
+            .to_string();
+        let scopes = claims
+            .get(&self.config.scope_claim)
+            .map(claim_values)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|scope| scope.parse().ok())
+            .collect();
+        // Tokens without tenants reach no repositories, rather than all of them
+        let tenants = Some(
+            claims
+                .get(&self.config.tenant_claim)
+                .map(claim_values)
+                .unwrap_or_default(),
+        );
+        let namespace = match claims
+            .get(&self.config.namespace_claim)
+            .and_then(|namespace| namespace.as_str())
+        {
+            Some(namespace) => {
+                Namespace::named(namespace).map_err(|e| AuthError::InvalidToken(e.to_string()))?
+            }
+            None => Namespace::default(),
+        };
+        Ok(Principal {
+            name,
+            scopes,
+            tenants,
+            namespace,
+        })
+    }
+
+    async fn decoding_key(&self, kid: &str) -> Result<VerifyingKey, AuthError> {
+        let unknown_key = || AuthError::InvalidToken(format!("unknown key id `{}`", kid));
+        {
+            let keys = self.keys.read().await;
+            if let Some(key) = keys.decoding_key(kid) {
+                return key;
+            }
+            if keys.fetched_recently() {
+                return Err(unknown_key());
+            }
+        }
+        let mut keys = self.keys.write().await;
+        // The keys may have been fetched while this request waited for them
+        if keys.decoding_key(kid).is_none() && !keys.fetched_recently() {
+            keys.jwks = self.fetch_keys().await?;
+            keys.fetched = Some(Instant::now());
+            info!("fetched json web keys from: {}", &self.config.jwks_url);
+        }
+        keys.decoding_key(kid).unwrap_or_else(|| Err(unknown_key()))
+    }
+
+    async fn fetch_keys(&self) -> Result<JwkSet, AuthError> {
+        let jwks = self
+            .client
+            .get(&self.config.jwks_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AuthError::KeysUnavailable(e.to_string()))?
+            .text()
+            .await
+            .map_err(|e| AuthError::KeysUnavailable(e.to_string()))?;
+        serde_json::from_str(&jwks).map_err(|e| AuthError::KeysUnavailable(e.to_string()))
+    }
+}
+
+/// Values of a claim which is either a space separated string or a list.
+fn claim_values(claim: &serde_json::Value) -> Vec<String> {
+    match claim {
+        serde_json::Value::String(values) => {
+            values.split_whitespace().map(|v| v.to_string()).collect()
+        }
+        serde_json::Value::Array(values) => values
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(|v| v.to_string())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose, Engine};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn authenticate_api_keys() {
+        let authenticator = Authenticator::new(&AuthConfig {
+            api_keys: vec![
+                ApiKey {
+                    name: "search".into(),
+                    key_hash: hash_api_key("read-key"),
+                    scopes: vec![Scope::Read],
+                    namespace: None,
+                },
+                ApiKey {
+                    name: "ingest".into(),
+                    key_hash: hash_api_key("write-key").to_uppercase(),
+                    scopes: vec![Scope::Write],
+                    namespace: Some("ingest".into()),
+                },
+            ],
+            api_keys_file: None,
+            jwt: None,
+            rate_limit: Default::default(),
+        })
+        .unwrap();
+        let search = authenticator.authenticate(Some("read-key")).await.unwrap();
+        assert_eq!(search.name, "search");
+        assert!(search.allows(Scope::Read, Repositories::All).is_ok());
+        assert!(matches!(
+            search.allows(Scope::Write, Repositories::None),
+            Err(AuthError::MissingScope(_, Scope::Write))
+        ));
+        // Scopes allow what the scopes before them do
+        let ingest = authenticator.authenticate(Some("write-key")).await.unwrap();
+        assert_eq!(ingest.namespace, Namespace::named("ingest").unwrap());
+        assert!(search.namespace.is_default());
+        assert!(ingest.allows(Scope::Read, Repositories::None).is_ok());
+        assert!(ingest.allows(Scope::Admin, Repositories::None).is_err());
+        assert!(matches!(
+            authenticator.authenticate(Some("other-key")).await,
+            Err(AuthError::InvalidKey)
+        ));
+        assert!(matches!(
+            authenticator.authenticate(None).await,
+            Err(AuthError::MissingKey)
+        ));
+        assert_eq!(bearer_key("Bearer read-key"), Some("read-key"));
+        assert_eq!(bearer_key("Basic cmVhZA=="), None);
+    }
+
+    #[tokio::test]
+    async fn authenticate_tokens() {
+        let secret = b"secret used to sign tokens of tests";
+        let jwks = serde_json::from_value(serde_json::json!({
+            "keys": [{
+                "kty": "oct",
+                "kid": "test-key",
+                "alg": "HS256",
+                "k": general_purpose::URL_SAFE_NO_PAD.encode(secret),
+            }]
+        }))
+        .unwrap();
+        let authenticator = Authenticator {
+            api_keys: HashMap::new(),
+            jwt: Some(JwtValidator {
+                config: JwtConfig {
+                    issuer: "https://sso.example.com".into(),
+                    audience: "indexify".into(),
+                    jwks_url: "http://localhost:1/jwks".into(),
+                    tenant_claim: "tenant".into(),
+                    scope_claim: "scope".into(),
+                    namespace_claim: "namespace".into(),
+                    algorithms: vec![Algorithm::HS256],
+                },
+                client: reqwest::Client::new(),
+                keys: RwLock::new(KeySet {
+                    jwks,
+                    fetched: Some(Instant::now()),
+                }),
+            }),
+        };
+        let token = |claims: serde_json::Value, kid: &str| {
+            let header = Header {
+                kid: Some(kid.into()),
+                ..Default::default()
+            };
+            encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+        };
+        let exp = jsonwebtoken::get_current_timestamp() + 600;
+
+        let principal = authenticator
+            .authenticate(Some(&token(
+                serde_json::json!({
+                    "sub": "alice",
+                    "iss": "https://sso.example.com",
+                    "aud": "indexify",
+                    "exp": exp,
+                    "scope": "openid write",
+                    "tenant": "acme",
+                    "namespace": "team-a",
+                }),
+                "test-key",
+            )))
+            .await
+            .unwrap();
+        assert_eq!(principal.name, "alice");
+        assert_eq!(principal.namespace, Namespace::named("team-a").unwrap());
+        assert!(principal
+            .allows(Scope::Write, Repositories::One("acme"))
+            .is_ok());
+        assert!(matches!(
+            principal.allows(Scope::Read, Repositories::One("globex")),
+            Err(AuthError::WrongTenant(..))
+        ));
+        assert!(matches!(
+            principal.allows(Scope::Read, Repositories::All),
+            Err(AuthError::TenantOnly(_))
+        ));
+        assert!(principal.allows(Scope::Read, Repositories::None).is_ok());
+
+        let wrong_audience = token(
+            serde_json::json!({
+                "sub": "alice",
+                "iss": "https://sso.example.com",
+                "aud": "other",
+                "exp": exp,
+            }),
+            "test-key",
+        );
+        assert!(matches!(
+            authenticator.authenticate(Some(&wrong_audience)).await,
+            Err(AuthError::InvalidToken(_))
+        ));
+        // The key only verifies tokens signed with its own algorithm
+        let other_algorithm = encode(
+            &Header {
+                kid: Some("test-key".into()),
+                ..Header::new(Algorithm::HS384)
+            },
+            &serde_json::json!({
+                "sub": "alice",
+                "iss": "https://sso.example.com",
+                "aud": "indexify",
+                "exp": exp,
+            }),
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap();
+        assert!(matches!(
+            authenticator.authenticate(Some(&other_algorithm)).await,
+            Err(AuthError::InvalidToken(_))
+        ));
+        let no_subject = token(
+            serde_json::json!({
+                "iss": "https://sso.example.com",
+                "aud": "indexify",
+                "exp": exp,
+            }),
+            "test-key",
+        );
+        assert!(matches!(
+            authenticator.authenticate(Some(&no_subject)).await,
+            Err(AuthError::InvalidToken(_))
+        ));
+        let no_tenant = authenticator
+            .authenticate(Some(&token(
+                serde_json::json!({
+                    "sub": "bob",
+                    "iss": "https://sso.example.com",
+                    "aud": "indexify",
+                    "exp": exp,
+                    "scope": "read",
+                }),
+                "test-key",
+            )))
+            .await
+            .unwrap();
+        assert!(no_tenant.namespace.is_default());
+        assert!(matches!(
+            no_tenant.allows(Scope::Read, Repositories::One("acme")),
+            Err(AuthError::WrongTenant(..))
+        ));
+        assert!(no_tenant.allows(Scope::Read, Repositories::None).is_ok());
+        // Keys were just fetched, so unknown keys aren't fetched again
+        let unknown_key = token(serde_json::json!({"exp": exp}), "other-key");
+        assert!(matches!(
+            authenticator.authenticate(Some(&unknown_key)).await,
+            Err(AuthError::InvalidToken(_))
+        ));
+    }
+}
@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use hf_hub::api::sync::Api;
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+use tracing::warn;
+
+use crate::{
+    extractors::{AttributeData, ExtractedEmbeddings, Extractor, ModelInfo},
+    persistence::{self, ExtractorConfig, ExtractorType},
+    server_config::DeviceKind,
+    vectordbs::IndexDistance,
+};
+
+/// Runs BERT family sentence embedding models, such as
+/// `sentence-transformers/all-MiniLM-L6-v2`, with candle rather than python.
+pub struct CandleBertExtractor {
+    name: String,
+    model: BertModel,
+    tokenizer: Tokenizer,
+    dim: usize,
+    max_sequence_length: usize,
+}
+
+impl CandleBertExtractor {
+    /// `model` is either a model id on the Hugging Face hub, or a local
+    /// directory with the model's config, tokenizer and safetensors weights.
+    pub fn new(model: &str, device: DeviceKind) -> Result<Self> {
+        let device = match device {
+            DeviceKind::Cpu => Device::Cpu,
+            DeviceKind::Cuda(index) => Device::new_cuda(index as usize)
+                .map_err(|e| anyhow!("device {} is not available: {}", device, e))?,
+            DeviceKind::Mps => Device::new_metal(0).unwrap_or_else(|e| {
+                warn!("metal is not available, running on the cpu: {}", e);
+                Device::Cpu
+            }),
+        };
+        let (config_file, tokenizer_file, weights_file) = Self::model_files(model)?;
+        let config: Config = serde_json::from_str(&std::fs::read_to_string(config_file)?)?;
+        let mut tokenizer =
+            Tokenizer::from_file(tokenizer_file).map_err(|e| anyhow!(e.to_string()))?;
+        tokenizer.with_padding(Some(PaddingParams::default()));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..Default::default()
+            }))
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[weights_file], DTYPE, &device)? };
+        let bert = BertModel::load(vb, &config)?;
+        let name = model
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or(model)
+            .to_string();
+        Ok(Self {
+            name,
+            model: bert,
+            tokenizer,
+            dim: config.hidden_size,
+            max_sequence_length: config.max_position_embeddings,
+        })
+    }
+
+    fn model_files(model: &str) -> Result<(PathBuf, PathBuf, PathBuf)> {
+        // Models saved to a local directory are loaded without going to the hub
+        let dir = Path::new(model);
+        if dir.is_dir() {
+            return Ok((
+                dir.join("config.json"),
+                dir.join("tokenizer.json"),
+                dir.join("model.safetensors"),
+            ));
+        }
+        let repo = Api::new()?.model(model.to_string());
+        Ok((
+            repo.get("config.json")?,
+            repo.get("tokenizer.json")?,
+            repo.get("model.safetensors")?,
+        ))
+    }
+
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts, true)
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let device = &self.model.device;
+        let input_ids = encodings
+            .iter()
+            .map(|e| Tensor::new(e.get_ids(), device))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        let attention_mask = encodings
+            .iter()
+            .map(|e| Tensor::new(e.get_attention_mask(), device))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        let input_ids = Tensor::stack(&input_ids, 0)?;
+        let attention_mask = Tensor::stack(&attention_mask, 0)?;
+        let token_type_ids = input_ids.zeros_like()?;
+        let token_embeddings =
+            self.model
+                .forward(&input_ids, &token_type_ids, Some(&attention_mask))?;
+
+        // Mean pooling over the tokens which aren't padding, then L2 normalize
+        let mask = attention_mask.to_dtype(DTYPE)?.unsqueeze(2)?;
+        let sum = token_embeddings.broadcast_mul(&mask)?.sum(1)?;
+        let count = mask.sum(1)?.clamp(1e-9, f32::MAX)?;
+        let pooled = sum.broadcast_div(&count)?;
+        let norm = pooled.sqr()?.sum_keepdim(1)?.sqrt()?;
+        let embeddings = pooled.broadcast_div(&norm)?;
+        Ok(embeddings.to_vec2::<f32>()?)
+    }
+}
+
+impl Extractor for CandleBertExtractor {
+    fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+        Ok(ExtractorConfig {
+            name: self.name.clone(),
+            description: format!("{} embeddings computed with candle", self.name),
+            extractor_type: ExtractorType::Embedding {
+                dim: self.dim,
+                distance: IndexDistance::Cosine,
+            },
+            input_params: serde_json::json!({}),
+        })
+    }
+
+    fn extract_embedding(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        _input_params: serde_json::Value,
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+        let texts = content.iter().map(|c| c.content.clone()).collect();
+        let embeddings = self.embed(texts)?;
+        let extracted_embeddings = content
+            .into_iter()
+            .zip(embeddings)
+            .map(|(c, embeddings)| ExtractedEmbeddings {
+                content_id: c.id,
+                text: c.content,
+                embeddings,
+            })
+            .collect();
+        Ok(extracted_embeddings)
+    }
+
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+        let mut embeddings = self.embed(vec![query.to_string()])?;
+        embeddings
+            .pop()
+            .ok_or_else(|| anyhow!("no embedding for query"))
+    }
+
+    fn extract_attributes(
+        &self,
+        _content: Vec<persistence::Content<String>>,
+        _input_params: serde_json::Value,
+    ) -> Result<Vec<AttributeData>, anyhow::Error> {
+        Err(anyhow!("candle extractors only extract embeddings"))
+    }
+
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        texts
+            .into_iter()
+            .map(|text| {
+                self.tokenizer
+                    .encode(text, true)
+                    .map(|encoding| encoding.len())
+                    .map_err(|e| anyhow!(e.to_string()))
+            })
+            .collect()
+    }
+
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        Ok(ModelInfo {
+            max_sequence_length: Some(self.max_sequence_length),
+            backend: Some("candle".into()),
+            device: Some(match self.model.device {
+                Device::Cuda(_) => "cuda".into(),
+                Device::Metal(_) => "metal".into(),
+                Device::Cpu => "cpu".into(),
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn extract_embeddings() {
+        let extractor =
+            CandleBertExtractor::new("sentence-transformers/all-MiniLM-L6-v2", DeviceKind::Cpu)
+                .unwrap();
+        assert_eq!(extractor.info().unwrap().name, "all-MiniLM-L6-v2");
+
+        let content = vec![
+            persistence::Content::new("1".into(), "hello world".to_string(), HashMap::new()),
+            persistence::Content::new(
+                "2".into(),
+                "indexify is awesome".to_string(),
+                HashMap::new(),
+            ),
+        ];
+        let extracted_data = extractor
+            .extract_embedding(content, serde_json::json!({}))
+            .unwrap();
+        assert_eq!(extracted_data.len(), 2);
+        assert_eq!(extracted_data[0].embeddings.len(), 384);
+
+        let query = extractor.extract_embedding_query("hello world").unwrap();
+        assert_eq!(query.len(), 384);
+    }
+}
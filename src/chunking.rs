@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Splits texts into chunks of whole words before they're embedded.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Chunking {
+    /// Most words in each chunk.
+    pub max_words: usize,
+    /// Words at the end of each chunk which the next chunk starts with, so
+    /// that sentences split across chunks are found from either.
+    #[serde(default)]
+    pub overlap_words: usize,
+}
+
+impl Chunking {
+    pub fn split(&self, text: &str) -> Vec<String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let max_words = self.max_words.max(1);
+        if words.len() <= max_words {
+            return vec![text.to_string()];
+        }
+        let step = max_words.saturating_sub(self.overlap_words).max(1);
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        loop {
+            let end = (start + max_words).min(words.len());
+            chunks.push(words[start..end].join(" "));
+            if end == words.len() {
+                return chunks;
+            }
+            start += step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split() {
+        let chunking = Chunking {
+            max_words: 3,
+            overlap_words: 1,
+        };
+        assert_eq!(
+            chunking.split("one two three four five six"),
+            vec!["one two three", "three four five", "five six"]
+        );
+        assert_eq!(chunking.split("one  two"), vec!["one  two"]);
+
+        let chunking = Chunking {
+            max_words: 2,
+            overlap_words: 0,
+        };
+        assert_eq!(
+            chunking.split("one two three four"),
+            vec!["one two", "three four"]
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use sea_orm::DbConn;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, future::Future, sync::Arc};
 use thiserror::Error;
 use tracing::{error, info};
 
@@ -8,12 +8,15 @@ pub const DEFAULT_REPOSITORY_NAME: &str = "default";
 
 use crate::{
     attribute_index::AttributeIndexManager,
+    chunking::Chunking,
+    entity::index::Model as IndexModel,
+    extractors::ModelInfo,
     index::IndexError,
     persistence::{
-        DataRepository, Event, ExtractedAttributes, ExtractorBinding, ExtractorConfig,
-        ExtractorType, Repository, RepositoryError, Text,
+        Content, DataRepository, DocumentFilter, Event, ExtractedAttributes, ExtractorBinding,
+        ExtractorConfig, ExtractorType, Repository, RepositoryError, Text,
     },
-    vector_index::{ScoredText, VectorIndexManager},
+    vector_index::{EmbeddingResult, ScoredText, SearchOptions, VectorIndexManager},
     ServerConfig,
 };
 
@@ -32,6 +35,25 @@ pub enum DataRepositoryError {
     NotAllowed(String),
 }
 
+/// An index of a repository along with what its extractor produces.
+pub struct IndexDescription {
+    pub name: String,
+    pub repository: String,
+    pub extractor_name: String,
+    /// Unknown if the extractor is no longer registered.
+    pub extractor_type: Option<ExtractorType>,
+    /// Number of chunks in the vector index, for embedding indexes.
+    pub num_vectors: Option<u64>,
+}
+
+/// What was deleted from an index by a request to delete documents.
+pub struct DeletedDocuments {
+    /// Documents deleted from the repository, which had no chunks left in
+    /// any of its indexes.
+    pub documents: u64,
+    pub chunks: u64,
+}
+
 pub struct DataRepositoryManager {
     repository: Arc<Repository>,
     vector_index_manager: Arc<VectorIndexManager>,
@@ -112,7 +134,29 @@ impl DataRepositoryManager {
         Ok(())
     }
 
+    fn resolve_alias(&self, repository: &str, binding: ExtractorBinding) -> ExtractorBinding {
+        let extractor_name = self
+            .vector_index_manager
+            .resolve_extractor_alias(&binding.extractor_name);
+        if extractor_name == binding.extractor_name {
+            return binding;
+        }
+        ExtractorBinding::new(
+            repository,
+            extractor_name,
+            binding.index_name,
+            binding.filters,
+            binding.input_params,
+        )
+    }
+
     pub async fn create(&self, repository: &DataRepository) -> Result<(), DataRepositoryError> {
+        let mut repository = repository.clone();
+        repository.extractor_bindings = repository
+            .extractor_bindings
+            .into_iter()
+            .map(|binding| self.resolve_alias(&repository.name, binding))
+            .collect();
         let _ = self
             .repository
             .upsert_repository(repository.clone())
@@ -139,6 +183,7 @@ impl DataRepositoryManager {
         repository: &str,
         extractor: ExtractorBinding,
     ) -> Result<(), DataRepositoryError> {
+        let extractor = self.resolve_alias(repository, extractor);
         info!(
             "adding extractor binding: repository: {}, extractor: {}, index: {}",
             repository, extractor.extractor_name, extractor.index_name
@@ -162,11 +207,226 @@ impl DataRepositoryManager {
         Ok(())
     }
 
+    /// Creates an embedding index which content isn't extracted into until an
+    /// extractor is bound to it, or content is added to it directly.
+    pub async fn create_embedding_index(
+        &self,
+        repository: &str,
+        index_name: &str,
+        extractor_name: &str,
+    ) -> Result<IndexDescription, DataRepositoryError> {
+        let _ = self.repository.repository_by_name(repository).await?;
+        if self
+            .repository
+            .get_index(index_name, repository)
+            .await
+            .is_ok()
+        {
+            return Err(DataRepositoryError::NotAllowed(format!(
+                "index with name `{}` already exists",
+                index_name
+            )));
+        }
+        let extractor_name = self
+            .vector_index_manager
+            .resolve_extractor_alias(extractor_name);
+        let extractor = self.repository.extractor_by_name(&extractor_name).await?;
+        if !matches!(extractor.extractor_type, ExtractorType::Embedding { .. }) {
+            return Err(DataRepositoryError::NotAllowed(format!(
+                "extractor `{}` doesn't extract embeddings",
+                extractor_name
+            )));
+        }
+        info!(
+            "creating index: repository: {}, extractor: {}, index: {}",
+            repository, extractor_name, index_name
+        );
+        self.vector_index_manager
+            .create_index(repository, index_name, extractor)
+            .await
+            .map_err(|e| DataRepositoryError::IndexCreation(e.to_string()))?;
+        self.describe_index(repository, index_name).await
+    }
+
+    pub async fn list_indexes(
+        &self,
+        repository: &str,
+    ) -> Result<Vec<IndexDescription>, DataRepositoryError> {
+        let _ = self.repository.repository_by_name(repository).await?;
+        let mut indexes = Vec::new();
+        for index in self.repository.list_indexes(repository).await? {
+            indexes.push(self.describe(index).await?);
+        }
+        Ok(indexes)
+    }
+
+    pub async fn describe_index(
+        &self,
+        repository: &str,
+        index_name: &str,
+    ) -> Result<IndexDescription, DataRepositoryError> {
+        let index = self.repository.get_index(index_name, repository).await?;
+        self.describe(index).await
+    }
+
+    async fn describe(&self, index: IndexModel) -> Result<IndexDescription, DataRepositoryError> {
+        let extractor_type = self
+            .repository
+            .extractor_by_name(&index.extractor_name)
+            .await
+            .ok()
+            .map(|extractor| extractor.extractor_type);
+        let num_vectors = match &index.vector_index_name {
+            Some(vector_index_name) => Some(
+                self.vector_index_manager
+                    .num_vectors(vector_index_name)
+                    .await?,
+            ),
+            None => None,
+        };
+        Ok(IndexDescription {
+            name: index.name,
+            repository: index.repository_id,
+            extractor_name: index.extractor_name,
+            extractor_type,
+            num_vectors,
+        })
+    }
+
+    /// Deletes an index along with its vectors, and the extractor bindings
+    /// which extract content into it.
+    pub async fn delete_index(
+        &self,
+        repository: &str,
+        index_name: &str,
+    ) -> Result<(), DataRepositoryError> {
+        let mut data_repository = self.repository.repository_by_name(repository).await?;
+        let index = self.repository.delete_index(index_name, repository).await?;
+        if let Some(vector_index_name) = &index.vector_index_name {
+            self.vector_index_manager
+                .drop_index(vector_index_name)
+                .await?;
+        }
+        let bindings = data_repository.extractor_bindings.len();
+        data_repository
+            .extractor_bindings
+            .retain(|binding| binding.index_name != index_name);
+        if data_repository.extractor_bindings.len() != bindings {
+            self.repository.upsert_repository(data_repository).await?;
+        }
+        info!(
+            "deleted index: repository: {}, index: {}",
+            repository, index_name
+        );
+        Ok(())
+    }
+
+    /// Deletes the chunks of the documents which match the filter from an
+    /// index, and their embeddings from the vector index, without rebuilding
+    /// it. The documents themselves are deleted once no index has chunks of
+    /// them, so that nothing of them is left to be searched for.
+    pub async fn delete_documents(
+        &self,
+        repository: &str,
+        index_name: &str,
+        filter: DocumentFilter,
+    ) -> Result<DeletedDocuments, DataRepositoryError> {
+        let index = self.repository.get_index(index_name, repository).await?;
+        let chunks = self
+            .repository
+            .chunks_of_documents(repository, index_name, &filter)
+            .await?;
+        if chunks.is_empty() {
+            return Ok(DeletedDocuments {
+                documents: 0,
+                chunks: 0,
+            });
+        }
+        // Embeddings go first, so searches never find chunks which are gone
+        if let Some(vector_index_name) = &index.vector_index_name {
+            self.vector_index_manager
+                .remove_chunks(
+                    vector_index_name,
+                    chunks.iter().map(|c| c.chunk_id.clone()).collect(),
+                )
+                .await?;
+        }
+        let documents = self
+            .repository
+            .delete_chunks(repository, index_name, &chunks)
+            .await?;
+        info!(
+            "deleted documents: repository: {}, index: {}, documents: {}, chunks: {}",
+            repository,
+            index_name,
+            documents,
+            chunks.len()
+        );
+        Ok(DeletedDocuments {
+            documents,
+            chunks: chunks.len() as u64,
+        })
+    }
+
+    /// Adds texts to the repository and embeds them into one of its indexes
+    /// straight away, split into chunks if `chunking` is set. Returns the
+    /// number of chunks added to the index.
+    pub async fn add_documents(
+        &self,
+        repository: &str,
+        index_name: &str,
+        texts: Vec<Text>,
+        chunking: Option<Chunking>,
+    ) -> Result<usize, DataRepositoryError> {
+        self.validate_documents(&texts)?;
+        let _ = self.repository.get_index(index_name, repository).await?;
+        let content = texts
+            .iter()
+            .flat_map(|text| {
+                let chunks = match &chunking {
+                    Some(chunking) => chunking.split(&text.text),
+                    None => vec![text.text.clone()],
+                };
+                chunks
+                    .into_iter()
+                    .map(|chunk| Content::new(text.id.clone(), chunk, text.metadata.clone()))
+            })
+            .collect();
+        self.repository.insert_content(repository, texts).await?;
+        let added = self
+            .vector_index_manager
+            .index_content(repository, index_name, content)
+            .await?;
+        info!(
+            "added {} chunks to index: repository: {}, index: {}",
+            added, repository, index_name
+        );
+        Ok(added)
+    }
+
+    /// Turns away requests with too many documents or empty ones. Documents
+    /// aren't held to the length limit of texts, since they may be chunked
+    /// before they're embedded.
+    fn validate_documents(&self, texts: &[Text]) -> Result<(), IndexError> {
+        self.vector_index_manager.validate_text_count(texts.len())?;
+        texts
+            .iter()
+            .enumerate()
+            .find(|(_, text)| text.text.trim().is_empty())
+            .map_or(Ok(()), |(index, _)| {
+                Err(IndexError::InvalidText {
+                    index,
+                    reason: "the document is empty".into(),
+                })
+            })
+    }
+
     pub async fn add_texts(
         &self,
         repo_name: &str,
         texts: Vec<Text>,
     ) -> Result<(), DataRepositoryError> {
+        self.validate_documents(&texts)?;
         let _ = self.repository.repository_by_name(repo_name).await?;
         self.repository
             .add_content(repo_name, texts)
@@ -174,15 +434,105 @@ impl DataRepositoryManager {
             .map_err(DataRepositoryError::Persistence)
     }
 
+    pub async fn search_embedding(
+        &self,
+        repository: &str,
+        index_name: &str,
+        embedding: Vec<f32>,
+        k: u64,
+        options: SearchOptions,
+    ) -> Result<Vec<ScoredText>, DataRepositoryError> {
+        self.vector_index_manager
+            .search_embedding(repository, index_name, embedding, k as usize, options)
+            .await
+            .map_err(DataRepositoryError::RetrievalError)
+    }
+
+    pub fn check_rate_limit(&self, caller: &str, cost: usize) -> Result<(), DataRepositoryError> {
+        self.vector_index_manager
+            .check_rate_limit(caller, cost)
+            .map_err(DataRepositoryError::RetrievalError)
+    }
+
     pub async fn search(
         &self,
         repository: &str,
         index_name: &str,
         query: &str,
         k: u64,
+        wait_for_extractor: bool,
+        options: SearchOptions,
     ) -> Result<Vec<ScoredText>, DataRepositoryError> {
         self.vector_index_manager
-            .search(repository, index_name, query, k as usize)
+            .search(
+                repository,
+                index_name,
+                query,
+                k as usize,
+                wait_for_extractor,
+                options,
+            )
+            .await
+            .map_err(DataRepositoryError::RetrievalError)
+    }
+
+    pub async fn embed(
+        &self,
+        extractor: &str,
+        texts: Vec<String>,
+    ) -> Result<Vec<Vec<f32>>, DataRepositoryError> {
+        self.vector_index_manager
+            .embed(extractor, texts)
+            .await
+            .map_err(DataRepositoryError::RetrievalError)
+    }
+
+    pub async fn embed_batch(
+        &self,
+        extractor: &str,
+        texts: Vec<String>,
+    ) -> Result<Vec<EmbeddingResult>, DataRepositoryError> {
+        self.vector_index_manager
+            .embed_batch(extractor, texts)
+            .await
+            .map_err(DataRepositoryError::RetrievalError)
+    }
+
+    pub async fn embed_batch_chunks<F, Fut>(
+        &self,
+        extractor: &str,
+        texts: Vec<String>,
+        on_results: F,
+    ) -> Result<(), DataRepositoryError>
+    where
+        F: FnMut(Vec<(usize, EmbeddingResult)>) -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        self.vector_index_manager
+            .embed_batch_chunks(extractor, texts, on_results)
+            .await
+            .map_err(DataRepositoryError::RetrievalError)
+    }
+
+    pub async fn count_tokens(
+        &self,
+        extractor: &str,
+        texts: Vec<String>,
+    ) -> Result<Vec<usize>, DataRepositoryError> {
+        self.vector_index_manager
+            .count_tokens(extractor, texts)
+            .await
+            .map_err(DataRepositoryError::RetrievalError)
+    }
+
+    pub async fn rerank(
+        &self,
+        reranker: &str,
+        query: &str,
+        results: Vec<ScoredText>,
+    ) -> Result<Vec<ScoredText>, DataRepositoryError> {
+        self.vector_index_manager
+            .rerank(reranker, query, results)
             .await
             .map_err(DataRepositoryError::RetrievalError)
     }
@@ -198,6 +548,16 @@ impl DataRepositoryManager {
             .await
     }
 
+    pub async fn get_extractor(
+        &self,
+        name: &str,
+    ) -> Result<(ExtractorConfig, Option<ModelInfo>), DataRepositoryError> {
+        let name = self.vector_index_manager.resolve_extractor_alias(name);
+        let extractor = self.repository.extractor_by_name(&name).await?;
+        let model_info = self.vector_index_manager.model_info(&name).await?;
+        Ok((extractor, model_info))
+    }
+
     pub async fn list_extractors(&self) -> Result<Vec<ExtractorConfig>, DataRepositoryError> {
         let extractors = self
             .repository
@@ -0,0 +1,189 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use anyhow::Result;
+use lru::LruCache;
+use md5::{Digest, Md5};
+use metrics::counter;
+use serde::Serialize;
+use tracing::error;
+
+/// Identifies what was embedded: the extractor which embedded it, and a hash
+/// of the text along with everything else that changes its embeddings.
+/// Versioned extractors are named after their version, so the embeddings of
+/// one version of a model are never returned for another.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    extractor: String,
+    hash: [u8; 16],
+}
+
+impl CacheKey {
+    /// Key for the embeddings of a piece of content extracted with `params`.
+    pub fn content(extractor: &str, params: &serde_json::Value, text: &str) -> Self {
+        Self::new(extractor, &["content", &params.to_string(), text])
+    }
+
+    /// Key for the embedding of a search query.
+    pub fn query(extractor: &str, query: &str) -> Self {
+        Self::new(extractor, &["query", query])
+    }
+
+    fn new(extractor: &str, parts: &[&str]) -> Self {
+        // The hash is stored in the disk cache, so it has to be stable across
+        // builds unlike the standard library's hasher
+        let mut hasher = Md5::new();
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        Self {
+            extractor: extractor.to_string(),
+            hash: hasher.finalize().into(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.extractor.as_bytes().to_vec();
+        bytes.push(0);
+        bytes.extend_from_slice(&self.hash);
+        bytes
+    }
+}
+
+/// The chunks a piece of content was split into, with their embeddings.
+pub type CachedEmbeddings = Vec<(String, Vec<f32>)>;
+
+/// Embeddings of pieces of content and queries kept in memory, along with a
+/// database which keeps them across restarts if there's one. The least
+/// recently used embeddings are dropped from memory past the capacity.
+#[derive(Default)]
+pub struct EmbeddingCache {
+    memory: Option<Mutex<LruCache<CacheKey, CachedEmbeddings>>>,
+    disk: Option<sled::Db>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl EmbeddingCache {
+    /// Keeps the embeddings of up to `capacity` pieces of content and queries
+    /// in memory, and none if it's `0`.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            memory: NonZeroUsize::new(capacity).map(|capacity| Mutex::new(LruCache::new(capacity))),
+            ..Default::default()
+        }
+    }
+
+    /// Keeps embeddings in a database at `path` as well, so they survive
+    /// restarts. Embeddings found on disk are copied into memory.
+    pub fn open_disk(&mut self, path: &str) -> Result<()> {
+        self.disk = Some(sled::open(path)?);
+        Ok(())
+    }
+
+    /// Writes the embeddings of the disk cache which haven't been written yet.
+    pub fn flush(&self) -> Result<()> {
+        if let Some(db) = &self.disk {
+            db.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.memory.is_some() || self.disk.is_some()
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<CachedEmbeddings> {
+        let mut embeddings = self
+            .memory
+            .as_ref()
+            .and_then(|memory| memory.lock().unwrap().get(key).cloned());
+        if embeddings.is_none() {
+            embeddings = self.get_from_disk(key);
+            if let (Some(embeddings), Some(memory)) = (&embeddings, &self.memory) {
+                memory.lock().unwrap().put(key.clone(), embeddings.clone());
+            }
+        }
+        if embeddings.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            counter!("indexify_embedding_cache_hits_total").increment(1);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            counter!("indexify_embedding_cache_misses_total").increment(1);
+        }
+        embeddings
+    }
+
+    fn get_from_disk(&self, key: &CacheKey) -> Option<CachedEmbeddings> {
+        let value = match self.disk.as_ref()?.get(key.to_bytes()) {
+            Ok(value) => value?,
+            Err(err) => {
+                error!("unable to read from the embedding cache: {}", err);
+                return None;
+            }
+        };
+        serde_json::from_slice(&value).ok()
+    }
+
+    pub fn put(&self, key: CacheKey, embeddings: CachedEmbeddings) {
+        if let Some(disk) = &self.disk {
+            let result = serde_json::to_vec(&embeddings)
+                .map_err(anyhow::Error::from)
+                .and_then(|value| Ok(disk.insert(key.to_bytes(), value)?));
+            if let Err(err) = result {
+                error!("unable to write to the embedding cache: {}", err);
+            }
+        }
+        if let Some(memory) = &self.memory {
+            memory.lock().unwrap().put(key, embeddings);
+        }
+    }
+
+    pub fn stats(&self) -> EmbeddingCacheStats {
+        let memory = self.memory.as_ref().map(|memory| memory.lock().unwrap());
+        EmbeddingCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: memory.as_ref().map(|memory| memory.len()).unwrap_or(0),
+            capacity: memory
+                .as_ref()
+                .map(|memory| memory.cap().get())
+                .unwrap_or(0),
+            disk_entries: self.disk.as_ref().map(|db| db.len()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EmbeddingCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub capacity: usize,
+    /// Embeddings in the disk cache, if there's one.
+    pub disk_entries: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_keys() {
+        let params = serde_json::json!({});
+        let key = CacheKey::content("MiniLML6", &params, "hello");
+        assert_eq!(key, CacheKey::content("MiniLML6", &params, "hello"));
+        assert_ne!(key, CacheKey::content("MiniLML6@2", &params, "hello"));
+        assert_ne!(
+            key,
+            CacheKey::content("MiniLML6", &serde_json::json!({"normalize": true}), "hello")
+        );
+        assert_ne!(key, CacheKey::query("MiniLML6", "hello"));
+    }
+}
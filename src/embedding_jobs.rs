@@ -0,0 +1,332 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use futures::{stream, Stream, StreamExt};
+use nanoid::nanoid;
+use tokio::sync::watch;
+use tracing::{error, info, info_span, Instrument};
+
+use crate::{
+    extractors::Priority,
+    index::IndexError,
+    namespace::Namespace,
+    vector_index::{EmbeddingResult, VectorIndexManager},
+};
+
+/// Texts of a job embedded in each call to the extractor.
+const EMBEDDING_JOB_CHUNK_SIZE: usize = 32;
+
+pub struct EmbeddingJobStatus {
+    pub id: String,
+    pub extractor: String,
+    pub total: usize,
+    pub done: usize,
+    pub failed: usize,
+    pub finished: bool,
+    /// Whether the job was deleted before it finished.
+    pub cancelled: bool,
+    /// Estimated from the rate the job has embedded texts at so far.
+    pub eta: Option<Duration>,
+}
+
+struct JobState {
+    results: Vec<Option<EmbeddingResult>>,
+    done: usize,
+    failed: usize,
+    finished: bool,
+}
+
+struct EmbeddingJob {
+    id: String,
+    /// Only requests of the namespace which submitted the job reach it.
+    namespace: Namespace,
+    extractor: String,
+    started: Instant,
+    state: Mutex<JobState>,
+    cancelled: AtomicBool,
+    /// Changes whenever the job makes progress, finishes or is cancelled.
+    progress: watch::Sender<()>,
+}
+
+impl EmbeddingJob {
+    fn new(namespace: &Namespace, extractor: &str, total: usize) -> Self {
+        Self {
+            id: nanoid!(),
+            namespace: namespace.clone(),
+            extractor: extractor.to_string(),
+            started: Instant::now(),
+            state: Mutex::new(JobState {
+                results: vec![None; total],
+                done: 0,
+                failed: 0,
+                finished: false,
+            }),
+            cancelled: AtomicBool::new(false),
+            progress: watch::channel(()).0,
+        }
+    }
+
+    fn status(&self) -> EmbeddingJobStatus {
+        let state = self.state.lock().unwrap();
+        let total = state.results.len();
+        let completed = state.done + state.failed;
+        let eta = (completed > 0 && !state.finished).then(|| {
+            self.started
+                .elapsed()
+                .mul_f64((total - completed) as f64 / completed as f64)
+        });
+        EmbeddingJobStatus {
+            id: self.id.clone(),
+            extractor: self.extractor.clone(),
+            total,
+            done: state.done,
+            failed: state.failed,
+            finished: state.finished,
+            cancelled: self.cancelled.load(Ordering::SeqCst),
+            eta,
+        }
+    }
+
+    fn record(&self, results: Vec<(usize, EmbeddingResult)>) {
+        let mut state = self.state.lock().unwrap();
+        for (position, result) in results {
+            match &result {
+                EmbeddingResult::Embedding(_) => state.done += 1,
+                EmbeddingResult::Error(_) => state.failed += 1,
+            }
+            state.results[position] = Some(result);
+        }
+        drop(state);
+        self.progress.send_replace(());
+    }
+
+    fn finish(&self) {
+        self.state.lock().unwrap().finished = true;
+        self.progress.send_replace(());
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.progress.send_replace(());
+    }
+
+    /// The job's status now, and again each time it changes, until it has
+    /// finished or been cancelled. Changes which happen while the last status
+    /// is still being handled are folded into the next one.
+    fn progress(self: Arc<Self>) -> impl Stream<Item = EmbeddingJobStatus> {
+        let changes = self.progress.subscribe();
+        stream::unfold(Some((self, changes, true)), |state| async move {
+            let (job, mut changes, first) = state?;
+            if !first && changes.changed().await.is_err() {
+                return None;
+            }
+            changes.borrow_and_update();
+            let status = job.status();
+            let next = (!status.finished && !status.cancelled).then_some((job, changes, false));
+            Some((status, next))
+        })
+    }
+}
+
+/// Embeds large sets of texts in the background, with the progress and
+/// results of each job kept in memory until the job is deleted.
+pub struct EmbeddingJobs {
+    vector_index_manager: Arc<VectorIndexManager>,
+    jobs: DashMap<String, Arc<EmbeddingJob>>,
+}
+
+impl EmbeddingJobs {
+    pub fn new(vector_index_manager: Arc<VectorIndexManager>) -> Self {
+        Self {
+            vector_index_manager,
+            jobs: DashMap::new(),
+        }
+    }
+
+    /// Starts embedding `texts` with an extractor and returns the id of the
+    /// job. Jobs can have more texts than the request limits allow, but each
+    /// of their texts is held to the limits. The job's calls to the extractor
+    /// run at bulk priority, behind searches, and each chunk waits for the
+    /// caller's rate limit rather than failing.
+    pub fn submit(
+        &self,
+        caller: &str,
+        namespace: &Namespace,
+        extractor: &str,
+        texts: Vec<String>,
+    ) -> Result<String, IndexError> {
+        for (index, text) in texts.iter().enumerate() {
+            self.vector_index_manager.validate_text(index, text)?;
+        }
+        let total = texts.len();
+        let mut embeddings = Box::pin(self.vector_index_manager.embed_texts(
+            extractor,
+            texts,
+            EMBEDDING_JOB_CHUNK_SIZE,
+            Priority::Bulk,
+        )?);
+        let job = Arc::new(EmbeddingJob::new(namespace, extractor, total));
+        self.jobs.insert(job.id.clone(), job.clone());
+        info!(
+            "started embedding job: {}, extractor: {}, texts: {}",
+            &job.id, extractor, total
+        );
+        let id = job.id.clone();
+        let vector_index_manager = self.vector_index_manager.clone();
+        let caller = caller.to_string();
+        // The job outlives the request which started it, but its events still
+        // carry the request's id
+        let span = info_span!("embedding_job", job = %id);
+        tokio::spawn(
+            async move {
+                let mut position = 0;
+                loop {
+                    let chunk_len = EMBEDDING_JOB_CHUNK_SIZE.min(total - position);
+                    while let Err(IndexError::RateLimited { retry_after, .. }) =
+                        vector_index_manager.check_rate_limit(&caller, chunk_len)
+                    {
+                        tokio::time::sleep(retry_after).await;
+                    }
+                    let Some(chunk) = embeddings.next().await else {
+                        break;
+                    };
+                    if job.cancelled.load(Ordering::SeqCst) {
+                        info!("cancelled embedding job: {}", &job.id);
+                        return;
+                    }
+                    let chunk_end = (position + EMBEDDING_JOB_CHUNK_SIZE).min(total);
+                    let results = match chunk {
+                        Ok(embeddings) => {
+                            let mut results: Vec<_> = (position..chunk_end)
+                                .map(|i| {
+                                    (
+                                        i,
+                                        EmbeddingResult::Error(
+                                            "extractor returned no embedding".into(),
+                                        ),
+                                    )
+                                })
+                                .collect();
+                            for (i, embedding) in embeddings {
+                                if let Some(result) = results.get_mut(i - position) {
+                                    result.1 = EmbeddingResult::Embedding(embedding);
+                                }
+                            }
+                            results
+                        }
+                        Err(err) => {
+                            error!("unable to embed texts of job: {}: {}", &job.id, err);
+                            (position..chunk_end)
+                                .map(|i| (i, EmbeddingResult::Error(err.to_string())))
+                                .collect()
+                        }
+                    };
+                    job.record(results);
+                    position = chunk_end;
+                }
+                job.finish();
+                let status = job.status();
+                info!(
+                    "finished embedding job: {}, done: {}, failed: {}",
+                    &job.id, status.done, status.failed
+                );
+            }
+            .instrument(span),
+        );
+        Ok(id)
+    }
+
+    fn job(&self, id: &str, namespace: &Namespace) -> Option<Arc<EmbeddingJob>> {
+        self.jobs
+            .get(id)
+            .filter(|job| &job.namespace == namespace)
+            .map(|job| job.clone())
+    }
+
+    pub fn status(&self, id: &str, namespace: &Namespace) -> Option<EmbeddingJobStatus> {
+        self.job(id, namespace).map(|job| job.status())
+    }
+
+    /// Status of a job each time it makes progress, ending once the job has
+    /// finished or been deleted.
+    pub fn progress(
+        &self,
+        id: &str,
+        namespace: &Namespace,
+    ) -> Option<impl Stream<Item = EmbeddingJobStatus>> {
+        self.job(id, namespace).map(EmbeddingJob::progress)
+    }
+
+    /// Results of up to `limit` texts from `offset`, along with their
+    /// positions. Texts which haven't been embedded yet are left out.
+    pub fn results(
+        &self,
+        id: &str,
+        namespace: &Namespace,
+        offset: usize,
+        limit: usize,
+    ) -> Option<Vec<(usize, EmbeddingResult)>> {
+        let job = self.job(id, namespace)?;
+        let state = job.state.lock().unwrap();
+        Some(
+            state
+                .results
+                .iter()
+                .enumerate()
+                .skip(offset)
+                .take(limit)
+                .filter_map(|(i, result)| result.clone().map(|result| (i, result)))
+                .collect(),
+        )
+    }
+
+    /// Forgets a job along with its results. A job which is still running
+    /// stops once the chunk it's on is embedded.
+    pub fn delete(&self, id: &str, namespace: &Namespace) -> bool {
+        match self
+            .jobs
+            .remove_if(id, |_, job| &job.namespace == namespace)
+        {
+            Some((_, job)) => {
+                job.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn job_progress() {
+        let job = Arc::new(EmbeddingJob::new(&Namespace::default(), "extractor", 2));
+        let mut progress = Box::pin(job.clone().progress());
+        assert_eq!(progress.next().await.unwrap().done, 0);
+
+        job.record(vec![(0, EmbeddingResult::Embedding(vec![1.0]))]);
+        job.record(vec![(1, EmbeddingResult::Error("failed".into()))]);
+        let status = progress.next().await.unwrap();
+        assert_eq!((status.done, status.failed), (1, 1));
+        assert!(!status.finished);
+
+        job.finish();
+        assert!(progress.next().await.unwrap().finished);
+        assert!(progress.next().await.is_none());
+
+        let job = Arc::new(EmbeddingJob::new(&Namespace::default(), "extractor", 1));
+        let mut progress = Box::pin(job.clone().progress());
+        progress.next().await.unwrap();
+        job.cancel();
+        assert!(progress.next().await.unwrap().cancelled);
+        assert!(progress.next().await.is_none());
+    }
+}
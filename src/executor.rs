@@ -1,22 +1,42 @@
 use crate::{
     api::IndexifyAPIError,
     attribute_index::AttributeIndexManager,
-    extractors::{self, ExtractorTS},
+    auth::{bearer_key, AuthError, Authenticator, Repositories},
+    embedding_cache,
+    extractors::{self, run_blocking, ExtractorTS, Priority},
+    index::IndexError,
     persistence::{ExtractedAttributes, Work, WorkState},
     persistence::{ExtractorConfig, ExtractorType, Repository},
+    server_config::{self, Scope},
     vector_index::VectorIndexManager,
     vectordbs, ExecutorInfo, ServerConfig, SyncExecutor, SyncWorkerResponse,
 };
 use anyhow::{anyhow, Result};
-use axum::{extract::State, routing::get, routing::post, Router};
+use axum::{
+    extract::{Path, State},
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::Response,
+    routing::{delete, get, post},
+    Json, Router,
+};
 use dashmap::DashMap;
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     net::SocketAddr,
-    sync::{Arc, RwLock},
-    time::SystemTime,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    signal,
+    sync::{mpsc, Notify},
+    task,
 };
-use tokio::{signal, sync::mpsc};
 use tracing::error;
 use tracing::info;
 
@@ -56,11 +76,33 @@ pub struct ExtractorExecutor {
     config: Arc<ServerConfig>,
     executor_id: String,
     extractors: DashMap<String, ExtractorTS>,
-    extractor_info_list: Vec<ExtractorConfig>,
+    extractor_info: DashMap<String, ExtractorConfig>,
+    extractor_configs: DashMap<String, server_config::Extractor>,
+    extractor_restarts: DashMap<String, u64>,
     vector_index_manager: Arc<VectorIndexManager>,
     attribute_index_manager: Arc<AttributeIndexManager>,
 
     work_store: WorkStore,
+    shutting_down: AtomicBool,
+    batches_in_progress: AtomicUsize,
+    batch_finished: Notify,
+}
+
+/// Counts a batch of work as in progress until it's dropped.
+struct BatchInProgress<'a>(&'a ExtractorExecutor);
+
+impl<'a> BatchInProgress<'a> {
+    fn start(executor: &'a ExtractorExecutor) -> Self {
+        executor.batches_in_progress.fetch_add(1, Ordering::SeqCst);
+        Self(executor)
+    }
+}
+
+impl Drop for BatchInProgress<'_> {
+    fn drop(&mut self) {
+        self.0.batches_in_progress.fetch_sub(1, Ordering::SeqCst);
+        self.0.batch_finished.notify_waiters();
+    }
 }
 
 impl ExtractorExecutor {
@@ -72,26 +114,33 @@ impl ExtractorExecutor {
             config.clone(),
             repository.clone(),
             vector_db,
-        ));
+        )?);
         let attribute_index_manager = Arc::new(AttributeIndexManager::new(repository.clone()));
 
         let available_extractors: DashMap<String, ExtractorTS> = DashMap::new();
-        let mut extractor_info_list: Vec<ExtractorConfig> = vec![];
+        let extractor_info: DashMap<String, ExtractorConfig> = DashMap::new();
+        let extractor_configs: DashMap<String, server_config::Extractor> = DashMap::new();
         for extractor_config in &config.extractors {
-            let extractor = extractors::create_extractor(extractor_config.clone())?;
-            let extractor_name = extractor.info()?.name.clone();
-            extractor_info_list.push(extractor.info()?);
-            available_extractors.insert(extractor_name, extractor);
+            let extractor = vector_index_manager.create_extractor(extractor_config.clone())?;
+            let info = extractor.info()?;
+            extractor_info.insert(info.name.clone(), info.clone());
+            extractor_configs.insert(info.name.clone(), extractor_config.clone());
+            available_extractors.insert(info.name, extractor);
         }
         let extractor_executor = Self {
             repository,
             config,
             executor_id,
             extractors: available_extractors,
-            extractor_info_list,
+            extractor_info,
+            extractor_configs,
+            extractor_restarts: DashMap::new(),
             vector_index_manager,
             attribute_index_manager,
             work_store: WorkStore::new(),
+            shutting_down: AtomicBool::new(false),
+            batches_in_progress: AtomicUsize::new(0),
+            batch_finished: Notify::new(),
         };
         Ok(extractor_executor)
     }
@@ -103,12 +152,14 @@ impl ExtractorExecutor {
         attribute_index_manager: Arc<AttributeIndexManager>,
     ) -> Result<Self> {
         let available_extractors: DashMap<String, ExtractorTS> = DashMap::new();
-        let mut extractor_info_list: Vec<ExtractorConfig> = vec![];
+        let extractor_info: DashMap<String, ExtractorConfig> = DashMap::new();
+        let extractor_configs: DashMap<String, server_config::Extractor> = DashMap::new();
         for extractor_config in &config.extractors {
-            let extractor = extractors::create_extractor(extractor_config.clone())?;
-            let extractor_name = extractor.info()?.name.clone();
-            extractor_info_list.push(extractor.info()?);
-            available_extractors.insert(extractor_name, extractor);
+            let extractor = vector_index_manager.create_extractor(extractor_config.clone())?;
+            let info = extractor.info()?;
+            extractor_info.insert(info.name.clone(), info.clone());
+            extractor_configs.insert(info.name.clone(), extractor_config.clone());
+            available_extractors.insert(info.name, extractor);
         }
         let executor_id = get_host_name(config.clone()).unwrap();
         Ok(Self {
@@ -116,10 +167,15 @@ impl ExtractorExecutor {
             config,
             executor_id,
             extractors: available_extractors,
-            extractor_info_list,
+            extractor_info,
+            extractor_configs,
+            extractor_restarts: DashMap::new(),
             vector_index_manager,
             attribute_index_manager,
             work_store: WorkStore::new(),
+            shutting_down: AtomicBool::new(false),
+            batches_in_progress: AtomicUsize::new(0),
+            batch_finished: Notify::new(),
         })
     }
 
@@ -130,11 +186,117 @@ impl ExtractorExecutor {
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
-            available_extractors: self.extractor_info_list.clone(),
+            available_extractors: self.available_extractors(),
+        }
+    }
+
+    fn available_extractors(&self) -> Vec<ExtractorConfig> {
+        self.extractor_info
+            .iter()
+            .map(|e| e.value().clone())
+            .collect()
+    }
+
+    /// Loads one of the configured extractors by its name without restarting
+    /// the executor. It's advertised to the coordinator on the next sync, and
+    /// replaces any loaded extractor with the same name once the work already
+    /// using that one finishes.
+    pub fn load_extractor(&self, name: &str) -> Result<ExtractorConfig> {
+        let extractor_config = self
+            .config
+            .extractors
+            .iter()
+            .find(|extractor| extractor.name == name)
+            .cloned()
+            .ok_or_else(|| anyhow!("extractor not in the configuration: {}", name))?;
+        self.add_extractor(extractor_config)
+    }
+
+    fn add_extractor(&self, extractor_config: server_config::Extractor) -> Result<ExtractorConfig> {
+        let aliases = extractor_config.aliases.clone();
+        let extractor = self
+            .vector_index_manager
+            .create_extractor(extractor_config.clone())?;
+        let info = self
+            .vector_index_manager
+            .add_extractor(extractor.clone(), &aliases)?;
+        self.extractor_info.insert(info.name.clone(), info.clone());
+        self.extractor_configs
+            .insert(info.name.clone(), extractor_config);
+        self.extractors.insert(info.name.clone(), extractor);
+        info!("loaded extractor: {}", &info.name);
+        Ok(info)
+    }
+
+    /// Unloads an extractor. Work which is already running with it finishes,
+    /// and work for it which hasn't started yet fails.
+    pub fn unload_extractor(&self, name: &str) -> Result<()> {
+        if self.extractors.remove(name).is_none() {
+            return Err(anyhow!("extractor not found: {}", name));
+        }
+        self.extractor_info.remove(name);
+        self.extractor_configs.remove(name);
+        self.vector_index_manager.unload_extractor(name);
+        info!("unloaded extractor: {}", name);
+        Ok(())
+    }
+
+    /// Loads a fresh copy of an extractor whose model panicked, since a
+    /// panic can leave the model, or the locks around it, unusable.
+    fn restart_extractor(&self, name: &str) {
+        let extractor_config = match self.extractor_configs.get(name) {
+            Some(extractor_config) => extractor_config.value().clone(),
+            None => return,
+        };
+        let restarts = {
+            let mut restarts = self.extractor_restarts.entry(name.to_string()).or_insert(0);
+            *restarts += 1;
+            *restarts
+        };
+        error!("restarting extractor: {}, restarts: {}", name, restarts);
+        if let Err(err) = self.add_extractor(extractor_config) {
+            error!("unable to restart extractor: {}, err: {}", name, err);
+        }
+    }
+
+    pub fn metrics(&self) -> ExecutorMetrics {
+        ExecutorMetrics {
+            extractor_restarts: self
+                .extractor_restarts
+                .iter()
+                .map(|r| (r.key().clone(), *r.value()))
+                .collect(),
+            embedding_cache: self.vector_index_manager.embedding_cache_stats(),
+        }
+    }
+
+    /// Stops the executor from starting any more work, and waits for the
+    /// batches which are in progress to finish. Work which hasn't started is
+    /// left for the coordinator to allocate again.
+    pub async fn shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        info!(
+            "waiting for {} batches of work to finish",
+            self.batches_in_progress.load(Ordering::SeqCst)
+        );
+        loop {
+            let batch_finished = self.batch_finished.notified();
+            if self.batches_in_progress.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            batch_finished.await;
         }
+        info!("executor shut down");
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
     }
 
     pub async fn sync_repo(&self) -> Result<u64, anyhow::Error> {
+        if self.is_shutting_down() {
+            return Err(anyhow!("executor is shutting down"));
+        }
         let work_status: Vec<Work> = self
             .work_store
             .allocated_work
@@ -145,7 +307,7 @@ impl ExtractorExecutor {
             .collect();
         let sync_executor_req = SyncExecutor {
             executor_id: self.executor_id.clone(),
-            available_extractors: self.extractor_info_list.clone(),
+            available_extractors: self.available_extractors(),
             work_status: work_status.clone(),
         };
         let json_resp = reqwest::Client::new()
@@ -198,44 +360,150 @@ impl ExtractorExecutor {
             .values()
             .cloned()
             .collect();
+        let workers = self.config.executor_config.workers.max(1);
+        let results: Vec<Result<()>> = stream::iter(self.batch_work(work_list))
+            .map(|batch| async move {
+                if self.is_shutting_down() {
+                    info!(
+                        "executor is shutting down, not starting work: {}",
+                        batch
+                            .iter()
+                            .map(|w| w.id.as_str())
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    );
+                    return Ok(());
+                }
+                let _batch_in_progress = BatchInProgress::start(self);
+                self.perform_work_batch(batch).await
+            })
+            .buffer_unordered(workers)
+            .collect()
+            .await;
+        results.into_iter().collect()
+    }
+
+    /// Groups work which goes through the same extractor, with the same
+    /// parameters, into the same index so that it's extracted in batches of
+    /// up to `batch_size` pieces of content.
+    fn batch_work(&self, work_list: Vec<Work>) -> Vec<Vec<Work>> {
+        let batch_size = self.config.executor_config.batch_size.max(1);
+        let mut groups: HashMap<(String, String, String, String), Vec<Work>> = HashMap::new();
         for work in work_list {
-            info!(
-                "performing work: {}, extractor: {}",
-                &work.id, &work.extractor
+            let key = (
+                work.extractor.clone(),
+                work.repository_id.clone(),
+                work.index_name.clone(),
+                work.extractor_params.to_string(),
             );
-            let extractor = self
-                .extractors
-                .get(&work.extractor)
-                .unwrap()
-                .value()
-                .clone();
+            groups.entry(key).or_default().push(work);
+        }
+        groups
+            .into_values()
+            .flat_map(|group| {
+                group
+                    .chunks(batch_size)
+                    .map(|batch| batch.to_vec())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    async fn perform_work_batch(&self, batch: Vec<Work>) -> Result<(), anyhow::Error> {
+        // Every piece of work in a batch has the same extractor, parameters
+        // and index
+        let work = &batch[0];
+        info!(
+            "performing work: {}, extractor: {}",
+            batch
+                .iter()
+                .map(|w| w.id.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+            &work.extractor
+        );
+        let extractor = match self.extractors.get(&work.extractor) {
+            Some(extractor) => extractor.value().clone(),
+            None => {
+                self.fail_work(&batch, "extractor is not loaded");
+                return Ok(());
+            }
+        };
+        let mut content_list = Vec::new();
+        for work in &batch {
             let content = self
                 .repository
                 .content_from_repo(&work.content_id, &work.repository_id)
                 .await
                 .map_err(|e| anyhow!(e.to_string()))?;
+            content_list.push(content);
+        }
 
-            if let ExtractorType::Embedding { .. } = extractor.info()?.extractor_type {
+        // Extractors run on the blocking thread pool, so the workers don't
+        // hold up each other or the executor's runtime while they do
+        let timeout = Duration::from_secs(self.config.extractor_timeout_secs);
+        match extractor.info()?.extractor_type {
+            ExtractorType::Embedding { .. } => {
                 info!(
-                    "extracting embedding - repository: {}, extractor: {}, index: {}, content id: {}",
-                    &work.repository_id, &work.extractor, &work.index_name, &content.id
+                    "extracting embedding - repository: {}, extractor: {}, index: {}, content ids: {}",
+                    &work.repository_id,
+                    &work.extractor,
+                    &work.index_name,
+                    content_list.iter().map(|c| c.id.as_str()).collect::<Vec<_>>().join(",")
                 );
-                let extracted_embeddings = extractor
-                    .extract_embedding(vec![content.clone()], work.extractor_params.clone())?;
-                self.vector_index_manager
-                    .add_embedding(&work.repository_id, &work.index_name, extracted_embeddings)
-                    .await?;
-                self.work_store
-                    .update_work_state(&work.id, WorkState::Completed);
+                let total = content_list.len();
+                let chunk_size = self
+                    .config
+                    .executor_config
+                    .stream_chunk_size
+                    .unwrap_or(total)
+                    .max(1);
+                let mut extracted_embeddings = Box::pin(extractors::extract_embedding_stream(
+                    self.vector_index_manager.call_limits(),
+                    extractor,
+                    content_list,
+                    work.extractor_params.clone(),
+                    chunk_size,
+                    Priority::Bulk,
+                    timeout,
+                ));
+                let mut embedded = 0;
+                while let Some(chunk) = extracted_embeddings.next().await {
+                    let chunk = match chunk {
+                        Ok(chunk) => chunk?,
+                        Err(err) => return self.extraction_failed(&batch, err).await,
+                    };
+                    self.vector_index_manager
+                        .add_embedding(&work.repository_id, &work.index_name, chunk)
+                        .await?;
+                    embedded = (embedded + chunk_size).min(total);
+                    info!(
+                        "embedded {} of {} pieces of content - extractor: {}, index: {}",
+                        embedded, total, &work.extractor, &work.index_name
+                    );
+                }
             }
-
-            if let ExtractorType::Attributes { .. } = extractor.info()?.extractor_type {
+            ExtractorType::Attributes { .. } => {
                 info!(
-                    "extracting attributes - repository: {}, extractor: {}, index: {}, content id: {}",
-                    &work.repository_id, &work.extractor, &work.index_name, &content.id
+                    "extracting attributes - repository: {}, extractor: {}, index: {}, content ids: {}",
+                    &work.repository_id,
+                    &work.extractor,
+                    &work.index_name,
+                    content_list.iter().map(|c| c.id.as_str()).collect::<Vec<_>>().join(",")
                 );
-                let extracted_attributes = extractor
-                    .extract_attributes(vec![content], work.extractor_params.clone())?
+                let extractor_params = work.extractor_params.clone();
+                let extracted_attributes = match run_blocking(
+                    self.vector_index_manager.call_limits(),
+                    Priority::Bulk,
+                    timeout,
+                    move || extractor.extract_attributes(content_list, extractor_params),
+                )
+                .await
+                {
+                    Ok(extracted_attributes) => extracted_attributes?,
+                    Err(err) => return self.extraction_failed(&batch, err).await,
+                };
+                let extracted_attributes = extracted_attributes
                     .into_iter()
                     .map(|d| {
                         ExtractedAttributes::new(
@@ -254,12 +522,50 @@ impl ExtractorExecutor {
                         )
                         .await?;
                 }
-                self.work_store
-                    .update_work_state(&work.id, WorkState::Completed);
             }
         }
+        for work in &batch {
+            self.work_store
+                .update_work_state(&work.id, WorkState::Completed);
+        }
         Ok(())
     }
+
+    /// Fails the batch when the extractor timed out or panicked, restarting
+    /// the extractor after a panic. Any other error is returned so the work is
+    /// tried again on the next sync.
+    async fn extraction_failed(&self, batch: &[Work], err: IndexError) -> Result<()> {
+        match err {
+            IndexError::Timeout(_) => {
+                self.fail_work(batch, &err.to_string());
+                Ok(())
+            }
+            IndexError::Join(join_err) if join_err.is_panic() => {
+                self.fail_work(batch, "extractor panicked");
+                self.restart_extractor(&batch[0].extractor);
+                Ok(())
+            }
+            err => Err(err.into()),
+        }
+    }
+
+    fn fail_work(&self, batch: &[Work], reason: &str) {
+        for work in batch {
+            error!(
+                "failing work: {}, extractor: {}, reason: {}",
+                &work.id, &work.extractor, reason
+            );
+            self.work_store
+                .update_work_state(&work.id, WorkState::Failed);
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExecutorMetrics {
+    /// Number of times each extractor was restarted after its model panicked
+    pub extractor_restarts: HashMap<String, u64>,
+    pub embedding_cache: embedding_cache::EmbeddingCacheStats,
 }
 
 enum TickerMessage {
@@ -279,6 +585,7 @@ async fn heartbeat(
         match message {
             Some(TickerMessage::Shutdown) => {
                 info!("received shutdown signal");
+                executor.shutdown().await;
                 break;
             }
             Some(TickerMessage::Heartbeat) => {
@@ -311,21 +618,45 @@ impl ExecutorServer {
     }
 
     pub async fn run(&self) -> Result<(), anyhow::Error> {
-        let app = Router::new().route("/", get(root)).route(
-            "/sync_executor",
-            post(sync_worker).with_state(self.executor.clone()),
-        );
+        let mut app = Router::new()
+            .route("/", get(root))
+            .route(
+                "/sync_executor",
+                post(sync_worker).with_state(self.executor.clone()),
+            )
+            .route("/metrics", get(metrics).with_state(self.executor.clone()));
+        // Extractors are only managed with the admin keys of `auth`, like the
+        // server's models
+        if let Some(auth) = &self.config.auth {
+            let authenticator = Arc::new(Authenticator::new(auth)?);
+            let extractors = Router::new()
+                .route(
+                    "/extractors",
+                    post(load_extractor).with_state(self.executor.clone()),
+                )
+                .route(
+                    "/extractors/:extractor_name",
+                    delete(unload_extractor).with_state(self.executor.clone()),
+                )
+                .route_layer(middleware::from_fn_with_state(
+                    authenticator,
+                    authorize_admin,
+                ));
+            app = app.merge(extractors);
+        }
         let addr: SocketAddr = self.config.executor_config.server_listen_addr.parse()?;
         info!("starting executor server on: {}", &addr);
-        let (tx, rx) = mpsc::channel(32);
+        let (tx, rx) = mpsc::channel(self.config.executor_config.queue_capacity);
         if let Err(err) = tx.send(TickerMessage::Heartbeat).await {
             error!("unable to send heartbeat: {:?}", err.to_string());
         }
-        tokio::spawn(heartbeat(tx.clone(), rx, self.executor.clone()));
+        let heartbeat = tokio::spawn(heartbeat(tx.clone(), rx, self.executor.clone()));
         axum::Server::bind(&addr)
             .serve(app.into_make_service())
             .with_graceful_shutdown(shutdown_signal(tx.clone()))
             .await?;
+        // Wait for the work in progress to finish before exiting
+        heartbeat.await??;
         Ok(())
     }
 }
@@ -338,13 +669,71 @@ async fn root() -> &'static str {
 async fn sync_worker(
     extractor_executor: State<Arc<ExtractorExecutor>>,
 ) -> Result<(), IndexifyAPIError> {
-    let extractor_executor = extractor_executor;
+    if extractor_executor.is_shutting_down() {
+        return Err(IndexifyAPIError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "executor is shutting down".into(),
+        ));
+    }
     tokio::spawn(async move {
         let _ = extractor_executor.sync_repo().await;
     });
     Ok(())
 }
 
+/// Turns away requests without an API key or token of the default namespace
+/// with the `admin` scope.
+async fn authorize_admin<B>(
+    State(authenticator): State<Arc<Authenticator>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, IndexifyAPIError> {
+    let key = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(bearer_key);
+    let principal = authenticator.authenticate(key).await?;
+    principal.allows(Scope::Admin, Repositories::None)?;
+    if !principal.namespace.is_default() {
+        return Err(AuthError::NamespaceOnly(principal.name).into());
+    }
+    Ok(next.run(request).await)
+}
+
+/// Loads one of the extractors in the executor's configuration by its name.
+#[derive(Deserialize)]
+struct LoadExtractorRequest {
+    name: String,
+}
+
+#[axum_macros::debug_handler]
+async fn load_extractor(
+    extractor_executor: State<Arc<ExtractorExecutor>>,
+    Json(request): Json<LoadExtractorRequest>,
+) -> Result<Json<ExtractorConfig>, IndexifyAPIError> {
+    let extractor_executor = extractor_executor.0;
+    task::spawn_blocking(move || extractor_executor.load_extractor(&request.name))
+        .await
+        .map_err(|e| IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Json)
+        .map_err(|e| IndexifyAPIError::new(StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+#[axum_macros::debug_handler]
+async fn unload_extractor(
+    extractor_executor: State<Arc<ExtractorExecutor>>,
+    Path(extractor_name): Path<String>,
+) -> Result<(), IndexifyAPIError> {
+    extractor_executor
+        .unload_extractor(&extractor_name)
+        .map_err(|e| IndexifyAPIError::new(StatusCode::NOT_FOUND, e.to_string()))
+}
+
+async fn metrics(extractor_executor: State<Arc<ExtractorExecutor>>) -> Json<ExecutorMetrics> {
+    Json(extractor_executor.metrics())
+}
+
 fn get_host_name(config: Arc<ServerConfig>) -> Result<String> {
     Ok(config
         .executor_config
@@ -1,83 +1,1497 @@
 use anyhow::{anyhow, Ok, Result};
 
-use pythonize::pythonize;
+use futures::{stream, Stream, StreamExt};
+use metrics::{counter, gauge, histogram};
+use pythonize::{depythonize, pythonize};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{str::FromStr, sync::Arc};
-use tracing::info;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{field, info, info_span, warn, Instrument};
+use utoipa::ToSchema;
+
+use crate::{
+    embedding_cache::{self, EmbeddingCache},
+    index::IndexError,
+    persistence::{self, ExtractorConfig, ExtractorType},
+    server_config,
+    vectordbs::IndexDistance,
+};
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyList, PyString},
+};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, FromPyObject)]
+pub struct EmbeddingSchema {
+    pub distance_metric: String,
+    pub dim: usize,
+}
+
+#[pyclass]
+struct PyContent {
+    #[pyo3(get, set)]
+    id: String,
+    #[pyo3(get, set)]
+    data: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, FromPyObject)]
+pub struct ExtractedEmbeddings {
+    pub content_id: String,
+    pub text: String,
+    pub embeddings: Vec<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct AttributeData {
+    pub content_id: String,
+    pub json: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, FromPyObject)]
+pub struct ExtractorInfo {
+    pub name: String,
+    pub description: String,
+    pub output_datatype: String,
+    pub input_params: String,
+}
+
+/// Runtime details of the model behind an extractor, which aren't part of
+/// its configuration. Fields are unset when the extractor doesn't know them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelInfo {
+    pub max_sequence_length: Option<usize>,
+    pub backend: Option<String>,
+    pub device: Option<String>,
+}
+
+/// Whether an extractor's model is ready to take calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractorStatus {
+    Loaded,
+    /// A lazy extractor which hasn't been used yet.
+    NotLoaded,
+    Loading,
+    /// The model failed to load, or its circuit breaker is open.
+    Failed,
+    /// A lazy extractor which was unloaded to stay within the limit of loaded
+    /// extractors, it's loaded again the next time it's used.
+    Evicted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExtractorHealth {
+    pub status: ExtractorStatus,
+    pub last_error: Option<String>,
+}
+
+impl ExtractorHealth {
+    fn loaded() -> Self {
+        Self {
+            status: ExtractorStatus::Loaded,
+            last_error: None,
+        }
+    }
+}
+
+/// Calls to the model of an extractor since it was loaded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtractorStats {
+    pub calls: u64,
+    pub errors: u64,
+    /// Texts the model embedded or extracted attributes from.
+    pub texts: u64,
+    /// Time spent in the calls altogether.
+    pub call_time: Duration,
+}
+
+static EXTRACTOR_STATS: Mutex<BTreeMap<String, ExtractorStats>> = Mutex::new(BTreeMap::new());
+
+/// Calls to the model of an extractor, by the extractor's name, since it was
+/// last created.
+pub fn extractor_stats(name: &str) -> ExtractorStats {
+    EXTRACTOR_STATS
+        .lock()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub type ExtractorTS = Arc<dyn Extractor + Sync + Send>;
+pub trait Extractor {
+    fn info(&self) -> Result<persistence::ExtractorConfig, anyhow::Error>;
+
+    fn extract_embedding(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error>;
+
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error>;
+
+    fn extract_attributes(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<AttributeData>, anyhow::Error>;
+
+    /// Number of tokens the extractor's tokenizer splits each text into.
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error>;
+
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error>;
+
+    /// Extractors which are created with their model loaded stay healthy.
+    fn health(&self) -> ExtractorHealth {
+        ExtractorHealth::loaded()
+    }
+}
+
+pub type RerankerTS = Arc<dyn Reranker + Sync + Send>;
+pub trait Reranker {
+    /// Scores how relevant each of the texts is to the query, higher is more relevant.
+    fn score_pairs(&self, query: &str, texts: Vec<String>) -> Result<Vec<f32>, anyhow::Error>;
+}
+
+/// Cancels a call waiting for a blocking thread when the future waiting for
+/// its result is dropped, e.g. because the client of a request disconnected.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// How urgently the result of an extractor call is needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Calls somebody is waiting on, like embedding a search query.
+    Interactive,
+    /// Calls for background work, like extracting the content added to a
+    /// repository. They wait for the interactive calls in the process to
+    /// finish before they start.
+    Bulk,
+}
+
+impl Priority {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Interactive => "interactive",
+            Priority::Bulk => "bulk",
+        }
+    }
+}
+
+static INTERACTIVE_CALLS: Mutex<usize> = Mutex::new(0);
+static INTERACTIVE_CALLS_FINISHED: Condvar = Condvar::new();
+
+/// Counts an interactive call from when it's queued until it has finished,
+/// or has been dropped without running.
+struct InteractiveCall;
+
+impl InteractiveCall {
+    fn start() -> Self {
+        *INTERACTIVE_CALLS.lock().unwrap() += 1;
+        Self
+    }
+}
+
+impl Drop for InteractiveCall {
+    fn drop(&mut self) {
+        let mut calls = INTERACTIVE_CALLS.lock().unwrap();
+        *calls -= 1;
+        if *calls == 0 {
+            INTERACTIVE_CALLS_FINISHED.notify_all();
+        }
+    }
+}
+
+/// Limits on the extractor calls which run at the same time, and on the ones
+/// which can be queued or running before new calls are turned away. Either is
+/// unlimited if unset. Every call made with the same limits counts against
+/// them.
+#[derive(Debug, Default)]
+pub struct CallLimits {
+    max_running: Option<usize>,
+    max_pending: Option<usize>,
+    running: Mutex<usize>,
+    finished: Condvar,
+    pending: AtomicUsize,
+}
+
+impl CallLimits {
+    pub fn new(max_running: Option<usize>, max_pending: Option<usize>) -> Self {
+        Self {
+            max_running,
+            max_pending,
+            ..Default::default()
+        }
+    }
+
+    /// Number of extractor calls which are queued or running.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+}
+
+/// Counts an extractor call from when it's queued for a blocking thread
+/// until it has finished.
+struct PendingCall(Arc<CallLimits>);
+
+impl PendingCall {
+    /// Turns the call away if the most calls the limits allow are already
+    /// queued or running.
+    fn start(limits: Arc<CallLimits>) -> Result<Self, IndexError> {
+        let max_pending = limits.max_pending;
+        limits
+            .pending
+            .fetch_update(
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+                |pending| match max_pending {
+                    Some(max_pending) if pending >= max_pending => None,
+                    _ => Some(pending + 1),
+                },
+            )
+            .map_err(|_| IndexError::Overloaded(max_pending.unwrap_or_default()))?;
+        gauge!("indexify_extractor_pending_calls").increment(1.0);
+        std::result::Result::Ok(Self(limits))
+    }
+}
+
+impl Drop for PendingCall {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::SeqCst);
+        gauge!("indexify_extractor_pending_calls").decrement(1.0);
+    }
+}
+
+/// Counts a call as running from when it gets one of the limited slots until
+/// it's dropped.
+struct RunningCall(Arc<CallLimits>);
+
+impl RunningCall {
+    fn start(limits: Arc<CallLimits>) -> Self {
+        let running = limits.running.lock().unwrap();
+        let mut running = limits
+            .finished
+            .wait_while(running, |running| {
+                limits
+                    .max_running
+                    .is_some_and(|max_running| *running >= max_running.max(1))
+            })
+            .unwrap();
+        *running += 1;
+        drop(running);
+        Self(limits)
+    }
+
+    /// Takes a slot only if one is free straight away.
+    fn try_start(limits: Arc<CallLimits>) -> Result<Self, IndexError> {
+        let mut running = limits.running.lock().unwrap();
+        match limits.max_running {
+            Some(max_running) if *running >= max_running.max(1) => {
+                Err(IndexError::Overloaded(max_running))
+            }
+            _ => {
+                *running += 1;
+                drop(running);
+                std::result::Result::Ok(Self(limits))
+            }
+        }
+    }
+}
+
+impl Drop for RunningCall {
+    fn drop(&mut self) {
+        *self.0.running.lock().unwrap() -= 1;
+        self.0.finished.notify_one();
+    }
+}
+
+fn wait_for_interactive_calls() {
+    let calls = INTERACTIVE_CALLS.lock().unwrap();
+    let _calls = INTERACTIVE_CALLS_FINISHED
+        .wait_while(calls, |calls| *calls > 0)
+        .unwrap();
+}
+
+/// Runs a blocking extractor call on the blocking thread pool, so it doesn't
+/// tie up the runtime's worker threads, and stops waiting for it after
+/// `timeout`. Calls which are timed out or dropped before they start are
+/// skipped, while the ones already running can't be interrupted and run to
+/// the end. The call counts against `limits` while it's queued or running.
+pub async fn run_blocking<T, F>(
+    limits: Arc<CallLimits>,
+    priority: Priority,
+    timeout: Duration,
+    f: F,
+) -> Result<Result<T>, IndexError>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    run_blocking_call(limits, priority, timeout, None, f).await
+}
+
+/// Runs an extractor call like [`run_blocking`] at interactive priority, but
+/// fails with [`IndexError::Overloaded`] straight away rather than waiting
+/// when every slot for running calls is taken, so that callers can shed load
+/// or fall back to other results instead of stalling.
+pub async fn try_run_blocking<T, F>(
+    limits: Arc<CallLimits>,
+    timeout: Duration,
+    f: F,
+) -> Result<Result<T>, IndexError>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let running_call = RunningCall::try_start(limits.clone()).inspect_err(|_| {
+        counter!("indexify_extractor_errors_total", "kind" => "overloaded").increment(1);
+    })?;
+    run_blocking_call(
+        limits,
+        Priority::Interactive,
+        timeout,
+        Some(running_call),
+        f,
+    )
+    .await
+}
+
+async fn run_blocking_call<T, F>(
+    limits: Arc<CallLimits>,
+    priority: Priority,
+    timeout: Duration,
+    running_call: Option<RunningCall>,
+    f: F,
+) -> Result<Result<T>, IndexError>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let _cancel_on_drop = CancelOnDrop(cancelled.clone());
+    let interactive_call = match priority {
+        Priority::Interactive => Some(InteractiveCall::start()),
+        Priority::Bulk => None,
+    };
+    let pending_call = PendingCall::start(limits.clone()).inspect_err(|_| {
+        counter!("indexify_extractor_errors_total", "kind" => "overloaded").increment(1);
+    })?;
+    let queued = Instant::now();
+    // Blocking threads don't inherit the caller's span, so the call enters its
+    // span itself once it's off the queue
+    let span = info_span!(
+        "extractor_dispatch",
+        priority = priority.as_str(),
+        queue_seconds = field::Empty,
+    );
+    let call_span = span.clone();
+    let call = tokio::task::spawn_blocking(move || {
+        let _pending_call = pending_call;
+        let _interactive_call = interactive_call;
+        let _running_call = running_call.unwrap_or_else(|| {
+            // Bulk calls wait for interactive ones before taking a slot, so
+            // that they can't hold every slot while interactive calls wait for
+            // them
+            if priority == Priority::Bulk {
+                wait_for_interactive_calls();
+            }
+            RunningCall::start(limits)
+        });
+        if cancelled.load(Ordering::SeqCst) {
+            counter!("indexify_extractor_errors_total", "kind" => "cancelled").increment(1);
+            return Err(anyhow!("extractor call cancelled before it started"));
+        }
+        let queue_seconds = queued.elapsed().as_secs_f64();
+        histogram!("indexify_extractor_queue_seconds", "priority" => priority.as_str())
+            .record(queue_seconds);
+        call_span.record("queue_seconds", queue_seconds);
+        let _span = call_span.entered();
+        f()
+    });
+    let result = tokio::time::timeout(timeout, call)
+        .instrument(span.clone())
+        .await
+        .map_err(|_| IndexError::Timeout(timeout))
+        .and_then(|result| result.map_err(IndexError::from));
+    let kind = match &result {
+        // `Ok` is anyhow's function here, so the variant is spelled out
+        std::result::Result::Ok(call) => call.is_err().then_some("extractor"),
+        Err(IndexError::Timeout(_)) => Some("timeout"),
+        Err(IndexError::Join(err)) if err.is_panic() => Some("panic"),
+        Err(_) => Some("join"),
+    };
+    if let Some(kind) = kind {
+        counter!("indexify_extractor_errors_total", "kind" => kind).increment(1);
+        let error = match &result {
+            std::result::Result::Ok(call) => call.as_ref().err().map(|e| e.to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        span.in_scope(|| warn!(kind, error, "extractor call failed"));
+    }
+    result
+}
+
+/// Embeds content in chunks of up to `chunk_size` pieces, yielding the
+/// embeddings of each chunk as soon as it's done so that large batches don't
+/// have to be held in memory at once. Each chunk is a separate call, with its
+/// own `timeout`, and counts against `limits` like [`run_blocking`].
+pub fn extract_embedding_stream(
+    limits: Arc<CallLimits>,
+    extractor: ExtractorTS,
+    content: Vec<persistence::Content<String>>,
+    input_params: serde_json::Value,
+    chunk_size: usize,
+    priority: Priority,
+    timeout: Duration,
+) -> impl Stream<Item = Result<Result<Vec<ExtractedEmbeddings>>, IndexError>> {
+    let mut content = content.into_iter();
+    let chunks = std::iter::from_fn(move || {
+        let chunk: Vec<_> = content.by_ref().take(chunk_size.max(1)).collect();
+        (!chunk.is_empty()).then_some(chunk)
+    });
+    stream::iter(chunks).then(move |chunk| {
+        let extractor = extractor.clone();
+        let input_params = input_params.clone();
+        run_blocking(limits.clone(), priority, timeout, move || {
+            extractor.extract_embedding(chunk, input_params)
+        })
+    })
+}
+
+/// Creates an extractor from its configuration. Lazy extractors count against
+/// the limit of `loaded` while their models are loaded, and embeddings are
+/// cached in `cache`.
+pub fn create_extractor(
+    extractor_config: server_config::Extractor,
+    loaded: Arc<LoadedExtractors>,
+    cache: Arc<EmbeddingCache>,
+) -> Result<ExtractorTS> {
+    let extractor: ExtractorTS = match extractor_config.replicas {
+        0 | 1 => create_replica(extractor_config.clone(), loaded)?,
+        replicas => {
+            // Replicas on GPUs are placed on consecutive devices starting at
+            // the configured one
+            let replicas = (0..replicas)
+                .map(|replica| {
+                    let mut replica_config = extractor_config.clone();
+                    if let Some(server_config::DeviceKind::Cuda(index)) = extractor_config.device {
+                        replica_config.device =
+                            Some(server_config::DeviceKind::Cuda(index + replica as u32));
+                    }
+                    create_replica(replica_config, loaded.clone())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Arc::new(ReplicatedExtractor {
+                replicas,
+                next: AtomicUsize::new(0),
+            })
+        }
+    };
+    let extractor: ExtractorTS = match extractor_config.long_text {
+        Some(long_text) => Arc::new(PooledExtractor {
+            extractor,
+            config: long_text,
+        }),
+        None => extractor,
+    };
+    let extractor: ExtractorTS = match extractor_config.circuit_breaker {
+        Some(circuit_breaker) => Arc::new(CircuitBreakerExtractor::new(extractor, circuit_breaker)),
+        None => extractor,
+    };
+    let extractor: ExtractorTS = match extractor_config.version {
+        Some(version) => {
+            let name = format!("{}@{}", extractor.info()?.name, version);
+            info!("extractor registered as: {:?}", name);
+            Arc::new(VersionedExtractor { name, extractor })
+        }
+        None => extractor,
+    };
+    let name = extractor.info()?.name;
+    // A reloaded extractor's stats start over, like its model's
+    EXTRACTOR_STATS.lock().unwrap().remove(&name);
+    let extractor = Arc::new(InstrumentedExtractor {
+        name: name.clone(),
+        extractor,
+    });
+    let extractor = Arc::new(DedupedExtractor {
+        name: name.clone(),
+        extractor,
+    });
+    Ok(Arc::new(CachedExtractor {
+        name,
+        extractor,
+        cache,
+    }))
+}
+
+fn create_replica(
+    extractor_config: server_config::Extractor,
+    loaded: Arc<LoadedExtractors>,
+) -> Result<ExtractorTS> {
+    if extractor_config.lazy {
+        Ok(Arc::new(LazyExtractor::new(extractor_config, loaded)?))
+    } else {
+        create_unversioned_extractor(extractor_config)
+    }
+}
+
+fn create_unversioned_extractor(extractor_config: server_config::Extractor) -> Result<ExtractorTS> {
+    let warmup = extractor_config.warmup;
+    let extractor = create_driver_extractor(extractor_config)?;
+    if warmup {
+        warm_up(extractor.as_ref());
+    }
+    Ok(extractor)
+}
+
+/// Words in each of the placeholder texts an extractor is warmed up with,
+/// from short texts up to ones most models truncate.
+const WARMUP_TEXT_WORDS: [usize; 3] = [8, 64, 512];
+const WARMUP_BATCH_SIZE: usize = 4;
+
+/// Embeds batches of placeholder text of a few lengths, and a query. The
+/// first calls to a model are slower while its kernels are compiled and its
+/// memory is allocated, and warming it up keeps that out of real calls.
+/// Failures are logged, and left for the real calls to report.
+fn warm_up(extractor: &(dyn Extractor + Send + Sync)) {
+    let info = match extractor.info() {
+        Result::Ok(info) => info,
+        Err(err) => {
+            warn!("unable to warm up extractor: {}", err);
+            return;
+        }
+    };
+    if let ExtractorType::Attributes { .. } = info.extractor_type {
+        return;
+    }
+    let started = Instant::now();
+    for words in WARMUP_TEXT_WORDS {
+        let text = vec!["warmup"; words].join(" ");
+        let content = (0..WARMUP_BATCH_SIZE)
+            .map(|i| {
+                persistence::Content::new(format!("warmup-{}", i), text.clone(), HashMap::new())
+            })
+            .collect();
+        if let Err(err) = extractor.extract_embedding(content, json!({})) {
+            warn!("unable to warm up extractor: {:?}: {}", info.name, err);
+            return;
+        }
+    }
+    if let Err(err) = extractor.extract_embedding_query("warmup") {
+        warn!("unable to warm up extractor: {:?}: {}", info.name, err);
+        return;
+    }
+    info!(
+        "warmed up extractor: {:?} in {:?}",
+        info.name,
+        started.elapsed()
+    );
+}
+
+fn create_driver_extractor(extractor_config: server_config::Extractor) -> Result<ExtractorTS> {
+    match extractor_config.driver {
+        server_config::ExtractorDriver::Python => {
+            let params = match extractor_config.device {
+                Some(device) => {
+                    let mut params = extractor_config.params.unwrap_or_else(|| json!({}));
+                    params
+                        .as_object_mut()
+                        .ok_or_else(|| anyhow!("extractor params must be a map"))?
+                        .insert("device".into(), json!(device.to_string()));
+                    Some(params)
+                }
+                None => extractor_config.params,
+            };
+            let extractor = PythonDriver::new(extractor_config.path, params)?;
+            info!("extractor created: {:?}", extractor.info()?.name);
+            Ok(Arc::new(extractor))
+        }
+        #[cfg(feature = "candle")]
+        server_config::ExtractorDriver::Candle => {
+            let extractor = crate::candle_extractor::CandleBertExtractor::new(
+                &extractor_config.path,
+                extractor_config
+                    .device
+                    .unwrap_or(server_config::DeviceKind::Cpu),
+            )?;
+            info!("extractor created: {:?}", extractor.info()?.name);
+            Ok(Arc::new(extractor))
+        }
+        #[cfg(not(feature = "candle"))]
+        server_config::ExtractorDriver::Candle => Err(anyhow!(
+            "candle extractors require indexify to be built with the candle feature"
+        )),
+        _ => Err(anyhow!("unsupported extractor driver")),
+    }
+}
+
+type ExtractorSlot = Arc<Mutex<Option<ExtractorTS>>>;
+type WeakExtractorSlot = Weak<Mutex<Option<ExtractorTS>>>;
+
+/// The lazy extractors created with the same limit whose models are loaded,
+/// the least recently used first. Past the limit, the least recently used
+/// ones are unloaded, and loaded again the next time they're used. It's
+/// unlimited if unset.
+#[derive(Default)]
+pub struct LoadedExtractors {
+    limit: Option<usize>,
+    lru: Mutex<VecDeque<(usize, WeakExtractorSlot)>>,
+}
+
+static NEXT_LAZY_EXTRACTOR_ID: AtomicUsize = AtomicUsize::new(0);
+
+impl LoadedExtractors {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    /// Marks an extractor as the most recently used, and returns the ones
+    /// which should be unloaded to stay within the limit.
+    fn touch(&self, id: usize, slot: &ExtractorSlot) -> Vec<ExtractorSlot> {
+        let mut lru = self.lru.lock().unwrap();
+        lru.retain(|(lru_id, slot)| *lru_id != id && slot.strong_count() > 0);
+        lru.push_back((id, Arc::downgrade(slot)));
+        let mut evicted = Vec::new();
+        while self.limit.is_some_and(|limit| lru.len() > limit.max(1)) {
+            if let Some((_, slot)) = lru.pop_front() {
+                evicted.extend(slot.upgrade());
+            }
+        }
+        evicted
+    }
+}
+
+/// Loads an extractor's model the first time it's used. Callers which arrive
+/// while it's loading wait for it, and a failed load is tried again by the
+/// next caller.
+struct LazyExtractor {
+    id: usize,
+    config: server_config::Extractor,
+    info: ExtractorConfig,
+    extractor: ExtractorSlot,
+    loaded: Arc<LoadedExtractors>,
+    health: Mutex<ExtractorHealth>,
+}
+
+impl LazyExtractor {
+    fn new(config: server_config::Extractor, loaded: Arc<LoadedExtractors>) -> Result<Self> {
+        let info = config.info.clone().ok_or_else(|| {
+            anyhow!(
+                "lazy extractor: {} needs `info` describing it until it's loaded",
+                config.name
+            )
+        })?;
+        Ok(Self {
+            id: NEXT_LAZY_EXTRACTOR_ID.fetch_add(1, Ordering::SeqCst),
+            config,
+            info,
+            extractor: Arc::new(Mutex::new(None)),
+            loaded,
+            health: Mutex::new(ExtractorHealth {
+                status: ExtractorStatus::NotLoaded,
+                last_error: None,
+            }),
+        })
+    }
+
+    fn extractor(&self) -> Result<ExtractorTS> {
+        let extractor = self.load()?;
+        // The registry's lock is released before unloading, since the lock of
+        // an evicted slot is held for as long as its extractor takes to load
+        let evicted = self.loaded.touch(self.id, &self.extractor);
+        for slot in evicted {
+            if let Some(extractor) = slot.lock().unwrap().take() {
+                info!(
+                    "unloaded least recently used extractor: {:?}",
+                    extractor.info().map(|info| info.name).unwrap_or_default()
+                );
+            }
+        }
+        Ok(extractor)
+    }
+
+    fn load(&self) -> Result<ExtractorTS> {
+        let mut extractor = self.extractor.lock().unwrap();
+        if let Some(extractor) = extractor.as_ref() {
+            return Ok(extractor.clone());
+        }
+        info!("loading lazy extractor: {:?}", self.info.name);
+        self.health.lock().unwrap().status = ExtractorStatus::Loading;
+        let started = std::time::Instant::now();
+        let loaded = create_unversioned_extractor(self.config.clone()).map_err(|e| {
+            let err = anyhow!("unable to load extractor: {}: {}", self.info.name, e);
+            *self.health.lock().unwrap() = ExtractorHealth {
+                status: ExtractorStatus::Failed,
+                last_error: Some(err.to_string()),
+            };
+            err
+        })?;
+        self.health.lock().unwrap().status = ExtractorStatus::Loaded;
+        info!(
+            "loaded lazy extractor: {:?} in {:?}",
+            self.info.name,
+            started.elapsed()
+        );
+        extractor.replace(loaded.clone());
+        Ok(loaded)
+    }
+}
+
+impl Extractor for LazyExtractor {
+    fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+        Ok(self.info.clone())
+    }
+
+    fn extract_embedding(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+        self.extractor()?.extract_embedding(content, input_params)
+    }
+
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+        self.extractor()?.extract_embedding_query(query)
+    }
+
+    fn extract_attributes(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<AttributeData>, anyhow::Error> {
+        self.extractor()?.extract_attributes(content, input_params)
+    }
+
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        self.extractor()?.count_tokens(texts)
+    }
+
+    /// Details of the model once it's loaded, looking them up doesn't load it.
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        match self.extractor.lock().unwrap().as_ref() {
+            Some(extractor) => extractor.model_info(),
+            None => Ok(ModelInfo::default()),
+        }
+    }
+
+    fn health(&self) -> ExtractorHealth {
+        let mut health = self.health.lock().unwrap().clone();
+        // The slot is locked while the model loads, and emptied when it's
+        // evicted
+        let unloaded = self
+            .extractor
+            .try_lock()
+            .map(|extractor| extractor.is_none())
+            .unwrap_or(false);
+        if health.status == ExtractorStatus::Loaded && unloaded {
+            health.status = ExtractorStatus::Evicted;
+        }
+        health
+    }
+}
+
+/// Returned by extractors which are failing calls fast after failing
+/// repeatedly.
+#[derive(Debug, thiserror::Error)]
+#[error("extractor: {name} is unavailable for {retry_after:?} after failing repeatedly")]
+pub struct ExtractorUnavailable {
+    pub name: String,
+    pub retry_after: Duration,
+}
+
+#[derive(Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// When the call probing the extractor after the cool down started, if
+    /// it hasn't finished yet.
+    probe_started: Option<Instant>,
+    last_error: Option<String>,
+}
+
+/// Stops calling an extractor for a cool down once it has failed a number of
+/// times in a row. After the cool down a single call probes the extractor
+/// while the others keep failing fast, and the circuit closes once the probe
+/// succeeds, or opens again straight away if it fails too.
+struct CircuitBreakerExtractor {
+    extractor: ExtractorTS,
+    config: server_config::CircuitBreakerConfig,
+    state: Mutex<CircuitState>,
+}
+
+impl CircuitBreakerExtractor {
+    fn new(extractor: ExtractorTS, config: server_config::CircuitBreakerConfig) -> Self {
+        Self {
+            extractor,
+            config,
+            state: Mutex::new(CircuitState::default()),
+        }
+    }
+
+    /// How long until a call can be tried, if one can't go through now.
+    fn retry_after(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let open_until = state.open_until?;
+        let now = Instant::now();
+        if now < open_until {
+            return Some(open_until - now);
+        }
+        // A probe which never finishes, e.g. because its call panicked, is
+        // given up on after another cool down so that the next call probes
+        let cool_down = Duration::from_secs(self.config.cool_down_secs);
+        if let Some(probe_until) = state
+            .probe_started
+            .map(|started| started + cool_down)
+            .filter(|probe_until| now < *probe_until)
+        {
+            return Some(probe_until - now);
+        }
+        state.probe_started = Some(now);
+        None
+    }
+
+    fn call<T>(&self, f: impl FnOnce(&ExtractorTS) -> Result<T>) -> Result<T> {
+        if let Some(retry_after) = self.retry_after() {
+            return Err(ExtractorUnavailable {
+                name: self.extractor.info()?.name,
+                retry_after,
+            }
+            .into());
+        }
+        let result = f(&self.extractor);
+        let mut state = self.state.lock().unwrap();
+        if let Err(err) = &result {
+            state.probe_started = None;
+            state.last_error = Some(err.to_string());
+            state.consecutive_failures += 1;
+            if state.consecutive_failures >= self.config.failure_threshold {
+                state.open_until =
+                    Some(Instant::now() + Duration::from_secs(self.config.cool_down_secs));
+            }
+        } else {
+            *state = CircuitState::default();
+        }
+        result
+    }
+}
+
+impl Extractor for CircuitBreakerExtractor {
+    fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+        self.extractor.info()
+    }
+
+    fn extract_embedding(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+        self.call(|e| e.extract_embedding(content, input_params))
+    }
+
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+        self.call(|e| e.extract_embedding_query(query))
+    }
+
+    fn extract_attributes(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<AttributeData>, anyhow::Error> {
+        self.call(|e| e.extract_attributes(content, input_params))
+    }
+
+    /// Counting tokens isn't supported by every extractor, so its failures
+    /// don't open the circuit.
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        self.extractor.count_tokens(texts)
+    }
+
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        self.extractor.model_info()
+    }
+
+    fn health(&self) -> ExtractorHealth {
+        let state = self.state.lock().unwrap();
+        if state
+            .open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+        {
+            return ExtractorHealth {
+                status: ExtractorStatus::Failed,
+                last_error: state.last_error.clone(),
+            };
+        }
+        let mut health = self.extractor.health();
+        health.last_error = health.last_error.or_else(|| state.last_error.clone());
+        health
+    }
+}
+
+/// Splits texts which are longer than the model takes into windows of whole
+/// words, and pools the embeddings of the windows into one per text.
+struct PooledExtractor {
+    extractor: ExtractorTS,
+    config: server_config::LongTextConfig,
+}
+
+impl PooledExtractor {
+    fn window_tokens(&self) -> Option<usize> {
+        self.config.window_tokens.or_else(|| {
+            self.extractor
+                .model_info()
+                .ok()
+                .and_then(|model_info| model_info.max_sequence_length)
+        })
+    }
+
+    fn windows(&self, text: &str, window_tokens: usize) -> Vec<String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        // Extractors which can't count tokens are assumed to split words into
+        // a token and a third on average
+        let tokens = self
+            .extractor
+            .count_tokens(vec![text.to_string()])
+            .ok()
+            .and_then(|counts| counts.first().copied())
+            .unwrap_or_else(|| (words.len() * 4).div_ceil(3));
+        // Words are spread evenly across the windows, so some room is left
+        // for windows whose words split into more tokens than average
+        let window_tokens = (window_tokens * 9 / 10).max(1);
+        if tokens <= window_tokens || words.len() <= 1 {
+            return vec![text.to_string()];
+        }
+        let words_per_window = words.len().div_ceil(tokens.div_ceil(window_tokens));
+        words
+            .chunks(words_per_window)
+            .map(|window| window.join(" "))
+            .collect()
+    }
+}
+
+/// Pools several embeddings, e.g. of the windows of a long text, into one.
+pub fn pool_embeddings(pooling: server_config::Pooling, embeddings: Vec<Vec<f32>>) -> Vec<f32> {
+    if embeddings.len() <= 1 {
+        return embeddings.into_iter().next().unwrap_or_default();
+    }
+    let normalized = embeddings.iter().all(|embedding| {
+        let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        (norm - 1.0).abs() < 1e-3
+    });
+    let dim = embeddings[0].len();
+    let mut pooled = match pooling {
+        server_config::Pooling::Mean => {
+            let mut sum = vec![0.0; dim];
+            for embedding in &embeddings {
+                for (s, x) in sum.iter_mut().zip(embedding) {
+                    *s += x;
+                }
+            }
+            sum.iter().map(|s| s / embeddings.len() as f32).collect()
+        }
+        server_config::Pooling::Max => {
+            let mut max = vec![f32::MIN; dim];
+            for embedding in &embeddings {
+                for (m, x) in max.iter_mut().zip(embedding) {
+                    *m = m.max(*x);
+                }
+            }
+            max
+        }
+    };
+    // Pooled embeddings of a model which normalizes its embeddings are
+    // normalized too
+    if normalized {
+        let norm: f32 = pooled.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            pooled.iter_mut().for_each(|x| *x /= norm);
+        }
+    }
+    pooled
+}
+
+impl Extractor for PooledExtractor {
+    fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+        self.extractor.info()
+    }
+
+    fn extract_embedding(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+        let window_tokens = match self.window_tokens() {
+            Some(window_tokens) => window_tokens,
+            None => return self.extractor.extract_embedding(content, input_params),
+        };
+        let windows = content
+            .iter()
+            .flat_map(|c| {
+                self.windows(&c.content, window_tokens)
+                    .into_iter()
+                    .map(|window| {
+                        persistence::Content::new(c.id.clone(), window, c.metadata.clone())
+                    })
+            })
+            .collect();
+        let mut window_embeddings: HashMap<String, Vec<Vec<f32>>> = HashMap::new();
+        for e in self.extractor.extract_embedding(windows, input_params)? {
+            window_embeddings
+                .entry(e.content_id)
+                .or_default()
+                .push(e.embeddings);
+        }
+        Ok(content
+            .into_iter()
+            .filter_map(|c| {
+                let embeddings = window_embeddings.remove(&c.id)?;
+                Some(ExtractedEmbeddings {
+                    embeddings: pool_embeddings(self.config.pooling, embeddings),
+                    content_id: c.id,
+                    text: c.content,
+                })
+            })
+            .collect())
+    }
+
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+        let window_tokens = match self.window_tokens() {
+            Some(window_tokens) => window_tokens,
+            None => return self.extractor.extract_embedding_query(query),
+        };
+        let embeddings = self
+            .windows(query, window_tokens)
+            .iter()
+            .map(|window| self.extractor.extract_embedding_query(window))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(pool_embeddings(self.config.pooling, embeddings))
+    }
+
+    fn extract_attributes(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<AttributeData>, anyhow::Error> {
+        self.extractor.extract_attributes(content, input_params)
+    }
+
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        self.extractor.count_tokens(texts)
+    }
+
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        self.extractor.model_info()
+    }
+
+    fn health(&self) -> ExtractorHealth {
+        self.extractor.health()
+    }
+}
+
+/// Spreads the calls to an extractor across replicas of its model, in turn.
+struct ReplicatedExtractor {
+    replicas: Vec<ExtractorTS>,
+    next: AtomicUsize,
+}
+
+impl ReplicatedExtractor {
+    fn replica(&self) -> &ExtractorTS {
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.replicas[next % self.replicas.len()]
+    }
+}
+
+impl Extractor for ReplicatedExtractor {
+    fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+        self.replicas[0].info()
+    }
+
+    fn extract_embedding(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+        self.replica().extract_embedding(content, input_params)
+    }
+
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+        self.replica().extract_embedding_query(query)
+    }
+
+    fn extract_attributes(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<AttributeData>, anyhow::Error> {
+        self.replica().extract_attributes(content, input_params)
+    }
+
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        self.replica().count_tokens(texts)
+    }
+
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        self.replicas[0].model_info()
+    }
+
+    /// The health of the first replica which isn't loaded, if there's one.
+    fn health(&self) -> ExtractorHealth {
+        self.replicas
+            .iter()
+            .map(|replica| replica.health())
+            .find(|health| health.status != ExtractorStatus::Loaded)
+            .unwrap_or_else(ExtractorHealth::loaded)
+    }
+}
+
+/// Names an extractor after the version of it that's configured, e.g.
+/// `MiniLML6@2024-06`, so that versions don't replace each other.
+struct VersionedExtractor {
+    name: String,
+    extractor: ExtractorTS,
+}
+
+impl Extractor for VersionedExtractor {
+    fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+        let mut info = self.extractor.info()?;
+        info.name = self.name.clone();
+        Ok(info)
+    }
+
+    fn extract_embedding(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+        self.extractor.extract_embedding(content, input_params)
+    }
+
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+        self.extractor.extract_embedding_query(query)
+    }
+
+    fn extract_attributes(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<AttributeData>, anyhow::Error> {
+        self.extractor.extract_attributes(content, input_params)
+    }
+
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        self.extractor.count_tokens(texts)
+    }
+
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        self.extractor.model_info()
+    }
+
+    fn health(&self) -> ExtractorHealth {
+        self.extractor.health()
+    }
+}
+
+/// Records how long each call to an extractor's model takes, how much
+/// content it's called with and how often it fails, labelled by extractor.
+struct InstrumentedExtractor {
+    name: String,
+    extractor: ExtractorTS,
+}
+
+impl InstrumentedExtractor {
+    fn call<T>(&self, call: &'static str, f: impl FnOnce(&ExtractorTS) -> Result<T>) -> Result<T> {
+        let _span = info_span!("inference", extractor = %self.name, call).entered();
+        let started = Instant::now();
+        let result = f(&self.extractor);
+        let elapsed = started.elapsed();
+        let labels = [("extractor", self.name.clone()), ("call", call.to_string())];
+        histogram!("indexify_extractor_call_seconds", &labels).record(elapsed.as_secs_f64());
+        if result.is_err() {
+            counter!("indexify_extractor_call_errors_total", &labels).increment(1);
+        }
+        let mut stats = EXTRACTOR_STATS.lock().unwrap();
+        let stats = stats.entry(self.name.clone()).or_default();
+        stats.calls += 1;
+        stats.errors += result.is_err() as u64;
+        stats.call_time += elapsed;
+        result
+    }
+
+    fn record_batch_size(&self, size: usize) {
+        histogram!("indexify_extractor_batch_size", "extractor" => self.name.clone())
+            .record(size as f64);
+        EXTRACTOR_STATS
+            .lock()
+            .unwrap()
+            .entry(self.name.clone())
+            .or_default()
+            .texts += size as u64;
+    }
+}
+
+impl Extractor for InstrumentedExtractor {
+    fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+        self.extractor.info()
+    }
+
+    fn extract_embedding(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+        self.record_batch_size(content.len());
+        self.call("extract_embedding", |e| {
+            e.extract_embedding(content, input_params)
+        })
+    }
+
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+        self.call("extract_embedding_query", |e| {
+            e.extract_embedding_query(query)
+        })
+    }
 
-use crate::{
-    persistence::{self, ExtractorConfig, ExtractorType},
-    server_config,
-    vectordbs::IndexDistance,
-};
-use pyo3::{
-    prelude::*,
-    types::{PyList, PyString},
-};
+    fn extract_attributes(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<AttributeData>, anyhow::Error> {
+        self.record_batch_size(content.len());
+        self.call("extract_attributes", |e| {
+            e.extract_attributes(content, input_params)
+        })
+    }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, FromPyObject)]
-pub struct EmbeddingSchema {
-    pub distance_metric: String,
-    pub dim: usize,
-}
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        self.call("count_tokens", |e| e.count_tokens(texts))
+    }
 
-#[pyclass]
-struct PyContent {
-    #[pyo3(get, set)]
-    id: String,
-    #[pyo3(get, set)]
-    data: String,
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        self.extractor.model_info()
+    }
+
+    fn health(&self) -> ExtractorHealth {
+        self.extractor.health()
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, FromPyObject)]
-pub struct ExtractedEmbeddings {
-    pub content_id: String,
-    pub text: String,
-    pub embeddings: Vec<f32>,
+/// Looks up the embeddings of content and queries in the embedding cache
+/// before calling the extractor, and caches the ones it computes. Cached
+/// embeddings are keyed by the extractor's name, which includes its version.
+struct CachedExtractor {
+    name: String,
+    extractor: ExtractorTS,
+    cache: Arc<EmbeddingCache>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
-pub struct AttributeData {
-    pub content_id: String,
-    pub json: Option<serde_json::Value>,
+impl Extractor for CachedExtractor {
+    fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+        self.extractor.info()
+    }
+
+    fn extract_embedding(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+        if !self.cache.enabled() {
+            return self.extractor.extract_embedding(content, input_params);
+        }
+        let keys: Vec<_> = content
+            .iter()
+            .map(|c| embedding_cache::CacheKey::content(&self.name, &input_params, &c.content))
+            .collect();
+        let mut cached: Vec<_> = keys.iter().map(|key| self.cache.get(key)).collect();
+        let misses: Vec<_> = content
+            .iter()
+            .zip(&cached)
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(c, _)| c.clone())
+            .collect();
+        if !misses.is_empty() {
+            let mut extracted: HashMap<String, embedding_cache::CachedEmbeddings> = HashMap::new();
+            for e in self.extractor.extract_embedding(misses, input_params)? {
+                extracted
+                    .entry(e.content_id)
+                    .or_default()
+                    .push((e.text, e.embeddings));
+            }
+            for ((c, key), cached) in content.iter().zip(keys).zip(cached.iter_mut()) {
+                if cached.is_none() {
+                    let embeddings = extracted.get(&c.id).cloned().unwrap_or_default();
+                    self.cache.put(key, embeddings.clone());
+                    cached.replace(embeddings);
+                }
+            }
+        }
+        Ok(content
+            .into_iter()
+            .zip(cached)
+            .flat_map(|(c, cached)| {
+                cached
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(move |(text, embeddings)| ExtractedEmbeddings {
+                        content_id: c.id.clone(),
+                        text,
+                        embeddings,
+                    })
+            })
+            .collect())
+    }
+
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+        if !self.cache.enabled() {
+            return self.extractor.extract_embedding_query(query);
+        }
+        let key = embedding_cache::CacheKey::query(&self.name, query);
+        if let Some(mut cached) = self.cache.get(&key) {
+            if let Some((_, embeddings)) = cached.pop() {
+                return Ok(embeddings);
+            }
+        }
+        let embeddings = self.extractor.extract_embedding_query(query)?;
+        self.cache
+            .put(key, vec![(query.to_string(), embeddings.clone())]);
+        Ok(embeddings)
+    }
+
+    fn extract_attributes(
+        &self,
+        content: Vec<persistence::Content<String>>,
+        input_params: serde_json::Value,
+    ) -> Result<Vec<AttributeData>, anyhow::Error> {
+        self.extractor.extract_attributes(content, input_params)
+    }
+
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        self.extractor.count_tokens(texts)
+    }
+
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        self.extractor.model_info()
+    }
+
+    fn health(&self) -> ExtractorHealth {
+        self.extractor.health()
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, FromPyObject)]
-pub struct ExtractorInfo {
-    pub name: String,
-    pub description: String,
-    pub output_datatype: String,
-    pub input_params: String,
+/// Embeds each distinct text of a batch once, and fans the embeddings back
+/// out to every piece of content with that text. Batches of real corpora often
+/// repeat boilerplate, such as headers or empty cells.
+struct DedupedExtractor {
+    name: String,
+    extractor: ExtractorTS,
 }
 
-pub type ExtractorTS = Arc<dyn Extractor + Sync + Send>;
-pub trait Extractor {
-    fn info(&self) -> Result<persistence::ExtractorConfig, anyhow::Error>;
+impl Extractor for DedupedExtractor {
+    fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+        self.extractor.info()
+    }
 
     fn extract_embedding(
         &self,
         content: Vec<persistence::Content<String>>,
         input_params: serde_json::Value,
-    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error>;
+    ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        let mut unique = Vec::new();
+        let owners: Vec<usize> = content
+            .iter()
+            .map(|c| {
+                *positions.entry(c.content.as_str()).or_insert_with(|| {
+                    // Positions stand in for content ids, which needn't be
+                    // unique within a batch
+                    unique.push(persistence::Content {
+                        id: unique.len().to_string(),
+                        ..c.clone()
+                    });
+                    unique.len() - 1
+                })
+            })
+            .collect();
+        if unique.len() == content.len() {
+            return self.extractor.extract_embedding(content, input_params);
+        }
+        counter!("indexify_extractor_duplicate_texts_total", "extractor" => self.name.clone())
+            .increment((content.len() - unique.len()) as u64);
+        let mut extracted: Vec<Vec<(String, Vec<f32>)>> = vec![vec![]; unique.len()];
+        for e in self.extractor.extract_embedding(unique, input_params)? {
+            let position: usize = e
+                .content_id
+                .parse()
+                .map_err(|_| anyhow!("unknown content id: {}", e.content_id))?;
+            if let Some(extracted) = extracted.get_mut(position) {
+                extracted.push((e.text, e.embeddings));
+            }
+        }
+        Ok(content
+            .into_iter()
+            .zip(owners)
+            .flat_map(|(c, owner)| {
+                extracted[owner]
+                    .iter()
+                    .map(move |(text, embeddings)| ExtractedEmbeddings {
+                        content_id: c.id.clone(),
+                        text: text.clone(),
+                        embeddings: embeddings.clone(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
 
-    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error>;
+    fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+        self.extractor.extract_embedding_query(query)
+    }
 
     fn extract_attributes(
         &self,
         content: Vec<persistence::Content<String>>,
         input_params: serde_json::Value,
-    ) -> Result<Vec<AttributeData>, anyhow::Error>;
+    ) -> Result<Vec<AttributeData>, anyhow::Error> {
+        self.extractor.extract_attributes(content, input_params)
+    }
+
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        self.extractor.count_tokens(texts)
+    }
+
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        self.extractor.model_info()
+    }
+
+    fn health(&self) -> ExtractorHealth {
+        self.extractor.health()
+    }
 }
 
-pub fn create_extractor(extractor_config: server_config::Extractor) -> Result<ExtractorTS> {
-    match extractor_config.driver {
+pub fn create_reranker(reranker_config: server_config::Extractor) -> Result<RerankerTS> {
+    match reranker_config.driver {
         server_config::ExtractorDriver::Python => {
-            let extractor = PythonDriver::new(extractor_config.path)?;
-            info!("extractor created: {:?}", extractor.info()?.name);
-            Ok(Arc::new(extractor))
+            let reranker = PythonDriver::new(reranker_config.path, reranker_config.params)?;
+            info!("reranker created: {:?}", reranker_config.name);
+            Ok(Arc::new(reranker))
         }
-        _ => Err(anyhow!("unsupported extractor driver")),
+        _ => Err(anyhow!("unsupported reranker driver")),
     }
 }
 
@@ -86,7 +1500,7 @@ pub struct PythonDriver {
 }
 
 impl PythonDriver {
-    pub fn new(module_name: String) -> Result<Self> {
+    pub fn new(module_name: String, params: Option<serde_json::Value>) -> Result<Self> {
         let (module, class_name) = Self::split_module_class(&module_name)
             .ok_or_else(|| anyhow!("invalid module name: {}", module_name))?;
 
@@ -99,7 +1513,16 @@ impl PythonDriver {
             syspath.insert(0, ".")?;
             let module = PyModule::import(py, PyString::new(py, &module))?;
             let dpr_class = module.getattr(PyString::new(py, &class_name))?;
-            let dpr_object = dpr_class.call0()?.into_py(py);
+            let kwargs = match params {
+                Some(params) => Some(
+                    pythonize(py, &params)?
+                        .into_ref(py)
+                        .downcast::<PyDict>()
+                        .map_err(|e| anyhow!(e.to_string()))?,
+                ),
+                None => None,
+            };
+            let dpr_object = dpr_class.call((), kwargs)?.into_py(py);
             Ok(dpr_object)
         })?;
         Ok(Self {
@@ -248,6 +1671,39 @@ impl Extractor for PythonDriver {
         })?;
         Ok(extracted_data)
     }
+
+    fn count_tokens(&self, texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+        let counts = Python::with_gil(|py| {
+            let counts = self
+                .module_object
+                .call_method1(py, "count_tokens", (texts,))?;
+            let counts: Vec<usize> = counts.extract(py)?;
+            Ok(counts)
+        })?;
+        Ok(counts)
+    }
+
+    fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+        let model_info = Python::with_gil(|py| {
+            let model_info = self.module_object.call_method0(py, "model_info")?;
+            let model_info: ModelInfo = depythonize(model_info.as_ref(py))?;
+            Ok(model_info)
+        })?;
+        Ok(model_info)
+    }
+}
+
+impl Reranker for PythonDriver {
+    fn score_pairs(&self, query: &str, texts: Vec<String>) -> Result<Vec<f32>, anyhow::Error> {
+        let scores = Python::with_gil(|py| {
+            let scores = self
+                .module_object
+                .call_method1(py, "score_pairs", (query, texts))?;
+            let scores: Vec<f32> = scores.extract(py)?;
+            Ok(scores)
+        })?;
+        Ok(scores)
+    }
 }
 
 #[cfg(test)]
@@ -262,13 +1718,15 @@ mod tests {
 
     #[test]
     fn extract_embeddings() {
-        let extractor =
-            PythonDriver::new("indexify_extractors.embedding_extractor.MiniLML6Extractor".into())
-                .unwrap();
+        let extractor = PythonDriver::new(
+            "indexify_extractors.embedding_extractor.MiniLML6Extractor".into(),
+            None,
+        )
+        .unwrap();
 
         let info = extractor.info().unwrap();
         assert_eq!(info.name, "MiniLML6");
-        let json_schema = "{\"properties\":{\"overlap\":{\"default\":0,\"title\":\"Overlap\",\"type\":\"integer\"},\"text_splitter\":{\"default\":\"recursive\",\"enum\":[\"char\",\"recursive\"],\"title\":\"Text Splitter\",\"type\":\"string\"}},\"title\":\"EmbeddingInputParams\",\"type\":\"object\"}";
+        let json_schema = "{\"properties\":{\"normalize\":{\"title\":\"Normalize\",\"type\":\"boolean\"},\"overlap\":{\"default\":0,\"title\":\"Overlap\",\"type\":\"integer\"},\"text_splitter\":{\"default\":\"recursive\",\"enum\":[\"char\",\"recursive\"],\"title\":\"Text Splitter\",\"type\":\"string\"}},\"title\":\"EmbeddingInputParams\",\"type\":\"object\"}";
         assert_eq!(info.input_params.to_string(), json_schema);
 
         let content1 = Content::new("1".into(), "hello world".to_string(), HashMap::new());
@@ -287,9 +1745,11 @@ mod tests {
 
     #[test]
     fn extract_embeddings_query() {
-        let extractor =
-            PythonDriver::new("indexify_extractors.embedding_extractor.MiniLML6Extractor".into())
-                .unwrap();
+        let extractor = PythonDriver::new(
+            "indexify_extractors.embedding_extractor.MiniLML6Extractor".into(),
+            None,
+        )
+        .unwrap();
 
         let info = extractor.info().unwrap();
         assert_eq!(info.name, "MiniLML6");
@@ -298,11 +1758,564 @@ mod tests {
         assert_eq!(extracted_data.len(), 384);
     }
 
+    #[tokio::test]
+    async fn run_blocking_timeout() {
+        let limits = Arc::new(CallLimits::default());
+        let result = run_blocking(
+            limits.clone(),
+            Priority::Interactive,
+            Duration::from_millis(10),
+            || {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(())
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(IndexError::Timeout(_))));
+
+        let result = run_blocking(
+            limits,
+            Priority::Interactive,
+            Duration::from_secs(5),
+            || Ok(1),
+        )
+        .await;
+        assert_eq!(result.unwrap().unwrap(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn run_blocking_priority() {
+        let limits = Arc::new(CallLimits::default());
+        let interactive = tokio::spawn(run_blocking(
+            limits.clone(),
+            Priority::Interactive,
+            Duration::from_secs(5),
+            || {
+                std::thread::sleep(Duration::from_millis(200));
+                Ok(std::time::Instant::now())
+            },
+        ));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let bulk_started = run_blocking(limits, Priority::Bulk, Duration::from_secs(5), || {
+            Ok(std::time::Instant::now())
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let interactive_finished = interactive.await.unwrap().unwrap().unwrap();
+        assert!(bulk_started >= interactive_finished);
+    }
+
+    #[test]
+    fn lazy_extractor() {
+        let info = ExtractorConfig {
+            name: "MissingExtractor".into(),
+            description: "not installed".into(),
+            extractor_type: ExtractorType::Embedding {
+                dim: 384,
+                distance: IndexDistance::Cosine,
+            },
+            input_params: json!({}),
+        };
+        let extractor = create_extractor(
+            server_config::Extractor {
+                path: "indexify_extractors.missing_extractor.MissingExtractor".into(),
+                lazy: true,
+                info: Some(info),
+                ..Default::default()
+            },
+            Arc::default(),
+            Arc::default(),
+        )
+        .unwrap();
+
+        // The extractor is described by its configured info until it's used
+        assert_eq!(extractor.info().unwrap().name, "MissingExtractor");
+        assert_eq!(extractor.model_info().unwrap(), ModelInfo::default());
+        assert_eq!(extractor.health().status, ExtractorStatus::NotLoaded);
+
+        let err = extractor
+            .extract_embedding_query("hello world")
+            .unwrap_err();
+        assert!(err.to_string().contains("unable to load extractor"));
+        let health = extractor.health();
+        assert_eq!(health.status, ExtractorStatus::Failed);
+        assert_eq!(health.last_error, Some(err.to_string()));
+    }
+
+    #[test]
+    fn circuit_breaker() {
+        let extractor = create_extractor(
+            server_config::Extractor {
+                path: "indexify_extractors.missing_extractor.MissingExtractor".into(),
+                lazy: true,
+                info: Some(ExtractorConfig::default()),
+                circuit_breaker: Some(server_config::CircuitBreakerConfig {
+                    failure_threshold: 2,
+                    cool_down_secs: 60,
+                }),
+                ..Default::default()
+            },
+            Arc::default(),
+            Arc::default(),
+        )
+        .unwrap();
+
+        for _ in 0..2 {
+            let err = extractor.extract_embedding_query("hello").unwrap_err();
+            assert!(err.downcast_ref::<ExtractorUnavailable>().is_none());
+        }
+        let err = extractor.extract_embedding_query("hello").unwrap_err();
+        assert!(err.downcast_ref::<ExtractorUnavailable>().is_some());
+    }
+
+    /// Answers each query with the error it's sent, or an embedding without
+    /// one, so calls can be held while they're in flight
+    struct Scripted(Mutex<std::sync::mpsc::Receiver<Option<&'static str>>>);
+
+    impl Extractor for Scripted {
+        fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+            Ok(ExtractorConfig::default())
+        }
+
+        fn extract_embedding(
+            &self,
+            _content: Vec<Content<String>>,
+            _input_params: serde_json::Value,
+        ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+            Ok(vec![])
+        }
+
+        fn extract_embedding_query(&self, _query: &str) -> Result<Vec<f32>, anyhow::Error> {
+            match self.0.lock().unwrap().recv().unwrap() {
+                Some(err) => Err(anyhow!(err)),
+                None => Ok(vec![1.0]),
+            }
+        }
+
+        fn extract_attributes(
+            &self,
+            _content: Vec<Content<String>>,
+            _input_params: serde_json::Value,
+        ) -> Result<Vec<AttributeData>, anyhow::Error> {
+            Ok(vec![])
+        }
+
+        fn count_tokens(&self, _texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+            Ok(vec![])
+        }
+
+        fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+            Ok(ModelInfo::default())
+        }
+    }
+
+    #[test]
+    fn half_open_circuit_breaker() {
+        let (results, receiver) = std::sync::mpsc::channel();
+        let extractor = Arc::new(CircuitBreakerExtractor::new(
+            Arc::new(Scripted(Mutex::new(receiver))),
+            server_config::CircuitBreakerConfig {
+                failure_threshold: 1,
+                cool_down_secs: 60,
+            },
+        ));
+        let unavailable = |extractor: &CircuitBreakerExtractor| {
+            let err = extractor.extract_embedding_query("hello").unwrap_err();
+            err.downcast_ref::<ExtractorUnavailable>().is_some()
+        };
+        // Ends the cool down of the open circuit
+        let cool_down = |extractor: &CircuitBreakerExtractor| {
+            extractor.state.lock().unwrap().open_until = Some(Instant::now());
+        };
+
+        results.send(Some("out of memory")).unwrap();
+        assert!(!unavailable(&extractor));
+        assert!(unavailable(&extractor));
+
+        // Only the probe reaches the extractor while it's in flight
+        cool_down(&extractor);
+        let probe = std::thread::spawn({
+            let extractor = extractor.clone();
+            move || extractor.extract_embedding_query("hello")
+        });
+        while extractor.state.lock().unwrap().probe_started.is_none() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(unavailable(&extractor));
+        results.send(Some("out of memory")).unwrap();
+        assert!(probe.join().unwrap().is_err());
+        // A failed probe opens the circuit again
+        assert!(unavailable(&extractor));
+
+        cool_down(&extractor);
+        results.send(None).unwrap();
+        assert!(extractor.extract_embedding_query("hello").is_ok());
+        results.send(None).unwrap();
+        assert!(extractor.extract_embedding_query("hello").is_ok());
+        assert!(extractor.state.lock().unwrap().open_until.is_none());
+    }
+
+    /// Embeds each piece of content as its length, and remembers what it
+    /// was asked to embed
+    #[derive(Default)]
+    struct Counting(Mutex<Vec<String>>);
+
+    impl Extractor for Counting {
+        fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+            Ok(ExtractorConfig::default())
+        }
+
+        fn extract_embedding(
+            &self,
+            content: Vec<Content<String>>,
+            _input_params: serde_json::Value,
+        ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+            let mut embedded = self.0.lock().unwrap();
+            Ok(content
+                .into_iter()
+                .map(|c| {
+                    embedded.push(c.content.clone());
+                    ExtractedEmbeddings {
+                        content_id: c.id,
+                        embeddings: vec![c.content.len() as f32],
+                        text: c.content,
+                    }
+                })
+                .collect())
+        }
+
+        fn extract_embedding_query(&self, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+            self.0.lock().unwrap().push(query.to_string());
+            Ok(vec![query.len() as f32])
+        }
+
+        fn extract_attributes(
+            &self,
+            _content: Vec<Content<String>>,
+            _input_params: serde_json::Value,
+        ) -> Result<Vec<AttributeData>, anyhow::Error> {
+            Ok(vec![])
+        }
+
+        fn count_tokens(&self, _texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+            Ok(vec![])
+        }
+
+        fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+            Ok(ModelInfo::default())
+        }
+    }
+
+    #[test]
+    fn cached_extractor() {
+        let counting = Arc::new(Counting::default());
+        let cache = Arc::new(EmbeddingCache::new(16));
+        let extractor = CachedExtractor {
+            name: "cached_extractor_test".into(),
+            extractor: counting.clone(),
+            cache: cache.clone(),
+        };
+        let content = |id: &str, text: &str| Content::new(id.into(), text.into(), HashMap::new());
+
+        extractor
+            .extract_embedding(vec![content("1", "hello")], json!({}))
+            .unwrap();
+        let extracted = extractor
+            .extract_embedding(vec![content("2", "hello"), content("3", "hi")], json!({}))
+            .unwrap();
+        assert_eq!(
+            extracted,
+            vec![
+                ExtractedEmbeddings {
+                    content_id: "2".into(),
+                    text: "hello".into(),
+                    embeddings: vec![5.0],
+                },
+                ExtractedEmbeddings {
+                    content_id: "3".into(),
+                    text: "hi".into(),
+                    embeddings: vec![2.0],
+                },
+            ]
+        );
+        extractor.extract_embedding_query("hello").unwrap();
+        extractor.extract_embedding_query("hello").unwrap();
+        assert_eq!(*counting.0.lock().unwrap(), vec!["hello", "hi", "hello"]);
+        assert_eq!(cache.stats().entries, 3);
+    }
+
+    #[test]
+    fn deduped_extractor() {
+        let counting = Arc::new(Counting::default());
+        let extractor = DedupedExtractor {
+            name: "deduped_extractor_test".into(),
+            extractor: counting.clone(),
+        };
+        let content = |id: &str, text: &str| Content::new(id.into(), text.into(), HashMap::new());
+
+        let extracted = extractor
+            .extract_embedding(
+                vec![
+                    content("1", "hello"),
+                    content("2", ""),
+                    content("3", "hello"),
+                    content("4", ""),
+                ],
+                json!({}),
+            )
+            .unwrap();
+        assert_eq!(*counting.0.lock().unwrap(), vec!["hello", ""]);
+        let ids: Vec<_> = extracted.iter().map(|e| e.content_id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2", "3", "4"]);
+        let embeddings: Vec<_> = extracted.into_iter().map(|e| e.embeddings).collect();
+        assert_eq!(embeddings, vec![vec![5.0], vec![0.0], vec![5.0], vec![0.0]]);
+    }
+
+    #[test]
+    fn extractor_stats() {
+        let extractor = InstrumentedExtractor {
+            name: "extractor_stats_test".into(),
+            extractor: Arc::new(Counting::default()),
+        };
+        let content = |id: &str, text: &str| Content::new(id.into(), text.into(), HashMap::new());
+
+        extractor
+            .extract_embedding(vec![content("1", "hello"), content("2", "hi")], json!({}))
+            .unwrap();
+        extractor.extract_embedding_query("hello").unwrap();
+        let stats = super::extractor_stats("extractor_stats_test");
+        assert_eq!(stats.calls, 2);
+        assert_eq!(stats.errors, 0);
+        assert_eq!(stats.texts, 2);
+        assert_eq!(
+            super::extractor_stats("missing_extractor"),
+            ExtractorStats::default()
+        );
+    }
+
+    #[test]
+    fn pooled_extractor() {
+        let extract = |pooling| {
+            let extractor = PooledExtractor {
+                extractor: Arc::new(Counting::default()),
+                config: server_config::LongTextConfig {
+                    pooling,
+                    window_tokens: Some(4),
+                },
+            };
+            let content = vec![
+                Content::new("1".into(), "aa bb cc d e f".into(), HashMap::new()),
+                Content::new("2".into(), "short".into(), HashMap::new()),
+            ];
+            extractor
+                .extract_embedding(content, json!({}))
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.text, e.embeddings))
+                .collect::<Vec<_>>()
+        };
+        // The long text is embedded as "aa bb", "cc d" and "e f"
+        assert_eq!(
+            extract(server_config::Pooling::Mean),
+            vec![
+                ("aa bb cc d e f".to_string(), vec![4.0]),
+                ("short".to_string(), vec![5.0])
+            ]
+        );
+        assert_eq!(
+            extract(server_config::Pooling::Max)[0],
+            ("aa bb cc d e f".to_string(), vec![5.0])
+        );
+    }
+
+    #[test]
+    fn warm_up() {
+        let counting = Counting::default();
+        super::warm_up(&counting);
+        let embedded = counting.0.lock().unwrap();
+        assert_eq!(
+            embedded.len(),
+            WARMUP_TEXT_WORDS.len() * WARMUP_BATCH_SIZE + 1
+        );
+        assert_eq!(embedded[0].split(' ').count(), WARMUP_TEXT_WORDS[0]);
+    }
+
+    #[tokio::test]
+    async fn extract_embedding_stream() {
+        let counting = Arc::new(Counting::default());
+        let content = ["a", "bb", "ccc"]
+            .iter()
+            .enumerate()
+            .map(|(id, text)| Content::new(id.to_string(), text.to_string(), HashMap::new()))
+            .collect();
+        let chunks: Vec<Vec<f32>> = super::extract_embedding_stream(
+            Arc::new(CallLimits::default()),
+            counting,
+            content,
+            json!({}),
+            2,
+            Priority::Bulk,
+            Duration::from_secs(5),
+        )
+        .map(|chunk| {
+            chunk
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .flat_map(|e| e.embeddings)
+                .collect()
+        })
+        .collect()
+        .await;
+        assert_eq!(chunks, vec![vec![1.0, 2.0], vec![3.0]]);
+    }
+
+    #[test]
+    fn try_start_running_call() {
+        let limits = Arc::new(CallLimits::new(Some(1), None));
+        let running_call = RunningCall::start(limits.clone());
+        assert!(matches!(
+            RunningCall::try_start(limits.clone()),
+            Err(IndexError::Overloaded(1))
+        ));
+        drop(running_call);
+        assert!(RunningCall::try_start(limits.clone()).is_ok());
+
+        let unlimited = Arc::new(CallLimits::default());
+        let _running_calls: Vec<_> = (0..4)
+            .map(|_| RunningCall::try_start(unlimited.clone()).unwrap())
+            .collect();
+
+        let limits = Arc::new(CallLimits::new(None, Some(1)));
+        let pending_call = PendingCall::start(limits.clone()).unwrap();
+        assert_eq!(limits.pending(), 1);
+        assert!(matches!(
+            PendingCall::start(limits.clone()),
+            Err(IndexError::Overloaded(1))
+        ));
+        drop(pending_call);
+        assert_eq!(limits.pending(), 0);
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let loaded = LoadedExtractors::new(Some(2));
+        let slots: Vec<ExtractorSlot> = (0..3).map(|_| Arc::new(Mutex::new(None))).collect();
+        assert!(loaded.touch(0, &slots[0]).is_empty());
+        assert!(loaded.touch(1, &slots[1]).is_empty());
+        assert!(loaded.touch(0, &slots[0]).is_empty());
+
+        let evicted = loaded.touch(2, &slots[2]);
+        assert_eq!(evicted.len(), 1);
+        assert!(Arc::ptr_eq(&evicted[0], &slots[1]));
+    }
+
+    #[test]
+    fn replicated_extractor() {
+        /// Embeds every query as the index of the replica
+        struct Replica(f32);
+
+        impl Extractor for Replica {
+            fn info(&self) -> Result<ExtractorConfig, anyhow::Error> {
+                Ok(ExtractorConfig::default())
+            }
+
+            fn extract_embedding(
+                &self,
+                _content: Vec<Content<String>>,
+                _input_params: serde_json::Value,
+            ) -> Result<Vec<ExtractedEmbeddings>, anyhow::Error> {
+                Ok(vec![])
+            }
+
+            fn extract_embedding_query(&self, _query: &str) -> Result<Vec<f32>, anyhow::Error> {
+                Ok(vec![self.0])
+            }
+
+            fn extract_attributes(
+                &self,
+                _content: Vec<Content<String>>,
+                _input_params: serde_json::Value,
+            ) -> Result<Vec<AttributeData>, anyhow::Error> {
+                Ok(vec![])
+            }
+
+            fn count_tokens(&self, _texts: Vec<String>) -> Result<Vec<usize>, anyhow::Error> {
+                Ok(vec![])
+            }
+
+            fn model_info(&self) -> Result<ModelInfo, anyhow::Error> {
+                Ok(ModelInfo::default())
+            }
+        }
+
+        let extractor = ReplicatedExtractor {
+            replicas: vec![Arc::new(Replica(0.0)), Arc::new(Replica(1.0))],
+            next: AtomicUsize::new(0),
+        };
+        let replicas: Vec<f32> = (0..4)
+            .map(|_| extractor.extract_embedding_query("hello").unwrap()[0])
+            .collect();
+        assert_eq!(replicas, vec![0.0, 1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn versioned_extractor() {
+        let extractor = create_extractor(
+            server_config::Extractor {
+                path: "indexify_extractors.embedding_extractor.MiniLML6Extractor".into(),
+                version: Some("2024-06".into()),
+                ..Default::default()
+            },
+            Arc::default(),
+            Arc::default(),
+        )
+        .unwrap();
+
+        let info = extractor.info().unwrap();
+        assert_eq!(info.name, "MiniLML6@2024-06");
+
+        let extracted_data = extractor.extract_embedding_query("hello world").unwrap();
+        assert_eq!(extracted_data.len(), 384);
+    }
+
+    #[test]
+    fn count_tokens() {
+        let extractor = PythonDriver::new(
+            "indexify_extractors.embedding_extractor.MiniLML6Extractor".into(),
+            None,
+        )
+        .unwrap();
+
+        let counts = extractor
+            .count_tokens(vec!["hello world".into(), "indexify is awesome".into()])
+            .unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[0], 4);
+    }
+
+    #[test]
+    fn model_info() {
+        let extractor = PythonDriver::new(
+            "indexify_extractors.embedding_extractor.MiniLML6Extractor".into(),
+            None,
+        )
+        .unwrap();
+
+        let model_info = extractor.model_info().unwrap();
+        assert_eq!(model_info.backend, Some("torch".into()));
+        assert_eq!(model_info.device, Some("cpu".into()));
+        assert_eq!(model_info.max_sequence_length, Some(512));
+    }
+
     #[test]
     fn extract_attributes() {
-        let extractor =
-            PythonDriver::new("indexify_extractors.entity_extractor.EntityExtractor".into())
-                .unwrap();
+        let extractor = PythonDriver::new(
+            "indexify_extractors.entity_extractor.EntityExtractor".into(),
+            None,
+        )
+        .unwrap();
 
         let info = extractor.info().unwrap();
         assert_eq!(info.name, "EntityExtractor");
@@ -317,4 +2330,25 @@ mod tests {
             .unwrap();
         assert_eq!(extracted_data.len(), 2);
     }
+
+    #[test]
+    fn score_pairs() {
+        let reranker = PythonDriver::new(
+            "indexify_extractors.cross_encoder.CrossEncoderReranker".into(),
+            None,
+        )
+        .unwrap();
+
+        let scores = reranker
+            .score_pairs(
+                "where does Donald live?",
+                vec![
+                    "My name is Donald and I live in Seattle".into(),
+                    "indexify is awesome".into(),
+                ],
+            )
+            .unwrap();
+        assert_eq!(scores.len(), 2);
+        assert!(scores[0] > scores[1]);
+    }
 }
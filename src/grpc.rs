@@ -0,0 +1,177 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use tonic::{metadata::MetadataMap, transport::ServerTlsConfig, Request, Response, Status};
+use tracing::{info, instrument};
+
+use crate::{
+    auth::{bearer_key, AuthError, Authenticator, Repositories},
+    data_repository_manager::{DataRepositoryError, DataRepositoryManager},
+    index::IndexError,
+    persistence::ExtractorType,
+    server_config::Scope,
+};
+
+pub mod proto {
+    tonic::include_proto!("indexify.embeddings");
+}
+
+use proto::{
+    embedding_service_server::{EmbeddingService, EmbeddingServiceServer},
+    Embedding, GenerateEmbeddingsRequest, GenerateEmbeddingsResponse, ListModelsRequest,
+    ListModelsResponse, Model,
+};
+
+/// Metadata naming the tenant or service a call is made for, whose rate limit
+/// the call counts against.
+const CALLER_METADATA: &str = "x-indexify-caller";
+
+const AUTHORIZATION_METADATA: &str = "authorization";
+
+/// Calls without a caller share one rate limit.
+const ANONYMOUS_CALLER: &str = "anonymous";
+
+fn caller(metadata: &MetadataMap) -> String {
+    metadata
+        .get(CALLER_METADATA)
+        .and_then(|caller| caller.to_str().ok())
+        .unwrap_or(ANONYMOUS_CALLER)
+        .to_string()
+}
+
+fn auth_status(e: AuthError) -> Status {
+    match e {
+        AuthError::MissingKey | AuthError::InvalidKey | AuthError::InvalidToken(_) => {
+            Status::unauthenticated(e.to_string())
+        }
+        AuthError::KeysUnavailable(_) => Status::unavailable(e.to_string()),
+        _ => Status::permission_denied(e.to_string()),
+    }
+}
+
+/// Maps errors to the gRPC status codes which tell clients whether the call is
+/// worth retrying, like the HTTP API's statuses do.
+fn status(e: DataRepositoryError) -> Status {
+    match e {
+        DataRepositoryError::RetrievalError(e) => match &e {
+            IndexError::RateLimited { .. } => Status::resource_exhausted(e.to_string()),
+            IndexError::Timeout(_) => Status::deadline_exceeded(e.to_string()),
+            e if e.is_retryable() => Status::unavailable(e.to_string()),
+            IndexError::InvalidInput(_) | IndexError::InvalidText { .. } => {
+                Status::invalid_argument(e.to_string())
+            }
+            IndexError::ExtractorNotFound(_) => Status::not_found(e.to_string()),
+            _ => Status::internal(e.to_string()),
+        },
+        _ => Status::internal(e.to_string()),
+    }
+}
+
+/// Serves embeddings over gRPC, whose packed floats are a fraction of the size
+/// of the HTTP API's JSON.
+pub struct EmbeddingServer {
+    repository_manager: Arc<DataRepositoryManager>,
+    authenticator: Option<Arc<Authenticator>>,
+}
+
+impl EmbeddingServer {
+    pub fn new(
+        repository_manager: Arc<DataRepositoryManager>,
+        authenticator: Option<Arc<Authenticator>>,
+    ) -> Self {
+        Self {
+            repository_manager,
+            authenticator,
+        }
+    }
+
+    /// Turns away calls without an API key or token which has the `read`
+    /// scope, which is all the service's calls need, and returns the caller
+    /// the call counts against. Authenticated calls count against the key
+    /// rather than the caller the client sent.
+    async fn authorize(&self, metadata: &MetadataMap) -> Result<String, AuthError> {
+        let Some(authenticator) = &self.authenticator else {
+            return Ok(caller(metadata));
+        };
+        let key = metadata
+            .get(AUTHORIZATION_METADATA)
+            .and_then(|authorization| authorization.to_str().ok())
+            .and_then(bearer_key);
+        let principal = authenticator.authenticate(key).await?;
+        principal.allows(Scope::Read, Repositories::None)?;
+        Ok(principal.name)
+    }
+
+    pub async fn run(
+        self,
+        addr: SocketAddr,
+        tls: Option<ServerTlsConfig>,
+        shutdown: impl std::future::Future<Output = ()>,
+    ) -> Result<(), tonic::transport::Error> {
+        info!("grpc server is listening at addr {:?}", addr.to_string());
+        let mut server = tonic::transport::Server::builder();
+        if let Some(tls) = tls {
+            server = server.tls_config(tls)?;
+        }
+        server
+            .add_service(EmbeddingServiceServer::new(self))
+            .serve_with_shutdown(addr, shutdown)
+            .await
+    }
+}
+
+#[tonic::async_trait]
+impl EmbeddingService for EmbeddingServer {
+    #[instrument(skip_all, fields(otel.kind = "server", model = %request.get_ref().model))]
+    async fn generate_embeddings(
+        &self,
+        request: Request<GenerateEmbeddingsRequest>,
+    ) -> Result<Response<GenerateEmbeddingsResponse>, Status> {
+        let caller = self
+            .authorize(request.metadata())
+            .await
+            .map_err(auth_status)?;
+        let request = request.into_inner();
+        self.repository_manager
+            .check_rate_limit(&caller, request.texts.len())
+            .map_err(status)?;
+        let embeddings = self
+            .repository_manager
+            .embed(&request.model, request.texts)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(GenerateEmbeddingsResponse {
+            embeddings: embeddings
+                .into_iter()
+                .map(|values| Embedding { values })
+                .collect(),
+        }))
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "server"))]
+    async fn list_models(
+        &self,
+        request: Request<ListModelsRequest>,
+    ) -> Result<Response<ListModelsResponse>, Status> {
+        self.authorize(request.metadata())
+            .await
+            .map_err(auth_status)?;
+        let extractors = self
+            .repository_manager
+            .list_extractors()
+            .await
+            .map_err(status)?;
+        let models = extractors
+            .into_iter()
+            .filter_map(|extractor| match extractor.extractor_type {
+                ExtractorType::Embedding { dim, distance } => Some(Model {
+                    name: extractor.name,
+                    description: extractor.description,
+                    dim: dim as u64,
+                    distance: distance.to_string(),
+                }),
+                _ => None,
+            })
+            .collect();
+        Ok(Response::new(ListModelsResponse { models }))
+    }
+}
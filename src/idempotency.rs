@@ -0,0 +1,263 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{body::Bytes, http::HeaderValue, http::StatusCode};
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::{namespace::Namespace, server_config::IdempotencyConfig};
+
+/// Longest idempotency key accepted, which is plenty for a UUID or a hash.
+const MAX_KEY_LEN: usize = 255;
+
+#[derive(Debug, Error)]
+pub enum IdempotencyError {
+    #[error("invalid idempotency key: keys are 1 to 255 visible ASCII characters")]
+    InvalidKey,
+
+    #[error("a request with idempotency key `{0}` is still in progress")]
+    InProgress(String),
+
+    #[error("idempotency key `{0}` was already used for a request with a different body")]
+    KeyReused(String),
+}
+
+/// Identifies a request by the caller and namespace it's made for, the
+/// endpoint it's made to, and the idempotency key the client sent, so that
+/// clients can't see each other's responses by reusing a key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestKey {
+    caller: String,
+    namespace: Namespace,
+    method: String,
+    path: String,
+    key: String,
+}
+
+impl RequestKey {
+    pub fn new(
+        caller: &str,
+        namespace: Namespace,
+        method: &str,
+        path: &str,
+        key: &str,
+    ) -> Result<Self, IdempotencyError> {
+        let valid = !key.is_empty()
+            && key.len() <= MAX_KEY_LEN
+            && key.chars().all(|c| c.is_ascii_graphic());
+        if !valid {
+            return Err(IdempotencyError::InvalidKey);
+        }
+        Ok(Self {
+            caller: caller.to_string(),
+            namespace,
+            method: method.to_string(),
+            path: path.to_string(),
+            key: key.to_string(),
+        })
+    }
+}
+
+/// Hash of a request's body, which retries of the request have to match.
+pub fn fingerprint(body: &[u8]) -> [u8; 32] {
+    Sha256::digest(body).into()
+}
+
+/// A response which is sent again to retries of its request.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub content_type: Option<HeaderValue>,
+    pub body: Bytes,
+}
+
+enum State {
+    InProgress,
+    Done(CachedResponse),
+}
+
+struct Entry {
+    fingerprint: [u8; 32],
+    started: Instant,
+    state: State,
+}
+
+/// Responses of requests made with an idempotency key, kept for a while so
+/// that a client which retries a request after losing its connection gets the
+/// response of the first attempt, rather than its documents being added and
+/// embedded twice. The least recently used keys are dropped past the
+/// capacity.
+pub struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<LruCache<RequestKey, Entry>>,
+}
+
+/// What to do with a request with an idempotency key.
+pub enum Attempt {
+    /// The request hasn't been made before, and its response is cached once
+    /// it's completed.
+    Run(Pending),
+    /// The request has been made before, and gets the same response.
+    Replay(CachedResponse),
+}
+
+impl IdempotencyCache {
+    pub fn new(config: &IdempotencyConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            entries: Mutex::new(LruCache::new(
+                NonZeroUsize::new(config.max_keys).unwrap_or(NonZeroUsize::MIN),
+            )),
+        }
+    }
+
+    /// Starts a request, unless a request with the same key has been made
+    /// before. A request whose key is reused for a different body, or whose
+    /// first attempt hasn't finished yet, is turned away.
+    pub fn begin(
+        self: &Arc<Self>,
+        key: RequestKey,
+        fingerprint: [u8; 32],
+    ) -> Result<Attempt, IdempotencyError> {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(&key) {
+            if entry.started.elapsed() < self.ttl {
+                if entry.fingerprint != fingerprint {
+                    return Err(IdempotencyError::KeyReused(key.key));
+                }
+                return match &entry.state {
+                    State::InProgress => Err(IdempotencyError::InProgress(key.key)),
+                    State::Done(response) => Ok(Attempt::Replay(response.clone())),
+                };
+            }
+        }
+        entries.put(
+            key.clone(),
+            Entry {
+                fingerprint,
+                started: Instant::now(),
+                state: State::InProgress,
+            },
+        );
+        Ok(Attempt::Run(Pending {
+            cache: self.clone(),
+            key: Some(key),
+        }))
+    }
+}
+
+/// A request which is in progress. Its key is released if the request is
+/// dropped without completing, e.g. when the client disconnects, or fails in
+/// a way which is worth retrying, so a retry runs it again.
+pub struct Pending {
+    cache: Arc<IdempotencyCache>,
+    key: Option<RequestKey>,
+}
+
+impl Pending {
+    /// Keeps the response of the request for its retries.
+    pub fn complete(mut self, response: CachedResponse) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        let mut entries = self.cache.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(&key) {
+            entry.state = State::Done(response);
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.cache.entries.lock().unwrap().pop(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idempotency_cache(ttl_secs: u64) -> Arc<IdempotencyCache> {
+        Arc::new(IdempotencyCache::new(&IdempotencyConfig {
+            ttl_secs,
+            max_keys: 16,
+        }))
+    }
+
+    fn key(caller: &str, key: &str) -> RequestKey {
+        RequestKey::new(
+            caller,
+            Namespace::default(),
+            "POST",
+            "/repositories/default/add_texts",
+            key,
+        )
+        .unwrap()
+    }
+
+    fn response() -> CachedResponse {
+        CachedResponse {
+            status: StatusCode::OK,
+            content_type: None,
+            body: Bytes::from_static(b"{}"),
+        }
+    }
+
+    #[test]
+    fn replays_completed_requests() {
+        let cache = idempotency_cache(60);
+        let body = fingerprint(b"body");
+        let Ok(Attempt::Run(pending)) = cache.begin(key("a", "1"), body) else {
+            panic!("expected the first attempt to run");
+        };
+        assert!(matches!(
+            cache.begin(key("a", "1"), body),
+            Err(IdempotencyError::InProgress(_))
+        ));
+        pending.complete(response());
+
+        let Ok(Attempt::Replay(replayed)) = cache.begin(key("a", "1"), body) else {
+            panic!("expected the retry to be replayed");
+        };
+        assert_eq!(replayed.body, Bytes::from_static(b"{}"));
+        assert!(matches!(
+            cache.begin(key("a", "1"), fingerprint(b"other")),
+            Err(IdempotencyError::KeyReused(_))
+        ));
+        // Keys are scoped to their caller
+        assert!(matches!(
+            cache.begin(key("b", "1"), body),
+            Ok(Attempt::Run(_))
+        ));
+    }
+
+    #[test]
+    fn releases_dropped_and_expired_requests() {
+        let cache = idempotency_cache(60);
+        let body = fingerprint(b"body");
+        drop(cache.begin(key("a", "1"), body).unwrap());
+        assert!(matches!(
+            cache.begin(key("a", "1"), body),
+            Ok(Attempt::Run(_))
+        ));
+
+        let cache = idempotency_cache(0);
+        let Ok(Attempt::Run(pending)) = cache.begin(key("a", "1"), body) else {
+            panic!("expected the first attempt to run");
+        };
+        pending.complete(response());
+        assert!(matches!(
+            cache.begin(key("a", "1"), fingerprint(b"other")),
+            Ok(Attempt::Run(_))
+        ));
+
+        assert!(RequestKey::new("a", Namespace::default(), "POST", "/", "").is_err());
+        assert!(RequestKey::new("a", Namespace::default(), "POST", "/", "a key").is_err());
+    }
+}
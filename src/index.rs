@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use thiserror::Error;
 
 use tracing::error;
@@ -16,6 +18,87 @@ pub enum IndexError {
     #[error("unable to serialize unique params `{0}`")]
     UniqueParamsSerializationError(#[from] serde_json::Error),
 
+    #[error(transparent)]
+    Join(#[from] tokio::task::JoinError),
+
+    #[error("extractor timed out after {0:?}")]
+    Timeout(std::time::Duration),
+
+    #[error("too many extractor calls are queued, the limit is {0}")]
+    Overloaded(usize),
+
+    #[error("caller `{caller}` is rate limited, retry after {retry_after:?}")]
+    RateLimited {
+        caller: String,
+        retry_after: Duration,
+    },
+
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("invalid text {index}: {reason}")]
+    InvalidText { index: usize, reason: String },
+
     #[error("chunk not found: `{0}`")]
     ChunkNotFound(String),
+
+    #[error("extractor not found: `{0}`")]
+    ExtractorNotFound(String),
+
+    #[error("unable to embed texts: `{0}`")]
+    EmbeddingError(String),
+
+    #[error("unable to embed query: `{0}`")]
+    QueryEmbeddingError(String),
+
+    #[error("{0}")]
+    ExtractorUnavailable(String),
+
+    #[error("unable to load extractor: `{0}`")]
+    ExtractorLoadError(String),
+
+    #[error("unable to count tokens: `{0}`")]
+    TokenCountError(String),
+
+    #[error("unable to get model info: `{0}`")]
+    ModelInfoError(String),
+
+    #[error("reranker not found: `{0}`")]
+    RerankerNotFound(String),
+
+    #[error("unable to rerank results: `{0}`")]
+    RerankError(String),
+
+    #[error("unable to load reranker `{name}`: `{reason}`")]
+    RerankerLoadError { name: String, reason: String },
+}
+
+impl IndexError {
+    /// Whether the same call could succeed if it's tried again later, because
+    /// it failed on the load or health of the server rather than on what was
+    /// asked of it.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            IndexError::Timeout(_)
+            | IndexError::Overloaded(_)
+            | IndexError::RateLimited { .. }
+            | IndexError::ExtractorUnavailable(_)
+            | IndexError::Join(_) => true,
+            IndexError::VectorDb(_)
+            | IndexError::Persistence(_)
+            | IndexError::UniqueParamsSerializationError(_)
+            | IndexError::InvalidInput(_)
+            | IndexError::InvalidText { .. }
+            | IndexError::ChunkNotFound(_)
+            | IndexError::ExtractorNotFound(_)
+            | IndexError::EmbeddingError(_)
+            | IndexError::QueryEmbeddingError(_)
+            | IndexError::ExtractorLoadError(_)
+            | IndexError::TokenCountError(_)
+            | IndexError::ModelInfoError(_)
+            | IndexError::RerankerNotFound(_)
+            | IndexError::RerankError(_)
+            | IndexError::RerankerLoadError { .. } => false,
+        }
+    }
 }
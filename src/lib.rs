@@ -1,16 +1,36 @@
 mod api;
+mod api_version;
 mod attribute_index;
+mod auth;
+#[cfg(feature = "candle")]
+mod candle_extractor;
+mod chunking;
 mod coordinator;
 mod data_repository_manager;
+mod embedding_cache;
+mod embedding_jobs;
 mod entity;
 mod executor;
 mod extractors;
+mod grpc;
+mod idempotency;
 mod index;
+mod namespace;
 mod persistence;
+mod rate_limit;
 mod server;
 mod server_config;
+mod telemetry;
 mod test_util;
+mod tls;
 mod vector_index;
 mod vectordbs;
 
-pub use {coordinator::*, executor::*, server::*, server_config::*};
+pub use {
+    auth::hash_api_key,
+    coordinator::*,
+    executor::*,
+    server::*,
+    server_config::*,
+    telemetry::{init_tracing, shutdown_tracing},
+};
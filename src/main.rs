@@ -33,32 +33,41 @@ enum Commands {
     InitConfig {
         config_path: String,
     },
+    #[command(about = "Print the hash of an API key for the server configuration")]
+    HashApiKey {
+        key: String,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let subscriber = tracing_subscriber::FmtSubscriber::new();
-    tracing::subscriber::set_global_default(subscriber)?;
-    //tracing_subscriber::fmt()
-    //.with_max_level(tracing::Level::DEBUG)
-    //.with_test_writer()
-    //.init();
-
     let args = Cli::parse();
+    // Commands which run a server are traced as their configuration says
+    let config = match &args.command {
+        Commands::StartServer { config_path, .. }
+        | Commands::Coordinator { config_path }
+        | Commands::Executor { config_path } => Some(ServerConfig::from_path(config_path)?),
+        Commands::InitConfig { .. } | Commands::HashApiKey { .. } => None,
+    };
+    indexify::init_tracing(
+        config
+            .as_ref()
+            .map(|config| config.log_format)
+            .unwrap_or_default(),
+        config.as_ref().and_then(|config| config.otlp.as_ref()),
+    )?;
+
     let version = format!(
         "git branch: {} - sha:{}",
         env!("VERGEN_GIT_BRANCH"),
         env!("VERGEN_GIT_SHA")
     );
     match args.command {
-        Commands::StartServer {
-            config_path,
-            dev_mode,
-        } => {
+        Commands::StartServer { dev_mode, .. } => {
             info!("starting indexify server....");
             info!("version: {}", version);
 
-            let config = indexify::ServerConfig::from_path(&config_path)?;
+            let config = config.unwrap();
             let server = indexify::Server::new(Arc::new(config.clone()))?;
             let server_handle = tokio::spawn(async move {
                 server.run().await.unwrap();
@@ -82,22 +91,24 @@ async fn main() -> Result<(), Error> {
             println!("Initializing config file at: {}", &config_path);
             indexify::ServerConfig::generate(config_path).unwrap();
         }
-        Commands::Coordinator { config_path } => {
+        Commands::HashApiKey { key } => {
+            println!("{}", indexify::hash_api_key(&key));
+        }
+        Commands::Coordinator { .. } => {
             info!("starting indexify coordinator....");
             info!("version: {}", version);
 
-            let config = ServerConfig::from_path(&config_path)?;
-            let coordinator = CoordinatorServer::new(Arc::new(config)).await?;
+            let coordinator = CoordinatorServer::new(Arc::new(config.unwrap())).await?;
             coordinator.run().await?
         }
-        Commands::Executor { config_path } => {
+        Commands::Executor { .. } => {
             info!("starting indexify executor....");
             info!("version: {}", version);
 
-            let config = ServerConfig::from_path(&config_path)?;
-            let executor_server = ExecutorServer::new(Arc::new(config)).await?;
+            let executor_server = ExecutorServer::new(Arc::new(config.unwrap())).await?;
             executor_server.run().await?
         }
     }
+    indexify::shutdown_tracing();
     Ok(())
 }
@@ -0,0 +1,111 @@
+use std::convert::Infallible;
+
+use async_trait::async_trait;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use thiserror::Error;
+
+/// Separates the namespace from the repository's name in the name a
+/// repository of a namespace is stored under.
+const SEPARATOR: char = '.';
+
+const MAX_NAMESPACE_LEN: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum NamespaceError {
+    #[error("invalid namespace `{0}`: namespaces are lowercase letters, digits, `-` and `_`")]
+    InvalidNamespace(String),
+
+    #[error("invalid repository name `{0}`: repository names can't contain `.`")]
+    InvalidRepositoryName(String),
+}
+
+/// Namespace a request is scoped to, which comes with the credentials it's
+/// made with. Every repository, and so every index, document and event,
+/// belongs to a namespace, and requests only reach the repositories of their
+/// own. Repositories of the default namespace are stored under their names,
+/// as they were before namespaces, and those of other namespaces under
+/// `<namespace>.<name>`, so that each namespace can use any name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Namespace(Option<String>);
+
+impl Namespace {
+    pub fn named(name: &str) -> Result<Self, NamespaceError> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_NAMESPACE_LEN
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !valid {
+            return Err(NamespaceError::InvalidNamespace(name.to_string()));
+        }
+        Ok(Self(Some(name.to_string())))
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Name a repository of the namespace is stored under. Names with the
+    /// separator are turned away in every namespace, since they'd reach a
+    /// repository of another namespace once they're stored.
+    pub fn repository(&self, name: &str) -> Result<String, NamespaceError> {
+        if name.contains(SEPARATOR) {
+            return Err(NamespaceError::InvalidRepositoryName(name.to_string()));
+        }
+        Ok(match &self.0 {
+            Some(namespace) => format!("{}{}{}", namespace, SEPARATOR, name),
+            None => name.to_string(),
+        })
+    }
+
+    /// Name within the namespace of a stored repository, if the repository
+    /// belongs to the namespace.
+    pub fn name_of(&self, stored: &str) -> Option<String> {
+        match &self.0 {
+            Some(namespace) => stored
+                .strip_prefix(namespace.as_str())
+                .and_then(|name| name.strip_prefix(SEPARATOR))
+                .map(|name| name.to_string()),
+            None => (!stored.contains(SEPARATOR)).then(|| stored.to_string()),
+        }
+    }
+}
+
+/// The namespace the authorization found in the request's credentials.
+/// Requests to a server without `auth` are all in the default namespace.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Namespace {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Namespace>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_names() {
+        let namespace = Namespace::named("team-a").unwrap();
+        assert_eq!(namespace.repository("docs").unwrap(), "team-a.docs");
+        assert_eq!(namespace.name_of("team-a.docs"), Some("docs".into()));
+        assert_eq!(namespace.name_of("team-ab.docs"), None);
+        assert_eq!(namespace.name_of("docs"), None);
+
+        let default = Namespace::default();
+        assert_eq!(default.repository("docs").unwrap(), "docs");
+        assert_eq!(default.name_of("docs"), Some("docs".into()));
+        assert_eq!(default.name_of("team-a.docs"), None);
+
+        assert!(Namespace::named("Team A").is_err());
+        assert!(Namespace::named("").is_err());
+        assert!(default.repository("team-a.docs").is_err());
+        assert!(namespace.repository("team-b.docs").is_err());
+    }
+}
@@ -143,6 +143,7 @@ pub struct ExtractorConfig {
     pub name: String,
     pub description: String,
     pub extractor_type: ExtractorType,
+    #[serde(default)]
     pub input_params: serde_json::Value,
 }
 
@@ -294,6 +295,14 @@ impl From<entity::content::Model> for Content<String> {
     }
 }
 
+/// Selects the documents of an index, by their ids or by their metadata.
+#[derive(Debug, Clone)]
+pub enum DocumentFilter {
+    Ids(Vec<String>),
+    /// Documents whose metadata has all of these keys with these values.
+    Metadata(HashMap<String, serde_json::Value>),
+}
+
 #[derive(Debug, Clone)]
 pub struct Chunk {
     pub text: String,
@@ -526,6 +535,37 @@ impl Repository {
             .ok_or(RepositoryError::IndexNotFound(index.into()))
     }
 
+    pub async fn list_indexes(&self, repository: &str) -> Result<Vec<IndexModel>, RepositoryError> {
+        Ok(IndexEntity::find()
+            .filter(index::Column::RepositoryId.eq(repository))
+            .all(&self.conn)
+            .await?)
+    }
+
+    /// Deletes an index along with the chunks of content added to it.
+    pub async fn delete_index(
+        &self,
+        index: &str,
+        repository: &str,
+    ) -> Result<IndexModel, RepositoryError> {
+        let index_model = self.get_index(index, repository).await?;
+        let index_name = index_model.name.clone();
+        self.conn
+            .transaction::<_, (), DbErr>(|txn| {
+                Box::pin(async move {
+                    entity::index_chunks::Entity::delete_many()
+                        .filter(entity::index_chunks::Column::IndexName.eq(index_name.as_str()))
+                        .exec(txn)
+                        .await?;
+                    IndexEntity::delete_by_id(index_name).exec(txn).await?;
+                    Ok(())
+                })
+            })
+            .await
+            .map_err(|e| RepositoryError::LogicError(e.to_string()))?;
+        Ok(index_model)
+    }
+
     pub async fn add_events(
         &self,
         repository: &str,
@@ -633,6 +673,38 @@ impl Repository {
         Ok(())
     }
 
+    /// Adds content without extraction events, for content which is embedded
+    /// into an index directly rather than by the repository's extractors.
+    pub async fn insert_content(
+        &self,
+        repository_name: &str,
+        texts: Vec<Text>,
+    ) -> Result<(), RepositoryError> {
+        let content_list: Vec<_> = texts
+            .into_iter()
+            .map(|text| entity::content::ActiveModel {
+                id: Set(text.id),
+                repository_id: Set(repository_name.into()),
+                text: Set(text.text),
+                metadata: Set(Some(json!(text.metadata))),
+                content_type: Set(ContentType::Text.to_string()),
+                extractor_bindings_state: Set(Some(json!(ExtractorBindingsState::default()))),
+            })
+            .collect();
+        let result = entity::content::Entity::insert_many(content_list)
+            .on_conflict(
+                OnConflict::column(entity::content::Column::Id)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec(&self.conn)
+            .await;
+        match result {
+            Err(err) if err != DbErr::RecordNotInserted => Err(RepositoryError::DatabaseError(err)),
+            _ => Ok(()),
+        }
+    }
+
     pub async fn content_from_repo(
         &self,
         content_id: &str,
@@ -773,6 +845,94 @@ impl Repository {
         Ok(())
     }
 
+    /// Chunks added to an index from the repository's documents which match
+    /// the filter.
+    pub async fn chunks_of_documents(
+        &self,
+        repository: &str,
+        index_name: &str,
+        filter: &DocumentFilter,
+    ) -> Result<Vec<Chunk>, RepositoryError> {
+        let content_ids: Vec<String> = match filter {
+            DocumentFilter::Ids(ids) => ids.clone(),
+            DocumentFilter::Metadata(metadata) => entity::content::Entity::find()
+                .from_raw_sql(Statement::from_sql_and_values(
+                    DbBackend::Postgres,
+                    "select * from content where repository_id=$1 and metadata @> $2",
+                    vec![repository.into(), json!(metadata).into()],
+                ))
+                .all(&self.conn)
+                .await?
+                .into_iter()
+                .map(|content| content.id)
+                .collect(),
+        };
+        let content_ids: Vec<String> = entity::content::Entity::find()
+            .filter(entity::content::Column::RepositoryId.eq(repository))
+            .filter(entity::content::Column::Id.is_in(content_ids))
+            .all(&self.conn)
+            .await?
+            .into_iter()
+            .map(|content| content.id)
+            .collect();
+        let chunks = entity::index_chunks::Entity::find()
+            .filter(entity::index_chunks::Column::IndexName.eq(index_name))
+            .filter(entity::index_chunks::Column::ContentId.is_in(content_ids))
+            .all(&self.conn)
+            .await?;
+        Ok(chunks
+            .into_iter()
+            .map(|chunk| Chunk {
+                text: chunk.text,
+                chunk_id: chunk.chunk_id,
+                content_id: chunk.content_id,
+            })
+            .collect())
+    }
+
+    /// Deletes chunks of an index, along with the documents they're from
+    /// which are left without chunks in any index. Returns the number of
+    /// documents deleted.
+    pub async fn delete_chunks(
+        &self,
+        repository: &str,
+        index_name: &str,
+        chunks: &[Chunk],
+    ) -> Result<u64, RepositoryError> {
+        let chunk_ids: Vec<String> = chunks.iter().map(|c| c.chunk_id.clone()).collect();
+        let mut content_ids: Vec<String> = chunks.iter().map(|c| c.content_id.clone()).collect();
+        content_ids.sort();
+        content_ids.dedup();
+        let repository = repository.to_string();
+        let index_name = index_name.to_string();
+        self.conn
+            .transaction::<_, u64, DbErr>(|txn| {
+                Box::pin(async move {
+                    entity::index_chunks::Entity::delete_many()
+                        .filter(entity::index_chunks::Column::IndexName.eq(index_name.as_str()))
+                        .filter(entity::index_chunks::Column::ChunkId.is_in(chunk_ids))
+                        .exec(txn)
+                        .await?;
+                    let indexed: Vec<String> = entity::index_chunks::Entity::find()
+                        .filter(entity::index_chunks::Column::ContentId.is_in(content_ids.clone()))
+                        .all(txn)
+                        .await?
+                        .into_iter()
+                        .map(|chunk| chunk.content_id)
+                        .collect();
+                    content_ids.retain(|id| !indexed.contains(id));
+                    let result = entity::content::Entity::delete_many()
+                        .filter(entity::content::Column::RepositoryId.eq(repository.as_str()))
+                        .filter(entity::content::Column::Id.is_in(content_ids))
+                        .exec(txn)
+                        .await?;
+                    Ok(result.rows_affected)
+                })
+            })
+            .await
+            .map_err(|e| RepositoryError::LogicError(e.to_string()))
+    }
+
     pub async fn chunk_with_id(&self, id: &str) -> Result<ChunkWithMetadata, RepositoryError> {
         let chunk = entity::index_chunks::Entity::find()
             .filter(entity::index_chunks::Column::ChunkId.eq(id))
@@ -797,6 +957,25 @@ impl Repository {
         })
     }
 
+    /// Metadata of the content of a repository with the ids, by their ids.
+    pub async fn content_metadata(
+        &self,
+        repository: &str,
+        content_ids: Vec<String>,
+    ) -> Result<HashMap<String, HashMap<String, serde_json::Value>>, RepositoryError> {
+        Ok(entity::content::Entity::find()
+            .filter(entity::content::Column::RepositoryId.eq(repository))
+            .filter(entity::content::Column::Id.is_in(content_ids))
+            .all(&self.conn)
+            .await?
+            .into_iter()
+            .map(|model| {
+                let content = Content::<String>::from(model);
+                (content.id, content.metadata)
+            })
+            .collect())
+    }
+
     pub async fn upsert_repository(
         &self,
         repository: DataRepository,
@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+use crate::server_config::RateLimitConfig;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets which limit how many texts and queries each caller can have
+/// embedded. Buckets start full, and refill at `rate` tokens a second up to
+/// `burst` tokens.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: DashMap<String, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Takes `cost` tokens from the caller's bucket, or returns how long the
+    /// caller has to wait until there are enough. Costs larger than the burst
+    /// take the whole bucket, so they aren't turned away forever.
+    pub fn acquire(&self, caller: &str, cost: usize) -> Result<(), Duration> {
+        let burst = self.config.burst as f64;
+        let cost = (cost as f64).min(burst);
+        let now = Instant::now();
+        let mut bucket = self.buckets.entry(caller.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() * self.config.rate;
+        bucket.tokens = (bucket.tokens + refilled).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64(
+            (cost - bucket.tokens) / self.config.rate,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acquire() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            rate: 1.0,
+            burst: 2,
+        });
+        assert!(limiter.acquire("tenant-a", 1).is_ok());
+        assert!(limiter.acquire("tenant-a", 1).is_ok());
+        let retry_after = limiter.acquire("tenant-a", 1).unwrap_err();
+        assert!(retry_after > Duration::from_millis(900));
+        assert!(retry_after <= Duration::from_secs(1));
+
+        // Callers have buckets of their own
+        assert!(limiter.acquire("tenant-b", 1).is_ok());
+        // Costs over the burst wait for a full bucket
+        assert!(limiter.acquire("tenant-c", 10).is_ok());
+        assert!(limiter.acquire("tenant-c", 10).is_err());
+    }
+}
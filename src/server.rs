@@ -1,49 +1,207 @@
+use crate::api_version::ApiVersion;
 use crate::attribute_index::AttributeIndexManager;
-use crate::data_repository_manager::DataRepositoryManager;
-use crate::persistence::Repository;
-use crate::vector_index::VectorIndexManager;
-use crate::ServerConfig;
-use crate::{api::*, persistence, vectordbs, CreateWork, CreateWorkResponse};
-
-use anyhow::Result;
-use axum::extract::{Path, Query};
-use axum::http::StatusCode;
-use axum::{extract::State, routing::get, routing::post, Json, Router};
+use crate::auth::{bearer_key, AuthError, Authenticator, Repositories};
+use crate::chunking::Chunking;
+use crate::data_repository_manager::{DataRepositoryError, DataRepositoryManager};
+use crate::embedding_jobs::EmbeddingJobs;
+use crate::extractors::{extractor_stats, ExtractorHealth, ExtractorStatus};
+use crate::grpc::EmbeddingServer;
+use crate::idempotency::{fingerprint, Attempt, CachedResponse, IdempotencyCache, RequestKey};
+use crate::index::IndexError;
+use crate::namespace::Namespace;
+use crate::persistence::{DocumentFilter, Repository, RepositoryError};
+use crate::rate_limit::RateLimiter;
+use crate::vector_index::{ScoredText, SearchOptions, VectorIndexManager};
+use crate::{api::*, persistence, telemetry, tls, vectordbs, CreateWork, CreateWorkResponse};
+use crate::{CorsConfig, Scope, ServerConfig};
+
+use anyhow::{anyhow, Result};
+use axum::body::{boxed, Body, Bytes, Full, HttpBody, StreamBody};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{DefaultBodyLimit, MatchedPath, Path, Query};
+use axum::http::{
+    header, Extensions, HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode, Version,
+};
+use axum::middleware::{self, Next};
+use axum::response::sse::{self, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::{extract::State, routing::delete, routing::get, routing::post, Json, Router};
+use futures::{channel::mpsc, stream, SinkExt, Stream, StreamExt};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use nanoid::nanoid;
 use pyo3::Python;
+use serde::Serialize;
 use tokio::signal;
-use tracing::{error, info};
+use tokio::sync::watch;
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+use tracing::{error, field, info, info_span, warn, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use utoipa::OpenApi;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_rapidoc::RapiDoc;
 use utoipa_redoc::{Redoc, Servable};
 
 use utoipa_swagger_ui::SwaggerUi;
 
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 const DEFAULT_SEARCH_LIMIT: u64 = 5;
 
 #[derive(Clone)]
 pub struct RepositoryEndpointState {
     repository_manager: Arc<DataRepositoryManager>,
+    embedding_jobs: Arc<EmbeddingJobs>,
     coordinator_addr: SocketAddr,
+    shutdown: Shutdown,
+}
+
+/// Extractors whose models are loaded as the server starts, which load after
+/// the server starts listening so that probes can tell a server which is
+/// still downloading weights from one which is stuck.
+#[derive(Default)]
+struct Startup {
+    loading: Mutex<Vec<String>>,
+    failed: Mutex<HashMap<String, String>>,
+}
+
+impl Startup {
+    fn loaded(&self, name: &str, result: Result<(), IndexError>) {
+        if let Err(e) = result {
+            error!("unable to load extractor: {}: {}", name, e);
+            self.failed
+                .lock()
+                .unwrap()
+                .insert(name.to_string(), e.to_string());
+        }
+        self.loading
+            .lock()
+            .unwrap()
+            .retain(|loading| loading != name);
+    }
+
+    fn status(&self) -> StartupResponse {
+        let loading = self.loading.lock().unwrap().clone();
+        let failed = self.failed.lock().unwrap().clone();
+        StartupResponse {
+            started: loading.is_empty() && failed.is_empty(),
+            loading,
+            failed,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ProbeState {
+    vector_index_manager: Arc<VectorIndexManager>,
+    startup: Arc<Startup>,
+    shutdown: Shutdown,
+}
+
+#[derive(Clone)]
+struct EmbeddingSessionState {
+    repository_manager: Arc<DataRepositoryManager>,
+    max_message_bytes: usize,
+}
+
+#[derive(Clone)]
+struct IdempotencyState {
+    cache: Arc<IdempotencyCache>,
+    max_body_bytes: usize,
+}
+
+#[derive(Clone)]
+struct AdminState {
+    vector_index_manager: Arc<VectorIndexManager>,
+    config: Arc<ServerConfig>,
+}
+
+#[derive(Clone)]
+struct MetricsState {
+    prometheus: PrometheusHandle,
+    vector_index_manager: Arc<VectorIndexManager>,
+}
+
+/// Buckets of the histograms of durations, in seconds, from the few
+/// milliseconds of a cached query to the minute of an extractor timeout.
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Installs the Prometheus recorder of the process the first time it's
+/// called, so the metrics recorded anywhere in the process are rendered at
+/// `/metrics`.
+fn prometheus_handle() -> Result<PrometheusHandle> {
+    static PROMETHEUS: OnceLock<PrometheusHandle> = OnceLock::new();
+    if let Some(prometheus) = PROMETHEUS.get() {
+        return Ok(prometheus.clone());
+    }
+    let prometheus = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".into()), DURATION_BUCKETS)?
+        .install_recorder()?;
+    // Histograms are summarized as they're recorded, and the summaries are
+    // only trimmed to their window by the upkeep
+    let upkeep = prometheus.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+    Ok(PROMETHEUS.get_or_init(|| prometheus).clone())
 }
 
 #[derive(OpenApi)]
 #[openapi(
+        info(description = "Indexes, searches and embeds the content of repositories with extractors. Describes version 1 of the API, which is served under `/api/v1` as well as at the paths without a prefix"),
         paths(
             create_repository,
             list_repositories,
             get_repository,
+            create_index,
+            list_indexes,
+            get_index,
+            delete_index,
+            add_documents,
+            delete_documents,
+            delete_document,
+            search_index,
             add_texts,
             index_search,
             list_extractors,
+            get_extractor,
+            count_tokens,
+            embeddings,
+            batch_embeddings,
+            openai_embeddings,
             bind_extractor,
             list_events,
             add_events,
             attribute_lookup,
-            list_executors
+            list_executors,
+            run_extractors,
+            create_embedding_job,
+            get_embedding_job,
+            embedding_job_events,
+            get_embedding_job_results,
+            metrics,
+            healthz,
+            startupz,
+            readyz,
+            delete_embedding_job,
+            embedding_session,
+            list_models,
+            load_model,
+            get_model,
+            unload_model
         ),
         components(
             schemas(CreateRepository, CreateRepositoryResponse, DataConnector,
@@ -51,14 +209,41 @@ pub struct RepositoryEndpointState {
                 SourceType, TextAddRequest, TextAdditionResponse, Text, IndexSearchResponse,
                 DocumentFragment, SearchRequest, ListRepositoriesResponse, ListExtractorsResponse
             , ExtractorConfig, DataRepository, ExtractorBinding, ExtractorFilter, ExtractorBindRequest, ExtractorBindResponse, Executor,
-        ListEventsResponse, EventAddRequest, EventAddResponse, Event, AttributeLookupResponse, ExtractedAttributes, ListExecutorsResponse)
+        ListEventsResponse, EventAddRequest, EventAddResponse, Event, AttributeLookupResponse, ExtractedAttributes, ListExecutorsResponse,
+        CreateIndexRequest, Index, ListIndexesResponse, AddDocumentsRequest, AddDocumentsResponse, Chunking,
+        DeleteDocumentsRequest, DeleteDocumentsResponse,
+        SearchIndexRequest, SearchIndexResponse, SearchHit, StreamError, InvalidRequest,
+        CountTokensRequest, CountTokensResponse, EncodingFormat, EmbeddingVector, EmbeddingsRequest, EmbeddingsResponse, BatchEmbeddingResult, BatchEmbeddingsResponse, GetExtractorResponse, ModelInfo,
+        OpenAIEmbeddingsRequest, OpenAIEmbeddingInput, OpenAIEncodingFormat, OpenAIEmbeddingsResponse, OpenAIEmbedding, OpenAIEmbeddingVector, OpenAIUsage,
+        CreateEmbeddingJobRequest, CreateEmbeddingJobResponse, EmbeddingJob, EmbeddingJobResult, EmbeddingJobResultsResponse,
+        StartupResponse, ReadinessResponse, ExtractorReadiness, ExtractorStatus,
+        EmbeddingSessionRequest, EmbeddingSessionResponse, EmbeddingSessionError,
+        LoadModelRequest, LoadedModel, ListModelsResponse, ModelStats)
         ),
         tags(
             (name = "indexify", description = "Indexify API")
-        )
+        ),
+        modifiers(&SecurityAddon),
+        security(("api_key" = []))
     )]
 struct ApiDoc;
 
+/// Declares the API keys and tokens of servers with `auth`, which are sent as
+/// bearer tokens.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "api_key",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+    }
+}
+
 pub struct Server {
     addr: SocketAddr,
     config: Arc<ServerConfig>,
@@ -70,19 +255,51 @@ impl Server {
     }
 
     pub async fn run(&self) -> Result<()> {
+        let shutdown = Shutdown::listen();
+        let drain_timeout = Duration::from_secs(self.config.drain_timeout_secs);
         let repository = Arc::new(Repository::new(&self.config.db_url).await?);
         let vectordb = vectordbs::create_vectordb(self.config.index_config.clone())?;
+        // Lazy extractors are created straight away, since they don't load
+        // their models until they're used, and the rest once the server is
+        // listening
+        let (eager_extractors, lazy_extractors): (Vec<_>, Vec<_>) = self
+            .config
+            .extractors
+            .iter()
+            .cloned()
+            .partition(|extractor| !extractor.lazy);
         let vector_index_manager = Arc::new(VectorIndexManager::new(
-            self.config.clone(),
+            Arc::new(ServerConfig {
+                extractors: lazy_extractors,
+                ..(*self.config).clone()
+            }),
             repository.clone(),
             vectordb.clone(),
-        ));
+        )?);
+        let startup = Arc::new(Startup {
+            loading: Mutex::new(eager_extractors.iter().map(|e| e.name.clone()).collect()),
+            ..Default::default()
+        });
+        let probe_state = ProbeState {
+            vector_index_manager: vector_index_manager.clone(),
+            startup: startup.clone(),
+            shutdown: shutdown.clone(),
+        };
         let attribute_index_manager = Arc::new(AttributeIndexManager::new(repository.clone()));
+        let embedding_jobs = Arc::new(EmbeddingJobs::new(vector_index_manager.clone()));
+        let admin_state = AdminState {
+            vector_index_manager: vector_index_manager.clone(),
+            config: self.config.clone(),
+        };
+        let metrics_state = MetricsState {
+            prometheus: prometheus_handle()?,
+            vector_index_manager: vector_index_manager.clone(),
+        };
 
         let repository_manager = Arc::new(
             DataRepositoryManager::new(
                 repository.clone(),
-                vector_index_manager,
+                vector_index_manager.clone(),
                 attribute_index_manager,
             )
             .await?,
@@ -96,20 +313,36 @@ impl Server {
         let coordinator_addr: SocketAddr = self.config.coordinator_addr.parse()?;
         let repository_endpoint_state = RepositoryEndpointState {
             repository_manager: repository_manager.clone(),
+            embedding_jobs,
             coordinator_addr,
+            shutdown: shutdown.clone(),
         };
-        let app = Router::new()
-            .merge(SwaggerUi::new("/api-docs-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
-            .merge(Redoc::with_url("/redoc", ApiDoc::openapi()))
-            .merge(RapiDoc::new("/api-docs/openapi.json").path("/rapidoc"))
-            .route("/", get(root))
+        let embedding_session_state = EmbeddingSessionState {
+            repository_manager: repository_manager.clone(),
+            max_message_bytes: self.config.request_limits.max_body_bytes,
+        };
+        let idempotency_state = IdempotencyState {
+            cache: Arc::new(IdempotencyCache::new(&self.config.idempotency)),
+            max_body_bytes: self.config.request_limits.max_body_bytes,
+        };
+        let idempotent = || middleware::from_fn_with_state(idempotency_state.clone(), idempotent);
+        let authenticator = self
+            .config
+            .auth
+            .as_ref()
+            .map(Authenticator::new)
+            .transpose()?
+            .map(Arc::new);
+        let mut routes = Router::new()
             .route(
                 "/repositories/:repository_name/extractor_bindings",
                 post(bind_extractor).with_state(repository_endpoint_state.clone()),
             )
             .route(
                 "/repositories/:repository_name/add_texts",
-                post(add_texts).with_state(repository_endpoint_state.clone()),
+                post(add_texts)
+                    .with_state(repository_endpoint_state.clone())
+                    .layer(idempotent()),
             )
             .route(
                 "/repositories/:repository_name/run_extractors",
@@ -125,7 +358,9 @@ impl Server {
             )
             .route(
                 "/repositories/:repository_name/events",
-                post(add_events).with_state(repository_endpoint_state.clone()),
+                post(add_events)
+                    .with_state(repository_endpoint_state.clone())
+                    .layer(idempotent()),
             )
             .route(
                 "/repositories/:repository_name/events",
@@ -143,6 +378,40 @@ impl Server {
                 "/repositories/:repository_name",
                 get(get_repository).with_state(repository_endpoint_state.clone()),
             )
+            .route(
+                "/repositories/:repository_name/indexes",
+                post(create_index).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/repositories/:repository_name/indexes",
+                get(list_indexes).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/repositories/:repository_name/indexes/:index_name",
+                get(get_index).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/repositories/:repository_name/indexes/:index_name",
+                delete(delete_index).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/repositories/:repository_name/indexes/:index_name/documents",
+                post(add_documents)
+                    .with_state(repository_endpoint_state.clone())
+                    .layer(idempotent()),
+            )
+            .route(
+                "/repositories/:repository_name/indexes/:index_name/documents",
+                delete(delete_documents).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/repositories/:repository_name/indexes/:index_name/documents/:document_id",
+                delete(delete_document).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/repositories/:repository_name/indexes/:index_name/search",
+                post(search_index).with_state(repository_endpoint_state.clone()),
+            )
             .route(
                 "/executors",
                 get(list_executors).with_state(repository_endpoint_state.clone()),
@@ -150,20 +419,655 @@ impl Server {
             .route(
                 "/extractors",
                 get(list_extractors).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/extractors/:extractor_name",
+                get(get_extractor).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/extractors/:extractor_name/count_tokens",
+                post(count_tokens).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/embeddings",
+                post(embeddings).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/embeddings/batch",
+                post(batch_embeddings).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/embeddings/session",
+                get(embedding_session).with_state(embedding_session_state),
+            )
+            .route(
+                "/embedding_jobs",
+                post(create_embedding_job)
+                    .with_state(repository_endpoint_state.clone())
+                    .layer(idempotent()),
+            )
+            .route(
+                "/embedding_jobs/:job_id",
+                get(get_embedding_job).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/embedding_jobs/:job_id",
+                delete(delete_embedding_job).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/embedding_jobs/:job_id/events",
+                get(embedding_job_events).with_state(repository_endpoint_state.clone()),
+            )
+            .route(
+                "/embedding_jobs/:job_id/results",
+                get(get_embedding_job_results).with_state(repository_endpoint_state.clone()),
+            );
+        // Without API keys there's no one to limit the admin endpoints to, so
+        // they're only served with `auth`
+        if authenticator.is_some() {
+            routes = routes
+                .route(
+                    "/admin/models",
+                    get(list_models).with_state(admin_state.clone()),
+                )
+                .route(
+                    "/admin/models",
+                    post(load_model).with_state(admin_state.clone()),
+                )
+                .route(
+                    "/admin/models/:model_name",
+                    get(get_model).with_state(admin_state.clone()),
+                )
+                .route(
+                    "/admin/models/:model_name",
+                    delete(unload_model).with_state(admin_state),
+                );
+        }
+        // Each version of the API is served under its prefix, and the paths
+        // from before versions serve the first version as they always have
+        let mut api = ApiVersion::ALL
+            .iter()
+            .fold(Router::new(), |api, version| {
+                api.nest(version.prefix(), versioned(routes.clone(), *version))
+            })
+            .merge(versioned(routes, ApiVersion::UNVERSIONED))
+            // Follows the versions of OpenAI's API rather than indexify's
+            .route(
+                "/v1/embeddings",
+                post(openai_embeddings).with_state(repository_endpoint_state.clone()),
+            )
+            .route("/metrics", get(metrics).with_state(metrics_state));
+        if let Some(authenticator) = &authenticator {
+            let auth_state = AuthState {
+                authenticator: authenticator.clone(),
+                request_limiter: self
+                    .config
+                    .auth
+                    .as_ref()
+                    .and_then(|auth| auth.rate_limit.requests())
+                    .map(|config| Arc::new(RateLimiter::new(config))),
+            };
+            api = api.route_layer(middleware::from_fn_with_state(auth_state, authorize));
+        }
+        // Recorded outside of the authorization, so that turned away requests
+        // are counted too
+        let api = api.route_layer(middleware::from_fn(record_request));
+        // Bodies past the limit are turned away with a 413 before they're read
+        // in full
+        let api = api.layer(DefaultBodyLimit::max(
+            self.config.request_limits.max_body_bytes,
+        ));
+        // Probes stay open without an API key, like the API docs
+        let mut app = Router::new()
+            .merge(SwaggerUi::new("/api-docs-ui").url("/openapi.json", ApiDoc::openapi()))
+            .merge(Redoc::with_url("/redoc", ApiDoc::openapi()))
+            .merge(RapiDoc::new("/openapi.json").path("/rapidoc"))
+            // Where the spec was served before `/openapi.json`
+            .route("/api-docs/openapi.json", get(openapi))
+            .route("/", get(root))
+            .route("/healthz", get(healthz))
+            .route("/startupz", get(startupz).with_state(probe_state.clone()))
+            .route("/readyz", get(readyz).with_state(probe_state))
+            .merge(api);
+        if self.config.compression {
+            app = app.layer(compression_layer());
+        }
+        // Preflight requests are answered by the layer, before they reach the
+        // authorization of the API's routes
+        if let Some(cors) = &self.config.cors {
+            app = app.layer(cors_layer(cors)?);
+        }
+        let mut grpc_task = None;
+        if let Some(grpc_listen_addr) = &self.config.grpc_listen_addr {
+            let grpc_addr: SocketAddr = grpc_listen_addr.parse()?;
+            let grpc_tls = self
+                .config
+                .tls
+                .as_ref()
+                .map(tls::grpc_tls_config)
+                .transpose()?;
+            let grpc_server =
+                EmbeddingServer::new(repository_manager.clone(), authenticator.clone());
+            let shutdown = shutdown.clone();
+            grpc_task = Some(tokio::spawn(async move {
+                let grpc_server =
+                    grpc_server.run(grpc_addr, grpc_tls, shutdown.clone().requested());
+                if let Err(err) = until_drained(grpc_server, shutdown, drain_timeout).await {
+                    error!("grpc server failed: {}", err);
+                }
+            }));
+        }
+        // Models are loaded one at a time, as loading them in parallel would
+        // only compete for the same bandwidth and memory
+        let loading_index_manager = vector_index_manager.clone();
+        tokio::task::spawn_blocking(move || {
+            for extractor in eager_extractors {
+                let name = extractor.name.clone();
+                info!("loading extractor: {}", &name);
+                startup.loaded(
+                    &name,
+                    loading_index_manager.load_extractor(extractor).map(|_| ()),
+                );
+            }
+        });
+        let served = if let Some(tls) = &self.config.tls {
+            let rustls_config = tls::rustls_config(tls).await?;
+            let handle = axum_server::Handle::new();
+            let shutdown_handle = handle.clone();
+            let requested = shutdown.clone().requested();
+            tokio::spawn(async move {
+                requested.await;
+                shutdown_handle.graceful_shutdown(None);
+            });
+            info!(
+                "server is listening with tls at addr {:?}",
+                &self.addr.to_string()
             );
-        info!("server is listening at addr {:?}", &self.addr.to_string());
-        axum::Server::bind(&self.addr)
-            .serve(app.into_make_service())
-            .with_graceful_shutdown(shutdown_signal())
-            .await?;
-        Ok(())
+            let server = axum_server::bind_rustls(self.addr, rustls_config)
+                .handle(handle)
+                .serve(app.into_make_service());
+            until_drained(server, shutdown, drain_timeout)
+                .await
+                .map_err(anyhow::Error::from)
+        } else {
+            info!("server is listening at addr {:?}", &self.addr.to_string());
+            let server = axum::Server::bind(&self.addr)
+                .serve(app.into_make_service())
+                .with_graceful_shutdown(shutdown.clone().requested());
+            until_drained(server, shutdown, drain_timeout)
+                .await
+                .map_err(anyhow::Error::from)
+        };
+        if let Some(grpc_task) = grpc_task {
+            let _ = grpc_task.await;
+        }
+        // The models go last, once no request can reach them or write to the
+        // vector store
+        info!("flushing vector store writes");
+        if let Err(err) = vector_index_manager.flush().await {
+            error!("unable to flush vector store writes: {}", err);
+        }
+        vector_index_manager.unload_extractors();
+        info!("server shut down");
+        served
+    }
+}
+
+/// Compresses responses with the encoding the client prefers of gzip and
+/// zstd. Streamed NDJSON responses are sent as they are, since the encoder
+/// would hold back the lines which are ready until it had enough to compress,
+/// and so are WebSocket upgrades, whose connection is handed off.
+fn compression_layer() -> CompressionLayer<impl Predicate> {
+    let predicate = DefaultPredicate::new()
+        .and(NotForContentType::const_new(NDJSON))
+        .and(
+            |status: StatusCode, _: Version, _: &HeaderMap, _: &Extensions| {
+                status != StatusCode::SWITCHING_PROTOCOLS
+            },
+        );
+    CompressionLayer::new().compress_when(predicate)
+}
+
+fn cors_layer(config: &CorsConfig) -> Result<CorsLayer> {
+    let any = |values: &[String]| values.iter().any(|value| value == "*");
+    let origins = if any(&config.allowed_origins) {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            config
+                .allowed_origins
+                .iter()
+                .map(|origin| HeaderValue::from_str(origin))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("invalid cors origin: {}", e))?,
+        )
+    };
+    let methods = if any(&config.allowed_methods) {
+        AllowMethods::any()
+    } else {
+        AllowMethods::list(
+            config
+                .allowed_methods
+                .iter()
+                .map(|method| Method::from_str(&method.to_uppercase()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("invalid cors method: {}", e))?,
+        )
+    };
+    let headers = if any(&config.allowed_headers) {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(
+            config
+                .allowed_headers
+                .iter()
+                .map(|header| HeaderName::from_str(header))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("invalid cors header: {}", e))?,
+        )
+    };
+    let mut layer = CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        // Lets rate limited browser apps see when to try again, and report
+        // the ids of their requests, which responses were replayed and which
+        // version of the API responded
+        .expose_headers([
+            header::RETRY_AFTER,
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            HeaderName::from_static(IDEMPOTENT_REPLAYED_HEADER),
+            HeaderName::from_static(API_VERSION_HEADER),
+        ]);
+    if let Some(max_age_secs) = config.max_age_secs {
+        layer = layer.max_age(Duration::from_secs(max_age_secs));
+    }
+    Ok(layer)
+}
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "The server's process is alive", body = String, content_type = "text/plain")
+    ),
+    security(()),
+)]
+#[axum_macros::debug_handler]
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+#[utoipa::path(
+    get,
+    path = "/startupz",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "The extractors loaded as the server starts are loaded", body = StartupResponse),
+        (status = 503, description = "Extractors are still loading, or failed to load", body = StartupResponse)
+    ),
+    security(()),
+)]
+#[axum_macros::debug_handler]
+async fn startupz(State(state): State<ProbeState>) -> (StatusCode, Json<StartupResponse>) {
+    let startup = state.startup.status();
+    let status = if startup.started {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(startup))
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "The server is ready to take traffic", body = ReadinessResponse),
+        (status = 503, description = "Models are loading, the vector store can't be reached, or the server is shutting down", body = ReadinessResponse)
+    ),
+    security(()),
+)]
+#[axum_macros::debug_handler]
+async fn readyz(State(state): State<ProbeState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let started = state.startup.status().started;
+    let extractors: HashMap<_, _> = state
+        .vector_index_manager
+        .health()
+        .extractors
+        .into_iter()
+        .map(|(name, health)| {
+            (
+                name,
+                ExtractorReadiness {
+                    status: health.status,
+                    last_error: health.last_error,
+                },
+            )
+        })
+        .collect();
+    let vector_store_error = state
+        .vector_index_manager
+        .vector_db_health()
+        .await
+        .err()
+        .map(|e| e.to_string());
+    // Extractors which failed stay in service, as their circuit breakers
+    // turn calls away until they recover and lazy ones are loaded again on
+    // their next call, taking the instance out of service would only move
+    // their traffic to the other instances
+    let loading = extractors
+        .values()
+        .any(|extractor| extractor.status == ExtractorStatus::Loading);
+    let draining = state.shutdown.is_requested();
+    let ready = started && !draining && !loading && vector_store_error.is_none();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            ready,
+            started,
+            draining,
+            extractors,
+            vector_store_error,
+        }),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Metrics of the server in the Prometheus text format", body = String, content_type = "text/plain")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn metrics(State(state): State<MetricsState>) -> String {
+    // Load states change without going through a place which records them,
+    // so they're read as the metrics are scraped
+    let health = state.vector_index_manager.health();
+    for (extractor, health) in health.extractors {
+        let loaded = health.status == ExtractorStatus::Loaded;
+        gauge!("indexify_extractor_loaded", "extractor" => extractor).set(loaded as u8 as f64);
     }
+    state.prometheus.render()
 }
 
 async fn root() -> &'static str {
     "Indexify Server"
 }
 
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Scope an API key needs for a request to an endpoint, by the route it
+/// matched.
+fn required_scope(method: &Method, path: &str) -> Scope {
+    match (method, path) {
+        // Loading models takes memory from every tenant of the server, so
+        // even reading how they're doing is left to operators
+        (_, path) if path.starts_with("/admin/") => Scope::Admin,
+        (&Method::GET, _) => Scope::Read,
+        // Searching and embedding don't change anything on the server
+        (
+            &Method::POST,
+            "/repositories/:repository_name/search"
+            | "/repositories/:repository_name/indexes/:index_name/search"
+            | "/extractors/:extractor_name/count_tokens"
+            | "/embeddings"
+            | "/embeddings/batch"
+            | "/v1/embeddings",
+        ) => Scope::Read,
+        (
+            &Method::POST,
+            "/repositories"
+            | "/repositories/:repository_name/indexes"
+            | "/repositories/:repository_name/extractor_bindings",
+        )
+        | (&Method::DELETE, "/repositories/:repository_name/indexes/:index_name") => Scope::Admin,
+        _ => Scope::Write,
+    }
+}
+
+/// Repositories a request to an endpoint reaches, by the route it matched.
+fn repositories<'a>(path: &str, uri_path: &'a str) -> Repositories<'a> {
+    if path == "/repositories" {
+        Repositories::All
+    } else if path.starts_with("/repositories/:repository_name") {
+        Repositories::One(uri_path.split('/').nth(2).unwrap_or_default())
+    } else {
+        Repositories::None
+    }
+}
+
+/// Header with the version of the API a response is from.
+const API_VERSION_HEADER: &str = "indexify-api-version";
+
+/// Serves the routes of the API as a version of it.
+fn versioned(routes: Router, version: ApiVersion) -> Router {
+    routes.route_layer(middleware::from_fn_with_state(version, serve_version))
+}
+
+async fn serve_version<B>(
+    State(version): State<ApiVersion>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    request.extensions_mut().insert(version);
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(API_VERSION_HEADER, HeaderValue::from(version.number()));
+    response
+}
+
+/// Header with the id of a request, which is sent back in the response and
+/// logged with every event of the request. Requests without one get a new id.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Counts the requests to each route by their status, and records how long
+/// they take until their response starts. Each request is handled in a span
+/// of its own, with the request's id and tenant, which is a child of the span
+/// of the caller the request was sent from, if any.
+async fn record_request<B>(request: Request<B>, next: Next<B>) -> Response {
+    let method = request.method().to_string();
+    let path = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => request.uri().path().to_string(),
+    };
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .filter(|id| !id.is_empty() && id.len() <= 128)
+        .cloned()
+        .unwrap_or_else(|| HeaderValue::from_str(&nanoid!()).unwrap());
+    let span = info_span!(
+        "request",
+        otel.name = format!("{} {}", method, path),
+        otel.kind = "server",
+        request_id = request_id.to_str().unwrap_or_default(),
+        tenant = caller(request.headers()),
+        http.request.method = %method,
+        http.route = %path,
+        http.response.status_code = field::Empty,
+    );
+    span.set_parent(telemetry::remote_context(request.headers()));
+    let started = Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    let status = response.status().as_u16();
+    let latency = started.elapsed();
+    span.record("http.response.status_code", status);
+    span.in_scope(|| {
+        info!(
+            status,
+            latency_ms = latency.as_secs_f64() * 1000.0,
+            "finished request"
+        )
+    });
+    response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+    let labels = [
+        ("method", method),
+        ("path", path),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    counter!("indexify_http_requests_total", &labels).increment(1);
+    histogram!("indexify_http_request_seconds", &labels[..2]).record(latency.as_secs_f64());
+    response
+}
+
+/// Header with the key of an ingestion request, which its retries are sent
+/// with too.
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header of responses which were sent for an earlier attempt of the request.
+const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Reads a body in full, unless it's longer than `limit` bytes.
+async fn read_body<B>(mut body: B, limit: usize) -> Result<Option<Bytes>, B::Error>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if bytes.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(Some(bytes.into()))
+}
+
+/// Runs each ingestion request with an idempotency key once, so that a
+/// client which retries a request after losing its connection doesn't add and
+/// embed its documents twice. Retries with the same body get the response of
+/// the first attempt, and retries while the first attempt is still running
+/// are turned away. Server errors and rate limits aren't kept, since the
+/// request is worth retrying after them.
+async fn idempotent(
+    State(state): State<IdempotencyState>,
+    namespace: Namespace,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, IndexifyAPIError> {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+    let key = RequestKey::new(
+        caller(request.headers()),
+        namespace,
+        request.method().as_str(),
+        request.uri().path(),
+        key.to_str().unwrap_or_default(),
+    )?;
+    let (parts, body) = request.into_parts();
+    let body = read_body(body, state.max_body_bytes)
+        .await
+        .map_err(|e| IndexifyAPIError::new(StatusCode::BAD_REQUEST, e.to_string()))?
+        .ok_or_else(|| {
+            IndexifyAPIError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "request body is too large".into(),
+            )
+        })?;
+    let pending = match state.cache.begin(key, fingerprint(&body))? {
+        Attempt::Run(pending) => pending,
+        Attempt::Replay(cached) => {
+            let mut response = (cached.status, cached.body).into_response();
+            match cached.content_type {
+                Some(content_type) => response
+                    .headers_mut()
+                    .insert(header::CONTENT_TYPE, content_type),
+                None => response.headers_mut().remove(header::CONTENT_TYPE),
+            };
+            response
+                .headers_mut()
+                .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+            return Ok(response);
+        }
+    };
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let status = response.status();
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+    let (parts, body) = response.into_parts();
+    let body = read_body(body, usize::MAX)
+        .await
+        .map_err(|e| IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .unwrap_or_default();
+    pending.complete(CachedResponse {
+        status,
+        content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+        body: body.clone(),
+    });
+    Ok(Response::from_parts(parts, boxed(Full::from(body))))
+}
+
+#[derive(Clone)]
+struct AuthState {
+    authenticator: Arc<Authenticator>,
+    /// Limits the requests of each key.
+    request_limiter: Option<Arc<RateLimiter>>,
+}
+
+/// Turns away requests without an API key or token which has the scope of
+/// the endpoint, and whose tenants don't include the repository of the
+/// endpoint. Requests which get through are made for the caller named by the
+/// key, whatever caller the client sent, so callers' rate limits are per key,
+/// and are scoped to the key's namespace.
+async fn authorize<B>(
+    State(state): State<AuthState>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, IndexifyAPIError> {
+    let key = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(bearer_key);
+    let principal = state.authenticator.authenticate(key).await?;
+    let path = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str(),
+        None => request.uri().path(),
+    };
+    // Every version of an endpoint needs the same scope
+    let (_, path) = ApiVersion::of_path(path);
+    let (_, uri_path) = ApiVersion::of_path(request.uri().path());
+    principal.allows(
+        required_scope(request.method(), path),
+        repositories(path, uri_path),
+    )?;
+    // Models are shared by every namespace, so only keys of the default
+    // namespace manage them
+    if path.starts_with("/admin/") && !principal.namespace.is_default() {
+        return Err(AuthError::NamespaceOnly(principal.name).into());
+    }
+    if let Some(request_limiter) = &state.request_limiter {
+        request_limiter
+            .acquire(&principal.name, 1)
+            .map_err(|retry_after| IndexError::RateLimited {
+                caller: principal.name.clone(),
+                retry_after,
+            })?;
+    }
+    Span::current().record("tenant", principal.name.as_str());
+    request.extensions_mut().insert(principal.namespace.clone());
+    match HeaderValue::from_str(&principal.name) {
+        Ok(caller) => request.headers_mut().insert(CALLER_HEADER, caller),
+        Err(_) => request.headers_mut().remove(CALLER_HEADER),
+    };
+    Ok(next.run(request).await)
+}
+
 #[axum_macros::debug_handler]
 #[utoipa::path(
     post,
@@ -177,16 +1081,18 @@ async fn root() -> &'static str {
 )]
 async fn create_repository(
     State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
     Json(payload): Json<CreateRepository>,
 ) -> Result<Json<CreateRepositoryResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&payload.name)?;
     let extractor_bindings = payload
         .extractor_bindings
         .clone()
         .into_iter()
-        .map(|e| into_persistence_extractor_binding(&payload.name, e))
+        .map(|e| into_persistence_extractor_binding(&repository, e))
         .collect();
     let data_repository = &persistence::DataRepository {
-        name: payload.name.clone(),
+        name: repository,
         extractor_bindings,
         metadata: payload.metadata.clone(),
         data_connectors: vec![],
@@ -215,6 +1121,7 @@ async fn create_repository(
 )]
 async fn list_repositories(
     State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
 ) -> Result<Json<ListRepositoriesResponse>, IndexifyAPIError> {
     let repositories = state
         .repository_manager
@@ -226,7 +1133,13 @@ async fn list_repositories(
                 format!("failed to list repositories: {}", e),
             )
         })?;
-    let data_repos = repositories.into_iter().map(|r| r.into()).collect();
+    let data_repos = repositories
+        .into_iter()
+        .filter_map(|r| {
+            let name = namespace.name_of(&r.name)?;
+            Some(DataRepository { name, ..r.into() })
+        })
+        .collect();
     Ok(Json(ListRepositoriesResponse {
         repositories: data_repos,
     }))
@@ -245,10 +1158,12 @@ async fn list_repositories(
 async fn get_repository(
     Path(repository_name): Path<String>,
     State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
 ) -> Result<Json<GetRepositoryResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
     let data_repo = state
         .repository_manager
-        .get(&repository_name)
+        .get(&repository)
         .await
         .map_err(|e| {
             IndexifyAPIError::new(
@@ -257,84 +1172,397 @@ async fn get_repository(
             )
         })?;
     Ok(Json(GetRepositoryResponse {
-        repository: data_repo.into(),
+        repository: DataRepository {
+            name: repository_name,
+            ..data_repo.into()
+        },
     }))
 }
 
+/// Maps errors of managing indexes to the status of the response.
+fn index_error(e: DataRepositoryError) -> IndexifyAPIError {
+    match e {
+        DataRepositoryError::Persistence(
+            RepositoryError::RepositoryNotFound(_)
+            | RepositoryError::IndexNotFound(_)
+            | RepositoryError::ExtractorNotFound(_),
+        ) => IndexifyAPIError::new(StatusCode::NOT_FOUND, e.to_string()),
+        DataRepositoryError::NotAllowed(_) => {
+            IndexifyAPIError::new(StatusCode::CONFLICT, e.to_string())
+        }
+        DataRepositoryError::RetrievalError(e) => e.into(),
+        _ => IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
 #[utoipa::path(
     post,
-    path = "/repositories/{repository_name}/extractor_bindings",
-    request_body = ExtractorBindRequest,
+    path = "/repositories/{repository_name}/indexes",
+    request_body = CreateIndexRequest,
     tag = "indexify",
     responses(
-        (status = 200, description = "Extractor binded successfully", body = ExtractorBindResponse),
-        (status = INTERNAL_SERVER_ERROR, description = "Unable to bind extractor to repository")
+        (status = 200, description = "Index created", body = Index),
+        (status = NOT_FOUND, description = "Repository or extractor not found"),
+        (status = CONFLICT, description = "Index already exists, or the extractor doesn't extract embeddings")
     ),
 )]
 #[axum_macros::debug_handler]
-async fn bind_extractor(
-    // TODO: shouldn't index_name be required here?
-    // FIXME: this throws a 500 when the binding already exists
-    // FIXME: also throws a 500 when the index name already exists
+async fn create_index(
     Path(repository_name): Path<String>,
     State(state): State<RepositoryEndpointState>,
-    Json(payload): Json<ExtractorBindRequest>,
-) -> Result<Json<ExtractorBindResponse>, IndexifyAPIError> {
-    state
+    namespace: Namespace,
+    Json(payload): Json<CreateIndexRequest>,
+) -> Result<Json<Index>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    let index = state
         .repository_manager
-        .add_extractor_binding(
-            &repository_name,
-            into_persistence_extractor_binding(&repository_name, payload.extractor_binding),
-        )
+        .create_embedding_index(&repository, &payload.name, &payload.extractor)
         .await
-        .map_err(|e| {
-            IndexifyAPIError::new(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("failed to bind extractor: {}", e),
-            )
-        })?;
-
-    if let Err(err) = _run_extractors(&repository_name, &state.coordinator_addr.to_string()).await {
-        error!("unable to run extractors: {}", err.to_string());
-    }
-    Ok(Json(ExtractorBindResponse {}))
+        .map_err(index_error)?;
+    Ok(Json(Index {
+        repository: repository_name,
+        ..index.into()
+    }))
 }
 
 #[utoipa::path(
-    post,
-    path = "/repositories/{repository_name}/add_texts",
-    request_body = TextAddRequest,
+    get,
+    path = "/repositories/{repository_name}/indexes",
     tag = "indexify",
     responses(
-        (status = 200, description = "Texts were successfully added to the repository", body = TextAdditionResponse),
-        (status = BAD_REQUEST, description = "Unable to add texts")
+        (status = 200, description = "Indexes of the repository", body = ListIndexesResponse),
+        (status = NOT_FOUND, description = "Repository not found")
     ),
 )]
 #[axum_macros::debug_handler]
-async fn add_texts(
+async fn list_indexes(
     Path(repository_name): Path<String>,
     State(state): State<RepositoryEndpointState>,
-    Json(payload): Json<TextAddRequest>,
-) -> Result<Json<TextAdditionResponse>, IndexifyAPIError> {
-    let texts = payload
-        .documents
-        .iter()
-        .map(|d| persistence::Text::from_text(&repository_name, &d.text, d.metadata.clone()))
-        .collect();
-    state
+    namespace: Namespace,
+) -> Result<Json<ListIndexesResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    let indexes = state
         .repository_manager
-        .add_texts(&repository_name, texts)
+        .list_indexes(&repository)
         .await
-        .map_err(|e| {
-            IndexifyAPIError::new(
-                StatusCode::BAD_REQUEST,
-                format!("failed to add text: {}", e),
-            )
-        })?;
+        .map_err(index_error)?;
+    Ok(Json(ListIndexesResponse {
+        indexes: indexes
+            .into_iter()
+            .map(|i| Index {
+                repository: repository_name.clone(),
+                ..i.into()
+            })
+            .collect(),
+    }))
+}
 
-    if let Err(err) = _run_extractors(&repository_name, &state.coordinator_addr.to_string()).await {
-        error!("unable to run extractors: {}", err.to_string());
-    }
+#[utoipa::path(
+    get,
+    path = "/repositories/{repository_name}/indexes/{index_name}",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Index with the given name", body = Index),
+        (status = NOT_FOUND, description = "Index not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn get_index(
+    Path((repository_name, index_name)): Path<(String, String)>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+) -> Result<Json<Index>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    let index = state
+        .repository_manager
+        .describe_index(&repository, &index_name)
+        .await
+        .map_err(index_error)?;
+    Ok(Json(Index {
+        repository: repository_name,
+        ..index.into()
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/repositories/{repository_name}/indexes/{index_name}",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Index, its vectors and the extractor bindings into it deleted"),
+        (status = NOT_FOUND, description = "Index not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn delete_index(
+    Path((repository_name, index_name)): Path<(String, String)>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+) -> Result<(), IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    state
+        .repository_manager
+        .delete_index(&repository, &index_name)
+        .await
+        .map_err(index_error)
+}
+
+#[utoipa::path(
+    post,
+    path = "/repositories/{repository_name}/indexes/{index_name}/documents",
+    request_body = AddDocumentsRequest,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Key which retries of the request are sent with too, so that they get the response of the first attempt rather than being ingested again")
+    ),
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Documents embedded into the index", body = AddDocumentsResponse),
+        (status = BAD_REQUEST, description = "Too many documents, or a document is empty", body = InvalidRequest),
+        (status = NOT_FOUND, description = "Index or its extractor not found"),
+        (status = TOO_MANY_REQUESTS, description = "Caller is rate limited"),
+        (status = CONFLICT, description = "A request with the same idempotency key is still in progress"),
+        (status = UNPROCESSABLE_ENTITY, description = "The idempotency key was already used for a request with a different body"),
+        (status = SERVICE_UNAVAILABLE, description = "Extractor is unavailable or overloaded, try again later")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn add_documents(
+    Path((repository_name, index_name)): Path<(String, String)>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+    headers: HeaderMap,
+    Json(payload): Json<AddDocumentsRequest>,
+) -> Result<Json<AddDocumentsResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    check_rate_limit(&state, &headers, payload.documents.len())?;
+    let texts = payload
+        .documents
+        .iter()
+        .map(|d| persistence::Text::from_text(&repository, &d.text, d.metadata.clone()))
+        .collect();
+    let chunks = state
+        .repository_manager
+        .add_documents(&repository, &index_name, texts, payload.chunking)
+        .await
+        .map_err(index_error)?;
+    Ok(Json(AddDocumentsResponse { chunks }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/repositories/{repository_name}/indexes/{index_name}/documents",
+    request_body = DeleteDocumentsRequest,
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Documents deleted from the index", body = DeleteDocumentsResponse),
+        (status = BAD_REQUEST, description = "Neither or both of ids and metadata, or empty metadata"),
+        (status = NOT_FOUND, description = "Index not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn delete_documents(
+    Path((repository_name, index_name)): Path<(String, String)>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+    Json(payload): Json<DeleteDocumentsRequest>,
+) -> Result<Json<DeleteDocumentsResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    let filter = match (payload.ids, payload.metadata) {
+        (Some(ids), None) => DocumentFilter::Ids(ids),
+        // Empty metadata would match every document of the repository
+        (None, Some(metadata)) if !metadata.is_empty() => DocumentFilter::Metadata(metadata),
+        _ => {
+            return Err(IndexifyAPIError::new(
+                StatusCode::BAD_REQUEST,
+                "delete documents by either ids or metadata".into(),
+            ))
+        }
+    };
+    remove_documents(&state, &repository, &index_name, filter).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/repositories/{repository_name}/indexes/{index_name}/documents/{document_id}",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Document deleted from the index", body = DeleteDocumentsResponse),
+        (status = NOT_FOUND, description = "Index not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn delete_document(
+    Path((repository_name, index_name, document_id)): Path<(String, String, String)>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+) -> Result<Json<DeleteDocumentsResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    let filter = DocumentFilter::Ids(vec![document_id]);
+    remove_documents(&state, &repository, &index_name, filter).await
+}
+
+async fn remove_documents(
+    state: &RepositoryEndpointState,
+    repository_name: &str,
+    index_name: &str,
+    filter: DocumentFilter,
+) -> Result<Json<DeleteDocumentsResponse>, IndexifyAPIError> {
+    let deleted = state
+        .repository_manager
+        .delete_documents(repository_name, index_name, filter)
+        .await
+        .map_err(index_error)?;
+    Ok(Json(DeleteDocumentsResponse {
+        documents: deleted.documents,
+        chunks: deleted.chunks,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/repositories/{repository_name}/indexes/{index_name}/search",
+    request_body = SearchIndexRequest,
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Chunks nearest to the query or vector, nearest first", body = SearchIndexResponse),
+        (status = BAD_REQUEST, description = "Neither or both of a query and a vector"),
+        (status = NOT_FOUND, description = "Index or its extractor not found"),
+        (status = TOO_MANY_REQUESTS, description = "Caller is rate limited"),
+        (status = SERVICE_UNAVAILABLE, description = "Extractor is unavailable or overloaded, try again later")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn search_index(
+    Path((repository_name, index_name)): Path<(String, String)>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+    headers: HeaderMap,
+    Json(payload): Json<SearchIndexRequest>,
+) -> Result<Json<SearchIndexResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    check_rate_limit(&state, &headers, 1)?;
+    let k = payload.k.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    let options = SearchOptions {
+        filters: into_persistence_filters(payload.filters.unwrap_or_default()),
+        text_query: payload.text_query,
+    };
+    let results = match (payload.query, payload.vector) {
+        (Some(query), None) => {
+            state
+                .repository_manager
+                .search(&repository, &index_name, &query, k, true, options)
+                .await
+        }
+        (None, Some(vector)) => {
+            state
+                .repository_manager
+                .search_embedding(&repository, &index_name, vector, k, options)
+                .await
+        }
+        _ => {
+            return Err(IndexifyAPIError::new(
+                StatusCode::BAD_REQUEST,
+                "search by either a query or a vector".into(),
+            ))
+        }
+    }
+    .map_err(index_error)?;
+    let hits = results
+        .into_iter()
+        .map(|result| SearchHit {
+            id: result.text.id,
+            text: result.text.text,
+            metadata: result.text.metadata,
+            score: result.confidence_score,
+        })
+        .collect();
+    Ok(Json(SearchIndexResponse { results: hits }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/repositories/{repository_name}/extractor_bindings",
+    request_body = ExtractorBindRequest,
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Extractor binded successfully", body = ExtractorBindResponse),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to bind extractor to repository")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn bind_extractor(
+    // TODO: shouldn't index_name be required here?
+    // FIXME: this throws a 500 when the binding already exists
+    // FIXME: also throws a 500 when the index name already exists
+    Path(repository_name): Path<String>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+    Json(payload): Json<ExtractorBindRequest>,
+) -> Result<Json<ExtractorBindResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    state
+        .repository_manager
+        .add_extractor_binding(
+            &repository,
+            into_persistence_extractor_binding(&repository, payload.extractor_binding),
+        )
+        .await
+        .map_err(|e| {
+            IndexifyAPIError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to bind extractor: {}", e),
+            )
+        })?;
+
+    if let Err(err) = _run_extractors(&repository, &state.coordinator_addr.to_string()).await {
+        error!("unable to run extractors: {}", err.to_string());
+    }
+    Ok(Json(ExtractorBindResponse {}))
+}
+
+#[utoipa::path(
+    post,
+    path = "/repositories/{repository_name}/add_texts",
+    request_body = TextAddRequest,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Key which retries of the request are sent with too, so that they get the response of the first attempt rather than being ingested again")
+    ),
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Texts were successfully added to the repository", body = TextAdditionResponse),
+        (status = CONFLICT, description = "A request with the same idempotency key is still in progress"),
+        (status = UNPROCESSABLE_ENTITY, description = "The idempotency key was already used for a request with a different body"),
+        (status = BAD_REQUEST, description = "Unable to add texts, or a document is empty", body = InvalidRequest)
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn add_texts(
+    Path(repository_name): Path<String>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+    Json(payload): Json<TextAddRequest>,
+) -> Result<Json<TextAdditionResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    let texts = payload
+        .documents
+        .iter()
+        .map(|d| persistence::Text::from_text(&repository, &d.text, d.metadata.clone()))
+        .collect();
+    state
+        .repository_manager
+        .add_texts(&repository, texts)
+        .await
+        .map_err(|e| match e {
+            DataRepositoryError::RetrievalError(e) => e.into(),
+            e => IndexifyAPIError::new(
+                StatusCode::BAD_REQUEST,
+                format!("failed to add text: {}", e),
+            ),
+        })?;
+
+    if let Err(err) = _run_extractors(&repository, &state.coordinator_addr.to_string()).await {
+        error!("unable to run extractors: {}", err.to_string());
+    }
 
     Ok(Json(TextAdditionResponse::default()))
 }
@@ -355,11 +1583,23 @@ async fn _run_extractors(repository: &str, coordinator_addr: &str) -> Result<(),
     Ok(())
 }
 
+#[utoipa::path(
+    post,
+    path = "/repositories/{repository_name}/run_extractors",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Extractors of the repository's bindings are run over its new content", body = RunExtractorsResponse),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to reach the coordinator")
+    ),
+)]
+#[axum_macros::debug_handler]
 async fn run_extractors(
     Path(repository_name): Path<String>,
     State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
 ) -> Result<Json<RunExtractorsResponse>, IndexifyAPIError> {
-    _run_extractors(&repository_name, &state.coordinator_addr.to_string())
+    let repository = namespace.repository(&repository_name)?;
+    _run_extractors(&repository, &state.coordinator_addr.to_string())
         .await
         .map_err(|e| IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     Ok(Json(RunExtractorsResponse {}))
@@ -369,9 +1609,14 @@ async fn run_extractors(
     post,
     path = "/repositories/{repository_name}/events",
     request_body =  EventAddRequest,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Key which retries of the request are sent with too, so that they get the response of the first attempt rather than being ingested again")
+    ),
     tag = "indexify",
     responses(
         (status = 200, description = "Events were successfully added to the repository", body = EventAddResponse),
+        (status = CONFLICT, description = "A request with the same idempotency key is still in progress"),
+        (status = UNPROCESSABLE_ENTITY, description = "The idempotency key was already used for a request with a different body"),
         (status = BAD_REQUEST, description = "Unable to add event")
     ),
 )]
@@ -379,16 +1624,18 @@ async fn run_extractors(
 async fn add_events(
     Path(repository_name): Path<String>,
     State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
     Json(payload): Json<EventAddRequest>,
 ) -> Result<Json<EventAddResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
     let events = payload.events.iter().map(|m| m.clone().into()).collect();
     state
         .repository_manager
-        .add_events(&repository_name, events)
+        .add_events(&repository, events)
         .await
         .map_err(|e| IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    if let Err(err) = _run_extractors(&repository_name, &state.coordinator_addr.to_string()).await {
+    if let Err(err) = _run_extractors(&repository, &state.coordinator_addr.to_string()).await {
         error!("unable to run extractors: {}", err.to_string());
     }
 
@@ -408,10 +1655,12 @@ async fn add_events(
 async fn list_events(
     Path(repository_name): Path<String>,
     State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
 ) -> Result<Json<ListEventsResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
     let messages = state
         .repository_manager
-        .list_events(&repository_name)
+        .list_events(&repository)
         .await
         .map_err(|e| IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
         .iter()
@@ -461,36 +1710,774 @@ async fn list_extractors(
     Ok(Json(ListExtractorsResponse { extractors }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/extractors/{extractor_name}",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Extractor and the model it runs", body = GetExtractorResponse),
+        (status = NOT_FOUND, description = "Extractor not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn get_extractor(
+    Path(extractor_name): Path<String>,
+    State(state): State<RepositoryEndpointState>,
+) -> Result<Json<GetExtractorResponse>, IndexifyAPIError> {
+    let (extractor, model_info) = state
+        .repository_manager
+        .get_extractor(&extractor_name)
+        .await
+        .map_err(|e| match e {
+            DataRepositoryError::Persistence(RepositoryError::ExtractorNotFound(_)) => {
+                IndexifyAPIError::new(StatusCode::NOT_FOUND, e.to_string())
+            }
+            _ => IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        })?;
+    Ok(Json(GetExtractorResponse {
+        extractor: extractor.into(),
+        loaded: model_info.is_some(),
+        model: model_info.map(|m| m.into()),
+    }))
+}
+
+/// Header naming the tenant or service a request is made for, whose rate limit
+/// the request counts against.
+const CALLER_HEADER: &str = "x-indexify-caller";
+
+/// Requests without a caller share one rate limit.
+const ANONYMOUS_CALLER: &str = "anonymous";
+
+fn caller(headers: &HeaderMap) -> &str {
+    headers
+        .get(CALLER_HEADER)
+        .and_then(|caller| caller.to_str().ok())
+        .unwrap_or(ANONYMOUS_CALLER)
+}
+
+fn check_rate_limit(
+    state: &RepositoryEndpointState,
+    headers: &HeaderMap,
+    cost: usize,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .repository_manager
+        .check_rate_limit(caller(headers), cost)
+        .map_err(retrieval_error)
+}
+
+/// Content type of responses with a JSON object on each line, which are
+/// streamed as the results are produced rather than buffered in memory.
+const NDJSON: &str = "application/x-ndjson";
+
+/// Chunks of a batch the extractor can be ahead of a streamed response by.
+const NDJSON_CHUNKS_BUFFERED: usize = 4;
+
+fn accepts_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(NDJSON))
+}
+
+fn ndjson_line(item: &impl Serialize) -> Result<Vec<u8>, serde_json::Error> {
+    let mut line = serde_json::to_vec(item)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+fn ndjson_response(
+    lines: impl Stream<Item = Result<Vec<u8>, serde_json::Error>> + Send + 'static,
+) -> Response {
+    ([(header::CONTENT_TYPE, NDJSON)], StreamBody::new(lines)).into_response()
+}
+
+/// Maps errors of retrieving from indexes to the status of the response, so
+/// clients can tell which ones are worth retrying.
+fn retrieval_error(e: DataRepositoryError) -> IndexifyAPIError {
+    match e {
+        DataRepositoryError::RetrievalError(e) => e.into(),
+        _ => IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
 #[utoipa::path(
     post,
-    path = "/repository/{repository_name}/search",
+    path = "/extractors/{extractor_name}/count_tokens",
+    request_body = CountTokensRequest,
     tag = "indexify",
     responses(
-        (status = 200, description = "Index search results", body = IndexSearchResponse),
-        (status = INTERNAL_SERVER_ERROR, description = "Unable to search index")
+        (status = 200, description = "Number of tokens in each text", body = CountTokensResponse),
+        (status = BAD_REQUEST, description = "A text is empty or over the request limits", body = InvalidRequest),
+        (status = NOT_FOUND, description = "Extractor not found"),
+        (status = TOO_MANY_REQUESTS, description = "Caller is rate limited"),
+        (status = SERVICE_UNAVAILABLE, description = "Extractor is unavailable or overloaded, try again later")
     ),
 )]
 #[axum_macros::debug_handler]
-async fn index_search(
-    Path(repository_name): Path<String>,
+async fn count_tokens(
+    Path(extractor_name): Path<String>,
     State(state): State<RepositoryEndpointState>,
-    Json(query): Json<SearchRequest>,
-) -> Result<Json<IndexSearchResponse>, IndexifyAPIError> {
+    headers: HeaderMap,
+    Json(payload): Json<CountTokensRequest>,
+) -> Result<Json<CountTokensResponse>, IndexifyAPIError> {
+    check_rate_limit(&state, &headers, payload.texts.len())?;
+    let counts = state
+        .repository_manager
+        .count_tokens(&extractor_name, payload.texts)
+        .await
+        .map_err(retrieval_error)?;
+    Ok(Json(CountTokensResponse { counts }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/embeddings",
+    request_body = EmbeddingsRequest,
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Embedding of each text, in the order of the texts", body = EmbeddingsResponse),
+        (status = BAD_REQUEST, description = "No texts to embed, or a text is empty or over the request limits", body = InvalidRequest),
+        (status = NOT_FOUND, description = "Extractor not found"),
+        (status = TOO_MANY_REQUESTS, description = "Caller is rate limited"),
+        (status = SERVICE_UNAVAILABLE, description = "Extractor is unavailable or overloaded, try again later")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn embeddings(
+    State(state): State<RepositoryEndpointState>,
+    headers: HeaderMap,
+    Json(payload): Json<EmbeddingsRequest>,
+) -> Result<Json<EmbeddingsResponse>, IndexifyAPIError> {
+    check_rate_limit(&state, &headers, payload.texts.len())?;
+    let embeddings = state
+        .repository_manager
+        .embed(&payload.model, payload.texts)
+        .await
+        .map_err(retrieval_error)?;
+    let embeddings = embeddings
+        .into_iter()
+        .map(|embedding| EmbeddingVector::new(embedding, payload.encoding_format))
+        .collect();
+    Ok(Json(EmbeddingsResponse { embeddings }))
+}
+
+/// Requests of an embedding session which are embedded at the same time.
+/// Their responses are sent as they finish rather than in order.
+const EMBEDDING_SESSION_CONCURRENCY: usize = 8;
+
+#[utoipa::path(
+    get,
+    path = "/embeddings/session",
+    tag = "indexify",
+    params(EmbeddingSessionParams),
+    responses(
+        (status = 101, description = "Switched to a WebSocket, which takes an EmbeddingSessionRequest in each text message and sends back an EmbeddingSessionResponse for each")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn embedding_session(
+    State(state): State<EmbeddingSessionState>,
+    headers: HeaderMap,
+    Query(params): Query<EmbeddingSessionParams>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let caller = caller(&headers).to_string();
+    // The session outlives the request which opened it, but its events
+    // still carry the request's id
+    let span = Span::current();
+    ws.max_message_size(state.max_message_bytes)
+        .on_upgrade(move |socket| {
+            run_embedding_session(state, caller, params.model, socket).instrument(span)
+        })
+}
+
+async fn run_embedding_session(
+    state: EmbeddingSessionState,
+    caller: String,
+    model: Option<String>,
+    socket: WebSocket,
+) {
+    info!("started embedding session");
+    let (sink, messages) = socket.split();
+    let result = messages
+        .take_while(|message| {
+            std::future::ready(!matches!(message, Err(_) | Ok(Message::Close(_))))
+        })
+        .map(|message| {
+            let state = state.clone();
+            let caller = caller.clone();
+            let model = model.clone();
+            async move {
+                match message {
+                    Ok(Message::Text(text)) => {
+                        Some(embed_session_request(&state, &caller, model.as_deref(), &text).await)
+                    }
+                    Ok(Message::Binary(_)) => Some(EmbeddingSessionResponse::error(
+                        None,
+                        IndexifyAPIError::invalid(
+                            "requests are sent as text messages".into(),
+                            None,
+                        ),
+                    )),
+                    // Pings are answered by the socket
+                    _ => None,
+                }
+            }
+        })
+        .buffer_unordered(EMBEDDING_SESSION_CONCURRENCY)
+        .filter_map(std::future::ready)
+        .map(|response| Ok(Message::Text(serde_json::to_string(&response).unwrap())))
+        .forward(sink)
+        .await;
+    match result {
+        Ok(()) => info!("finished embedding session"),
+        Err(e) => info!("embedding session closed: {}", e),
+    }
+}
+
+async fn embed_session_request(
+    state: &EmbeddingSessionState,
+    caller: &str,
+    model: Option<&str>,
+    text: &str,
+) -> EmbeddingSessionResponse {
+    let request: EmbeddingSessionRequest = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => {
+            return EmbeddingSessionResponse::error(
+                None,
+                IndexifyAPIError::invalid(format!("invalid request: {}", e), None),
+            )
+        }
+    };
+    let Some(model) = request.model.as_deref().or(model) else {
+        return EmbeddingSessionResponse::error(
+            request.id,
+            IndexifyAPIError::invalid("no model to embed the texts with".into(), None),
+        );
+    };
+    let result = match state
+        .repository_manager
+        .check_rate_limit(caller, request.texts.len())
+    {
+        Ok(()) => state.repository_manager.embed(model, request.texts).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(embeddings) => EmbeddingSessionResponse::embeddings(
+            request.id,
+            embeddings
+                .into_iter()
+                .map(|embedding| EmbeddingVector::new(embedding, request.encoding_format))
+                .collect(),
+        ),
+        Err(e) => EmbeddingSessionResponse::error(request.id, retrieval_error(e)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/embeddings/batch",
+    request_body = EmbeddingsRequest,
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Embedding or error of each text, in the order of the texts", body = BatchEmbeddingsResponse),
+        (status = BAD_REQUEST, description = "Batch is over the server's limits, or a text is over the request limits", body = InvalidRequest),
+        (status = NOT_FOUND, description = "Extractor not found"),
+        (status = TOO_MANY_REQUESTS, description = "Caller is rate limited"),
+        (status = SERVICE_UNAVAILABLE, description = "Extractor is unavailable or overloaded, try again later")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn batch_embeddings(
+    State(state): State<RepositoryEndpointState>,
+    headers: HeaderMap,
+    Json(payload): Json<EmbeddingsRequest>,
+) -> Result<Response, IndexifyAPIError> {
+    check_rate_limit(&state, &headers, payload.texts.len())?;
+    if accepts_ndjson(&headers) {
+        return stream_batch_embeddings(state, payload).await;
+    }
+    let results = state
+        .repository_manager
+        .embed_batch(&payload.model, payload.texts)
+        .await
+        .map_err(retrieval_error)?;
+    Ok(Json(BatchEmbeddingsResponse {
+        results: results
+            .into_iter()
+            .enumerate()
+            .map(|result| BatchEmbeddingResult::new(result, payload.encoding_format))
+            .collect(),
+    })
+    .into_response())
+}
+
+/// Streams the result of each text of a batch as soon as its chunk is
+/// embedded. The response starts once the first chunk is done, so that errors
+/// which fail the whole batch, like a batch over the limits, still get their
+/// status; later ones end the stream with a `StreamError` line.
+async fn stream_batch_embeddings(
+    state: RepositoryEndpointState,
+    payload: EmbeddingsRequest,
+) -> Result<Response, IndexifyAPIError> {
+    let (mut tx, mut rx) = mpsc::channel(NDJSON_CHUNKS_BUFFERED);
+    let repository_manager = state.repository_manager.clone();
+    let encoding_format = payload.encoding_format;
+    tokio::spawn(async move {
+        let result = repository_manager
+            .embed_batch_chunks(&payload.model, payload.texts, |results| {
+                let mut tx = tx.clone();
+                // Sending fails once the client has gone away
+                async move { tx.send(Ok(results)).await.is_ok() }
+            })
+            .await;
+        if let Err(e) = result {
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+    let first = match rx.next().await {
+        Some(Err(e)) => return Err(retrieval_error(e)),
+        first => first,
+    };
+    let lines = stream::iter(first).chain(rx).flat_map(move |chunk| {
+        let lines: Vec<_> = match chunk {
+            Ok(results) => results
+                .into_iter()
+                .map(|result| ndjson_line(&BatchEmbeddingResult::new(result, encoding_format)))
+                .collect(),
+            Err(e) => vec![ndjson_line(&StreamError {
+                error: e.to_string(),
+            })],
+        };
+        stream::iter(lines)
+    });
+    Ok(ndjson_response(lines))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/embeddings",
+    request_body = OpenAIEmbeddingsRequest,
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Embeddings in the schema of OpenAI's embeddings API", body = OpenAIEmbeddingsResponse),
+        (status = BAD_REQUEST, description = "No texts to embed, or a text is empty or over the request limits", body = InvalidRequest),
+        (status = NOT_FOUND, description = "Extractor not found"),
+        (status = TOO_MANY_REQUESTS, description = "Caller is rate limited"),
+        (status = SERVICE_UNAVAILABLE, description = "Extractor is unavailable or overloaded, try again later")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn openai_embeddings(
+    State(state): State<RepositoryEndpointState>,
+    headers: HeaderMap,
+    Json(payload): Json<OpenAIEmbeddingsRequest>,
+) -> Result<Json<OpenAIEmbeddingsResponse>, IndexifyAPIError> {
+    let texts = payload.input.into_texts();
+    check_rate_limit(&state, &headers, texts.len())?;
+    // Extractors which can't count tokens are assumed to split words into a
+    // token and a third on average
+    let prompt_tokens = match state
+        .repository_manager
+        .count_tokens(&payload.model, texts.clone())
+        .await
+    {
+        Ok(counts) => counts.into_iter().sum(),
+        Err(_) => texts
+            .iter()
+            .map(|text| (text.split_whitespace().count() * 4).div_ceil(3))
+            .sum(),
+    };
+    let embeddings = state
+        .repository_manager
+        .embed(&payload.model, texts)
+        .await
+        .map_err(retrieval_error)?;
+    Ok(Json(OpenAIEmbeddingsResponse {
+        object: "list".into(),
+        data: embeddings
+            .into_iter()
+            .enumerate()
+            .map(|(index, embedding)| OpenAIEmbedding {
+                object: "embedding".into(),
+                index,
+                embedding: OpenAIEmbeddingVector::new(embedding, payload.encoding_format),
+            })
+            .collect(),
+        model: payload.model,
+        usage: OpenAIUsage {
+            prompt_tokens,
+            total_tokens: prompt_tokens,
+        },
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/embedding_jobs",
+    request_body = CreateEmbeddingJobRequest,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Key which retries of the request are sent with too, so that they get the response of the first attempt rather than being ingested again")
+    ),
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Embedding job started", body = CreateEmbeddingJobResponse),
+        (status = BAD_REQUEST, description = "No texts to embed, or a text is empty or over the request limits", body = InvalidRequest),
+        (status = CONFLICT, description = "A request with the same idempotency key is still in progress"),
+        (status = UNPROCESSABLE_ENTITY, description = "The idempotency key was already used for a request with a different body"),
+        (status = NOT_FOUND, description = "Extractor not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn create_embedding_job(
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+    headers: HeaderMap,
+    Json(payload): Json<CreateEmbeddingJobRequest>,
+) -> Result<Json<CreateEmbeddingJobResponse>, IndexifyAPIError> {
+    let id = state.embedding_jobs.submit(
+        caller(&headers),
+        &namespace,
+        &payload.extractor,
+        payload.texts,
+    )?;
+    Ok(Json(CreateEmbeddingJobResponse { id }))
+}
+
+fn embedding_job_not_found(job_id: &str) -> IndexifyAPIError {
+    IndexifyAPIError::new(
+        StatusCode::NOT_FOUND,
+        format!("embedding job not found: {}", job_id),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/embedding_jobs/{job_id}",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Progress of the embedding job", body = EmbeddingJob),
+        (status = NOT_FOUND, description = "Embedding job not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn get_embedding_job(
+    Path(job_id): Path<String>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+) -> Result<Json<EmbeddingJob>, IndexifyAPIError> {
+    let status = state
+        .embedding_jobs
+        .status(&job_id, &namespace)
+        .ok_or_else(|| embedding_job_not_found(&job_id))?;
+    Ok(Json(status.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/embedding_jobs/{job_id}/events",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Server-sent events with the progress of the embedding job, each with an EmbeddingJob: `progress` now and as each chunk of texts is embedded, then `finished` or `cancelled`", body = EmbeddingJob, content_type = "text/event-stream"),
+        (status = NOT_FOUND, description = "Embedding job not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn embedding_job_events(
+    Path(job_id): Path<String>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+) -> Result<Sse<impl Stream<Item = Result<sse::Event, serde_json::Error>>>, IndexifyAPIError> {
+    let progress = state
+        .embedding_jobs
+        .progress(&job_id, &namespace)
+        .ok_or_else(|| embedding_job_not_found(&job_id))?;
+    let events = progress.map(|status| {
+        let event = if status.cancelled {
+            "cancelled"
+        } else if status.finished {
+            "finished"
+        } else {
+            "progress"
+        };
+        sse::Event::default()
+            .event(event)
+            .json_data(EmbeddingJob::from(status))
+    });
+    // Streams end when the server shuts down rather than holding up the
+    // drain until the job finishes
+    let events = events.take_until(state.shutdown.requested());
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/embedding_jobs/{job_id}/results",
+    tag = "indexify",
+    params(EmbeddingJobResultsRequest),
+    responses(
+        (status = 200, description = "Embeddings of the texts the job has finished with", body = EmbeddingJobResultsResponse),
+        (status = NOT_FOUND, description = "Embedding job not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn get_embedding_job_results(
+    Path(job_id): Path<String>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+    Query(query): Query<EmbeddingJobResultsRequest>,
+) -> Result<Json<EmbeddingJobResultsResponse>, IndexifyAPIError> {
+    let results = state
+        .embedding_jobs
+        .results(&job_id, &namespace, query.offset, query.limit)
+        .ok_or_else(|| embedding_job_not_found(&job_id))?;
+    Ok(Json(EmbeddingJobResultsResponse {
+        results: results
+            .into_iter()
+            .map(|result| EmbeddingJobResult::new(result, query.encoding_format))
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/embedding_jobs/{job_id}",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Embedding job and its results deleted"),
+        (status = NOT_FOUND, description = "Embedding job not found")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn delete_embedding_job(
+    Path(job_id): Path<String>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+) -> Result<(), IndexifyAPIError> {
+    if !state.embedding_jobs.delete(&job_id, &namespace) {
+        return Err(embedding_job_not_found(&job_id));
+    }
+    Ok(())
+}
+
+fn loaded_model(
+    vector_index_manager: &VectorIndexManager,
+    name: &str,
+    health: ExtractorHealth,
+) -> LoadedModel {
+    LoadedModel {
+        name: name.to_string(),
+        aliases: vector_index_manager.extractor_aliases(name),
+        status: health.status,
+        last_error: health.last_error,
+        stats: extractor_stats(name).into(),
+    }
+}
+
+fn model_not_found(model_name: &str) -> IndexifyAPIError {
+    IndexifyAPIError::new(
+        StatusCode::NOT_FOUND,
+        format!("model not found: {}", model_name),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/models",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Models loaded by the server, along with their calls", body = ListModelsResponse)
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn list_models(State(state): State<AdminState>) -> Json<ListModelsResponse> {
+    let mut models: Vec<_> = state
+        .vector_index_manager
+        .health()
+        .extractors
+        .into_iter()
+        .map(|(name, health)| loaded_model(&state.vector_index_manager, &name, health))
+        .collect();
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(ListModelsResponse { models })
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/models",
+    tag = "indexify",
+    request_body = LoadModelRequest,
+    responses(
+        (status = 200, description = "Model loaded, replacing any loaded model with the same name", body = LoadedModel),
+        (status = BAD_REQUEST, description = "The model couldn't be loaded", body = InvalidRequest),
+        (status = NOT_FOUND, description = "No extractor with the name in the server's configuration")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn load_model(
+    State(state): State<AdminState>,
+    Json(request): Json<LoadModelRequest>,
+) -> Result<Json<LoadedModel>, IndexifyAPIError> {
+    // Only the extractors the server was configured with are loaded, rather
+    // than any module named in the request
+    let extractor_config = state
+        .config
+        .extractors
+        .iter()
+        .find(|extractor| extractor.name == request.name)
+        .cloned()
+        .ok_or_else(|| model_not_found(&request.name))?;
+    let vector_index_manager = state.vector_index_manager.clone();
+    let info =
+        tokio::task::spawn_blocking(move || vector_index_manager.load_extractor(extractor_config))
+            .await
+            .map_err(|e| IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(|e| match e {
+                IndexError::ExtractorLoadError(_) => IndexifyAPIError::invalid(e.to_string(), None),
+                e => e.into(),
+            })?;
+    info!("loaded model: {}", &info.name);
+    let health = state
+        .vector_index_manager
+        .health()
+        .extractors
+        .remove(&info.name)
+        .ok_or_else(|| {
+            // Only embedding extractors are kept, but their aliases would be
+            state.vector_index_manager.unload_extractor(&info.name);
+            IndexifyAPIError::invalid(
+                format!("extractor is not an embedding model: {}", &info.name),
+                None,
+            )
+        })?;
+    Ok(Json(loaded_model(
+        &state.vector_index_manager,
+        &info.name,
+        health,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/models/{model_name}",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "State of the model, along with its calls", body = LoadedModel),
+        (status = NOT_FOUND, description = "Model not loaded")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn get_model(
+    Path(model_name): Path<String>,
+    State(state): State<AdminState>,
+) -> Result<Json<LoadedModel>, IndexifyAPIError> {
+    let health = state
+        .vector_index_manager
+        .health()
+        .extractors
+        .remove(&model_name)
+        .ok_or_else(|| model_not_found(&model_name))?;
+    Ok(Json(loaded_model(
+        &state.vector_index_manager,
+        &model_name,
+        health,
+    )))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/models/{model_name}",
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Model unloaded, requests which were already using it finish with it"),
+        (status = NOT_FOUND, description = "Model not loaded")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn unload_model(
+    Path(model_name): Path<String>,
+    State(state): State<AdminState>,
+) -> Result<(), IndexifyAPIError> {
+    if !state.vector_index_manager.unload_extractor(&model_name) {
+        return Err(model_not_found(&model_name));
+    }
+    info!("unloaded model: {}", &model_name);
+    Ok(())
+}
+
+async fn search_and_rerank(
+    state: &RepositoryEndpointState,
+    repository_name: &str,
+    query: &SearchRequest,
+) -> Result<Vec<ScoredText>, IndexifyAPIError> {
     let results = state
         .repository_manager
         .search(
-            &repository_name,
+            repository_name,
             &query.index,
             &query.query,
             query.k.unwrap_or(DEFAULT_SEARCH_LIMIT),
+            !query.no_wait,
+            SearchOptions {
+                filters: into_persistence_filters(query.filters.clone().unwrap_or_default()),
+                ..Default::default()
+            },
         )
         .await
-        .map_err(|e| IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let document_fragments: Vec<DocumentFragment> = results
-        .iter()
+        .map_err(retrieval_error)?;
+    match &query.rerank {
+        Some(reranker) => state
+            .repository_manager
+            .rerank(reranker, &query.query, results)
+            .await
+            .map_err(retrieval_error),
+        None => Ok(results),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/repositories/{repository_name}/search",
+    request_body = SearchRequest,
+    tag = "indexify",
+    responses(
+        (status = 200, description = "Index search results", body = IndexSearchResponse),
+        (status = BAD_REQUEST, description = "Query is empty"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to search index"),
+        (status = TOO_MANY_REQUESTS, description = "Caller is rate limited"),
+        (status = SERVICE_UNAVAILABLE, description = "Extractor is unavailable or overloaded, try again later"),
+        (status = GATEWAY_TIMEOUT, description = "Search timed out, try again later")
+    ),
+)]
+#[axum_macros::debug_handler]
+async fn index_search(
+    Path(repository_name): Path<String>,
+    State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
+    headers: HeaderMap,
+    Json(query): Json<SearchRequest>,
+) -> Result<Json<IndexSearchResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
+    check_rate_limit(&state, &headers, 1)?;
+    let search = search_and_rerank(&state, &repository, &query);
+    let results = match query.timeout_secs {
+        Some(timeout_secs) => tokio::time::timeout(Duration::from_secs(timeout_secs), search)
+            .await
+            .map_err(|_| {
+                IndexifyAPIError::new(
+                    StatusCode::GATEWAY_TIMEOUT,
+                    format!("search timed out after {}s", timeout_secs),
+                )
+            })??,
+        None => search.await?,
+    };
+    let document_fragments = results
+        .into_iter()
         .map(|text| DocumentFragment {
-            text: text.text.text.clone(),
-            metadata: text.text.metadata.clone(),
+            text: text.text.text,
+            metadata: text.text.metadata,
             confidence_score: text.confidence_score,
         })
         .collect();
@@ -501,7 +2488,7 @@ async fn index_search(
 
 #[utoipa::path(
     get,
-    path = "/repository/{repository_name}/attributes",
+    path = "/repositories/{repository_name}/attributes",
     tag = "indexify",
     params(AttributeLookupRequest),
     responses(
@@ -513,11 +2500,13 @@ async fn index_search(
 async fn attribute_lookup(
     Path(repository_name): Path<String>,
     State(state): State<RepositoryEndpointState>,
+    namespace: Namespace,
     Query(query): Query<AttributeLookupRequest>,
 ) -> Result<Json<AttributeLookupResponse>, IndexifyAPIError> {
+    let repository = namespace.repository(&repository_name)?;
     let attributes = state
         .repository_manager
-        .attribute_lookup(&repository_name, &query.index, query.content_id.as_ref())
+        .attribute_lookup(&repository, &query.index, query.content_id.as_ref())
         .await
         .map_err(|e| IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -526,6 +2515,55 @@ async fn attribute_lookup(
     }))
 }
 
+/// Tells the HTTP and gRPC servers to stop taking new connections once the
+/// process is told to shut down, and the readiness probe to take the server
+/// out of service.
+#[derive(Clone)]
+struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    fn listen() -> Self {
+        let (requested, receiver) = watch::channel(false);
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            let _ = requested.send(true);
+        });
+        Self(receiver)
+    }
+
+    fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    async fn requested(mut self) {
+        let _ = self.0.wait_for(|requested| *requested).await;
+    }
+}
+
+/// Runs a server until it has finished the requests in flight after being
+/// told to shut down, or until the drain timeout has passed since it was
+/// told to, when the requests left are dropped.
+async fn until_drained<E>(
+    server: impl std::future::Future<Output = Result<(), E>>,
+    shutdown: Shutdown,
+    drain_timeout: Duration,
+) -> Result<(), E> {
+    let deadline = async {
+        shutdown.requested().await;
+        tokio::time::sleep(drain_timeout).await;
+    };
+    tokio::select! {
+        result = server => result,
+        _ = deadline => {
+            warn!(
+                "requests were still in flight after the drain timeout of {}s, dropping them",
+                drain_timeout.as_secs()
+            );
+            Ok(())
+        }
+    }
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -554,3 +2592,206 @@ async fn shutdown_signal() {
     }
     info!("signal received, shutting down server gracefully");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openapi_spec() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/repositories/{repository_name}/search"));
+        assert!(paths.keys().all(|path| !path.starts_with("/repository/")));
+        assert_eq!(
+            spec["components"]["securitySchemes"]["api_key"]["scheme"],
+            "bearer"
+        );
+        assert_eq!(spec["security"][0]["api_key"], serde_json::json!([]));
+        // Probes don't need an API key
+        assert_eq!(
+            paths["/healthz"]["get"]["security"],
+            serde_json::json!([{}])
+        );
+    }
+
+    #[tokio::test]
+    async fn versioned_routes() {
+        let routes = Router::new().route(
+            "/repositories/:repository_name",
+            get(|Path(name): Path<String>, version: ApiVersion| async move {
+                format!("{} {}", name, version)
+            }),
+        );
+        let app = ApiVersion::ALL
+            .iter()
+            .fold(Router::new(), |api, version| {
+                api.nest(version.prefix(), versioned(routes.clone(), *version))
+            })
+            .merge(versioned(routes, ApiVersion::UNVERSIONED));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        for (path, expected) in [
+            ("/api/v1/repositories/docs", Some("docs 1")),
+            ("/repositories/docs", Some("docs 1")),
+            ("/api/v2/repositories/docs", None),
+        ] {
+            let response = reqwest::get(format!("http://{}{}", addr, path))
+                .await
+                .unwrap();
+            let Some(expected) = expected else {
+                assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+                continue;
+            };
+            assert_eq!(response.headers()[API_VERSION_HEADER], "1", "{}", path);
+            assert_eq!(response.text().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn namespaced_repositories() {
+        let key = |name: &str, namespace: Option<&str>| crate::server_config::ApiKey {
+            name: name.into(),
+            key_hash: crate::auth::hash_api_key(name),
+            scopes: vec![Scope::Read],
+            namespace: namespace.map(|namespace| namespace.into()),
+        };
+        let authenticator = Authenticator::new(&crate::server_config::AuthConfig {
+            api_keys: vec![key("default-key", None), key("team-a-key", Some("team-a"))],
+            ..Default::default()
+        })
+        .unwrap();
+        let routes = Router::new().route(
+            "/repositories/:repository_name",
+            get(
+                |namespace: Namespace, Path(name): Path<String>| async move {
+                    namespace.repository(&name).map_err(IndexifyAPIError::from)
+                },
+            ),
+        );
+        let auth_state = AuthState {
+            authenticator: Arc::new(authenticator),
+            request_limiter: None,
+        };
+        let authorized = routes
+            .clone()
+            .route_layer(middleware::from_fn_with_state(auth_state, authorize));
+        let client = reqwest::Client::new();
+        for (app, key_name, path, expected) in [
+            (
+                authorized.clone(),
+                Some("team-a-key"),
+                "/repositories/docs",
+                Some("team-a.docs"),
+            ),
+            (
+                authorized.clone(),
+                Some("default-key"),
+                "/repositories/docs",
+                Some("docs"),
+            ),
+            // Repositories of other namespaces can't be reached by their
+            // stored names, from the default namespace or any other
+            (
+                authorized.clone(),
+                Some("default-key"),
+                "/repositories/team-a.docs",
+                None,
+            ),
+            (
+                authorized,
+                Some("team-a-key"),
+                "/repositories/team-b.docs",
+                None,
+            ),
+            (routes, None, "/repositories/team-a.docs", None),
+        ] {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(
+                axum::Server::from_tcp(listener)
+                    .unwrap()
+                    .serve(app.into_make_service()),
+            );
+            let mut request = client.get(format!("http://{}{}", addr, path));
+            if let Some(key_name) = key_name {
+                request = request.bearer_auth(key_name);
+            }
+            let response = request.send().await.unwrap();
+            match expected {
+                Some(expected) => assert_eq!(response.text().await.unwrap(), expected),
+                None => assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", path),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn drained_servers() {
+        let (requested, receiver) = watch::channel(false);
+        let shutdown = Shutdown(receiver);
+        let server = tokio::spawn(until_drained(
+            std::future::pending::<Result<(), ()>>(),
+            shutdown.clone(),
+            Duration::from_millis(10),
+        ));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!server.is_finished());
+
+        requested.send(true).unwrap();
+        assert!(shutdown.is_requested());
+        let drained = tokio::time::timeout(Duration::from_secs(1), server).await;
+        assert!(drained.unwrap().unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn compressed_responses() {
+        let body = "0.123456789, ".repeat(1000);
+        let json = body.clone();
+        let app = Router::new()
+            .route("/json", get(move || async move { json }))
+            .route(
+                "/ndjson",
+                get(move || async move { ([(header::CONTENT_TYPE, NDJSON)], body) }),
+            )
+            .layer(compression_layer());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        let client = reqwest::Client::new();
+        for (encoding, path, expected) in [
+            ("gzip", "/json", Some("gzip")),
+            ("zstd", "/json", Some("zstd")),
+            ("br", "/json", None),
+            ("gzip", "/ndjson", None),
+        ] {
+            let response = client
+                .get(format!("http://{}{}", addr, path))
+                .header(header::ACCEPT_ENCODING, encoding)
+                .send()
+                .await
+                .unwrap();
+            let content_encoding = response
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|encoding| encoding.to_str().unwrap().to_string());
+            assert_eq!(
+                content_encoding.as_deref(),
+                expected,
+                "{} {}",
+                encoding,
+                path
+            );
+        }
+    }
+}
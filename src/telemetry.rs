@@ -0,0 +1,87 @@
+use anyhow::Result;
+use axum::http::HeaderMap;
+use opentelemetry::{global, propagation::Extractor, Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{self, Sampler},
+    Resource,
+};
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, Layer};
+
+use crate::server_config::{LogFormat, OtlpConfig};
+
+/// Logs the process' events in `log_format`, and exports its spans over OTLP
+/// if `otlp` is set. Spans of requests which carry a W3C `traceparent` join
+/// the caller's trace.
+pub fn init_tracing(log_format: LogFormat, otlp: Option<&OtlpConfig>) -> Result<()> {
+    let text = (log_format == LogFormat::Text)
+        .then(|| tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO));
+    // Events are logged with the fields of every span they happened in, so
+    // the events of an extractor call carry the request id of its request
+    let json = (log_format == LogFormat::Json).then(|| {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(false)
+            .with_span_list(true)
+            .flatten_event(true)
+            .with_filter(LevelFilter::INFO)
+    });
+    let otel = match otlp {
+        Some(otlp) => {
+            let exporter = opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&otlp.endpoint);
+            let config = trace::config()
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                    otlp.sample_ratio,
+                ))))
+                .with_resource(Resource::new(vec![KeyValue::new(
+                    "service.name",
+                    otlp.service_name.clone(),
+                )]));
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(exporter)
+                .with_trace_config(config)
+                .install_batch(runtime::Tokio)?;
+            global::set_text_map_propagator(TraceContextPropagator::new());
+            Some(
+                tracing_opentelemetry::layer()
+                    .with_tracer(tracer)
+                    .with_filter(LevelFilter::INFO),
+            )
+        }
+        None => None,
+    };
+    let subscriber = tracing_subscriber::registry()
+        .with(text)
+        .with(json)
+        .with(otel);
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(())
+}
+
+/// Exports the spans which haven't been exported yet, before the process
+/// exits.
+pub fn shutdown_tracing() {
+    global::shutdown_tracer_provider();
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// The trace context a request was sent with, which its span is a child of.
+pub fn remote_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
@@ -40,17 +40,16 @@ pub mod db_utils {
         let index_name = format!("{}/{}", DEFAULT_TEST_REPOSITORY, DEFAULT_TEST_EXTRACTOR);
         let qdrant: VectorDBTS = Arc::new(QdrantDb::new(crate::QdrantConfig {
             addr: "http://localhost:6334".into(),
+            ..Default::default()
         }));
         let _ = qdrant.drop_index(index_name).await;
         let repo = Arc::new(Repository::new_with_db(db.clone()));
         let coordinator = Coordinator::new(repo.clone());
         let server_config = Arc::new(ServerConfig::from_path("local_config.yaml").unwrap());
         let vector_db = vectordbs::create_vectordb(server_config.index_config.clone()).unwrap();
-        let vector_index_manager = Arc::new(VectorIndexManager::new(
-            server_config.clone(),
-            repo.clone(),
-            vector_db,
-        ));
+        let vector_index_manager = Arc::new(
+            VectorIndexManager::new(server_config.clone(), repo.clone(), vector_db).unwrap(),
+        );
         let attribute_index_manage = Arc::new(AttributeIndexManager::new(repo.clone()));
         let extractor_executor = ExtractorExecutor::new_test(
             repo.clone(),
@@ -0,0 +1,119 @@
+use std::{
+    fs,
+    io::BufReader,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::{server::AllowAnyAuthenticatedClient, Certificate, PrivateKey, RootCertStore};
+use tonic::transport::{Identity, ServerTlsConfig};
+use tracing::{error, info};
+
+use crate::server_config::TlsConfig;
+
+fn read_certs(path: &str) -> Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificates in {}", path));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &str) -> Result<PrivateKey> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    rustls_pemfile::read_all(&mut reader)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("no private key in {}", path))
+}
+
+/// TLS configuration of the HTTP API, which requires clients to present a
+/// certificate signed by one of the `client_ca_path` certificates if set.
+fn server_config(config: &TlsConfig) -> Result<rustls::ServerConfig> {
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = match &config.client_ca_path {
+        Some(client_ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(client_ca_path)? {
+                roots.add(&cert)?;
+            }
+            builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config =
+        builder.with_single_cert(read_certs(&config.cert_path)?, read_key(&config.key_path)?)?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(server_config)
+}
+
+/// Loads the certificates and key the HTTP API is served with, and reloads
+/// them when they change if `reload_interval_secs` is set.
+pub async fn rustls_config(config: &TlsConfig) -> Result<RustlsConfig> {
+    let rustls_config = RustlsConfig::from_config(Arc::new(server_config(config)?));
+    if let Some(reload_interval_secs) = config.reload_interval_secs {
+        tokio::spawn(reload_on_change(
+            config.clone(),
+            rustls_config.clone(),
+            Duration::from_secs(reload_interval_secs),
+        ));
+    }
+    Ok(rustls_config)
+}
+
+/// TLS configuration of the gRPC service, which is loaded once.
+pub fn grpc_tls_config(config: &TlsConfig) -> Result<ServerTlsConfig> {
+    let identity = Identity::from_pem(fs::read(&config.cert_path)?, fs::read(&config.key_path)?);
+    let mut tls_config = ServerTlsConfig::new().identity(identity);
+    if let Some(client_ca_path) = &config.client_ca_path {
+        tls_config = tls_config.client_ca_root(tonic::transport::Certificate::from_pem(fs::read(
+            client_ca_path,
+        )?));
+    }
+    Ok(tls_config)
+}
+
+fn modified(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [
+        Some(&config.cert_path),
+        Some(&config.key_path),
+        config.client_ca_path.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+    .collect()
+}
+
+/// Reloads the certificates and key once any of their files change, such as
+/// when the certificate is renewed, without dropping connections. Connections
+/// made after the reload get the new certificates.
+async fn reload_on_change(config: TlsConfig, rustls_config: RustlsConfig, interval: Duration) {
+    let mut loaded = modified(&config);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let current = modified(&config);
+        if current == loaded {
+            continue;
+        }
+        // Files which are being replaced one at a time may not match yet, in
+        // which case they're reloaded once the others change too
+        loaded = current;
+        match server_config(&config) {
+            Ok(server_config) => {
+                rustls_config.reload_from_config(Arc::new(server_config));
+                info!("reloaded tls certificate: {}", &config.cert_path);
+            }
+            Err(e) => error!("unable to reload tls certificate: {}", e),
+        }
+    }
+}
@@ -2,20 +2,93 @@ use anyhow::Result;
 use dashmap::DashMap;
 
 use crate::{
-    extractors::{create_extractor, ExtractedEmbeddings, ExtractorTS},
+    embedding_cache::{EmbeddingCache, EmbeddingCacheStats},
+    extractors::{
+        create_extractor, create_reranker, extract_embedding_stream, pool_embeddings, run_blocking,
+        try_run_blocking, CallLimits, ExtractedEmbeddings, ExtractorHealth, ExtractorTS,
+        ExtractorUnavailable, LoadedExtractors, ModelInfo, Priority, RerankerTS,
+    },
     index::IndexError,
-    persistence::{Chunk, ExtractorConfig, ExtractorType, Repository, Text},
-    vectordbs::{CreateIndexParams, VectorChunk, VectorDBTS},
+    persistence::{
+        Chunk, Content, ExtractorConfig, ExtractorFilter, ExtractorType, Repository, Text,
+    },
+    rate_limit::RateLimiter,
+    server_config::{self, Pooling},
+    vectordbs::{CreateIndexParams, SearchParams, VectorChunk, VectorDBTS},
     ServerConfig,
 };
-use std::sync::Arc;
-use tracing::error;
+use futures::{future, Future, Stream, StreamExt};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{error, instrument};
 
 pub struct VectorIndexManager {
     repository: Arc<Repository>,
     vector_db: VectorDBTS,
 
     embedding_extractors: DashMap<String, ExtractorTS>,
+    rerankers: DashMap<String, RerankerTS>,
+    extractor_aliases: DashMap<String, String>,
+    extractor_timeout: Duration,
+    /// Limits on the extractor calls of queries and of the work run through
+    /// the manager's extractors.
+    call_limits: Arc<CallLimits>,
+    /// Lazy extractors of the manager whose models are loaded.
+    loaded_extractors: Arc<LoadedExtractors>,
+    embedding_cache: Arc<EmbeddingCache>,
+    /// Limits on each caller, all of which a caller has to be within.
+    rate_limiters: Vec<RateLimiter>,
+    batch_limits: server_config::BatchLimits,
+    request_limits: server_config::RequestLimits,
+}
+
+/// Result of embedding one text of a batch, which fails on its own rather
+/// than failing the whole batch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmbeddingResult {
+    Embedding(Vec<f32>),
+    Error(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexManagerHealth {
+    pub extractors: HashMap<String, ExtractorHealth>,
+    /// Extractor calls of the manager which are queued or running.
+    pub pending_calls: usize,
+}
+
+/// Embedding of a text along with the text's position in the texts embedded.
+pub type PositionedEmbedding = (usize, Vec<f32>);
+
+/// Texts embedded in each call to the extractor by [`VectorIndexManager::embed`].
+const EMBED_CHUNK_SIZE: usize = 32;
+
+/// Reports extractors which are failing calls fast as unavailable, and other
+/// extractor errors as `other`.
+fn extractor_error(e: anyhow::Error, other: fn(String) -> IndexError) -> IndexError {
+    match e.downcast_ref::<ExtractorUnavailable>() {
+        Some(unavailable) => IndexError::ExtractorUnavailable(unavailable.to_string()),
+        None => other(e.to_string()),
+    }
+}
+
+/// Checks that filters of searches only match values which every index store
+/// that filters can match: strings, integers and booleans.
+fn validate_filters(filters: &[ExtractorFilter]) -> Result<(), IndexError> {
+    for filter in filters {
+        let (ExtractorFilter::Eq { field, value } | ExtractorFilter::Neq { field, value }) = filter;
+        if !(value.is_string() || value.is_i64() || value.is_boolean()) {
+            return Err(IndexError::InvalidInput(format!(
+                "the filter of `{}` has to match a string, an integer or a boolean",
+                field
+            )));
+        }
+    }
+    Ok(())
 }
 
 pub struct ScoredText {
@@ -23,24 +96,218 @@ pub struct ScoredText {
     pub confidence_score: f32,
 }
 
+/// Narrows or ranks a search beyond the nearness of the chunks.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// Filters on the metadata of the documents which every result matches.
+    pub filters: Vec<ExtractorFilter>,
+    /// Text whose keyword matches are ranked along with the nearness.
+    pub text_query: Option<String>,
+}
+
 impl VectorIndexManager {
     pub fn new(
         server_config: Arc<ServerConfig>,
         repository: Arc<Repository>,
         vector_db: VectorDBTS,
-    ) -> Self {
-        let extractor_index = DashMap::new();
-        for extractor in server_config.extractors.iter() {
-            let extractor = create_extractor(extractor.clone()).unwrap();
-            if let ExtractorType::Embedding { .. } = extractor.info().unwrap().extractor_type {
-                extractor_index.insert(extractor.info().unwrap().name, extractor);
+    ) -> Result<Self, IndexError> {
+        let mut embedding_cache = EmbeddingCache::new(server_config.embedding_cache_size);
+        if let Some(path) = &server_config.embedding_cache_path {
+            if let Err(err) = embedding_cache.open_disk(path) {
+                error!("unable to open the embedding cache at {}: {}", path, err);
             }
         }
-        Self {
+        let rerankers = DashMap::new();
+        for reranker in server_config.rerankers.iter() {
+            let created =
+                create_reranker(reranker.clone()).map_err(|e| IndexError::RerankerLoadError {
+                    name: reranker.name.clone(),
+                    reason: e.to_string(),
+                })?;
+            rerankers.insert(reranker.name.clone(), created);
+        }
+        let vector_index_manager = Self {
             repository,
             vector_db,
-            embedding_extractors: extractor_index,
+            embedding_extractors: DashMap::new(),
+            rerankers,
+            extractor_aliases: DashMap::new(),
+            extractor_timeout: Duration::from_secs(server_config.extractor_timeout_secs),
+            call_limits: Arc::new(CallLimits::new(
+                server_config.max_running_extractor_calls,
+                server_config.max_pending_extractor_calls,
+            )),
+            loaded_extractors: Arc::new(LoadedExtractors::new(server_config.max_loaded_extractors)),
+            embedding_cache: Arc::new(embedding_cache),
+            rate_limiters: server_config
+                .caller_rate_limit
+                .clone()
+                .into_iter()
+                .chain(
+                    server_config
+                        .auth
+                        .as_ref()
+                        .and_then(|auth| auth.rate_limit.texts()),
+                )
+                .map(RateLimiter::new)
+                .collect(),
+            batch_limits: server_config.batch_limits.clone(),
+            request_limits: server_config.request_limits.clone(),
+        };
+        for extractor_config in server_config.extractors.iter() {
+            vector_index_manager
+                .load_extractor(extractor_config.clone())
+                .map_err(|e| match e {
+                    IndexError::ExtractorLoadError(reason) => IndexError::ExtractorLoadError(
+                        format!("{}: {}", extractor_config.name, reason),
+                    ),
+                    e => e,
+                })?;
+        }
+        Ok(vector_index_manager)
+    }
+
+    /// Creates an extractor and makes it available for embedding queries,
+    /// replacing any loaded extractor with the same name.
+    pub fn load_extractor(
+        &self,
+        extractor_config: server_config::Extractor,
+    ) -> Result<ExtractorConfig, IndexError> {
+        let aliases = extractor_config.aliases.clone();
+        let extractor = self
+            .create_extractor(extractor_config)
+            .map_err(|e| IndexError::ExtractorLoadError(e.to_string()))?;
+        self.add_extractor(extractor, &aliases)
+    }
+
+    /// Creates an extractor whose lazy models count against the manager's
+    /// limit of loaded extractors, and whose embeddings are cached in the
+    /// manager's cache, without making it available for queries.
+    pub fn create_extractor(
+        &self,
+        extractor_config: server_config::Extractor,
+    ) -> Result<ExtractorTS> {
+        create_extractor(
+            extractor_config,
+            self.loaded_extractors.clone(),
+            self.embedding_cache.clone(),
+        )
+    }
+
+    /// Makes an already created extractor available for embedding queries.
+    pub fn add_extractor(
+        &self,
+        extractor: ExtractorTS,
+        aliases: &[String],
+    ) -> Result<ExtractorConfig, IndexError> {
+        let info = extractor
+            .info()
+            .map_err(|e| IndexError::ExtractorLoadError(e.to_string()))?;
+        for alias in aliases {
+            self.extractor_aliases
+                .insert(alias.clone(), info.name.clone());
+        }
+        if let ExtractorType::Embedding { .. } = info.extractor_type {
+            self.embedding_extractors
+                .insert(info.name.clone(), extractor);
+        }
+        Ok(info)
+    }
+
+    /// Drops an extractor along with its aliases, and returns whether it was
+    /// loaded. Queries which already hold the extractor finish with it.
+    pub fn unload_extractor(&self, name: &str) -> bool {
+        self.extractor_aliases.retain(|_, target| target != name);
+        self.embedding_extractors.remove(name).is_some()
+    }
+
+    /// Aliases which refer to an extractor.
+    pub fn extractor_aliases(&self, name: &str) -> Vec<String> {
+        let mut aliases: Vec<_> = self
+            .extractor_aliases
+            .iter()
+            .filter(|alias| alias.value() == name)
+            .map(|alias| alias.key().clone())
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    /// Whether each of the embedding extractors is ready for queries, e.g.
+    /// for a readiness probe to hold off traffic until their models load.
+    pub fn health(&self) -> IndexManagerHealth {
+        IndexManagerHealth {
+            extractors: self
+                .embedding_extractors
+                .iter()
+                .map(|e| (e.key().clone(), e.value().health()))
+                .collect(),
+            pending_calls: self.call_limits.pending(),
+        }
+    }
+
+    /// Limits which extractor calls made on behalf of the manager count
+    /// against, such as the work run through an executor's extractors.
+    pub fn call_limits(&self) -> Arc<CallLimits> {
+        self.call_limits.clone()
+    }
+
+    /// Hits, misses and size of the manager's embedding cache.
+    pub fn embedding_cache_stats(&self) -> EmbeddingCacheStats {
+        self.embedding_cache.stats()
+    }
+
+    /// Checks that the vector database can be reached.
+    pub async fn vector_db_health(&self) -> Result<(), IndexError> {
+        Ok(self.vector_db.health().await?)
+    }
+
+    /// Persists the writes of the vector database and the embedding cache,
+    /// once the server has stopped taking requests.
+    pub async fn flush(&self) -> Result<(), IndexError> {
+        if let Err(err) = self.embedding_cache.flush() {
+            error!("unable to flush the embedding cache: {}", err);
         }
+        Ok(self.vector_db.flush().await?)
+    }
+
+    /// Drops every extractor along with its model, once the server has
+    /// stopped taking requests.
+    pub fn unload_extractors(&self) {
+        self.extractor_aliases.clear();
+        self.embedding_extractors.clear();
+        self.rerankers.clear();
+    }
+
+    pub async fn num_vectors(&self, vector_index_name: &str) -> Result<u64, IndexError> {
+        Ok(self.vector_db.num_vectors(vector_index_name).await?)
+    }
+
+    pub async fn remove_chunks(
+        &self,
+        vector_index_name: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), IndexError> {
+        Ok(self
+            .vector_db
+            .remove_embeddings(vector_index_name, chunk_ids)
+            .await?)
+    }
+
+    pub async fn drop_index(&self, vector_index_name: &str) -> Result<(), IndexError> {
+        Ok(self
+            .vector_db
+            .drop_index(vector_index_name.to_string())
+            .await?)
+    }
+
+    /// Returns the name of the extractor an alias refers to, or the name
+    /// itself if it isn't an alias.
+    pub fn resolve_extractor_alias(&self, name: &str) -> String {
+        self.extractor_aliases
+            .get(name)
+            .map(|e| e.value().clone())
+            .unwrap_or_else(|| name.to_string())
     }
 
     pub async fn create_index(
@@ -75,21 +342,40 @@ impl VectorIndexManager {
 
     pub async fn add_embedding(
         &self,
-        _repository: &str,
+        repository: &str,
         index: &str,
         embeddings: Vec<ExtractedEmbeddings>,
-    ) -> Result<()> {
-        let index_info = self.repository.get_index(index, _repository).await?;
+    ) -> Result<(), IndexError> {
+        let index_info = self.repository.get_index(index, repository).await?;
         let vector_index_name = index_info.vector_index_name.clone().unwrap();
+        // Chunks only keep the metadata of their content in stores which
+        // filter searches by it
+        let metadata = if self.vector_db.supports_filters() {
+            let mut content_ids: Vec<String> =
+                embeddings.iter().map(|e| e.content_id.clone()).collect();
+            content_ids.sort();
+            content_ids.dedup();
+            self.repository
+                .content_metadata(repository, content_ids)
+                .await?
+        } else {
+            HashMap::new()
+        };
         let mut vector_chunks = Vec::new();
         let mut chunks = Vec::new();
         embeddings.iter().for_each(|embedding| {
             let chunk = Chunk::new(embedding.text.clone(), embedding.content_id.clone());
-            let vector_chunk = VectorChunk::new(
-                chunk.chunk_id.clone(),
-                chunk.text.clone(),
-                embedding.embeddings.clone(),
-            );
+            let vector_chunk = VectorChunk {
+                metadata: metadata
+                    .get(&embedding.content_id)
+                    .cloned()
+                    .unwrap_or_default(),
+                ..VectorChunk::new(
+                    chunk.chunk_id.clone(),
+                    chunk.text.clone(),
+                    embedding.embeddings.clone(),
+                )
+            };
             chunks.push(chunk);
             vector_chunks.push(vector_chunk);
         });
@@ -100,25 +386,134 @@ impl VectorIndexManager {
         Ok(())
     }
 
+    /// Embeds content into an index while the caller waits, and returns the
+    /// number of chunks added to it.
+    #[instrument(
+        skip_all,
+        fields(repository = %repository, index = %index, content = content.len())
+    )]
+    pub async fn index_content(
+        &self,
+        repository: &str,
+        index: &str,
+        content: Vec<Content<String>>,
+    ) -> Result<usize, IndexError> {
+        let index_info = self.repository.get_index(index, repository).await?;
+        let extractor = self.embedding_extractor(&index_info.extractor_name)?;
+        let mut chunks = Box::pin(extract_embedding_stream(
+            self.call_limits.clone(),
+            extractor,
+            content,
+            serde_json::json!({}),
+            EMBED_CHUNK_SIZE,
+            Priority::Interactive,
+            self.extractor_timeout,
+        ));
+        let mut added = 0;
+        while let Some(chunk) = chunks.next().await {
+            let embeddings = chunk?.map_err(|e| extractor_error(e, IndexError::EmbeddingError))?;
+            added += embeddings.len();
+            self.add_embedding(repository, index, embeddings).await?;
+        }
+        Ok(added)
+    }
+
+    /// Searches an index for the `k` chunks nearest to the query. With
+    /// `wait_for_extractor` unset, the search fails with
+    /// [`IndexError::Overloaded`] rather than waiting when the extractors are
+    /// busy.
+    #[instrument(skip_all, fields(repository = %repository, index = %index, k = k))]
     pub async fn search(
         &self,
         repository: &str,
         index: &str,
         query: &str,
         k: usize,
+        wait_for_extractor: bool,
+        options: SearchOptions,
     ) -> Result<Vec<ScoredText>, IndexError> {
+        if query.trim().is_empty() {
+            return Err(IndexError::InvalidInput("the query is empty".into()));
+        }
         let index_info = self.repository.get_index(index, repository).await?;
         let vector_index_name = index_info.vector_index_name.clone().unwrap();
-        let embeddings = self
+        let extractor = self
             .embedding_extractors
             .get(index_info.extractor_name.as_str())
-            .unwrap()
-            .value()
-            .extract_embedding_query(query)
-            .unwrap();
+            .map(|e| e.value().clone())
+            .ok_or_else(|| IndexError::ExtractorNotFound(index_info.extractor_name.clone()))?;
+        let query = query.to_string();
+        let embed_query = move || extractor.extract_embedding_query(&query);
+        let embeddings = if wait_for_extractor {
+            run_blocking(
+                self.call_limits.clone(),
+                Priority::Interactive,
+                self.extractor_timeout,
+                embed_query,
+            )
+            .await
+        } else {
+            try_run_blocking(
+                self.call_limits.clone(),
+                self.extractor_timeout,
+                embed_query,
+            )
+            .await
+        }?
+        .map_err(|e| extractor_error(e, IndexError::QueryEmbeddingError))?;
+        self.search_vector(vector_index_name, embeddings, k, options)
+            .await
+    }
+
+    /// Searches an index for the `k` chunks nearest to an embedding, which has
+    /// to have the dimensions of the index's extractor.
+    #[instrument(skip_all, fields(repository = %repository, index = %index, k = k))]
+    pub async fn search_embedding(
+        &self,
+        repository: &str,
+        index: &str,
+        embedding: Vec<f32>,
+        k: usize,
+        options: SearchOptions,
+    ) -> Result<Vec<ScoredText>, IndexError> {
+        let index_info = self.repository.get_index(index, repository).await?;
+        let vector_index_name = index_info.vector_index_name.clone().unwrap();
+        self.search_vector(vector_index_name, embedding, k, options)
+            .await
+    }
+
+    async fn search_vector(
+        &self,
+        vector_index_name: String,
+        embedding: Vec<f32>,
+        k: usize,
+        options: SearchOptions,
+    ) -> Result<Vec<ScoredText>, IndexError> {
+        let SearchOptions {
+            filters,
+            text_query,
+        } = options;
+        validate_filters(&filters)?;
+        if !filters.is_empty() && !self.vector_db.supports_filters() {
+            return Err(IndexError::InvalidInput(format!(
+                "the {} index store can't filter searches",
+                self.vector_db.name()
+            )));
+        }
+        let text_query = text_query.filter(|text_query| !text_query.trim().is_empty());
+        if text_query.is_some() && !self.vector_db.supports_text_query() {
+            return Err(IndexError::InvalidInput(format!(
+                "the {} index store can't search by text",
+                self.vector_db.name()
+            )));
+        }
         let results = self
             .vector_db
-            .search(vector_index_name, embeddings, k as u64)
+            .search(SearchParams {
+                filters,
+                text_query,
+                ..SearchParams::new(vector_index_name, embedding, k as u64)
+            })
             .await?;
         let mut index_search_results = Vec::new();
         for result in results {
@@ -139,6 +534,370 @@ impl VectorIndexManager {
         }
         Ok(index_search_results)
     }
+
+    /// Takes `cost` texts or queries from each of the caller's rate limits, if
+    /// callers are rate limited.
+    pub fn check_rate_limit(&self, caller: &str, cost: usize) -> Result<(), IndexError> {
+        for rate_limiter in &self.rate_limiters {
+            rate_limiter
+                .acquire(caller, cost)
+                .map_err(|retry_after| IndexError::RateLimited {
+                    caller: caller.to_string(),
+                    retry_after,
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Checks the texts of a request against the request limits before any of
+    /// them reach an extractor, so that a text which can't be embedded fails
+    /// the request with its position rather than with an error of the model.
+    pub fn validate_texts(&self, texts: &[String]) -> Result<(), IndexError> {
+        self.validate_text_count(texts.len())?;
+        texts
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, text)| self.validate_text(index, text))
+    }
+
+    pub fn validate_text_count(&self, count: usize) -> Result<(), IndexError> {
+        if count > self.request_limits.max_texts {
+            return Err(IndexError::InvalidInput(format!(
+                "the request has {} texts, the limit is {}",
+                count, self.request_limits.max_texts
+            )));
+        }
+        Ok(())
+    }
+
+    /// Turns away texts which are empty or longer than the request limits.
+    pub fn validate_text(&self, index: usize, text: &str) -> Result<(), IndexError> {
+        if text.trim().is_empty() {
+            return Err(IndexError::InvalidText {
+                index,
+                reason: "the text is empty".into(),
+            });
+        }
+        self.validate_text_length(index, text)
+    }
+
+    fn validate_text_length(&self, index: usize, text: &str) -> Result<(), IndexError> {
+        let chars = text.chars().count();
+        if chars > self.request_limits.max_text_chars {
+            return Err(IndexError::InvalidText {
+                index,
+                reason: format!(
+                    "the text has {} characters, the limit is {}",
+                    chars, self.request_limits.max_text_chars
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Looks up a loaded embedding extractor by its name or one of its
+    /// aliases.
+    pub fn embedding_extractor(&self, extractor: &str) -> Result<ExtractorTS, IndexError> {
+        let extractor_name = self.resolve_extractor_alias(extractor);
+        self.embedding_extractors
+            .get(&extractor_name)
+            .map(|e| e.value().clone())
+            .ok_or(IndexError::ExtractorNotFound(extractor_name))
+    }
+
+    /// Embeds texts in chunks of up to `chunk_size`, yielding the embedding of
+    /// each text of a chunk along with its position in `texts` once the chunk
+    /// is done. Texts which the extractor splits into several parts get the
+    /// mean of their parts' embeddings.
+    pub fn embed_texts(
+        &self,
+        extractor: &str,
+        texts: Vec<String>,
+        chunk_size: usize,
+        priority: Priority,
+    ) -> Result<impl Stream<Item = Result<Vec<PositionedEmbedding>, IndexError>>, IndexError> {
+        if texts.is_empty() {
+            return Err(IndexError::InvalidInput(
+                "there are no texts to embed".into(),
+            ));
+        }
+        let extractor = self.embedding_extractor(extractor)?;
+        let content = texts
+            .into_iter()
+            .enumerate()
+            .map(|(i, text)| Content::new(i.to_string(), text, HashMap::new()))
+            .collect();
+        let embeddings = extract_embedding_stream(
+            self.call_limits.clone(),
+            extractor,
+            content,
+            serde_json::json!({}),
+            chunk_size,
+            priority,
+            self.extractor_timeout,
+        );
+        Ok(embeddings.map(|chunk| {
+            let chunk = chunk?.map_err(|e| extractor_error(e, IndexError::EmbeddingError))?;
+            let mut embeddings: BTreeMap<usize, Vec<Vec<f32>>> = BTreeMap::new();
+            for e in chunk {
+                let position = e
+                    .content_id
+                    .parse()
+                    .map_err(|_| IndexError::EmbeddingError("unknown content id".into()))?;
+                embeddings.entry(position).or_default().push(e.embeddings);
+            }
+            Ok(embeddings
+                .into_iter()
+                .map(|(position, embeddings)| {
+                    (position, pool_embeddings(Pooling::Mean, embeddings))
+                })
+                .collect())
+        }))
+    }
+
+    /// Embeds texts while the caller waits, returning one embedding per text
+    /// in the order of the texts.
+    #[instrument(skip_all, fields(extractor = %extractor, texts = texts.len()))]
+    pub async fn embed(
+        &self,
+        extractor: &str,
+        texts: Vec<String>,
+    ) -> Result<Vec<Vec<f32>>, IndexError> {
+        self.validate_texts(&texts)?;
+        let total = texts.len();
+        let mut embedded = vec![None; total];
+        let mut chunks = Box::pin(self.embed_texts(
+            extractor,
+            texts,
+            EMBED_CHUNK_SIZE,
+            Priority::Interactive,
+        )?);
+        while let Some(chunk) = chunks.next().await {
+            for (position, embedding) in chunk? {
+                if let Some(embedded) = embedded.get_mut(position) {
+                    *embedded = Some(embedding);
+                }
+            }
+        }
+        embedded
+            .into_iter()
+            .enumerate()
+            .map(|(position, embedding)| {
+                embedding.ok_or_else(|| {
+                    IndexError::EmbeddingError(format!("no embedding for text {}", position))
+                })
+            })
+            .collect()
+    }
+
+    /// Embeds a batch of texts while the caller waits, with a result for each
+    /// text. Empty texts fail without reaching the extractor, texts over the
+    /// request limits fail the whole batch, and the texts of a chunk the
+    /// extractor fails on are embedded one at a time, so that only the texts
+    /// it can't embed fail. Errors worth retrying, like timeouts, fail the
+    /// whole batch.
+    pub async fn embed_batch(
+        &self,
+        extractor: &str,
+        texts: Vec<String>,
+    ) -> Result<Vec<EmbeddingResult>, IndexError> {
+        let mut results = vec![None; texts.len()];
+        self.embed_batch_chunks(extractor, texts, |chunk| {
+            for (position, result) in chunk {
+                results[position] = Some(result);
+            }
+            future::ready(true)
+        })
+        .await?;
+        Ok(results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| {
+                    EmbeddingResult::Error("extractor returned no embedding".into())
+                })
+            })
+            .collect())
+    }
+
+    /// Embeds a batch like `embed_batch`, but hands the results of each chunk
+    /// of texts, along with their positions, to `on_results` as soon as the
+    /// chunk is done rather than holding on to all of them. Stops embedding
+    /// once `on_results` returns false.
+    #[instrument(skip_all, fields(extractor = %extractor, texts = texts.len()))]
+    pub async fn embed_batch_chunks<F, Fut>(
+        &self,
+        extractor: &str,
+        texts: Vec<String>,
+        mut on_results: F,
+    ) -> Result<(), IndexError>
+    where
+        F: FnMut(Vec<(usize, EmbeddingResult)>) -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        let limits = &self.batch_limits;
+        if texts.len() > limits.max_texts {
+            return Err(IndexError::InvalidInput(format!(
+                "the batch has {} texts, the limit is {}",
+                texts.len(),
+                limits.max_texts
+            )));
+        }
+        let chars: usize = texts.iter().map(|text| text.chars().count()).sum();
+        if chars > limits.max_chars {
+            return Err(IndexError::InvalidInput(format!(
+                "the batch has {} characters, the limit is {}",
+                chars, limits.max_chars
+            )));
+        }
+        for (index, text) in texts.iter().enumerate() {
+            self.validate_text_length(index, text)?;
+        }
+        let (empty, texts): (Vec<_>, Vec<_>) = texts
+            .into_iter()
+            .enumerate()
+            .partition(|(_, text)| text.trim().is_empty());
+        if !empty.is_empty() {
+            let results = empty
+                .into_iter()
+                .map(|(position, _)| (position, EmbeddingResult::Error("the text is empty".into())))
+                .collect();
+            if !on_results(results).await {
+                return Ok(());
+            }
+        }
+        if texts.is_empty() {
+            return Ok(());
+        }
+        let (positions, texts): (Vec<usize>, Vec<String>) = texts.into_iter().unzip();
+        let mut chunks = Box::pin(self.embed_texts(
+            extractor,
+            texts.clone(),
+            EMBED_CHUNK_SIZE,
+            Priority::Interactive,
+        )?);
+        let mut chunk_start = 0;
+        while let Some(chunk) = chunks.next().await {
+            let chunk_end = (chunk_start + EMBED_CHUNK_SIZE).min(texts.len());
+            let mut results = vec![None; chunk_end - chunk_start];
+            match chunk {
+                Ok(embeddings) => {
+                    for (i, embedding) in embeddings {
+                        if let Some(result) =
+                            i.checked_sub(chunk_start).and_then(|i| results.get_mut(i))
+                        {
+                            *result = Some(EmbeddingResult::Embedding(embedding));
+                        }
+                    }
+                }
+                Err(e) if e.is_retryable() => return Err(e),
+                Err(_) => {
+                    for i in chunk_start..chunk_end {
+                        let result = match self.embed(extractor, vec![texts[i].clone()]).await {
+                            Ok(mut embeddings) => EmbeddingResult::Embedding(embeddings.remove(0)),
+                            Err(e) if e.is_retryable() => return Err(e),
+                            Err(e) => EmbeddingResult::Error(e.to_string()),
+                        };
+                        results[i - chunk_start] = Some(result);
+                    }
+                }
+            }
+            let results = results
+                .into_iter()
+                .enumerate()
+                .map(|(i, result)| {
+                    (
+                        positions[chunk_start + i],
+                        result.unwrap_or_else(|| {
+                            EmbeddingResult::Error("extractor returned no embedding".into())
+                        }),
+                    )
+                })
+                .collect();
+            if !on_results(results).await {
+                return Ok(());
+            }
+            chunk_start = chunk_end;
+        }
+        Ok(())
+    }
+
+    /// Details of the model behind an extractor, or None if the extractor
+    /// isn't loaded by this server.
+    pub async fn model_info(&self, extractor: &str) -> Result<Option<ModelInfo>, IndexError> {
+        let extractor_name = self.resolve_extractor_alias(extractor);
+        let extractor = match self.embedding_extractors.get(&extractor_name) {
+            Some(extractor) => extractor.value().clone(),
+            None => return Ok(None),
+        };
+        run_blocking(
+            self.call_limits.clone(),
+            Priority::Interactive,
+            self.extractor_timeout,
+            move || extractor.model_info(),
+        )
+        .await?
+        .map(Some)
+        .map_err(|e| IndexError::ModelInfoError(e.to_string()))
+    }
+
+    #[instrument(skip_all, fields(extractor = %extractor, texts = texts.len()))]
+    pub async fn count_tokens(
+        &self,
+        extractor: &str,
+        texts: Vec<String>,
+    ) -> Result<Vec<usize>, IndexError> {
+        self.validate_texts(&texts)?;
+        let extractor_name = self.resolve_extractor_alias(extractor);
+        let extractor = self
+            .embedding_extractors
+            .get(&extractor_name)
+            .map(|e| e.value().clone())
+            .ok_or(IndexError::ExtractorNotFound(extractor_name))?;
+        run_blocking(
+            self.call_limits.clone(),
+            Priority::Interactive,
+            self.extractor_timeout,
+            move || extractor.count_tokens(texts),
+        )
+        .await?
+        .map_err(|e| IndexError::TokenCountError(e.to_string()))
+    }
+
+    /// Reorders search results by the scores of a cross encoder, which replace
+    /// the similarity scores of the vector index.
+    #[instrument(skip_all, fields(reranker = %reranker, results = results.len()))]
+    pub async fn rerank(
+        &self,
+        reranker: &str,
+        query: &str,
+        results: Vec<ScoredText>,
+    ) -> Result<Vec<ScoredText>, IndexError> {
+        let reranker = self
+            .rerankers
+            .get(reranker)
+            .map(|r| r.value().clone())
+            .ok_or_else(|| IndexError::RerankerNotFound(reranker.into()))?;
+        let texts = results.iter().map(|r| r.text.text.clone()).collect();
+        let query = query.to_string();
+        let scores = run_blocking(
+            self.call_limits.clone(),
+            Priority::Interactive,
+            self.extractor_timeout,
+            move || reranker.score_pairs(&query, texts),
+        )
+        .await?
+        .map_err(|e| IndexError::RerankError(e.to_string()))?;
+        let mut results: Vec<ScoredText> = results
+            .into_iter()
+            .zip(scores)
+            .map(|(result, score)| ScoredText {
+                text: result.text,
+                confidence_score: score,
+            })
+            .collect();
+        results.sort_by(|a, b| b.confidence_score.total_cmp(&a.confidence_score));
+        Ok(results)
+    }
 }
 
 #[cfg(test)]
@@ -154,6 +913,8 @@ mod tests {
         create_index_manager, DEFAULT_TEST_EXTRACTOR, DEFAULT_TEST_REPOSITORY,
     };
 
+    use super::SearchOptions;
+
     #[tokio::test]
     #[tracing_test::traced_test]
     async fn test_index_search_basic() {
@@ -207,7 +968,14 @@ mod tests {
 
         extractor_executor.sync_repo_test(work_list).await.unwrap();
         let result = index_manager
-            .search(DEFAULT_TEST_REPOSITORY, DEFAULT_TEST_EXTRACTOR, "pipe", 1)
+            .search(
+                DEFAULT_TEST_REPOSITORY,
+                DEFAULT_TEST_EXTRACTOR,
+                "pipe",
+                1,
+                true,
+                SearchOptions::default(),
+            )
             .await
             .unwrap();
         assert_eq!(1, result.len())
@@ -0,0 +1,501 @@
+use async_trait::async_trait;
+use dashmap::DashMap;
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::{CreateIndexParams, VectorDb, VectorDbError};
+use crate::{
+    vectordbs::{IndexDistance, SearchParams, SearchResult, VectorChunk},
+    ElasticsearchConfig, ElasticsearchFlavor,
+};
+
+/// Documents written in each bulk request.
+const BULK_BATCH_SIZE: usize = 500;
+
+/// Weights of the kNN and keyword scores of searches with a text query. Both
+/// engines add them up, and keyword scores aren't bounded like similarities,
+/// so they're weighed less to keep the nearest chunks first.
+const KNN_BOOST: f32 = 0.9;
+const TEXT_BOOST: f32 = 0.1;
+
+/// Stores the chunks of each index in an Elasticsearch or OpenSearch index
+/// of its own, with their embeddings in a vector field searched by kNN. The
+/// text of the chunks is analyzed as well, so that keyword queries can be
+/// combined with vector queries in the same index outside of indexify.
+pub struct ElasticsearchDb {
+    config: ElasticsearchConfig,
+    client: reqwest::Client,
+    distances: DashMap<String, IndexDistance>,
+}
+
+impl ElasticsearchDb {
+    pub fn new(config: ElasticsearchConfig) -> ElasticsearchDb {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            distances: DashMap::new(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}/{}", self.config.addr.trim_end_matches('/'), path);
+        let mut request = self.client.request(method, url);
+        if let Some(api_key) = &self.config.api_key {
+            request = request.header("Authorization", format!("ApiKey {}", api_key));
+        } else if let Some(username) = &self.config.username {
+            request = request.basic_auth(username, self.config.password.as_ref());
+        }
+        request
+    }
+
+    /// Sends a request, returning the status and body of its response, or
+    /// the reason the server gave for failing it.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<(StatusCode, Value), String> {
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body: Value = response.json().await.map_err(|e| e.to_string())?;
+        if status.is_success() || status == StatusCode::NOT_FOUND {
+            return Ok((status, body));
+        }
+        Err(format!("{}: {}", status, error_reason(&body)))
+    }
+
+    /// Writes bulk actions, each of them followed by its document if it has
+    /// one.
+    async fn bulk(&self, lines: Vec<Value>) -> Result<(), String> {
+        let mut body = String::new();
+        for line in lines {
+            body.push_str(&line.to_string());
+            body.push('\n');
+        }
+        let (_, response) = self
+            .send(
+                self.request(Method::POST, "_bulk")
+                    .header("Content-Type", "application/x-ndjson")
+                    .body(body),
+            )
+            .await?;
+        // Bulk requests succeed even if some of their actions fail
+        if response["errors"].as_bool() == Some(true) {
+            let failed = response["items"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|item| item.as_object()?.values().next())
+                .find(|result| {
+                    !result["error"].is_null() && result["status"].as_u64() != Some(404)
+                });
+            if let Some(failed) = failed {
+                return Err(error_reason(failed));
+            }
+        }
+        Ok(())
+    }
+
+    fn similarity(&self, distance: &IndexDistance) -> &'static str {
+        match (&self.config.flavor, distance) {
+            (ElasticsearchFlavor::Elasticsearch, IndexDistance::Cosine) => "cosine",
+            (ElasticsearchFlavor::Elasticsearch, IndexDistance::Dot) => "max_inner_product",
+            (ElasticsearchFlavor::Elasticsearch, IndexDistance::Euclidean) => "l2_norm",
+            (ElasticsearchFlavor::OpenSearch, IndexDistance::Cosine) => "cosinesimil",
+            (ElasticsearchFlavor::OpenSearch, IndexDistance::Dot) => "innerproduct",
+            (ElasticsearchFlavor::OpenSearch, IndexDistance::Euclidean) => "l2",
+        }
+    }
+
+    fn settings(&self, index: &CreateIndexParams) -> Value {
+        let similarity = self.similarity(&index.distance);
+        let (settings, embedding) = match self.config.flavor {
+            ElasticsearchFlavor::Elasticsearch => (
+                json!({}),
+                json!({
+                    "type": "dense_vector",
+                    "dims": index.vector_dim,
+                    "index": true,
+                    "similarity": similarity,
+                }),
+            ),
+            ElasticsearchFlavor::OpenSearch => (
+                json!({ "index": { "knn": true } }),
+                json!({
+                    "type": "knn_vector",
+                    "dimension": index.vector_dim,
+                    "method": {
+                        "name": "hnsw",
+                        "space_type": similarity,
+                        "engine": "lucene",
+                    },
+                }),
+            ),
+        };
+        json!({
+            "settings": settings,
+            "mappings": {
+                "_meta": { "distance": index.distance.to_string() },
+                "properties": {
+                    "chunk_id": { "type": "keyword" },
+                    "text": { "type": "text" },
+                    "embedding": embedding,
+                },
+            },
+        })
+    }
+
+    /// Body of a kNN search, whose hits also match the text query if there
+    /// is one.
+    fn search_query(&self, query_embedding: Vec<f32>, k: u64, text_query: Option<String>) -> Value {
+        let text_match = text_query
+            .map(|query| json!({ "match": { "text": { "query": query, "boost": TEXT_BOOST } } }));
+        match self.config.flavor {
+            ElasticsearchFlavor::Elasticsearch => {
+                let mut body = json!({
+                    "size": k,
+                    "knn": {
+                        "field": "embedding",
+                        "query_vector": query_embedding,
+                        "k": k,
+                        "num_candidates": (k * 10).max(100),
+                    },
+                    "_source": ["chunk_id", "text"],
+                });
+                if let Some(text_match) = text_match {
+                    body["knn"]["boost"] = json!(KNN_BOOST);
+                    body["query"] = text_match;
+                }
+                body
+            }
+            ElasticsearchFlavor::OpenSearch => {
+                let mut knn = json!({ "embedding": { "vector": query_embedding, "k": k } });
+                let query = match text_match {
+                    Some(text_match) => {
+                        knn["embedding"]["boost"] = json!(KNN_BOOST);
+                        json!({ "bool": { "should": [{ "knn": knn }, text_match] } })
+                    }
+                    None => json!({ "knn": knn }),
+                };
+                json!({
+                    "size": k,
+                    "query": query,
+                    "_source": ["chunk_id", "text"],
+                })
+            }
+        }
+    }
+
+    async fn distance(&self, index: &str) -> Result<IndexDistance, String> {
+        if let Some(distance) = self.distances.get(index) {
+            return Ok(distance.clone());
+        }
+        let name = index_name(index);
+        let (status, body) = self
+            .send(self.request(Method::GET, &format!("{}/_mapping", name)))
+            .await?;
+        if status == StatusCode::NOT_FOUND {
+            return Err("index not found".into());
+        }
+        let distance: IndexDistance = body[&name]["mappings"]["_meta"]["distance"]
+            .as_str()
+            .ok_or("missing distance")?
+            .parse()
+            .map_err(|e: strum::ParseError| e.to_string())?;
+        self.distances.insert(index.into(), distance.clone());
+        Ok(distance)
+    }
+}
+
+/// Name of the Elasticsearch index of an index. Index names can only have
+/// lowercase letters, digits and some punctuation, and can't start with
+/// `-`, so other characters of the index's name are escaped after a prefix.
+fn index_name(index: &str) -> String {
+    let mut name = String::from("indexify-");
+    for c in index.chars() {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' {
+            name.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                name.push_str(&format!("_{:02x}", b));
+            }
+        }
+    }
+    name
+}
+
+/// Similarity for cosine and dot, or distance for euclidean, of a kNN hit
+/// scoring `score`. Both engines turn them into positive scores which are
+/// higher for nearer vectors, which is undone so that scores match the other
+/// stores.
+fn knn_score(distance: &IndexDistance, score: f32) -> f32 {
+    match distance {
+        IndexDistance::Cosine => 2. * score - 1.,
+        IndexDistance::Dot if score >= 1. => score - 1.,
+        IndexDistance::Dot => 1. - 1. / score,
+        IndexDistance::Euclidean => (1. / score - 1.).max(0.).sqrt(),
+    }
+}
+
+fn error_reason(body: &Value) -> String {
+    match &body["error"] {
+        Value::String(reason) => reason.clone(),
+        error if !error.is_null() => format!("{}: {}", error["type"], error["reason"]),
+        _ => body.to_string(),
+    }
+}
+
+#[async_trait]
+impl VectorDb for ElasticsearchDb {
+    fn name(&self) -> String {
+        "elasticsearch".into()
+    }
+
+    async fn create_index(&self, index: CreateIndexParams) -> Result<(), VectorDbError> {
+        let name = index_name(&index.vectordb_index_name);
+        let result = self
+            .send(
+                self.request(Method::PUT, &name)
+                    .json(&self.settings(&index)),
+            )
+            .await;
+        match result {
+            Err(e) if !e.contains("resource_already_exists_exception") => {
+                Err(VectorDbError::IndexCreationError(e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn add_embedding(
+        &self,
+        index: &str,
+        chunks: Vec<VectorChunk>,
+    ) -> Result<(), VectorDbError> {
+        let name = index_name(index);
+        for batch in chunks.chunks(BULK_BATCH_SIZE) {
+            let mut lines = Vec::with_capacity(batch.len() * 2);
+            for chunk in batch {
+                lines.push(json!({ "index": { "_index": name, "_id": chunk.chunk_id } }));
+                lines.push(json!({
+                    "chunk_id": chunk.chunk_id,
+                    "text": chunk.text,
+                    "embedding": chunk.embeddings,
+                }));
+            }
+            self.bulk(lines)
+                .await
+                .map_err(VectorDbError::IndexWriteError)?;
+        }
+        Ok(())
+    }
+
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError> {
+        let SearchParams {
+            vectordb_index_name: index,
+            query_embedding,
+            k,
+            text_query,
+            ..
+        } = params;
+        let distance = self
+            .distance(&index)
+            .await
+            .map_err(VectorDbError::IndexReadError)?;
+        let hybrid = text_query.is_some();
+        let (_, body) = self
+            .send(
+                self.request(Method::POST, &format!("{}/_search", index_name(&index)))
+                    .json(&self.search_query(query_embedding, k, text_query)),
+            )
+            .await
+            .map_err(VectorDbError::IndexReadError)?;
+        let hits = body["hits"]["hits"]
+            .as_array()
+            .ok_or(VectorDbError::IndexReadError("missing hits".into()))?;
+        Ok(hits
+            .iter()
+            .map(|hit| SearchResult {
+                text: hit["_source"]["text"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                chunk_id: hit["_source"]["chunk_id"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                // Scores of searches with a text query mix both scores, so
+                // they're kept as the engine ranked them
+                confidence_score: match hit["_score"].as_f64().unwrap_or_default() as f32 {
+                    score if hybrid => score,
+                    score => knn_score(&distance, score),
+                },
+            })
+            .collect())
+    }
+
+    fn supports_text_query(&self) -> bool {
+        true
+    }
+
+    async fn remove_embeddings(
+        &self,
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError> {
+        let name = index_name(index);
+        for batch in chunk_ids.chunks(BULK_BATCH_SIZE) {
+            let lines = batch
+                .iter()
+                .map(|chunk_id| json!({ "delete": { "_index": name, "_id": chunk_id } }))
+                .collect();
+            self.bulk(lines)
+                .await
+                .map_err(VectorDbError::IndexWriteError)?;
+        }
+        Ok(())
+    }
+
+    async fn drop_index(&self, index: String) -> Result<(), VectorDbError> {
+        self.send(self.request(Method::DELETE, &index_name(&index)))
+            .await
+            .map_err(|e| VectorDbError::IndexDeletionError(index.clone(), e))?;
+        self.distances.remove(&index);
+        Ok(())
+    }
+
+    async fn num_vectors(&self, index: &str) -> Result<u64, VectorDbError> {
+        let name = index_name(index);
+        // Documents are only counted once the index is refreshed, so it's
+        // refreshed first to count the writes before the call
+        self.send(self.request(Method::POST, &format!("{}/_refresh", name)))
+            .await
+            .map_err(VectorDbError::IndexReadError)?;
+        let (status, body) = self
+            .send(self.request(Method::GET, &format!("{}/_count", name)))
+            .await
+            .map_err(VectorDbError::IndexReadError)?;
+        if status == StatusCode::NOT_FOUND {
+            return Err(VectorDbError::IndexReadError("index not found".into()));
+        }
+        body["count"]
+            .as_u64()
+            .ok_or(VectorDbError::IndexReadError("missing count".into()))
+    }
+
+    async fn health(&self) -> Result<(), VectorDbError> {
+        self.send(self.request(Method::GET, "_cluster/health"))
+            .await
+            .map_err(VectorDbError::InternalError)?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), VectorDbError> {
+        // Writes are in the translog on disk by the time they're acknowledged
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use crate::{
+        vectordbs::{IndexDistance, SearchParams, VectorChunk, VectorDBTS},
+        ElasticsearchConfig, ElasticsearchFlavor,
+    };
+
+    use super::{index_name, knn_score, CreateIndexParams, ElasticsearchDb, KNN_BOOST, TEXT_BOOST};
+
+    #[test]
+    fn test_index_names_and_scores() {
+        assert_eq!(
+            index_name("default-Hello_index"),
+            "indexify-default-_48ello_5findex"
+        );
+        assert_eq!(knn_score(&IndexDistance::Cosine, 0.75), 0.5);
+        assert_eq!(knn_score(&IndexDistance::Dot, 3.), 2.);
+        assert_eq!(knn_score(&IndexDistance::Dot, 0.5), -1.);
+        assert_eq!(knn_score(&IndexDistance::Euclidean, 0.5), 1.);
+    }
+
+    #[test]
+    fn test_search_queries() {
+        let elasticsearch = ElasticsearchDb::new(ElasticsearchConfig::default());
+        let body = elasticsearch.search_query(vec![1., 0.], 2, None);
+        assert!(body["query"].is_null());
+        assert!(body["knn"]["boost"].is_null());
+
+        let body = elasticsearch.search_query(vec![1., 0.], 2, Some("pets".into()));
+        assert_eq!(body["knn"]["query_vector"], json!([1., 0.]));
+        assert_eq!(body["knn"]["boost"], json!(KNN_BOOST));
+        assert_eq!(
+            body["query"],
+            json!({ "match": { "text": { "query": "pets", "boost": TEXT_BOOST } } })
+        );
+
+        let opensearch = ElasticsearchDb::new(ElasticsearchConfig {
+            flavor: ElasticsearchFlavor::OpenSearch,
+            ..Default::default()
+        });
+        let body = opensearch.search_query(vec![1., 0.], 2, None);
+        assert_eq!(
+            body["query"],
+            json!({ "knn": { "embedding": { "vector": [1., 0.], "k": 2 } } })
+        );
+        let body = opensearch.search_query(vec![1., 0.], 2, Some("pets".into()));
+        assert_eq!(
+            body["query"]["bool"]["should"],
+            json!([
+                { "knn": { "embedding": { "vector": [1., 0.], "k": 2, "boost": KNN_BOOST } } },
+                { "match": { "text": { "query": "pets", "boost": TEXT_BOOST } } },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_search_basic() {
+        let elasticsearch: VectorDBTS =
+            Arc::new(ElasticsearchDb::new(crate::ElasticsearchConfig::default()));
+        elasticsearch
+            .drop_index("hello-index".into())
+            .await
+            .unwrap();
+        elasticsearch
+            .create_index(CreateIndexParams {
+                vectordb_index_name: "hello-index".into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+            })
+            .await
+            .unwrap();
+        let chunk = VectorChunk::new("0".into(), "test".into(), vec![0., 2.]);
+        elasticsearch
+            .add_embedding("hello-index", vec![chunk.clone()])
+            .await
+            .unwrap();
+        elasticsearch
+            .add_embedding("hello-index", vec![chunk])
+            .await
+            .unwrap();
+        assert_eq!(elasticsearch.num_vectors("hello-index").await.unwrap(), 1);
+
+        let results = elasticsearch
+            .search(SearchParams::new("hello-index".into(), vec![10., 8.], 1))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "0");
+
+        let results = elasticsearch
+            .search(SearchParams {
+                text_query: Some("test".into()),
+                ..SearchParams::new("hello-index".into(), vec![10., 8.], 1)
+            })
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "0");
+    }
+}
@@ -0,0 +1,678 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use faiss::{
+    index::{autotune::ParameterSpace, IndexImpl},
+    index_factory, read_index,
+    selector::IdSelector,
+    write_index, Idx, Index, MetricType,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use super::{escaped_index_name, CreateIndexParams, VectorDb, VectorDbError};
+use crate::{
+    vectordbs::{IndexDistance, SearchParams, SearchResult, VectorChunk},
+    FaissConfig, FaissIndexType,
+};
+
+/// Settings of an index, kept so that it can be loaded again.
+#[derive(Serialize, Deserialize)]
+struct IndexInfo {
+    distance: IndexDistance,
+    vector_dim: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Chunk {
+    chunk_id: String,
+    text: String,
+}
+
+/// A FAISS index along with the trees of its chunks in the store's database.
+/// FAISS only keeps the vectors, under ids of the store's choosing, so the
+/// chunk of each id and the id of each chunk are kept beside them.
+struct FaissIndex {
+    info: IndexInfo,
+    index: IndexImpl,
+    file: PathBuf,
+    chunks: sled::Tree,
+    ids: sled::Tree,
+    /// Vectors added before an IVF index is trained, which are searched
+    /// exhaustively until there are enough of them to train it on.
+    pending: sled::Tree,
+    /// Ids which are still in an HNSW graph after their chunk was removed,
+    /// since the graph can't remove them.
+    removed: usize,
+    changed: bool,
+}
+
+impl FaissIndex {
+    fn metric(&self) -> MetricType {
+        match self.info.distance {
+            IndexDistance::Euclidean => MetricType::L2,
+            IndexDistance::Cosine | IndexDistance::Dot => MetricType::InnerProduct,
+        }
+    }
+
+    /// Vector as it's kept in the index, which for cosine is normalized, so
+    /// that inner products are cosine similarities.
+    fn prepare(&self, mut vector: Vec<f32>) -> Vec<f32> {
+        if let IndexDistance::Cosine = self.info.distance {
+            let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm > 0. {
+                vector.iter_mut().for_each(|x| *x /= norm);
+            }
+        }
+        vector
+    }
+
+    /// Score of a result FAISS found, which is the similarity for cosine and
+    /// dot, and the distance for euclidean, as in the other stores.
+    fn score(&self, distance: f32) -> f32 {
+        match self.info.distance {
+            IndexDistance::Euclidean => distance.sqrt(),
+            IndexDistance::Cosine | IndexDistance::Dot => distance,
+        }
+    }
+
+    fn chunk(&self, id: u64) -> Result<Option<Chunk>, String> {
+        match self
+            .chunks
+            .get(id.to_be_bytes())
+            .map_err(|e| e.to_string())?
+        {
+            Some(chunk) => serde_json::from_slice(&chunk)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    fn remove_ids(&mut self, ids: &[u64]) -> Result<(), String> {
+        for id in ids {
+            self.chunks
+                .remove(id.to_be_bytes())
+                .map_err(|e| e.to_string())?;
+            if self
+                .pending
+                .remove(id.to_be_bytes())
+                .map_err(|e| e.to_string())?
+                .is_some()
+            {
+                continue;
+            }
+            if self.index.is_trained() {
+                let selector = IdSelector::batch(&[Idx::new(*id)]).map_err(|e| e.to_string())?;
+                if self.index.remove_ids(&selector).is_err() {
+                    self.removed += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add(
+        &mut self,
+        chunks: Vec<VectorChunk>,
+        db: &sled::Db,
+        train_size: usize,
+    ) -> Result<(), String> {
+        let mut vectors = Vec::new();
+        let mut ids = Vec::new();
+        for chunk in chunks {
+            if let Some(old) = self.ids.get(&chunk.chunk_id).map_err(|e| e.to_string())? {
+                let old = u64::from_be_bytes(old.as_ref().try_into().map_err(|_| "invalid id")?);
+                self.remove_ids(&[old])?;
+            }
+            let id = db.generate_id().map_err(|e| e.to_string())?;
+            let record = serde_json::to_vec(&Chunk {
+                chunk_id: chunk.chunk_id.clone(),
+                text: chunk.text,
+            })
+            .map_err(|e| e.to_string())?;
+            self.chunks
+                .insert(id.to_be_bytes(), record)
+                .map_err(|e| e.to_string())?;
+            self.ids
+                .insert(chunk.chunk_id.as_bytes(), &id.to_be_bytes())
+                .map_err(|e| e.to_string())?;
+            let vector = self.prepare(chunk.embeddings);
+            if self.index.is_trained() {
+                vectors.extend(vector);
+                ids.push(Idx::new(id));
+            } else {
+                let bytes: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+                self.pending
+                    .insert(id.to_be_bytes(), bytes)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        if !ids.is_empty() {
+            self.index
+                .add_with_ids(&vectors, &ids)
+                .map_err(|e| e.to_string())?;
+        }
+        if !self.index.is_trained() && self.pending.len() >= train_size {
+            self.train()?;
+        }
+        self.changed = true;
+        Ok(())
+    }
+
+    /// Trains an IVF index on the vectors which were added before it was
+    /// trained, and then adds them to it.
+    fn train(&mut self) -> Result<(), String> {
+        let (ids, vectors) = self.pending_vectors()?;
+        let vectors: Vec<f32> = vectors.into_iter().flatten().collect();
+        info!("training faiss index on {} vectors", ids.len());
+        self.index.train(&vectors).map_err(|e| e.to_string())?;
+        let ids: Vec<Idx> = ids.into_iter().map(Idx::new).collect();
+        self.index
+            .add_with_ids(&vectors, &ids)
+            .map_err(|e| e.to_string())?;
+        self.pending.clear().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn pending_vectors(&self) -> Result<(Vec<u64>, Vec<Vec<f32>>), String> {
+        let mut ids = Vec::new();
+        let mut vectors = Vec::new();
+        for entry in self.pending.iter() {
+            let (id, bytes) = entry.map_err(|e| e.to_string())?;
+            ids.push(u64::from_be_bytes(
+                id.as_ref().try_into().map_err(|_| "invalid id")?,
+            ));
+            vectors.push(
+                bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect(),
+            );
+        }
+        Ok((ids, vectors))
+    }
+
+    fn search(&mut self, query: Vec<f32>, k: usize) -> Result<Vec<SearchResult>, String> {
+        let query = self.prepare(query);
+        let mut found: Vec<(u64, f32)> = Vec::new();
+        if self.index.is_trained() {
+            let fetch = (k + self.removed).min(self.index.ntotal() as usize);
+            if fetch > 0 {
+                let result = self
+                    .index
+                    .search(&query, fetch)
+                    .map_err(|e| e.to_string())?;
+                for (label, distance) in result.labels.into_iter().zip(result.distances) {
+                    if let Some(id) = label.get() {
+                        found.push((id, distance));
+                    }
+                }
+            }
+        } else {
+            let (ids, vectors) = self.pending_vectors()?;
+            for (id, vector) in ids.into_iter().zip(vectors) {
+                let distance = match self.metric() {
+                    MetricType::L2 => query
+                        .iter()
+                        .zip(&vector)
+                        .map(|(a, b)| (a - b) * (a - b))
+                        .sum(),
+                    MetricType::InnerProduct => query.iter().zip(&vector).map(|(a, b)| a * b).sum(),
+                };
+                found.push((id, distance));
+            }
+            match self.metric() {
+                MetricType::L2 => found.sort_by(|a, b| a.1.total_cmp(&b.1)),
+                MetricType::InnerProduct => found.sort_by(|a, b| b.1.total_cmp(&a.1)),
+            }
+        }
+        let mut results = Vec::new();
+        for (id, distance) in found {
+            if results.len() == k {
+                break;
+            }
+            // Ids of removed chunks which are still in the index are skipped
+            if let Some(chunk) = self.chunk(id)? {
+                results.push(SearchResult {
+                    text: chunk.text,
+                    chunk_id: chunk.chunk_id,
+                    confidence_score: self.score(distance),
+                });
+            }
+        }
+        Ok(results)
+    }
+}
+
+struct Indexes {
+    db: sled::Db,
+    loaded: RwLock<HashMap<String, Arc<Mutex<FaissIndex>>>>,
+}
+
+impl Indexes {
+    /// Saves the indexes which have changed to their files, replacing each
+    /// file at once so that a crash doesn't leave half of it behind.
+    fn persist(&self) -> Result<(), VectorDbError> {
+        let indexes: Vec<_> = self.loaded.read().unwrap().values().cloned().collect();
+        for index in indexes {
+            let mut index = index.lock().unwrap();
+            if !index.changed {
+                continue;
+            }
+            let tmp = index.file.with_extension("tmp");
+            write_index(&index.index, tmp.to_string_lossy())
+                .map_err(|e| e.to_string())
+                .and_then(|_| std::fs::rename(&tmp, &index.file).map_err(|e| e.to_string()))
+                .map_err(|e| {
+                    VectorDbError::IndexWriteError(format!(
+                        "unable to save faiss index to `{}`: {}",
+                        index.file.display(),
+                        e
+                    ))
+                })?;
+            index.changed = false;
+        }
+        self.db
+            .flush()
+            .map_err(|e| VectorDbError::IndexWriteError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Keeps the vectors of each index in a FAISS index in memory, which is
+/// saved to a file under the config's `path` every `persist_interval_secs`
+/// and when the server shuts down, with the chunks of the vectors in a
+/// database beside the files. IVF indexes are trained on the first
+/// `train_size` vectors added to them, and searched exhaustively until then.
+pub struct FaissDb {
+    config: FaissConfig,
+    path: PathBuf,
+    indexes: Arc<Indexes>,
+}
+
+impl FaissDb {
+    pub fn new(config: FaissConfig) -> Result<FaissDb, VectorDbError> {
+        let path = PathBuf::from(&config.path);
+        let db = sled::open(path.join("chunks")).map_err(|e| {
+            VectorDbError::InternalError(format!(
+                "unable to open faiss store at `{}`: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let indexes = Arc::new(Indexes {
+            db,
+            loaded: RwLock::new(HashMap::new()),
+        });
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let interval = Duration::from_secs(config.persist_interval_secs.max(1));
+            runtime.spawn(persist_periodically(Arc::downgrade(&indexes), interval));
+        }
+        Ok(Self {
+            config,
+            path,
+            indexes,
+        })
+    }
+
+    fn train_size(&self) -> usize {
+        match self.config.index_type {
+            FaissIndexType::Ivf => self
+                .config
+                .train_size
+                .unwrap_or(40 * self.config.nlist as usize),
+            FaissIndexType::Hnsw => 0,
+        }
+    }
+
+    fn new_index(&self, info: &IndexInfo) -> Result<IndexImpl, String> {
+        let (description, parameter, value) = match self.config.index_type {
+            FaissIndexType::Ivf => (
+                format!("IVF{},Flat", self.config.nlist),
+                "nprobe",
+                self.config.nprobe,
+            ),
+            FaissIndexType::Hnsw => (
+                format!("IDMap,HNSW{}", self.config.m),
+                "efSearch",
+                self.config.ef_search,
+            ),
+        };
+        let metric = match info.distance {
+            IndexDistance::Euclidean => MetricType::L2,
+            IndexDistance::Cosine | IndexDistance::Dot => MetricType::InnerProduct,
+        };
+        let index =
+            index_factory(info.vector_dim, description, metric).map_err(|e| e.to_string())?;
+        Self::set_parameter(&index, parameter, value)?;
+        Ok(index)
+    }
+
+    fn set_parameter(index: &IndexImpl, parameter: &str, value: u32) -> Result<(), String> {
+        ParameterSpace::new()
+            .and_then(|space| space.set_index_parameter(index, parameter, value))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Index of the given name, which is loaded from its file the first time
+    /// it's used.
+    fn index(&self, name: &str) -> Result<Arc<Mutex<FaissIndex>>, String> {
+        if let Some(index) = self.indexes.loaded.read().unwrap().get(name) {
+            return Ok(index.clone());
+        }
+        let mut loaded = self.indexes.loaded.write().unwrap();
+        if let Some(index) = loaded.get(name) {
+            return Ok(index.clone());
+        }
+        let info: IndexInfo = match self
+            .indexes
+            .db
+            .open_tree("indexes")
+            .and_then(|tree| tree.get(name))
+            .map_err(|e| e.to_string())?
+        {
+            Some(info) => serde_json::from_slice(&info).map_err(|e| e.to_string())?,
+            None => return Err("index not found".into()),
+        };
+        let escaped = escaped_index_name(name);
+        let file = self.path.join(format!("{}.faiss", escaped));
+        let index = if file.exists() {
+            let index = read_index(file.to_string_lossy()).map_err(|e| e.to_string())?;
+            let (parameter, value) = match self.config.index_type {
+                FaissIndexType::Ivf => ("nprobe", self.config.nprobe),
+                FaissIndexType::Hnsw => ("efSearch", self.config.ef_search),
+            };
+            Self::set_parameter(&index, parameter, value)?;
+            index
+        } else {
+            self.new_index(&info)?
+        };
+        let tree = |suffix: &str| {
+            self.indexes
+                .db
+                .open_tree(format!("{}.{}", escaped, suffix))
+                .map_err(|e| e.to_string())
+        };
+        let (chunks, ids, pending) = (tree("chunks")?, tree("ids")?, tree("pending")?);
+        // Vectors in the index which no longer have a chunk were removed
+        let removed = (index.ntotal() as usize).saturating_sub(chunks.len() - pending.len());
+        let index = Arc::new(Mutex::new(FaissIndex {
+            info,
+            index,
+            file,
+            chunks,
+            ids,
+            pending,
+            removed,
+            changed: false,
+        }));
+        loaded.insert(name.to_string(), index.clone());
+        Ok(index)
+    }
+
+    /// Runs a call, which can take a while on large indexes, on a thread
+    /// which is allowed to block.
+    async fn blocking<T: Send + 'static>(
+        call: impl FnOnce() -> Result<T, String> + Send + 'static,
+    ) -> Result<T, String> {
+        tokio::task::spawn_blocking(call)
+            .await
+            .map_err(|e| e.to_string())?
+    }
+}
+
+async fn persist_periodically(indexes: Weak<Indexes>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(indexes) = indexes.upgrade() else {
+            return;
+        };
+        let result = tokio::task::spawn_blocking(move || indexes.persist()).await;
+        if let Ok(Err(err)) = result {
+            error!("{}", err);
+        }
+    }
+}
+
+#[async_trait]
+impl VectorDb for FaissDb {
+    fn name(&self) -> String {
+        "faiss".into()
+    }
+
+    async fn create_index(&self, index: CreateIndexParams) -> Result<(), VectorDbError> {
+        let info = IndexInfo {
+            distance: index.distance,
+            vector_dim: index.vector_dim as u32,
+        };
+        let info = serde_json::to_vec(&info)
+            .map_err(|e| VectorDbError::IndexCreationError(e.to_string()))?;
+        self.indexes
+            .db
+            .open_tree("indexes")
+            .and_then(|tree| {
+                tree.compare_and_swap(
+                    index.vectordb_index_name.as_bytes(),
+                    None as Option<&[u8]>,
+                    Some(info),
+                )
+            })
+            .map_err(|e| VectorDbError::IndexCreationError(e.to_string()))?
+            // An index which exists already is left as it is
+            .ok();
+        Ok(())
+    }
+
+    async fn add_embedding(
+        &self,
+        index: &str,
+        chunks: Vec<VectorChunk>,
+    ) -> Result<(), VectorDbError> {
+        let faiss_index = self.index(index).map_err(VectorDbError::IndexWriteError)?;
+        let db = self.indexes.db.clone();
+        let train_size = self.train_size();
+        Self::blocking(move || faiss_index.lock().unwrap().add(chunks, &db, train_size))
+            .await
+            .map_err(VectorDbError::IndexWriteError)
+    }
+
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError> {
+        let SearchParams {
+            vectordb_index_name: index,
+            query_embedding,
+            k,
+            ..
+        } = params;
+        let faiss_index = self.index(&index).map_err(VectorDbError::IndexReadError)?;
+        Self::blocking(move || {
+            faiss_index
+                .lock()
+                .unwrap()
+                .search(query_embedding, k as usize)
+        })
+        .await
+        .map_err(VectorDbError::IndexReadError)
+    }
+
+    async fn remove_embeddings(
+        &self,
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError> {
+        let faiss_index = self.index(index).map_err(VectorDbError::IndexWriteError)?;
+        Self::blocking(move || {
+            let mut faiss_index = faiss_index.lock().unwrap();
+            let mut ids = Vec::new();
+            for chunk_id in chunk_ids {
+                if let Some(id) = faiss_index
+                    .ids
+                    .remove(chunk_id.as_bytes())
+                    .map_err(|e| e.to_string())?
+                {
+                    ids.push(u64::from_be_bytes(
+                        id.as_ref().try_into().map_err(|_| "invalid id")?,
+                    ));
+                }
+            }
+            faiss_index.remove_ids(&ids)?;
+            faiss_index.changed = true;
+            Ok(())
+        })
+        .await
+        .map_err(VectorDbError::IndexWriteError)
+    }
+
+    async fn drop_index(&self, index: String) -> Result<(), VectorDbError> {
+        let drop = |e: String| VectorDbError::IndexDeletionError(index.clone(), e);
+        self.indexes.loaded.write().unwrap().remove(&index);
+        let escaped = escaped_index_name(&index);
+        for suffix in ["chunks", "ids", "pending"] {
+            self.indexes
+                .db
+                .drop_tree(format!("{}.{}", escaped, suffix))
+                .map_err(|e| drop(e.to_string()))?;
+        }
+        self.indexes
+            .db
+            .open_tree("indexes")
+            .and_then(|tree| tree.remove(index.as_bytes()))
+            .map_err(|e| drop(e.to_string()))?;
+        let file = self.path.join(format!("{}.faiss", escaped));
+        if file.exists() {
+            std::fs::remove_file(file).map_err(|e| drop(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    async fn num_vectors(&self, index: &str) -> Result<u64, VectorDbError> {
+        let faiss_index = self.index(index).map_err(VectorDbError::IndexReadError)?;
+        let count = faiss_index.lock().unwrap().chunks.len();
+        Ok(count as u64)
+    }
+
+    async fn health(&self) -> Result<(), VectorDbError> {
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), VectorDbError> {
+        let indexes = self.indexes.clone();
+        tokio::task::spawn_blocking(move || indexes.persist())
+            .await
+            .map_err(|e| VectorDbError::InternalError(e.to_string()))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        vectordbs::{IndexDistance, SearchParams, VectorChunk, VectorDBTS},
+        FaissConfig, FaissIndexType,
+    };
+
+    use super::{CreateIndexParams, FaissDb};
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_search_basic() {
+        let dir = std::env::temp_dir().join(format!("indexify-faiss-{}", std::process::id()));
+        let faiss_db: VectorDBTS = Arc::new(
+            FaissDb::new(FaissConfig {
+                path: dir.to_string_lossy().into(),
+                index_type: FaissIndexType::Ivf,
+                nlist: 2,
+                nprobe: 2,
+                train_size: Some(4),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        faiss_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: "default/hello-index".into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+            })
+            .await
+            .unwrap();
+        let chunks = vec![
+            VectorChunk::new("0".into(), "test".into(), vec![0., 2.]),
+            VectorChunk::new("1".into(), "other".into(), vec![2., 0.]),
+        ];
+        faiss_db
+            .add_embedding("default/hello-index", chunks.clone())
+            .await
+            .unwrap();
+        faiss_db
+            .add_embedding("default/hello-index", chunks)
+            .await
+            .unwrap();
+        assert_eq!(
+            faiss_db.num_vectors("default/hello-index").await.unwrap(),
+            2
+        );
+
+        // The index isn't trained yet, so it's searched exhaustively
+        let results = faiss_db
+            .search(SearchParams::new(
+                "default/hello-index".into(),
+                vec![1., 8.],
+                1,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "0");
+
+        let chunks = vec![
+            VectorChunk::new("2".into(), "more".into(), vec![1., 1.]),
+            VectorChunk::new("3".into(), "last".into(), vec![-1., 1.]),
+        ];
+        faiss_db
+            .add_embedding("default/hello-index", chunks)
+            .await
+            .unwrap();
+        let results = faiss_db
+            .search(SearchParams::new(
+                "default/hello-index".into(),
+                vec![1., 8.],
+                2,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].chunk_id, "0");
+
+        faiss_db
+            .remove_embeddings("default/hello-index", vec!["0".into()])
+            .await
+            .unwrap();
+        assert_eq!(
+            faiss_db.num_vectors("default/hello-index").await.unwrap(),
+            3
+        );
+        let results = faiss_db
+            .search(SearchParams::new(
+                "default/hello-index".into(),
+                vec![1., 8.],
+                1,
+            ))
+            .await
+            .unwrap();
+        assert_ne!(results[0].chunk_id, "0");
+        faiss_db.flush().await.unwrap();
+        faiss_db
+            .drop_index("default/hello-index".into())
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,609 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{hash_map::Entry, BinaryHeap, HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        Arc, RwLock, Weak,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use super::{CreateIndexParams, VectorDb, VectorDbError};
+use crate::{
+    vectordbs::{IndexDistance, SearchParams, SearchResult, VectorChunk},
+    HnswConfig,
+};
+
+/// A node of a graph along with its distance to the vector being searched
+/// for, ordered by the distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Node {
+    chunk_id: String,
+    text: String,
+    vector: Vec<f32>,
+    /// Neighbors of the node on each of the layers it's on, from the bottom.
+    neighbors: Vec<Vec<usize>>,
+    /// Removed nodes stay in the graph, so that searches can still go
+    /// through them, until the graph is rebuilt.
+    removed: bool,
+}
+
+/// A hierarchical navigable small world graph of the vectors of an index, as
+/// described in https://arxiv.org/abs/1603.09320.
+#[derive(Serialize, Deserialize)]
+struct Graph {
+    distance: IndexDistance,
+    vector_dim: usize,
+    m: usize,
+    ef_construction: usize,
+    nodes: Vec<Node>,
+    /// Node of each chunk which hasn't been removed.
+    chunks: HashMap<String, usize>,
+    entry: Option<usize>,
+}
+
+impl Graph {
+    fn new(distance: IndexDistance, vector_dim: usize, config: &HnswConfig) -> Self {
+        Self {
+            distance,
+            vector_dim,
+            m: config.m.max(2),
+            ef_construction: config.ef_construction.max(1),
+            nodes: Vec::new(),
+            chunks: HashMap::new(),
+            entry: None,
+        }
+    }
+
+    /// Distance between two vectors, which is smaller the nearer they are.
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        let dot = || a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+        match self.distance {
+            IndexDistance::Cosine => {
+                let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let norms = norm(a) * norm(b);
+                if norms == 0. {
+                    1.
+                } else {
+                    1. - dot() / norms
+                }
+            }
+            IndexDistance::Dot => -dot(),
+            IndexDistance::Euclidean => a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum::<f32>()
+                .sqrt(),
+        }
+    }
+
+    /// Score of a result at a distance, which is the similarity for cosine
+    /// and dot, and the distance for euclidean, as in the other stores.
+    fn score(&self, distance: f32) -> f32 {
+        match self.distance {
+            IndexDistance::Cosine => 1. - distance,
+            IndexDistance::Dot => -distance,
+            IndexDistance::Euclidean => distance,
+        }
+    }
+
+    fn max_neighbors(&self, layer: usize) -> usize {
+        if layer == 0 {
+            2 * self.m
+        } else {
+            self.m
+        }
+    }
+
+    /// Top layer of a new node, with the number of nodes shrinking
+    /// exponentially from each layer to the next.
+    fn random_level(&self) -> usize {
+        let uniform = 1. - rand::random::<f64>();
+        (-uniform.ln() / (self.m as f64).ln()) as usize
+    }
+
+    /// The `ef` nearest nodes to a vector on a layer found by walking the
+    /// graph from the entries, nearest first.
+    fn search_layer(
+        &self,
+        vector: &[f32],
+        entries: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entries.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut nearest = BinaryHeap::new();
+        for &node in entries {
+            let candidate = Candidate {
+                distance: self.distance(vector, &self.nodes[node].vector),
+                node,
+            };
+            candidates.push(Reverse(candidate));
+            nearest.push(candidate);
+        }
+        while let Some(Reverse(candidate)) = candidates.pop() {
+            let furthest = nearest.peek().map(|c: &Candidate| c.distance);
+            if nearest.len() >= ef && furthest.is_some_and(|d| candidate.distance > d) {
+                break;
+            }
+            for &neighbor in &self.nodes[candidate.node].neighbors[layer] {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let distance = self.distance(vector, &self.nodes[neighbor].vector);
+                let furthest = nearest.peek().map(|c: &Candidate| c.distance);
+                if nearest.len() < ef || furthest.is_some_and(|d| distance < d) {
+                    let neighbor = Candidate {
+                        distance,
+                        node: neighbor,
+                    };
+                    candidates.push(Reverse(neighbor));
+                    nearest.push(neighbor);
+                    if nearest.len() > ef {
+                        nearest.pop();
+                    }
+                }
+            }
+        }
+        nearest.into_sorted_vec()
+    }
+
+    /// Nearest node to a vector on the layer below `layer` found by
+    /// descending the layers above it greedily.
+    fn descend(&self, vector: &[f32], entry: usize, layer: usize) -> usize {
+        let top = self.nodes[entry].neighbors.len() - 1;
+        let mut nearest = entry;
+        for layer in (layer..=top).rev() {
+            nearest = self.search_layer(vector, &[nearest], 1, layer)[0].node;
+        }
+        nearest
+    }
+
+    /// Adds a chunk, in place of the chunk with the same id if it was added
+    /// before.
+    fn insert(&mut self, chunk: VectorChunk) {
+        let replaced = self.chunks.remove(&chunk.chunk_id);
+        if let Some(old) = replaced {
+            self.nodes[old].removed = true;
+        }
+        self.add_node(chunk);
+        if replaced.is_some() {
+            self.rebuild_if_sparse();
+        }
+    }
+
+    fn add_node(&mut self, chunk: VectorChunk) {
+        let level = self.random_level();
+        let id = self.nodes.len();
+        let vector = chunk.embeddings;
+        self.nodes.push(Node {
+            chunk_id: chunk.chunk_id.clone(),
+            text: chunk.text,
+            vector: vector.clone(),
+            neighbors: vec![Vec::new(); level + 1],
+            removed: false,
+        });
+        self.chunks.insert(chunk.chunk_id, id);
+        let Some(entry) = self.entry else {
+            self.entry = Some(id);
+            return;
+        };
+        let top = self.nodes[entry].neighbors.len() - 1;
+        let mut entries = vec![self.descend(&vector, entry, level + 1)];
+        for layer in (0..=level.min(top)).rev() {
+            let nearest = self.search_layer(&vector, &entries, self.ef_construction, layer);
+            let neighbors: Vec<usize> = nearest.iter().take(self.m).map(|c| c.node).collect();
+            for &neighbor in &neighbors {
+                self.nodes[neighbor].neighbors[layer].push(id);
+                if self.nodes[neighbor].neighbors[layer].len() > self.max_neighbors(layer) {
+                    self.prune(neighbor, layer);
+                }
+            }
+            self.nodes[id].neighbors[layer] = neighbors;
+            entries = nearest.iter().map(|c| c.node).collect();
+        }
+        if level > top {
+            self.entry = Some(id);
+        }
+    }
+
+    /// Keeps the nearest neighbors of a node which has too many.
+    fn prune(&mut self, node: usize, layer: usize) {
+        let vector = &self.nodes[node].vector;
+        let mut neighbors: Vec<Candidate> = self.nodes[node].neighbors[layer]
+            .iter()
+            .map(|&neighbor| Candidate {
+                distance: self.distance(vector, &self.nodes[neighbor].vector),
+                node: neighbor,
+            })
+            .collect();
+        neighbors.sort();
+        neighbors.truncate(self.max_neighbors(layer));
+        self.nodes[node].neighbors[layer] = neighbors.into_iter().map(|c| c.node).collect();
+    }
+
+    fn search(&self, vector: &[f32], k: usize, ef_search: usize) -> Vec<SearchResult> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        let nearest = self.descend(vector, entry, 1);
+        self.search_layer(vector, &[nearest], ef_search.max(k), 0)
+            .into_iter()
+            .filter(|c| !self.nodes[c.node].removed)
+            .take(k)
+            .map(|c| SearchResult {
+                text: self.nodes[c.node].text.clone(),
+                chunk_id: self.nodes[c.node].chunk_id.clone(),
+                confidence_score: self.score(c.distance),
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, chunk_ids: &[String]) {
+        for chunk_id in chunk_ids {
+            if let Some(node) = self.chunks.remove(chunk_id) {
+                self.nodes[node].removed = true;
+            }
+        }
+        self.rebuild_if_sparse();
+    }
+
+    /// Removed and replaced nodes crowd the live ones out of searches, and
+    /// take up memory, so the graph is rebuilt from the live ones once they
+    /// outnumber them.
+    fn rebuild_if_sparse(&mut self) {
+        if self.nodes.len() - self.chunks.len() > self.chunks.len() {
+            let nodes = std::mem::take(&mut self.nodes);
+            self.chunks.clear();
+            self.entry = None;
+            for node in nodes.into_iter().filter(|node| !node.removed) {
+                self.add_node(VectorChunk::new(node.chunk_id, node.text, node.vector));
+            }
+        }
+    }
+}
+
+struct Indexes {
+    graphs: RwLock<HashMap<String, Graph>>,
+    /// Whether the indexes have changed since they were last saved.
+    changed: AtomicBool,
+    path: Option<PathBuf>,
+}
+
+impl Indexes {
+    /// Saves the indexes to their file if they've changed, replacing the
+    /// file at once so that a crash doesn't leave half of it behind.
+    fn persist(&self) -> Result<(), VectorDbError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.changed.swap(false, AtomicOrdering::SeqCst) {
+            return Ok(());
+        }
+        let result = (|| {
+            let graphs = self.graphs.read().unwrap();
+            let bytes = serde_json::to_vec(&*graphs).map_err(|e| e.to_string())?;
+            drop(graphs);
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+            fs::rename(&tmp, path).map_err(|e| e.to_string())
+        })();
+        result.map_err(|e| {
+            self.changed.store(true, AtomicOrdering::SeqCst);
+            VectorDbError::IndexWriteError(format!(
+                "unable to save indexes to `{}`: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+}
+
+/// Keeps the vectors of each index in memory, searched through an HNSW graph,
+/// so that the server can run without a vector database, e.g. in development
+/// and tests. The indexes are saved to a file every `persist_interval_secs`
+/// and when the server shuts down, if the config has a `path`, and loaded
+/// from it when the server starts.
+pub struct HnswDb {
+    config: HnswConfig,
+    indexes: Arc<Indexes>,
+}
+
+impl HnswDb {
+    pub fn new(config: HnswConfig) -> Result<HnswDb, VectorDbError> {
+        let path = config.path.as_ref().map(PathBuf::from);
+        let graphs = match &path {
+            Some(path) if path.exists() => {
+                let bytes = fs::read(path).map_err(|e| {
+                    VectorDbError::InternalError(format!(
+                        "unable to read indexes from `{}`: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                serde_json::from_slice(&bytes).map_err(|e| {
+                    VectorDbError::InternalError(format!(
+                        "unable to load indexes from `{}`: {}",
+                        path.display(),
+                        e
+                    ))
+                })?
+            }
+            _ => HashMap::new(),
+        };
+        let indexes = Arc::new(Indexes {
+            graphs: RwLock::new(graphs),
+            changed: AtomicBool::new(false),
+            path,
+        });
+        if indexes.path.is_some() {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let interval = Duration::from_secs(config.persist_interval_secs.max(1));
+                runtime.spawn(persist_periodically(Arc::downgrade(&indexes), interval));
+            }
+        }
+        Ok(Self { config, indexes })
+    }
+}
+
+async fn persist_periodically(indexes: Weak<Indexes>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(indexes) = indexes.upgrade() else {
+            return;
+        };
+        let result = tokio::task::spawn_blocking(move || indexes.persist()).await;
+        if let Ok(Err(err)) = result {
+            error!("{}", err);
+        }
+    }
+}
+
+#[async_trait]
+impl VectorDb for HnswDb {
+    fn name(&self) -> String {
+        "hnsw".into()
+    }
+
+    async fn create_index(&self, index: CreateIndexParams) -> Result<(), VectorDbError> {
+        let mut graphs = self.indexes.graphs.write().unwrap();
+        if let Entry::Vacant(entry) = graphs.entry(index.vectordb_index_name) {
+            entry.insert(Graph::new(
+                index.distance,
+                index.vector_dim as usize,
+                &self.config,
+            ));
+            self.indexes.changed.store(true, AtomicOrdering::SeqCst);
+        }
+        Ok(())
+    }
+
+    async fn add_embedding(
+        &self,
+        index: &str,
+        chunks: Vec<VectorChunk>,
+    ) -> Result<(), VectorDbError> {
+        let mut graphs = self.indexes.graphs.write().unwrap();
+        let graph = graphs
+            .get_mut(index)
+            .ok_or(VectorDbError::IndexWriteError("index not found".into()))?;
+        if let Some(chunk) = chunks
+            .iter()
+            .find(|chunk| chunk.embeddings.len() != graph.vector_dim)
+        {
+            return Err(VectorDbError::IndexWriteError(format!(
+                "embedding of chunk `{}` has {} dimensions, the index has {}",
+                chunk.chunk_id,
+                chunk.embeddings.len(),
+                graph.vector_dim
+            )));
+        }
+        for chunk in chunks {
+            graph.insert(chunk);
+        }
+        self.indexes.changed.store(true, AtomicOrdering::SeqCst);
+        Ok(())
+    }
+
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError> {
+        let SearchParams {
+            vectordb_index_name: index,
+            query_embedding,
+            k,
+            ..
+        } = params;
+        let graphs = self.indexes.graphs.read().unwrap();
+        let graph = graphs
+            .get(&index)
+            .ok_or(VectorDbError::IndexReadError("index not found".into()))?;
+        Ok(graph.search(&query_embedding, k as usize, self.config.ef_search))
+    }
+
+    async fn remove_embeddings(
+        &self,
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError> {
+        let mut graphs = self.indexes.graphs.write().unwrap();
+        if let Some(graph) = graphs.get_mut(index) {
+            graph.remove(&chunk_ids);
+            self.indexes.changed.store(true, AtomicOrdering::SeqCst);
+        }
+        Ok(())
+    }
+
+    async fn drop_index(&self, index: String) -> Result<(), VectorDbError> {
+        if self
+            .indexes
+            .graphs
+            .write()
+            .unwrap()
+            .remove(&index)
+            .is_some()
+        {
+            self.indexes.changed.store(true, AtomicOrdering::SeqCst);
+        }
+        Ok(())
+    }
+
+    async fn num_vectors(&self, index: &str) -> Result<u64, VectorDbError> {
+        let graphs = self.indexes.graphs.read().unwrap();
+        let graph = graphs
+            .get(index)
+            .ok_or(VectorDbError::IndexReadError("index not found".into()))?;
+        Ok(graph.chunks.len() as u64)
+    }
+
+    async fn health(&self) -> Result<(), VectorDbError> {
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), VectorDbError> {
+        let indexes = self.indexes.clone();
+        tokio::task::spawn_blocking(move || indexes.persist())
+            .await
+            .map_err(|e| VectorDbError::InternalError(e.to_string()))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vectordbs::{IndexDistance, SearchParams, VectorChunk, VectorDb};
+
+    use super::{CreateIndexParams, Graph, HnswDb};
+
+    fn random_vector(dim: usize) -> Vec<f32> {
+        (0..dim).map(|_| rand::random::<f32>() - 0.5).collect()
+    }
+
+    #[test]
+    fn search_recall() {
+        let config = crate::HnswConfig::default();
+        let mut graph = Graph::new(IndexDistance::Cosine, 16, &config);
+        let vectors: Vec<Vec<f32>> = (0..1000).map(|_| random_vector(16)).collect();
+        for (i, vector) in vectors.iter().enumerate() {
+            graph.insert(VectorChunk::new(i.to_string(), "".into(), vector.clone()));
+        }
+
+        let mut found = 0;
+        for _ in 0..20 {
+            let query = random_vector(16);
+            let mut exact: Vec<(f32, usize)> = vectors
+                .iter()
+                .enumerate()
+                .map(|(i, vector)| (graph.distance(&query, vector), i))
+                .collect();
+            exact.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let results = graph.search(&query, 10, config.ef_search);
+            assert_eq!(results.len(), 10);
+            found += exact[..10]
+                .iter()
+                .filter(|(_, i)| results.iter().any(|r| r.chunk_id == i.to_string()))
+                .count();
+        }
+        assert!(found >= 180, "recall of {} out of 200", found);
+    }
+
+    #[test]
+    fn repeated_upserts() {
+        let config = crate::HnswConfig::default();
+        let mut graph = Graph::new(IndexDistance::Cosine, 4, &config);
+        graph.insert(VectorChunk::new(
+            "other".into(),
+            "".into(),
+            random_vector(4),
+        ));
+        for _ in 0..100 {
+            graph.insert(VectorChunk::new("0".into(), "".into(), random_vector(4)));
+        }
+        // Replaced nodes are dropped rather than piling up
+        assert!(graph.nodes.len() <= 2 * graph.chunks.len() + 1);
+        assert_eq!(graph.chunks.len(), 2);
+        let results = graph.search(&random_vector(4), 2, config.ef_search);
+        assert_eq!(results.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn upserts_removals_and_persistence() {
+        let path = std::env::temp_dir().join(format!("indexify-hnsw-{}.json", std::process::id()));
+        let config = crate::HnswConfig {
+            path: Some(path.to_string_lossy().into()),
+            ..Default::default()
+        };
+        let hnsw = HnswDb::new(config.clone()).unwrap();
+        hnsw.create_index(CreateIndexParams {
+            vectordb_index_name: "hello-index".into(),
+            vector_dim: 2,
+            distance: IndexDistance::Cosine,
+            unique_params: None,
+        })
+        .await
+        .unwrap();
+        let chunk = VectorChunk::new("0".into(), "test".into(), vec![0., 2.]);
+        hnsw.add_embedding("hello-index", vec![chunk.clone()])
+            .await
+            .unwrap();
+        hnsw.add_embedding("hello-index", vec![chunk])
+            .await
+            .unwrap();
+        hnsw.add_embedding(
+            "hello-index",
+            vec![VectorChunk::new("1".into(), "other".into(), vec![2., 0.])],
+        )
+        .await
+        .unwrap();
+        assert_eq!(hnsw.num_vectors("hello-index").await.unwrap(), 2);
+        assert!(hnsw
+            .add_embedding(
+                "hello-index",
+                vec![VectorChunk::new("2".into(), "".into(), vec![1.])]
+            )
+            .await
+            .is_err());
+
+        hnsw.remove_embeddings("hello-index", vec!["1".into()])
+            .await
+            .unwrap();
+        hnsw.flush().await.unwrap();
+
+        let hnsw = HnswDb::new(config).unwrap();
+        assert_eq!(hnsw.num_vectors("hello-index").await.unwrap(), 1);
+        let results = hnsw
+            .search(SearchParams::new("hello-index".into(), vec![1., 8.], 2))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "0");
+        let _ = std::fs::remove_file(path);
+    }
+}
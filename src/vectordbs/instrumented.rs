@@ -0,0 +1,107 @@
+use std::{future::Future, time::Instant};
+
+use async_trait::async_trait;
+use metrics::{counter, histogram};
+use tracing::{info_span, Instrument};
+
+use super::{
+    CreateIndexParams, SearchParams, SearchResult, VectorChunk, VectorDBTS, VectorDb, VectorDbError,
+};
+
+/// Records how long each operation of a vector database takes, and how many
+/// of them fail, labelled by the database and the operation. Each operation
+/// runs in a span of its own.
+pub struct InstrumentedVectorDb {
+    vector_db: VectorDBTS,
+    name: String,
+}
+
+impl InstrumentedVectorDb {
+    pub fn new(vector_db: VectorDBTS) -> Self {
+        let name = vector_db.name();
+        Self { vector_db, name }
+    }
+
+    async fn record<T>(
+        &self,
+        operation: &'static str,
+        call: impl Future<Output = Result<T, VectorDbError>>,
+    ) -> Result<T, VectorDbError> {
+        let started = Instant::now();
+        let span = info_span!("vectordb", otel.kind = "client", store = %self.name, operation);
+        let result = call.instrument(span).await;
+        let labels = [
+            ("store", self.name.clone()),
+            ("operation", operation.into()),
+        ];
+        histogram!("indexify_vectordb_operation_seconds", &labels)
+            .record(started.elapsed().as_secs_f64());
+        if result.is_err() {
+            counter!("indexify_vectordb_operation_errors_total", &labels).increment(1);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl VectorDb for InstrumentedVectorDb {
+    async fn create_index(&self, index: CreateIndexParams) -> Result<(), VectorDbError> {
+        self.record("create_index", self.vector_db.create_index(index))
+            .await
+    }
+
+    async fn add_embedding(
+        &self,
+        index: &str,
+        chunks: Vec<VectorChunk>,
+    ) -> Result<(), VectorDbError> {
+        self.record("add_embedding", self.vector_db.add_embedding(index, chunks))
+            .await
+    }
+
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError> {
+        self.record("search", self.vector_db.search(params)).await
+    }
+
+    fn supports_filters(&self) -> bool {
+        self.vector_db.supports_filters()
+    }
+
+    fn supports_text_query(&self) -> bool {
+        self.vector_db.supports_text_query()
+    }
+
+    async fn remove_embeddings(
+        &self,
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError> {
+        self.record(
+            "remove_embeddings",
+            self.vector_db.remove_embeddings(index, chunk_ids),
+        )
+        .await
+    }
+
+    async fn drop_index(&self, index: String) -> Result<(), VectorDbError> {
+        self.record("drop_index", self.vector_db.drop_index(index))
+            .await
+    }
+
+    async fn num_vectors(&self, index: &str) -> Result<u64, VectorDbError> {
+        self.record("num_vectors", self.vector_db.num_vectors(index))
+            .await
+    }
+
+    async fn health(&self) -> Result<(), VectorDbError> {
+        self.record("health", self.vector_db.health()).await
+    }
+
+    async fn flush(&self) -> Result<(), VectorDbError> {
+        self.record("flush", self.vector_db.flush()).await
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
@@ -0,0 +1,340 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use lancedb::{
+    arrow::{
+        arrow_array::{
+            types::Float32Type, Array, FixedSizeListArray, Float32Array, RecordBatch,
+            RecordBatchIterator, StringArray,
+        },
+        arrow_schema::{DataType, Field, Schema},
+    },
+    query::{ExecutableQuery, QueryBase, Select},
+    Connection, DistanceType, Table,
+};
+use tokio::sync::OnceCell;
+
+use super::{escaped_index_name, CreateIndexParams, VectorDb, VectorDbError};
+use crate::{
+    vectordbs::{IndexDistance, SearchParams, SearchResult, VectorChunk},
+    LanceDbConfig,
+};
+
+/// Key of the table's schema metadata which keeps the distance the index is
+/// searched by.
+const DISTANCE_KEY: &str = "indexify.distance";
+
+const DISTANCE_COLUMN: &str = "_distance";
+
+/// Stores the vectors of each index in a LanceDB table of its own, in files
+/// under a local directory, so that the server doesn't need a database to
+/// run. Tables are searched exhaustively, which is fast enough for the sizes
+/// of indexes such deployments have.
+pub struct LanceDb {
+    config: LanceDbConfig,
+    conn: OnceCell<Connection>,
+}
+
+impl LanceDb {
+    pub fn new(config: LanceDbConfig) -> LanceDb {
+        Self {
+            config,
+            conn: OnceCell::new(),
+        }
+    }
+
+    async fn conn(&self) -> Result<&Connection, VectorDbError> {
+        self.conn
+            .get_or_try_init(|| lancedb::connect(&self.config.path).execute())
+            .await
+            .map_err(|e| {
+                VectorDbError::InternalError(format!("unable to open lancedb database: {}", e))
+            })
+    }
+
+    async fn table(&self, index: &str) -> Result<Table, lancedb::Error> {
+        let conn = self.conn().await.map_err(|e| lancedb::Error::Runtime {
+            message: e.to_string(),
+        })?;
+        conn.open_table(escaped_index_name(index)).execute().await
+    }
+
+    fn schema(vector_dim: i32, metadata: HashMap<String, String>) -> Arc<Schema> {
+        Arc::new(
+            Schema::new(vec![
+                Field::new("chunk_id", DataType::Utf8, false),
+                Field::new("text", DataType::Utf8, false),
+                Field::new(
+                    "embedding",
+                    DataType::FixedSizeList(
+                        Arc::new(Field::new("item", DataType::Float32, true)),
+                        vector_dim,
+                    ),
+                    false,
+                ),
+            ])
+            .with_metadata(metadata),
+        )
+    }
+
+    fn to_distance(distance: &IndexDistance) -> DistanceType {
+        match distance {
+            IndexDistance::Cosine => DistanceType::Cosine,
+            IndexDistance::Dot => DistanceType::Dot,
+            IndexDistance::Euclidean => DistanceType::L2,
+        }
+    }
+
+    /// Score of a result at a distance, so that nearer results score higher
+    /// for cosine and dot, as they do in the other stores.
+    fn score(distance: &IndexDistance, d: f32) -> f32 {
+        match distance {
+            IndexDistance::Cosine => 1. - d,
+            IndexDistance::Dot => 1. - d,
+            IndexDistance::Euclidean => d,
+        }
+    }
+}
+
+fn string_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a StringArray, VectorDbError> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+        .ok_or_else(|| VectorDbError::IndexReadError(format!("missing column `{}`", name)))
+}
+
+#[async_trait]
+impl VectorDb for LanceDb {
+    fn name(&self) -> String {
+        "lancedb".into()
+    }
+
+    async fn create_index(&self, index: CreateIndexParams) -> Result<(), VectorDbError> {
+        let metadata = HashMap::from([(DISTANCE_KEY.to_string(), index.distance.to_string())]);
+        let schema = Self::schema(index.vector_dim as i32, metadata);
+        let result = self
+            .conn()
+            .await?
+            .create_empty_table(escaped_index_name(&index.vectordb_index_name), schema)
+            .execute()
+            .await;
+        match result {
+            Ok(_) | Err(lancedb::Error::TableAlreadyExists { .. }) => Ok(()),
+            Err(e) => Err(VectorDbError::IndexCreationError(e.to_string())),
+        }
+    }
+
+    async fn add_embedding(
+        &self,
+        index: &str,
+        chunks: Vec<VectorChunk>,
+    ) -> Result<(), VectorDbError> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let write = |e: lancedb::Error| VectorDbError::IndexWriteError(e.to_string());
+        let table = self.table(index).await.map_err(write)?;
+        let schema = table.schema().await.map_err(write)?;
+        let vector_dim = chunks[0].embeddings.len() as i32;
+        let chunk_ids = StringArray::from_iter_values(chunks.iter().map(|c| c.chunk_id.as_str()));
+        let texts = StringArray::from_iter_values(chunks.iter().map(|c| c.text.as_str()));
+        let embeddings = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            chunks
+                .iter()
+                .map(|c| Some(c.embeddings.iter().copied().map(Some))),
+            vector_dim,
+        );
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(chunk_ids), Arc::new(texts), Arc::new(embeddings)],
+        )
+        .map_err(|e| VectorDbError::IndexWriteError(e.to_string()))?;
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut merge_insert = table.merge_insert(&["chunk_id"]);
+        merge_insert
+            .when_matched_update_all(None)
+            .when_not_matched_insert_all();
+        merge_insert
+            .execute(Box::new(batches))
+            .await
+            .map_err(write)?;
+        Ok(())
+    }
+
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError> {
+        let SearchParams {
+            vectordb_index_name: index,
+            query_embedding,
+            k,
+            ..
+        } = params;
+        let read = |e: lancedb::Error| VectorDbError::IndexReadError(e.to_string());
+        let table = self.table(&index).await.map_err(read)?;
+        let schema = table.schema().await.map_err(read)?;
+        let distance: IndexDistance = schema
+            .metadata()
+            .get(DISTANCE_KEY)
+            .and_then(|distance| distance.parse().ok())
+            .unwrap_or(IndexDistance::Cosine);
+        let batches: Vec<RecordBatch> = table
+            .query()
+            .nearest_to(query_embedding)
+            .map_err(read)?
+            .distance_type(Self::to_distance(&distance))
+            .select(Select::columns(&["chunk_id", "text"]))
+            .limit(k as usize)
+            .execute()
+            .await
+            .map_err(read)?
+            .try_collect()
+            .await
+            .map_err(read)?;
+        let mut results = Vec::new();
+        for batch in batches {
+            let chunk_ids = string_column(&batch, "chunk_id")?;
+            let texts = string_column(&batch, "text")?;
+            let distances = batch
+                .column_by_name(DISTANCE_COLUMN)
+                .and_then(|column| column.as_any().downcast_ref::<Float32Array>())
+                .ok_or(VectorDbError::IndexReadError("missing distances".into()))?;
+            for i in 0..batch.num_rows() {
+                results.push(SearchResult {
+                    text: texts.value(i).to_string(),
+                    chunk_id: chunk_ids.value(i).to_string(),
+                    confidence_score: Self::score(&distance, distances.value(i)),
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    async fn remove_embeddings(
+        &self,
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError> {
+        if chunk_ids.is_empty() {
+            return Ok(());
+        }
+        let write = |e: lancedb::Error| VectorDbError::IndexWriteError(e.to_string());
+        let ids: Vec<String> = chunk_ids
+            .iter()
+            .map(|id| format!("'{}'", id.replace('\'', "''")))
+            .collect();
+        self.table(index)
+            .await
+            .map_err(write)?
+            .delete(format!("chunk_id IN ({})", ids.join(", ")).as_str())
+            .await
+            .map_err(write)?;
+        Ok(())
+    }
+
+    async fn drop_index(&self, index: String) -> Result<(), VectorDbError> {
+        let result = self
+            .conn()
+            .await?
+            .drop_table(escaped_index_name(&index), &[])
+            .await;
+        match result {
+            Ok(()) | Err(lancedb::Error::TableNotFound { .. }) => Ok(()),
+            Err(e) => Err(VectorDbError::IndexDeletionError(index, e.to_string())),
+        }
+    }
+
+    async fn num_vectors(&self, index: &str) -> Result<u64, VectorDbError> {
+        let read = |e: lancedb::Error| VectorDbError::IndexReadError(e.to_string());
+        let count = self
+            .table(index)
+            .await
+            .map_err(read)?
+            .count_rows(None)
+            .await
+            .map_err(read)?;
+        Ok(count as u64)
+    }
+
+    async fn health(&self) -> Result<(), VectorDbError> {
+        self.conn()
+            .await?
+            .table_names()
+            .execute()
+            .await
+            .map_err(|e| VectorDbError::InternalError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), VectorDbError> {
+        // Every write is committed to the table's files before it returns
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::vectordbs::{IndexDistance, SearchParams, VectorChunk, VectorDBTS};
+
+    use super::{CreateIndexParams, LanceDb};
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_search_basic() {
+        let dir = std::env::temp_dir().join(format!("indexify-lancedb-{}", std::process::id()));
+        let lance_db: VectorDBTS = Arc::new(LanceDb::new(crate::LanceDbConfig {
+            path: dir.to_string_lossy().into(),
+        }));
+        lance_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: "default/hello-index".into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+            })
+            .await
+            .unwrap();
+        let chunks = vec![
+            VectorChunk::new("0".into(), "test".into(), vec![0., 2.]),
+            VectorChunk::new("1".into(), "other".into(), vec![2., 0.]),
+        ];
+        lance_db
+            .add_embedding("default/hello-index", chunks.clone())
+            .await
+            .unwrap();
+        lance_db
+            .add_embedding("default/hello-index", chunks)
+            .await
+            .unwrap();
+        assert_eq!(
+            lance_db.num_vectors("default/hello-index").await.unwrap(),
+            2
+        );
+
+        let results = lance_db
+            .search(SearchParams::new(
+                "default/hello-index".into(),
+                vec![1., 8.],
+                1,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "0");
+
+        lance_db
+            .remove_embeddings("default/hello-index", vec!["0".into()])
+            .await
+            .unwrap();
+        assert_eq!(
+            lance_db.num_vectors("default/hello-index").await.unwrap(),
+            1
+        );
+        lance_db
+            .drop_index("default/hello-index".into())
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,507 @@
+use async_trait::async_trait;
+use dashmap::DashMap;
+use serde_json::{json, Value};
+
+use super::{CreateIndexParams, VectorDb, VectorDbError};
+use crate::{
+    persistence::ExtractorFilter,
+    vectordbs::{IndexDistance, SearchParams, SearchResult, VectorChunk},
+    MilvusConfig,
+};
+
+/// Rows written in each upsert, which keeps the requests well under Milvus's
+/// limit on their size.
+const UPSERT_BATCH_SIZE: usize = 1000;
+
+/// Longest text Milvus keeps in a `VarChar` field.
+const MAX_TEXT_LENGTH: usize = 65535;
+
+/// Stores the vectors of each index in a Milvus collection of its own, or in
+/// a partition of its own of the config's `collection`, through Milvus's
+/// RESTful API. Rows are looked up by their `chunk_id` scalar field, and the
+/// metadata of their documents is kept in a `metadata` JSON field.
+pub struct MilvusDb {
+    config: MilvusConfig,
+    client: reqwest::Client,
+    distances: DashMap<String, IndexDistance>,
+}
+
+impl MilvusDb {
+    pub fn new(config: MilvusConfig) -> MilvusDb {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            distances: DashMap::new(),
+        }
+    }
+
+    /// Calls an endpoint of the API, returning the `data` of its response.
+    /// Milvus answers errors with a non-zero `code` rather than an HTTP
+    /// status.
+    async fn call(&self, path: &str, mut body: Value) -> Result<Value, String> {
+        if let Some(db_name) = &self.config.db_name {
+            body["dbName"] = json!(db_name);
+        }
+        let mut request = self
+            .client
+            .post(format!(
+                "{}/v2/vectordb/{}",
+                self.config.addr.trim_end_matches('/'),
+                path
+            ))
+            .json(&body);
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+        let response: Value = request
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+        match response["code"].as_i64() {
+            Some(0) => Ok(response["data"].clone()),
+            _ => Err(format!(
+                "milvus returned `{}`: {}",
+                response["code"], response["message"]
+            )),
+        }
+    }
+
+    /// Collection and partition the vectors of an index are kept in.
+    fn location(&self, index: &str) -> (String, Option<String>) {
+        match &self.config.collection {
+            Some(collection) => (collection.clone(), Some(milvus_name(index))),
+            None => (milvus_name(index), None),
+        }
+    }
+
+    /// Body of a request to the index's collection, limited to its partition
+    /// if it has one.
+    fn request(&self, index: &str, mut body: Value) -> Value {
+        let (collection, partition) = self.location(index);
+        body["collectionName"] = json!(collection);
+        if let Some(partition) = partition {
+            body["partitionNames"] = json!([partition]);
+        }
+        body
+    }
+
+    fn metric_type(distance: &IndexDistance) -> &'static str {
+        match distance {
+            IndexDistance::Cosine => "COSINE",
+            IndexDistance::Dot => "IP",
+            IndexDistance::Euclidean => "L2",
+        }
+    }
+
+    /// Score of a result at a distance, which is the similarity for cosine
+    /// and dot, and Milvus's squared distance's root for euclidean, as in the
+    /// other stores.
+    fn score(distance: &IndexDistance, d: f32) -> f32 {
+        match distance {
+            IndexDistance::Cosine | IndexDistance::Dot => d,
+            IndexDistance::Euclidean => d.sqrt(),
+        }
+    }
+
+    async fn distance(&self, index: &str) -> Result<IndexDistance, String> {
+        if let Some(distance) = self.distances.get(index) {
+            return Ok(distance.clone());
+        }
+        let (collection, _) = self.location(index);
+        let data = self
+            .call(
+                "collections/describe",
+                json!({ "collectionName": collection }),
+            )
+            .await?;
+        let distance = match data["indexes"][0]["metricType"].as_str() {
+            Some("COSINE") => IndexDistance::Cosine,
+            Some("IP") => IndexDistance::Dot,
+            Some("L2") => IndexDistance::Euclidean,
+            metric => return Err(format!("unsupported metric type `{:?}`", metric)),
+        };
+        self.distances.insert(index.into(), distance.clone());
+        Ok(distance)
+    }
+
+    async fn create_collection(
+        &self,
+        collection: &str,
+        index: &CreateIndexParams,
+    ) -> Result<(), String> {
+        let data = self
+            .call("collections/has", json!({ "collectionName": collection }))
+            .await?;
+        if data["has"].as_bool() == Some(true) {
+            return Ok(());
+        }
+        self.call(
+            "collections/create",
+            json!({
+                "collectionName": collection,
+                "schema": {
+                    "autoId": false,
+                    "enableDynamicField": false,
+                    "fields": [
+                        {
+                            "fieldName": "chunk_id",
+                            "dataType": "VarChar",
+                            "isPrimary": true,
+                            "elementTypeParams": { "max_length": "512" },
+                        },
+                        {
+                            "fieldName": "text",
+                            "dataType": "VarChar",
+                            "elementTypeParams": { "max_length": MAX_TEXT_LENGTH.to_string() },
+                        },
+                        {
+                            "fieldName": "metadata",
+                            "dataType": "JSON",
+                        },
+                        {
+                            "fieldName": "embedding",
+                            "dataType": "FloatVector",
+                            "elementTypeParams": { "dim": index.vector_dim.to_string() },
+                        },
+                    ],
+                },
+                "indexParams": [{
+                    "fieldName": "embedding",
+                    "indexName": "embedding",
+                    "metricType": Self::metric_type(&index.distance),
+                    "params": { "index_type": "AUTOINDEX" },
+                }],
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Name of a collection or partition of an index. Milvus only allows
+/// letters, digits and `_` in names, which can't start with a digit, so other
+/// characters of the index's name are escaped.
+fn milvus_name(index: &str) -> String {
+    let mut name = String::with_capacity(index.len());
+    if index.starts_with(|c: char| c.is_ascii_digit()) {
+        name.push('_');
+    }
+    for c in index.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                name.push_str(&format!("_{:02x}", b));
+            }
+        }
+    }
+    name
+}
+
+/// Milvus's filter expression matching the rows of the given chunks.
+fn chunk_filter(chunk_ids: &[String]) -> String {
+    let ids: Vec<String> = chunk_ids.iter().map(|id| json!(id).to_string()).collect();
+    format!("chunk_id in [{}]", ids.join(", "))
+}
+
+/// Milvus's filter expression matching the rows whose metadata matches every
+/// one of the filters, with the fields and values quoted as in
+/// `chunk_filter`.
+fn metadata_filter(filters: &[ExtractorFilter]) -> String {
+    let conditions: Vec<String> = filters
+        .iter()
+        .map(|filter| {
+            let (field, op, value) = match filter {
+                ExtractorFilter::Eq { field, value } => (field, "==", value),
+                ExtractorFilter::Neq { field, value } => (field, "!=", value),
+            };
+            format!("metadata[{}] {} {}", json!(field), op, value)
+        })
+        .collect();
+    conditions.join(" and ")
+}
+
+#[async_trait]
+impl VectorDb for MilvusDb {
+    fn name(&self) -> String {
+        "milvus".into()
+    }
+
+    async fn create_index(&self, index: CreateIndexParams) -> Result<(), VectorDbError> {
+        let create = |e: String| VectorDbError::IndexCreationError(e);
+        let (collection, partition) = self.location(&index.vectordb_index_name);
+        self.create_collection(&collection, &index)
+            .await
+            .map_err(create)?;
+        if let Some(partition) = partition {
+            let body = json!({ "collectionName": collection, "partitionName": partition });
+            let data = self
+                .call("partitions/has", body.clone())
+                .await
+                .map_err(create)?;
+            if data["has"].as_bool() != Some(true) {
+                self.call("partitions/create", body).await.map_err(create)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_embedding(
+        &self,
+        index: &str,
+        chunks: Vec<VectorChunk>,
+    ) -> Result<(), VectorDbError> {
+        let (collection, partition) = self.location(index);
+        for batch in chunks.chunks(UPSERT_BATCH_SIZE) {
+            let data: Vec<Value> = batch
+                .iter()
+                .map(|chunk| {
+                    json!({
+                        "chunk_id": chunk.chunk_id,
+                        "text": chunk.text,
+                        "metadata": chunk.metadata,
+                        "embedding": chunk.embeddings,
+                    })
+                })
+                .collect();
+            let mut body = json!({ "collectionName": collection, "data": data });
+            if let Some(partition) = &partition {
+                body["partitionName"] = json!(partition);
+            }
+            self.call("entities/upsert", body)
+                .await
+                .map_err(VectorDbError::IndexWriteError)?;
+        }
+        Ok(())
+    }
+
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError> {
+        let SearchParams {
+            vectordb_index_name: index,
+            query_embedding,
+            k,
+            filters,
+            ..
+        } = params;
+        let distance = self
+            .distance(&index)
+            .await
+            .map_err(VectorDbError::IndexReadError)?;
+        let body = self.request(
+            &index,
+            json!({
+                "data": [query_embedding],
+                "annsField": "embedding",
+                "filter": metadata_filter(&filters),
+                "limit": k,
+                "outputFields": ["chunk_id", "text"],
+            }),
+        );
+        let data = self
+            .call("entities/search", body)
+            .await
+            .map_err(VectorDbError::IndexReadError)?;
+        let rows = data
+            .as_array()
+            .ok_or(VectorDbError::IndexReadError("missing results".into()))?;
+        Ok(rows
+            .iter()
+            .map(|row| SearchResult {
+                text: row["text"].as_str().unwrap_or_default().to_string(),
+                chunk_id: row["chunk_id"].as_str().unwrap_or_default().to_string(),
+                confidence_score: Self::score(
+                    &distance,
+                    row["distance"].as_f64().unwrap_or_default() as f32,
+                ),
+            })
+            .collect())
+    }
+
+    fn supports_filters(&self) -> bool {
+        true
+    }
+
+    async fn remove_embeddings(
+        &self,
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError> {
+        if chunk_ids.is_empty() {
+            return Ok(());
+        }
+        let (collection, partition) = self.location(index);
+        let mut body = json!({
+            "collectionName": collection,
+            "filter": chunk_filter(&chunk_ids),
+        });
+        if let Some(partition) = partition {
+            body["partitionName"] = json!(partition);
+        }
+        self.call("entities/delete", body)
+            .await
+            .map_err(VectorDbError::IndexWriteError)?;
+        Ok(())
+    }
+
+    async fn drop_index(&self, index: String) -> Result<(), VectorDbError> {
+        let drop = |e: String| VectorDbError::IndexDeletionError(index.clone(), e);
+        let (collection, partition) = self.location(&index);
+        match partition {
+            Some(partition) => {
+                let data = self
+                    .call(
+                        "partitions/has",
+                        json!({ "collectionName": collection, "partitionName": partition }),
+                    )
+                    .await
+                    .map_err(drop)?;
+                if data["has"].as_bool() == Some(true) {
+                    // Milvus only drops partitions which aren't loaded
+                    self.call(
+                        "partitions/release",
+                        json!({ "collectionName": collection, "partitionNames": [partition] }),
+                    )
+                    .await
+                    .map_err(drop)?;
+                    self.call(
+                        "partitions/drop",
+                        json!({ "collectionName": collection, "partitionName": partition }),
+                    )
+                    .await
+                    .map_err(drop)?;
+                }
+            }
+            None => {
+                self.call("collections/drop", json!({ "collectionName": collection }))
+                    .await
+                    .map_err(drop)?;
+            }
+        }
+        self.distances.remove(&index);
+        Ok(())
+    }
+
+    async fn num_vectors(&self, index: &str) -> Result<u64, VectorDbError> {
+        let body = self.request(
+            index,
+            // Counts are read after the writes before them, as other stores do
+            json!({
+                "filter": "",
+                "outputFields": ["count(*)"],
+                "consistencyLevel": "Strong",
+            }),
+        );
+        let data = self
+            .call("entities/query", body)
+            .await
+            .map_err(VectorDbError::IndexReadError)?;
+        data[0]["count(*)"]
+            .as_u64()
+            .ok_or(VectorDbError::IndexReadError("missing count".into()))
+    }
+
+    async fn health(&self) -> Result<(), VectorDbError> {
+        self.call("collections/list", json!({}))
+            .await
+            .map_err(VectorDbError::InternalError)?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), VectorDbError> {
+        // Milvus persists every write it has acknowledged in its log
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use crate::{
+        persistence::ExtractorFilter,
+        vectordbs::{IndexDistance, SearchParams, VectorChunk, VectorDBTS},
+    };
+
+    use super::{chunk_filter, metadata_filter, milvus_name, CreateIndexParams, MilvusDb};
+
+    #[test]
+    fn test_names_and_filters() {
+        assert_eq!(
+            milvus_name("default-hello_index"),
+            "default_2dhello_5findex"
+        );
+        assert_eq!(milvus_name("1index"), "_1index");
+        assert_eq!(
+            chunk_filter(&["a".into(), "b\"c".into()]),
+            r#"chunk_id in ["a", "b\"c"]"#
+        );
+        assert_eq!(metadata_filter(&[]), "");
+        assert_eq!(
+            metadata_filter(&[
+                ExtractorFilter::Eq {
+                    field: "topic".into(),
+                    value: json!("pets\" or true"),
+                },
+                ExtractorFilter::Neq {
+                    field: "a\"b".into(),
+                    value: json!(2021),
+                },
+            ]),
+            r#"metadata["topic"] == "pets\" or true" and metadata["a\"b"] != 2021"#
+        );
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_search_basic() {
+        let milvus: VectorDBTS = Arc::new(MilvusDb::new(crate::MilvusConfig::default()));
+        milvus.drop_index("hello-index".into()).await.unwrap();
+        milvus
+            .create_index(CreateIndexParams {
+                vectordb_index_name: "hello-index".into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+            })
+            .await
+            .unwrap();
+        let chunk = VectorChunk {
+            metadata: [("topic".to_string(), json!("pets"))].into(),
+            ..VectorChunk::new("0".into(), "test".into(), vec![0., 2.])
+        };
+        milvus
+            .add_embedding("hello-index", vec![chunk.clone()])
+            .await
+            .unwrap();
+        milvus
+            .add_embedding("hello-index", vec![chunk])
+            .await
+            .unwrap();
+        assert_eq!(milvus.num_vectors("hello-index").await.unwrap(), 1);
+
+        let results = milvus
+            .search(SearchParams::new("hello-index".into(), vec![10., 8.], 1))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "0");
+
+        let filtered = |value| SearchParams {
+            filters: vec![ExtractorFilter::Eq {
+                field: "topic".into(),
+                value: json!(value),
+            }],
+            ..SearchParams::new("hello-index".into(), vec![10., 8.], 1)
+        };
+        let results = milvus.search(filtered("pets")).await.unwrap();
+        assert_eq!(results.len(), 1);
+        let results = milvus.search(filtered("cars")).await.unwrap();
+        assert!(results.is_empty());
+    }
+}
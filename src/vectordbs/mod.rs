@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -7,11 +7,29 @@ use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
 use thiserror::Error;
 
-use crate::VectorIndexConfig;
-
+use crate::{persistence::ExtractorFilter, VectorIndexConfig};
+
+pub mod elasticsearch;
+#[cfg(feature = "faiss")]
+pub mod faiss_db;
+pub mod hnsw;
+pub mod instrumented;
+#[cfg(feature = "lancedb")]
+pub mod lance_db;
+pub mod milvus;
+pub mod pg_vector;
 pub mod qdrant;
-
+pub mod redis_db;
+pub mod sqlite;
+
+use elasticsearch::ElasticsearchDb;
+use hnsw::HnswDb;
+use instrumented::InstrumentedVectorDb;
+use milvus::MilvusDb;
+use pg_vector::PgVectorDb;
 use qdrant::QdrantDb;
+use redis_db::RedisDb;
+use sqlite::SqliteDb;
 
 #[derive(Display, Debug, Clone, EnumString, Serialize, Deserialize)]
 pub enum IndexDistance {
@@ -32,6 +50,32 @@ pub struct CreateIndexParams {
     pub unique_params: Option<Vec<String>>,
 }
 
+/// A request to search a vector index for the nearest neighbors of a query
+/// vector.
+#[derive(Debug, Clone, Default)]
+pub struct SearchParams {
+    pub vectordb_index_name: String,
+    pub query_embedding: Vec<f32>,
+    pub k: u64,
+    /// Conditions on the metadata of the chunks, which every result meets.
+    /// Only stores which `supports_filters` are searched with any.
+    pub filters: Vec<ExtractorFilter>,
+    /// Text matched against the chunks' text along with the query vector.
+    /// Only stores which `supports_text_query` are searched with one.
+    pub text_query: Option<String>,
+}
+
+impl SearchParams {
+    pub fn new(vectordb_index_name: String, query_embedding: Vec<f32>, k: u64) -> Self {
+        Self {
+            vectordb_index_name,
+            query_embedding,
+            k,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct SearchResult {
     pub text: String,
@@ -68,6 +112,9 @@ pub struct VectorChunk {
     pub chunk_id: String,
     pub text: String,
     pub embeddings: Vec<f32>,
+    /// Metadata of the content the chunk is of, which stores that filter
+    /// searches keep along with the chunk.
+    pub metadata: HashMap<String, serde_json::Value>,
 }
 impl VectorChunk {
     pub fn new(chunk_id: String, text: String, embeddings: Vec<f32>) -> Self {
@@ -75,12 +122,16 @@ impl VectorChunk {
             chunk_id,
             text,
             embeddings,
+            metadata: HashMap::new(),
         }
     }
 }
 
 /// A trait that defines the interface for interacting with a vector database.
 /// The vector database is responsible for storing and querying vector embeddings.
+/// Ingestion and search only ever reach the database through this trait, so a
+/// new backend is a module implementing it, along with an `IndexStoreKind` to
+/// select it by in `create_vectordb`.
 #[async_trait]
 pub trait VectorDb {
     /// Creates a new vector index with the specified configuration.
@@ -94,12 +145,26 @@ pub trait VectorDb {
     ) -> Result<(), VectorDbError>;
 
     /// Searches for the nearest neighbors of a query vector in the specified index.
-    async fn search(
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError>;
+
+    /// Whether searches can have filters on the metadata of the chunks.
+    /// Searches with filters are turned away for stores which can't, rather
+    /// than answered without them.
+    fn supports_filters(&self) -> bool {
+        false
+    }
+
+    /// Whether searches can match a text query along with the query vector.
+    fn supports_text_query(&self) -> bool {
+        false
+    }
+
+    /// Removes the embeddings of chunks from the specified index.
+    async fn remove_embeddings(
         &self,
-        index: String,
-        query_embedding: Vec<f32>,
-        k: u64,
-    ) -> Result<Vec<SearchResult>, VectorDbError>;
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError>;
 
     /// Deletes the specified vector index from the vector database.
     async fn drop_index(&self, index: String) -> Result<(), VectorDbError>;
@@ -107,12 +172,83 @@ pub trait VectorDb {
     /// Returns the number of vectors in the specified index.
     async fn num_vectors(&self, index: &str) -> Result<u64, VectorDbError>;
 
+    /// Checks that the vector database can be reached.
+    async fn health(&self) -> Result<(), VectorDbError>;
+
+    /// Persists the writes which were acknowledged but haven't been persisted
+    /// yet, before the server shuts down.
+    async fn flush(&self) -> Result<(), VectorDbError>;
+
     fn name(&self) -> String;
 }
 
-/// Creates a new vector database based on the specified configuration.
-pub fn create_vectordb(config: VectorIndexConfig) -> Result<VectorDBTS, VectorDbError> {
-    match config.index_store {
-        crate::IndexStoreKind::Qdrant => Ok(Arc::new(QdrantDb::new(config.qdrant_config.unwrap()))),
+/// Name of an index made of letters, digits, `-` and `_` only, for stores
+/// which name tables, files or keys after their indexes. Other characters are
+/// escaped, so that different indexes never get the same name.
+pub(crate) fn escaped_index_name(index: &str) -> String {
+    let mut name = String::with_capacity(index.len());
+    for c in index.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            name.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                name.push_str(&format!("_{:02x}", b));
+            }
+        }
     }
+    name
+}
+
+/// Creates a new vector database based on the specified configuration, whose
+/// operations are recorded in the server's metrics.
+pub fn create_vectordb(config: VectorIndexConfig) -> Result<VectorDBTS, VectorDbError> {
+    let vector_db: VectorDBTS = match config.index_store {
+        crate::IndexStoreKind::Qdrant => Arc::new(QdrantDb::new(config.qdrant_config.unwrap())),
+        crate::IndexStoreKind::PgVector => Arc::new(PgVectorDb::new(
+            config
+                .pg_vector_config
+                .ok_or(VectorDbError::ConfigNotPresent)?,
+        )),
+        #[cfg(feature = "lancedb")]
+        crate::IndexStoreKind::LanceDb => Arc::new(lance_db::LanceDb::new(
+            config.lance_db_config.unwrap_or_default(),
+        )),
+        #[cfg(not(feature = "lancedb"))]
+        crate::IndexStoreKind::LanceDb => {
+            return Err(VectorDbError::InternalError(
+                "the lancedb index store requires the lancedb feature".into(),
+            ))
+        }
+        crate::IndexStoreKind::Hnsw => {
+            Arc::new(HnswDb::new(config.hnsw_config.unwrap_or_default())?)
+        }
+        #[cfg(feature = "faiss")]
+        crate::IndexStoreKind::Faiss => Arc::new(faiss_db::FaissDb::new(
+            config.faiss_config.unwrap_or_default(),
+        )?),
+        #[cfg(not(feature = "faiss"))]
+        crate::IndexStoreKind::Faiss => {
+            return Err(VectorDbError::InternalError(
+                "the faiss index store requires the faiss feature".into(),
+            ))
+        }
+        crate::IndexStoreKind::Milvus => Arc::new(MilvusDb::new(
+            config
+                .milvus_config
+                .ok_or(VectorDbError::ConfigNotPresent)?,
+        )),
+        crate::IndexStoreKind::Redis => Arc::new(RedisDb::new(
+            config.redis_config.ok_or(VectorDbError::ConfigNotPresent)?,
+        )),
+        crate::IndexStoreKind::Elasticsearch => Arc::new(ElasticsearchDb::new(
+            config
+                .elasticsearch_config
+                .ok_or(VectorDbError::ConfigNotPresent)?,
+        )),
+        crate::IndexStoreKind::Sqlite => {
+            Arc::new(SqliteDb::new(config.sqlite_config.unwrap_or_default()))
+        }
+    };
+    Ok(Arc::new(InstrumentedVectorDb::new(vector_db)))
 }
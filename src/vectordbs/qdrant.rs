@@ -1,20 +1,21 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
 
 use qdrant_client::{
     client::QdrantClient,
     client::{Payload, QdrantClientConfig},
     qdrant::{
-        vectors_config::Config, with_payload_selector::SelectorOptions, CreateCollection, Distance,
-        PointStruct, SearchPoints, VectorParams, VectorsConfig, WithPayloadSelector,
+        r#match::MatchValue, vectors_config::Config, with_payload_selector::SelectorOptions,
+        Condition, CreateCollection, Distance, FieldCondition, Filter, Match, PointId, PointStruct,
+        SearchPoints, VectorParams, VectorsConfig, WithPayloadSelector,
     },
 };
 
 use super::{CreateIndexParams, VectorDb, VectorDbError};
 use crate::{
-    vectordbs::{IndexDistance, SearchResult, VectorChunk},
+    persistence::ExtractorFilter,
+    vectordbs::{IndexDistance, SearchParams, SearchResult, VectorChunk},
     QdrantConfig,
 };
 
@@ -27,6 +28,38 @@ fn u64_to_hex(number: u64) -> String {
     format!("{:x}", number)
 }
 
+/// Filter of the points whose payload's metadata matches every one of the
+/// filters, if there are any.
+fn payload_filter(filters: &[ExtractorFilter]) -> Result<Option<Filter>, VectorDbError> {
+    if filters.is_empty() {
+        return Ok(None);
+    }
+    let condition = |field: &str, value: &serde_json::Value| {
+        let match_value = match value {
+            serde_json::Value::String(value) => MatchValue::Keyword(value.clone()),
+            serde_json::Value::Bool(value) => MatchValue::Boolean(*value),
+            value => MatchValue::Integer(value.as_i64().ok_or_else(|| {
+                VectorDbError::IndexReadError(format!("unable to filter `{}` by {}", field, value))
+            })?),
+        };
+        Ok::<_, VectorDbError>(Condition::from(FieldCondition {
+            key: format!("metadata.{}", field),
+            r#match: Some(Match {
+                match_value: Some(match_value),
+            }),
+            ..Default::default()
+        }))
+    };
+    let mut filter = Filter::default();
+    for extractor_filter in filters {
+        match extractor_filter {
+            ExtractorFilter::Eq { field, value } => filter.must.push(condition(field, value)?),
+            ExtractorFilter::Neq { field, value } => filter.must_not.push(condition(field, value)?),
+        }
+    }
+    Ok(Some(filter))
+}
+
 pub struct QdrantDb {
     qdrant_config: QdrantConfig,
 }
@@ -46,7 +79,10 @@ impl QdrantDb {
     }
 
     fn create_client(&self) -> Result<QdrantClient, VectorDbError> {
-        let client_config = QdrantClientConfig::from_url(&self.qdrant_config.addr);
+        let mut client_config = QdrantClientConfig::from_url(&self.qdrant_config.addr);
+        if let Some(api_key) = &self.qdrant_config.api_key {
+            client_config.set_api_key(api_key);
+        }
         let client = QdrantClient::new(Some(client_config)).map_err(|e| {
             VectorDbError::InternalError(format!("unable to create a new quadrant index: {}", e))
         })?;
@@ -106,7 +142,7 @@ impl VectorDb for QdrantDb {
             let payload: Payload = json!(QdrantPayload {
                 text: chunk.text.clone(),
                 chunk_id: chunk_id.clone(),
-                metadata: json!(HashMap::<String, String>::new()),
+                metadata: json!(chunk.metadata),
             })
             .try_into()
             .unwrap();
@@ -124,17 +160,20 @@ impl VectorDb for QdrantDb {
         Ok(())
     }
 
-    async fn search(
-        &self,
-        index: String,
-        query_embedding: Vec<f32>,
-        k: u64,
-    ) -> Result<Vec<SearchResult>, VectorDbError> {
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError> {
+        let SearchParams {
+            vectordb_index_name: index,
+            query_embedding,
+            k,
+            filters,
+            ..
+        } = params;
         let result = self
             .create_client()?
             .search_points(&SearchPoints {
                 collection_name: index,
                 vector: query_embedding,
+                filter: payload_filter(&filters)?,
                 limit: k,
                 with_payload: Some(WithPayloadSelector {
                     selector_options: Some(SelectorOptions::Enable(true)),
@@ -158,6 +197,27 @@ impl VectorDb for QdrantDb {
         Ok(documents)
     }
 
+    fn supports_filters(&self) -> bool {
+        true
+    }
+
+    async fn remove_embeddings(
+        &self,
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError> {
+        let ids = chunk_ids
+            .iter()
+            .map(|chunk_id| hex_to_u64(chunk_id).map(PointId::from))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| VectorDbError::IndexWriteError(e.to_string()))?;
+        self.create_client()?
+            .delete_points_blocking(index, &ids.into(), None)
+            .await
+            .map_err(|e| VectorDbError::IndexWriteError(e.to_string()))?;
+        Ok(())
+    }
+
     async fn drop_index(&self, index: String) -> Result<(), VectorDbError> {
         let result = self.create_client()?.delete_collection(index.clone()).await;
         if let Err(err) = result {
@@ -180,21 +240,42 @@ impl VectorDb for QdrantDb {
             .ok_or(VectorDbError::IndexReadError("index not found".into()))?;
         Ok(collection_info.points_count)
     }
+
+    async fn health(&self) -> Result<(), VectorDbError> {
+        self.create_client()?
+            .health_check()
+            .await
+            .map_err(|e| VectorDbError::InternalError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), VectorDbError> {
+        // Points are upserted as they're added, and Qdrant persists them
+        // from its write-ahead log
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
-    use crate::vectordbs::{IndexDistance, VectorChunk, VectorDBTS};
+    use qdrant_client::qdrant::{condition::ConditionOneOf, r#match::MatchValue};
+    use serde_json::json;
+
+    use crate::{
+        persistence::ExtractorFilter,
+        vectordbs::{IndexDistance, SearchParams, VectorChunk, VectorDBTS},
+    };
 
-    use super::{CreateIndexParams, QdrantDb};
+    use super::{payload_filter, CreateIndexParams, QdrantDb};
 
     #[tokio::test]
     #[tracing_test::traced_test]
     async fn test_search_basic() {
         let qdrant: VectorDBTS = Arc::new(QdrantDb::new(crate::QdrantConfig {
             addr: "http://localhost:6334".into(),
+            ..Default::default()
         }));
         qdrant.drop_index("hello-index".into()).await.unwrap();
         qdrant
@@ -207,9 +288,8 @@ mod tests {
             .await
             .unwrap();
         let chunk = VectorChunk {
-            chunk_id: "0".into(),
-            text: "test".into(),
-            embeddings: vec![0., 2.],
+            metadata: [("topic".to_string(), json!("pets"))].into(),
+            ..VectorChunk::new("0".into(), "test".into(), vec![0., 2.])
         };
         qdrant
             .add_embedding("hello-index", vec![chunk])
@@ -217,10 +297,72 @@ mod tests {
             .unwrap();
 
         let results = qdrant
-            .search("hello-index".into(), vec![10., 8.], 1)
+            .search(SearchParams::new("hello-index".into(), vec![10., 8.], 1))
             .await
             .unwrap();
         assert_eq!(results.len(), 1);
+
+        let filtered = |value| SearchParams {
+            filters: vec![ExtractorFilter::Eq {
+                field: "topic".into(),
+                value: json!(value),
+            }],
+            ..SearchParams::new("hello-index".into(), vec![10., 8.], 1)
+        };
+        let results = qdrant.search(filtered("pets")).await.unwrap();
+        assert_eq!(results.len(), 1);
+        let results = qdrant.search(filtered("cars")).await.unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_payload_filter() {
+        assert!(payload_filter(&[]).unwrap().is_none());
+
+        let filter = payload_filter(&[
+            ExtractorFilter::Eq {
+                field: "topic".into(),
+                value: json!("pets"),
+            },
+            ExtractorFilter::Neq {
+                field: "year".into(),
+                value: json!(2021),
+            },
+            ExtractorFilter::Eq {
+                field: "draft".into(),
+                value: json!(false),
+            },
+        ])
+        .unwrap()
+        .unwrap();
+        let conditions = |conditions: Vec<qdrant_client::qdrant::Condition>| {
+            conditions
+                .into_iter()
+                .map(|condition| match condition.condition_one_of {
+                    Some(ConditionOneOf::Field(field)) => {
+                        (field.key, field.r#match.unwrap().match_value.unwrap())
+                    }
+                    condition => panic!("unexpected condition: {:?}", condition),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            conditions(filter.must),
+            vec![
+                ("metadata.topic".into(), MatchValue::Keyword("pets".into())),
+                ("metadata.draft".into(), MatchValue::Boolean(false)),
+            ]
+        );
+        assert_eq!(
+            conditions(filter.must_not),
+            vec![("metadata.year".into(), MatchValue::Integer(2021))]
+        );
+
+        assert!(payload_filter(&[ExtractorFilter::Eq {
+            field: "score".into(),
+            value: json!(0.5),
+        }])
+        .is_err());
     }
 
     #[tokio::test]
@@ -230,6 +372,7 @@ mod tests {
         let hash_on = vec!["user_id".to_string(), "url".to_string()];
         let qdrant: VectorDBTS = Arc::new(QdrantDb::new(crate::QdrantConfig {
             addr: "http://localhost:6334".into(),
+            ..Default::default()
         }));
         qdrant.drop_index(index_name.into()).await.unwrap();
         qdrant
@@ -241,11 +384,7 @@ mod tests {
             })
             .await
             .unwrap();
-        let chunk = VectorChunk {
-            chunk_id: "0".into(),
-            text: "test".into(),
-            embeddings: vec![0., 2.],
-        };
+        let chunk = VectorChunk::new("0".into(), "test".into(), vec![0., 2.]);
         qdrant
             .add_embedding(index_name, vec![chunk.clone()])
             .await
@@ -0,0 +1,401 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use redis::{
+    aio::{ConnectionManager, ConnectionManagerConfig},
+    from_redis_value, Value,
+};
+use tokio::sync::OnceCell;
+
+use super::{escaped_index_name, CreateIndexParams, VectorDb, VectorDbError};
+use crate::{
+    vectordbs::{IndexDistance, SearchParams, SearchResult, VectorChunk},
+    RedisConfig,
+};
+
+/// Hash which keeps the distance of every index.
+const INDEXES_KEY: &str = "indexify:indexes";
+
+const CONNECTION_TIMEOUT_SECS: u64 = 5;
+
+/// Field of the results of a search which has the distance of each result.
+const SCORE_FIELD: &str = "__score";
+
+/// Stores the chunks of each index in Redis hashes under a prefix of its
+/// own, searched through a RediSearch index over the prefix, so that Redis
+/// Stack can serve as a store of recent content with low latency. Chunks
+/// expire after the config's `ttl_secs`, and drop out of their index then.
+pub struct RedisDb {
+    config: RedisConfig,
+    conn: OnceCell<ConnectionManager>,
+    distances: DashMap<String, IndexDistance>,
+}
+
+impl RedisDb {
+    pub fn new(config: RedisConfig) -> RedisDb {
+        Self {
+            config,
+            conn: OnceCell::new(),
+            distances: DashMap::new(),
+        }
+    }
+
+    /// Connects on first use, so that the server starts while Redis is still
+    /// coming up. The connection reconnects by itself when it's lost, and a
+    /// first connection which fails is tried again on the next call, so it's
+    /// only retried once, rather than holding up the call for minutes.
+    async fn conn(&self) -> Result<ConnectionManager, VectorDbError> {
+        self.conn
+            .get_or_try_init(|| async {
+                let config = ConnectionManagerConfig::new()
+                    .set_connection_timeout(Duration::from_secs(CONNECTION_TIMEOUT_SECS))
+                    .set_number_of_retries(1);
+                redis::Client::open(self.config.addr.as_str())?
+                    .get_connection_manager_with_config(config)
+                    .await
+            })
+            .await
+            .cloned()
+            .map_err(|e| VectorDbError::InternalError(format!("unable to connect to redis: {}", e)))
+    }
+
+    /// Name of the RediSearch index of an index.
+    fn search_index(index: &str) -> String {
+        format!("indexify:{}", escaped_index_name(index))
+    }
+
+    /// Key of the hash of a chunk. Escaped index names don't have `:`, so
+    /// the prefix of one index never matches the keys of another.
+    fn key(index: &str, chunk_id: &str) -> String {
+        format!("{}:{}", Self::search_index(index), chunk_id)
+    }
+
+    fn distance_metric(distance: &IndexDistance) -> &'static str {
+        match distance {
+            IndexDistance::Cosine => "COSINE",
+            IndexDistance::Dot => "IP",
+            IndexDistance::Euclidean => "L2",
+        }
+    }
+
+    /// Score of a result at a distance. RediSearch subtracts cosine
+    /// similarities and inner products from 1, and squares euclidean
+    /// distances, which is undone so that scores match the other stores.
+    fn score(distance: &IndexDistance, d: f32) -> f32 {
+        match distance {
+            IndexDistance::Cosine | IndexDistance::Dot => 1. - d,
+            IndexDistance::Euclidean => d.sqrt(),
+        }
+    }
+
+    async fn distance(&self, index: &str) -> Result<IndexDistance, VectorDbError> {
+        if let Some(distance) = self.distances.get(index) {
+            return Ok(distance.clone());
+        }
+        let distance: Option<String> = redis::cmd("HGET")
+            .arg(INDEXES_KEY)
+            .arg(index)
+            .query_async(&mut self.conn().await?)
+            .await
+            .map_err(|e| VectorDbError::IndexReadError(e.to_string()))?;
+        let distance: IndexDistance = distance
+            .ok_or(VectorDbError::IndexReadError("index not found".into()))?
+            .parse()
+            .map_err(|e: strum::ParseError| VectorDbError::IndexReadError(e.to_string()))?;
+        self.distances.insert(index.into(), distance.clone());
+        Ok(distance)
+    }
+}
+
+/// Bytes RediSearch reads a `FLOAT32` vector from.
+fn vector_bytes(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Results of an `FT.SEARCH`, which replies with the number of results,
+/// followed by the key and the fields of each of them.
+fn parse_search_results(
+    reply: Value,
+    distance: &IndexDistance,
+) -> Result<Vec<SearchResult>, redis::RedisError> {
+    let Value::Array(items) = reply else {
+        return Err((redis::ErrorKind::TypeError, "unexpected search reply").into());
+    };
+    let mut results = Vec::new();
+    // Every other item after the count is a result's key
+    for fields in items.into_iter().skip(2).step_by(2) {
+        let fields: Vec<String> = from_redis_value(&fields)?;
+        let mut result = SearchResult::default();
+        for pair in fields.chunks_exact(2) {
+            match pair[0].as_str() {
+                "chunk_id" => result.chunk_id = pair[1].clone(),
+                "text" => result.text = pair[1].clone(),
+                SCORE_FIELD => {
+                    result.confidence_score =
+                        RedisDb::score(distance, pair[1].parse().unwrap_or_default())
+                }
+                _ => {}
+            }
+        }
+        results.push(result);
+    }
+    Ok(results)
+}
+
+#[async_trait]
+impl VectorDb for RedisDb {
+    fn name(&self) -> String {
+        "redis".into()
+    }
+
+    async fn create_index(&self, index: CreateIndexParams) -> Result<(), VectorDbError> {
+        let create = |e: redis::RedisError| VectorDbError::IndexCreationError(e.to_string());
+        let mut conn = self.conn().await?;
+        let name = Self::search_index(&index.vectordb_index_name);
+        let result: Result<(), _> = redis::cmd("FT.CREATE")
+            .arg(&name)
+            .arg("ON")
+            .arg("HASH")
+            .arg("PREFIX")
+            .arg(1)
+            .arg(format!("{}:", name))
+            .arg("SCHEMA")
+            .arg("chunk_id")
+            .arg("TAG")
+            .arg("embedding")
+            .arg("VECTOR")
+            .arg("HNSW")
+            .arg(6)
+            .arg("TYPE")
+            .arg("FLOAT32")
+            .arg("DIM")
+            .arg(index.vector_dim)
+            .arg("DISTANCE_METRIC")
+            .arg(Self::distance_metric(&index.distance))
+            .query_async(&mut conn)
+            .await;
+        match result {
+            Err(e) if !e.to_string().contains("Index already exists") => return Err(create(e)),
+            _ => {}
+        }
+        redis::cmd("HSETNX")
+            .arg(INDEXES_KEY)
+            .arg(&index.vectordb_index_name)
+            .arg(index.distance.to_string())
+            .query_async::<()>(&mut conn)
+            .await
+            .map_err(create)?;
+        Ok(())
+    }
+
+    async fn add_embedding(
+        &self,
+        index: &str,
+        chunks: Vec<VectorChunk>,
+    ) -> Result<(), VectorDbError> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let mut pipe = redis::pipe();
+        for chunk in chunks {
+            let key = Self::key(index, &chunk.chunk_id);
+            pipe.cmd("HSET")
+                .arg(&key)
+                .arg("chunk_id")
+                .arg(&chunk.chunk_id)
+                .arg("text")
+                .arg(&chunk.text)
+                .arg("embedding")
+                .arg(vector_bytes(&chunk.embeddings))
+                .ignore();
+            if let Some(ttl_secs) = self.config.ttl_secs {
+                pipe.cmd("EXPIRE").arg(&key).arg(ttl_secs).ignore();
+            }
+        }
+        pipe.query_async::<()>(&mut self.conn().await?)
+            .await
+            .map_err(|e| VectorDbError::IndexWriteError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError> {
+        let SearchParams {
+            vectordb_index_name: index,
+            query_embedding,
+            k,
+            ..
+        } = params;
+        let read = |e: redis::RedisError| VectorDbError::IndexReadError(e.to_string());
+        let distance = self.distance(&index).await?;
+        let reply: Value = redis::cmd("FT.SEARCH")
+            .arg(Self::search_index(&index))
+            .arg(format!(
+                "*=>[KNN {} @embedding $vector AS {}]",
+                k, SCORE_FIELD
+            ))
+            .arg("PARAMS")
+            .arg(2)
+            .arg("vector")
+            .arg(vector_bytes(&query_embedding))
+            .arg("SORTBY")
+            .arg(SCORE_FIELD)
+            .arg("RETURN")
+            .arg(3)
+            .arg("chunk_id")
+            .arg("text")
+            .arg(SCORE_FIELD)
+            .arg("LIMIT")
+            .arg(0)
+            .arg(k)
+            .arg("DIALECT")
+            .arg(2)
+            .query_async(&mut self.conn().await?)
+            .await
+            .map_err(read)?;
+        parse_search_results(reply, &distance).map_err(read)
+    }
+
+    async fn remove_embeddings(
+        &self,
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError> {
+        if chunk_ids.is_empty() {
+            return Ok(());
+        }
+        let keys: Vec<String> = chunk_ids.iter().map(|id| Self::key(index, id)).collect();
+        redis::cmd("DEL")
+            .arg(keys)
+            .query_async::<()>(&mut self.conn().await?)
+            .await
+            .map_err(|e| VectorDbError::IndexWriteError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn drop_index(&self, index: String) -> Result<(), VectorDbError> {
+        let drop =
+            |e: redis::RedisError| VectorDbError::IndexDeletionError(index.clone(), e.to_string());
+        let mut conn = self.conn().await?;
+        // `DD` deletes the chunks' hashes along with the index
+        let result: Result<(), _> = redis::cmd("FT.DROPINDEX")
+            .arg(Self::search_index(&index))
+            .arg("DD")
+            .query_async(&mut conn)
+            .await;
+        match result {
+            Err(e) if !e.to_string().to_lowercase().contains("unknown index name") => {
+                return Err(drop(e))
+            }
+            _ => {}
+        }
+        redis::cmd("HDEL")
+            .arg(INDEXES_KEY)
+            .arg(&index)
+            .query_async::<()>(&mut conn)
+            .await
+            .map_err(drop)?;
+        self.distances.remove(&index);
+        Ok(())
+    }
+
+    async fn num_vectors(&self, index: &str) -> Result<u64, VectorDbError> {
+        let read = |e: redis::RedisError| VectorDbError::IndexReadError(e.to_string());
+        // `FT.INFO` replies with the names of the index's attributes, each of
+        // them followed by its value
+        let info: Vec<Value> = redis::cmd("FT.INFO")
+            .arg(Self::search_index(index))
+            .query_async(&mut self.conn().await?)
+            .await
+            .map_err(read)?;
+        for pair in info.chunks_exact(2) {
+            if from_redis_value::<String>(&pair[0]).ok().as_deref() == Some("num_docs") {
+                let count: String = from_redis_value(&pair[1]).map_err(read)?;
+                return count
+                    .parse::<f64>()
+                    .map(|count| count as u64)
+                    .map_err(|e| VectorDbError::IndexReadError(e.to_string()));
+            }
+        }
+        Err(VectorDbError::IndexReadError("missing num_docs".into()))
+    }
+
+    async fn health(&self) -> Result<(), VectorDbError> {
+        redis::cmd("PING")
+            .query_async::<()>(&mut self.conn().await?)
+            .await
+            .map_err(|e| VectorDbError::InternalError(e.to_string()))
+    }
+
+    async fn flush(&self) -> Result<(), VectorDbError> {
+        // Redis persists writes by its own configuration, whether they come
+        // from indexify or not
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use redis::Value;
+
+    use crate::vectordbs::{IndexDistance, SearchParams, VectorChunk, VectorDBTS};
+
+    use super::{parse_search_results, CreateIndexParams, RedisDb};
+
+    #[test]
+    fn test_parse_search_results() {
+        let bulk = |s: &str| Value::BulkString(s.as_bytes().to_vec());
+        let reply = Value::Array(vec![
+            Value::Int(1),
+            bulk("indexify:hello-index:0"),
+            Value::Array(vec![
+                bulk("chunk_id"),
+                bulk("0"),
+                bulk("text"),
+                bulk("test"),
+                bulk("__score"),
+                bulk("0.25"),
+            ]),
+        ]);
+        let results = parse_search_results(reply, &IndexDistance::Cosine).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "0");
+        assert_eq!(results[0].text, "test");
+        assert_eq!(results[0].confidence_score, 0.75);
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_search_basic() {
+        let redis_db: VectorDBTS = Arc::new(RedisDb::new(crate::RedisConfig::default()));
+        redis_db.drop_index("hello-index".into()).await.unwrap();
+        redis_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: "hello-index".into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+            })
+            .await
+            .unwrap();
+        let chunk = VectorChunk::new("0".into(), "test".into(), vec![0., 2.]);
+        redis_db
+            .add_embedding("hello-index", vec![chunk.clone()])
+            .await
+            .unwrap();
+        redis_db
+            .add_embedding("hello-index", vec![chunk])
+            .await
+            .unwrap();
+        assert_eq!(redis_db.num_vectors("hello-index").await.unwrap(), 1);
+
+        let results = redis_db
+            .search(SearchParams::new("hello-index".into(), vec![10., 8.], 1))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "0");
+    }
+}
@@ -0,0 +1,494 @@
+use std::sync::Once;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use libsqlite3_sys::sqlite3_auto_extension;
+use sea_orm::{
+    ConnectOptions, ConnectionTrait, Database, DatabaseConnection, DbBackend, DbErr, Statement,
+    TransactionTrait, Value,
+};
+use serde_json::json;
+use tokio::sync::OnceCell;
+
+use super::{CreateIndexParams, VectorDb, VectorDbError};
+use crate::{
+    persistence::ExtractorFilter,
+    vectordbs::{IndexDistance, SearchParams, SearchResult, VectorChunk},
+    SqliteConfig,
+};
+
+/// Statements of each version of the schema, after the one before it. The
+/// database's `user_version` is the number of them which have been run, so
+/// that the ones after it are run when it's opened. Versions which have been
+/// released are never changed, only followed by new ones.
+const MIGRATIONS: &[&[&str]] = &[&[
+    "create table indexify_indexes (name text primary key, distance text not null)",
+    "create table indexify_vectors (index_name text not null, chunk_id text not null, \
+     text text not null, metadata text not null default '{}', embedding blob not null, \
+     squared_norm real not null, primary key (index_name, chunk_id))",
+]];
+
+/// Rows written in each insert, which keeps the statements well under
+/// SQLite's limit on bind parameters.
+const UPSERT_BATCH_SIZE: usize = 1000;
+
+static REGISTER_SQLITE_VEC: Once = Once::new();
+
+/// Stores the vectors of every index in a single SQLite database file,
+/// searched exhaustively through the sqlite-vec extension, along with the
+/// metadata of their documents as JSON, so that small deployments keep their
+/// vectors without running any other service. The schema of the database is
+/// migrated when it's opened.
+pub struct SqliteDb {
+    config: SqliteConfig,
+    conn: OnceCell<DatabaseConnection>,
+    distances: DashMap<String, IndexDistance>,
+}
+
+impl SqliteDb {
+    pub fn new(config: SqliteConfig) -> SqliteDb {
+        Self {
+            config,
+            conn: OnceCell::new(),
+            distances: DashMap::new(),
+        }
+    }
+
+    /// Opens the database on first use, creating it if it doesn't exist.
+    async fn conn(&self) -> Result<&DatabaseConnection, VectorDbError> {
+        self.conn
+            .get_or_try_init(|| async {
+                // Every connection SQLite opens after this loads sqlite-vec
+                REGISTER_SQLITE_VEC.call_once(|| unsafe {
+                    sqlite3_auto_extension(Some(sqlite_vec::sqlite3_vec_init));
+                });
+                let mut opt =
+                    ConnectOptions::new(format!("sqlite://{}?mode=rwc", self.config.path));
+                opt.sqlx_logging(false);
+                let conn = Database::connect(opt).await?;
+                conn.execute_unprepared("pragma journal_mode = wal").await?;
+                migrate(&conn).await?;
+                Ok(conn)
+            })
+            .await
+            .map_err(|e: DbErr| {
+                VectorDbError::InternalError(format!("unable to open sqlite database: {}", e))
+            })
+    }
+
+    async fn execute(&self, sql: &str, values: Vec<Value>) -> Result<(), DbErr> {
+        let conn = self
+            .conn()
+            .await
+            .map_err(|e| DbErr::Custom(e.to_string()))?;
+        conn.execute(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            sql,
+            values,
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Distance of a row to the `query`'s vector, along with the order which
+    /// puts nearer rows first, and the score of a row at a distance.
+    /// sqlite-vec has no inner product, which is worked out from the
+    /// euclidean distance and the norms instead.
+    fn distance_expr(distance: &IndexDistance) -> (&'static str, &'static str, fn(f64) -> f32) {
+        match distance {
+            IndexDistance::Cosine => ("vec_distance_cosine(embedding, query.vector)", "asc", |d| {
+                (1. - d) as f32
+            }),
+            IndexDistance::Dot => (
+                "(indexify_vectors.squared_norm + query.squared_norm - \
+                 vec_distance_l2(embedding, query.vector) * \
+                 vec_distance_l2(embedding, query.vector)) / 2",
+                "desc",
+                |d| d as f32,
+            ),
+            IndexDistance::Euclidean => ("vec_distance_l2(embedding, query.vector)", "asc", |d| {
+                d as f32
+            }),
+        }
+    }
+
+    async fn distance(&self, index: &str) -> Result<IndexDistance, VectorDbError> {
+        if let Some(distance) = self.distances.get(index) {
+            return Ok(distance.clone());
+        }
+        let row = self
+            .conn()
+            .await?
+            .query_one(Statement::from_sql_and_values(
+                DbBackend::Sqlite,
+                "select distance from indexify_indexes where name = ?",
+                vec![index.into()],
+            ))
+            .await
+            .map_err(|e| VectorDbError::IndexReadError(e.to_string()))?
+            .ok_or(VectorDbError::IndexReadError("index not found".into()))?;
+        let distance: String = row
+            .try_get("", "distance")
+            .map_err(|e| VectorDbError::IndexReadError(e.to_string()))?;
+        let distance: IndexDistance = distance
+            .parse()
+            .map_err(|e: strum::ParseError| VectorDbError::IndexReadError(e.to_string()))?;
+        self.distances.insert(index.into(), distance.clone());
+        Ok(distance)
+    }
+}
+
+/// Runs the migrations which haven't been run on the database yet, each of
+/// them in a transaction along with the update of its version.
+async fn migrate(conn: &DatabaseConnection) -> Result<(), DbErr> {
+    let row = conn
+        .query_one(Statement::from_string(
+            DbBackend::Sqlite,
+            "pragma user_version".into(),
+        ))
+        .await?
+        .ok_or(DbErr::Custom("missing user_version".into()))?;
+    let version: i32 = row.try_get("", "user_version")?;
+    for (i, statements) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let txn = conn.begin().await?;
+        for statement in statements.iter() {
+            txn.execute_unprepared(statement).await?;
+        }
+        txn.execute_unprepared(&format!("pragma user_version = {}", i + 1))
+            .await?;
+        txn.commit().await?;
+    }
+    Ok(())
+}
+
+/// Bytes sqlite-vec reads a float32 vector from.
+fn vector_blob(embedding: &[f32]) -> Value {
+    let bytes: Vec<u8> = embedding.iter().flat_map(|v| v.to_le_bytes()).collect();
+    bytes.into()
+}
+
+fn squared_norm(embedding: &[f32]) -> f64 {
+    embedding.iter().map(|v| (*v as f64) * (*v as f64)).sum()
+}
+
+/// Conditions matching the rows whose metadata matches every one of the
+/// filters, along with the values bound to them. `json_extract` reads
+/// booleans as integers, so they're compared as such, and rows without a
+/// field don't match filters on it being equal to anything.
+fn metadata_conditions(filters: &[ExtractorFilter]) -> (String, Vec<Value>) {
+    let mut conditions = Vec::with_capacity(filters.len());
+    let mut values = Vec::with_capacity(filters.len() * 2);
+    for filter in filters {
+        let (field, value, op) = match filter {
+            ExtractorFilter::Eq { field, value } => (field, value, "="),
+            ExtractorFilter::Neq { field, value } => (field, value, "is not"),
+        };
+        conditions.push(format!(" and json_extract(metadata, ?) {} ?", op));
+        values.push(format!("$.{}", json!(field)).into());
+        values.push(match value {
+            serde_json::Value::String(value) => value.clone().into(),
+            serde_json::Value::Bool(value) => (*value as i64).into(),
+            value => value.as_i64().into(),
+        });
+    }
+    (conditions.concat(), values)
+}
+
+#[async_trait]
+impl VectorDb for SqliteDb {
+    fn name(&self) -> String {
+        "sqlite".into()
+    }
+
+    async fn create_index(&self, index: CreateIndexParams) -> Result<(), VectorDbError> {
+        self.execute(
+            "insert into indexify_indexes (name, distance) values (?, ?) \
+             on conflict (name) do nothing",
+            vec![
+                index.vectordb_index_name.into(),
+                index.distance.to_string().into(),
+            ],
+        )
+        .await
+        .map_err(|e| VectorDbError::IndexCreationError(e.to_string()))
+    }
+
+    async fn add_embedding(
+        &self,
+        index: &str,
+        chunks: Vec<VectorChunk>,
+    ) -> Result<(), VectorDbError> {
+        let write = |e: DbErr| VectorDbError::IndexWriteError(e.to_string());
+        // A single transaction saves SQLite from syncing the file per batch
+        let txn = self.conn().await?.begin().await.map_err(write)?;
+        for batch in chunks.chunks(UPSERT_BATCH_SIZE) {
+            let rows = vec!["(?, ?, ?, ?, ?, ?)"; batch.len()];
+            let mut values = Vec::with_capacity(batch.len() * 6);
+            for chunk in batch {
+                values.push(index.into());
+                values.push(chunk.chunk_id.clone().into());
+                values.push(chunk.text.clone().into());
+                values.push(json!(chunk.metadata).to_string().into());
+                values.push(vector_blob(&chunk.embeddings));
+                values.push(squared_norm(&chunk.embeddings).into());
+            }
+            let sql = format!(
+                "insert into indexify_vectors (index_name, chunk_id, text, metadata, embedding, \
+                 squared_norm) values {} on conflict (index_name, chunk_id) do update set \
+                 text = excluded.text, metadata = excluded.metadata, \
+                 embedding = excluded.embedding, squared_norm = excluded.squared_norm",
+                rows.join(", ")
+            );
+            txn.execute(Statement::from_sql_and_values(
+                DbBackend::Sqlite,
+                &sql,
+                values,
+            ))
+            .await
+            .map_err(write)?;
+        }
+        txn.commit().await.map_err(write)?;
+        Ok(())
+    }
+
+    async fn search(&self, params: SearchParams) -> Result<Vec<SearchResult>, VectorDbError> {
+        let SearchParams {
+            vectordb_index_name: index,
+            query_embedding,
+            k,
+            filters,
+            ..
+        } = params;
+        let (expr, order, score) = Self::distance_expr(&self.distance(&index).await?);
+        let (conditions, filter_values) = metadata_conditions(&filters);
+        let sql = format!(
+            "with query (vector, squared_norm) as (select ?, ?) select chunk_id, text, {} as \
+             distance from indexify_vectors, query where index_name = ?{} order by distance {} \
+             limit ?",
+            expr, conditions, order
+        );
+        let mut values = vec![
+            vector_blob(&query_embedding),
+            squared_norm(&query_embedding).into(),
+            index.into(),
+        ];
+        values.extend(filter_values);
+        values.push((k as i64).into());
+        let rows = self
+            .conn()
+            .await?
+            .query_all(Statement::from_sql_and_values(
+                DbBackend::Sqlite,
+                &sql,
+                values,
+            ))
+            .await
+            .map_err(|e| VectorDbError::IndexReadError(e.to_string()))?;
+        rows.iter()
+            .map(|row| {
+                let distance: f64 = row.try_get("", "distance")?;
+                Ok(SearchResult {
+                    text: row.try_get("", "text")?,
+                    chunk_id: row.try_get("", "chunk_id")?,
+                    confidence_score: score(distance),
+                })
+            })
+            .collect::<Result<Vec<_>, DbErr>>()
+            .map_err(|e| VectorDbError::IndexReadError(e.to_string()))
+    }
+
+    fn supports_filters(&self) -> bool {
+        true
+    }
+
+    async fn remove_embeddings(
+        &self,
+        index: &str,
+        chunk_ids: Vec<String>,
+    ) -> Result<(), VectorDbError> {
+        for batch in chunk_ids.chunks(UPSERT_BATCH_SIZE) {
+            let params = vec!["?"; batch.len()];
+            let sql = format!(
+                "delete from indexify_vectors where index_name = ? and chunk_id in ({})",
+                params.join(", ")
+            );
+            let mut values: Vec<Value> = vec![index.into()];
+            values.extend(batch.iter().map(|id| Value::from(id.clone())));
+            self.execute(&sql, values)
+                .await
+                .map_err(|e| VectorDbError::IndexWriteError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    async fn drop_index(&self, index: String) -> Result<(), VectorDbError> {
+        let drop = |e: DbErr| VectorDbError::IndexDeletionError(index.clone(), e.to_string());
+        self.execute(
+            "delete from indexify_vectors where index_name = ?",
+            vec![index.clone().into()],
+        )
+        .await
+        .map_err(drop)?;
+        self.execute(
+            "delete from indexify_indexes where name = ?",
+            vec![index.clone().into()],
+        )
+        .await
+        .map_err(drop)?;
+        self.distances.remove(&index);
+        Ok(())
+    }
+
+    async fn num_vectors(&self, index: &str) -> Result<u64, VectorDbError> {
+        let row = self
+            .conn()
+            .await?
+            .query_one(Statement::from_sql_and_values(
+                DbBackend::Sqlite,
+                "select count(*) as count from indexify_vectors where index_name = ?",
+                vec![index.into()],
+            ))
+            .await
+            .map_err(|e| VectorDbError::IndexReadError(e.to_string()))?
+            .ok_or(VectorDbError::IndexReadError("index not found".into()))?;
+        let count: i64 = row
+            .try_get("", "count")
+            .map_err(|e| VectorDbError::IndexReadError(e.to_string()))?;
+        Ok(count as u64)
+    }
+
+    async fn health(&self) -> Result<(), VectorDbError> {
+        self.execute("select vec_version()", vec![])
+            .await
+            .map_err(|e| VectorDbError::InternalError(e.to_string()))
+    }
+
+    async fn flush(&self) -> Result<(), VectorDbError> {
+        // Every write is committed by the time it's acknowledged
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use crate::{
+        persistence::ExtractorFilter,
+        vectordbs::{IndexDistance, SearchParams, VectorChunk, VectorDBTS},
+    };
+
+    use super::{CreateIndexParams, SqliteDb};
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_search_basic() {
+        let path = std::env::temp_dir().join(format!("indexify-sqlite-{}.db", std::process::id()));
+        let sqlite: VectorDBTS = Arc::new(SqliteDb::new(crate::SqliteConfig {
+            path: path.to_string_lossy().into(),
+        }));
+        for (index, distance) in [
+            ("cosine-index", IndexDistance::Cosine),
+            ("dot-index", IndexDistance::Dot),
+            ("euclidean-index", IndexDistance::Euclidean),
+        ] {
+            sqlite
+                .create_index(CreateIndexParams {
+                    vectordb_index_name: index.into(),
+                    vector_dim: 2,
+                    distance,
+                    unique_params: None,
+                })
+                .await
+                .unwrap();
+            let chunks = vec![
+                VectorChunk::new("0".into(), "test".into(), vec![0., 2.]),
+                VectorChunk {
+                    metadata: [
+                        ("topic".to_string(), json!("pets")),
+                        ("year".to_string(), json!(2021)),
+                        ("draft".to_string(), json!(true)),
+                    ]
+                    .into(),
+                    ..VectorChunk::new("1".into(), "other".into(), vec![4., 4.])
+                },
+            ];
+            sqlite.add_embedding(index, chunks.clone()).await.unwrap();
+            sqlite.add_embedding(index, chunks).await.unwrap();
+            assert_eq!(sqlite.num_vectors(index).await.unwrap(), 2);
+        }
+
+        let results = sqlite
+            .search(SearchParams::new("cosine-index".into(), vec![1., 8.], 1))
+            .await
+            .unwrap();
+        assert_eq!(results[0].chunk_id, "0");
+        // The other vector is longer, which outweighs its angle
+        let results = sqlite
+            .search(SearchParams::new("dot-index".into(), vec![1., 8.], 2))
+            .await
+            .unwrap();
+        assert_eq!(results[0].chunk_id, "1");
+        assert!((results[0].confidence_score - 36.).abs() < 1e-3);
+        let results = sqlite
+            .search(SearchParams::new("euclidean-index".into(), vec![0., 3.], 1))
+            .await
+            .unwrap();
+        assert_eq!(results[0].chunk_id, "0");
+        assert!((results[0].confidence_score - 1.).abs() < 1e-6);
+
+        let filtered = |filters| SearchParams {
+            filters,
+            ..SearchParams::new("cosine-index".into(), vec![1., 8.], 2)
+        };
+        let eq = |field: &str, value: serde_json::Value| ExtractorFilter::Eq {
+            field: field.into(),
+            value,
+        };
+        let results = sqlite
+            .search(filtered(vec![
+                eq("topic", json!("pets")),
+                eq("year", json!(2021)),
+                eq("draft", json!(true)),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "1");
+        let results = sqlite
+            .search(filtered(vec![eq("draft", json!(false))]))
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+        // Rows without the field aren't equal to it
+        let results = sqlite
+            .search(filtered(vec![ExtractorFilter::Neq {
+                field: "topic".into(),
+                value: json!("pets"),
+            }]))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "0");
+
+        sqlite
+            .remove_embeddings("cosine-index", vec!["0".into()])
+            .await
+            .unwrap();
+        assert_eq!(sqlite.num_vectors("cosine-index").await.unwrap(), 1);
+        assert_eq!(sqlite.num_vectors("dot-index").await.unwrap(), 2);
+        sqlite.drop_index("dot-index".into()).await.unwrap();
+        assert_eq!(sqlite.num_vectors("dot-index").await.unwrap(), 0);
+
+        // Opening the database again finds it migrated already
+        let sqlite: VectorDBTS = Arc::new(SqliteDb::new(crate::SqliteConfig {
+            path: path.to_string_lossy().into(),
+        }));
+        assert_eq!(sqlite.num_vectors("euclidean-index").await.unwrap(), 2);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}